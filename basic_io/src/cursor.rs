@@ -1,3 +1,5 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
 use core::cmp;
 use {Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
 
@@ -96,6 +98,28 @@ impl<'a> Write for Cursor<&'a mut [u8]> {
     }
 }
 
+#[cfg(feature = "alloc")]
+impl Write for Cursor<Vec<u8>> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        vec_write(&mut self.pos, &mut self.inner, buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> Write for Cursor<&'a mut Vec<u8>> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        vec_write(&mut self.pos, self.inner, buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
 impl<T> Cursor<T>
 where
     T: AsRef<[u8]>,
@@ -113,3 +137,21 @@ fn slice_write(pos_mut: &mut u64, slice: &mut [u8], buf: &[u8]) -> Result<usize>
     *pos_mut += amt as u64;
     Ok(amt)
 }
+
+// Resizing write implementation - writes past the end extend the vector and the gap between the
+// old end and the write position is filled with zeros
+#[cfg(feature = "alloc")]
+fn vec_write(pos_mut: &mut u64, vec: &mut Vec<u8>, buf: &[u8]) -> Result<usize> {
+    if *pos_mut > usize::MAX as u64 {
+        return Err(Error::new(ErrorKind::InvalidInput, "cursor position too large"));
+    }
+    let pos = *pos_mut as usize;
+    if vec.len() < pos {
+        vec.resize(pos, 0);
+    }
+    let overlap = cmp::min(vec.len() - pos, buf.len());
+    vec[pos..pos + overlap].copy_from_slice(&buf[..overlap]);
+    vec.extend_from_slice(&buf[overlap..]);
+    *pos_mut += buf.len() as u64;
+    Ok(buf.len())
+}
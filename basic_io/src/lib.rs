@@ -1,5 +1,8 @@
 #![no_std]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 mod cursor;
 
 use core::fmt;
@@ -11,6 +14,22 @@ pub enum ErrorKind {
     NotFound,
     UnexpectedEof,
     WriteZero,
+    NotEnoughSpace,
+    FileTooLarge,
+    AlreadyExists,
+    CorruptedFile,
+    TruncatedVolume,
+    NotADirectory,
+    IsADirectory,
+    DirectoryNotEmpty,
+    NameTooLong,
+    PathTooDeep,
+    TooManyEntries,
+    DirectoryFull,
+    FilesystemPoisoned,
+    MediaChanged,
+    Busy,
+    Interrupted,
     Other,
 }
 
@@ -123,6 +142,12 @@ impl<'a, T: ?Sized + Write + 'a> Write for &'a mut T {
     }
 }
 
+impl<'a, T: ?Sized + Seek + 'a> Seek for &'a mut T {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        <T as Seek>::seek(&mut **self, pos)
+    }
+}
+
 impl<'a> Write for &'a mut [u8] {
     #[inline]
     fn write(&mut self, data: &[u8]) -> Result<usize> {
@@ -0,0 +1,24 @@
+// Subset of embedded-hal 0.2 traits used by the SD card driver.
+//
+// Signatures are the same as `embedded_hal::blocking::spi::Transfer` and
+// `embedded_hal::digital::v2::OutputPin`, so on a real target this module is replaced by
+// `use embedded_hal::blocking::spi::Transfer; use embedded_hal::digital::v2::OutputPin;` and the
+// driver is used with the SPI peripheral and chip select pin from the board HAL.
+
+pub mod spi {
+    pub trait Transfer<W> {
+        type Error;
+
+        fn transfer<'w>(&mut self, words: &'w mut [W]) -> Result<&'w [W], Self::Error>;
+    }
+}
+
+pub mod digital {
+    pub trait OutputPin {
+        type Error;
+
+        fn set_low(&mut self) -> Result<(), Self::Error>;
+
+        fn set_high(&mut self) -> Result<(), Self::Error>;
+    }
+}
@@ -0,0 +1,50 @@
+// Example of fatfs on an SD card connected over SPI.
+//
+// `sd` module contains a driver written against embedded-hal SPI and GPIO traits and a device
+// adapter implementing fatfs `Read`, `Write` and `Seek` traits on top of it. It can be used as
+// a template for embedded targets. On a host the card is simulated on the SPI byte level.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod hal;
+mod sd;
+mod sim;
+
+use basic_io::{Read, Seek, SeekFrom, Write};
+use fatfs::{FileSystem, FormatOptions, FsOptions};
+
+use sd::{SdCard, SdDevice};
+
+fn main() {
+    // 16 MB card
+    let (spi, cs) = sim::card(32 * 1024);
+    let mut card = SdCard::new(spi, cs);
+    card.init().expect("card initialization failed");
+    let mut device = SdDevice::new(card).unwrap();
+
+    fatfs::format_volume(&mut device, FormatOptions::new()).unwrap();
+    {
+        let fs = FileSystem::new(&mut device, FsOptions::new()).unwrap();
+        {
+            let mut dir = fs.root_dir().create_dir("logs").unwrap();
+            let mut file = dir.create_file("boot.txt").unwrap();
+            file.write_all(b"Hello from SD card!\n").unwrap();
+        }
+        fs.unmount().unwrap();
+    }
+
+    device.seek(SeekFrom::Start(0)).unwrap();
+    let fs = FileSystem::new(&mut device, FsOptions::new()).unwrap();
+    let mut file = fs.root_dir().open_file("logs/boot.txt").unwrap();
+    let mut buf = [0; 64];
+    let n = file.read(&mut buf).unwrap();
+    print!("{}", String::from_utf8_lossy(&buf[..n]));
+    let stats = fs.stats().unwrap();
+    println!(
+        "{} of {} clusters free, cluster size {} bytes",
+        stats.free_clusters,
+        stats.total_clusters,
+        fs.cluster_size()
+    );
+}
@@ -0,0 +1,346 @@
+// SD card driver in SPI mode and a fatfs device on top of it.
+//
+// Only SDHC/SDXC cards (block addressing, 512 byte blocks) are supported. The driver does not
+// allocate and can be copied to a no_std project as it is.
+
+use basic_io::{self as io, ErrorKind, Read, Seek, SeekFrom, Write};
+
+use hal::digital::OutputPin;
+use hal::spi::Transfer;
+
+pub const BLOCK_SIZE: usize = 512;
+
+const CMD_GO_IDLE_STATE: u8 = 0;
+const CMD_SEND_IF_COND: u8 = 8;
+const CMD_SEND_CSD: u8 = 9;
+const CMD_READ_SINGLE_BLOCK: u8 = 17;
+const CMD_WRITE_BLOCK: u8 = 24;
+const CMD_APP_CMD: u8 = 55;
+const CMD_READ_OCR: u8 = 58;
+const ACMD_SD_SEND_OP_COND: u8 = 41;
+
+const R1_IDLE_STATE: u8 = 0x01;
+const R1_ADDRESS_ERROR: u8 = 0x20;
+const DATA_START_TOKEN: u8 = 0xFE;
+const DATA_ACCEPTED: u8 = 0x05;
+const OCR_CCS: u32 = 0x4000_0000;
+
+// Number of bytes polled before giving up on a response
+const MAX_POLLS: u32 = 0x1_0000;
+
+#[derive(Debug)]
+pub enum Error<S, P> {
+    Spi(S),
+    Pin(P),
+    Timeout,
+    // Card returned an error in R1 response or rejected written data
+    Card(u8),
+    UnsupportedCard,
+}
+
+pub struct SdCard<SPI, CS> {
+    spi: SPI,
+    cs: CS,
+}
+
+impl<SPI, CS> SdCard<SPI, CS>
+where
+    SPI: Transfer<u8>,
+    CS: OutputPin,
+{
+    pub fn new(spi: SPI, cs: CS) -> Self {
+        SdCard { spi, cs }
+    }
+
+    // Switches the card to SPI mode and waits until its initialization finishes. SPI clock must
+    // be 100-400 kHz during initialization and can be raised afterwards.
+    pub fn init(&mut self) -> Result<(), Error<SPI::Error, CS::Error>> {
+        self.cs.set_high().map_err(Error::Pin)?;
+        // at least 74 clock cycles with chip select deasserted
+        for _ in 0..10 {
+            self.transfer_byte(0xFF)?;
+        }
+        self.select()?;
+        let r = self.init_selected();
+        self.deselect()?;
+        r
+    }
+
+    fn init_selected(&mut self) -> Result<(), Error<SPI::Error, CS::Error>> {
+        if self.command(CMD_GO_IDLE_STATE, 0)? != R1_IDLE_STATE {
+            return Err(Error::UnsupportedCard);
+        }
+        // SD version 2 cards echo the check pattern, older cards do not support the command
+        if self.command(CMD_SEND_IF_COND, 0x1AA)? != R1_IDLE_STATE {
+            return Err(Error::UnsupportedCard);
+        }
+        if self.read_u32()? & 0xFFF != 0x1AA {
+            return Err(Error::UnsupportedCard);
+        }
+        let mut polls = 0;
+        loop {
+            self.command(CMD_APP_CMD, 0)?;
+            match self.command(ACMD_SD_SEND_OP_COND, 0x4000_0000)? {
+                0 => break,
+                R1_IDLE_STATE if polls < MAX_POLLS => polls += 1,
+                R1_IDLE_STATE => return Err(Error::Timeout),
+                r => return Err(Error::Card(r)),
+            }
+        }
+        self.check_r1(CMD_READ_OCR, 0)?;
+        // byte addressed SDSC cards are not supported
+        if self.read_u32()? & OCR_CCS == 0 {
+            return Err(Error::UnsupportedCard);
+        }
+        Ok(())
+    }
+
+    // Returns number of blocks read from the CSD register
+    pub fn num_blocks(&mut self) -> Result<u64, Error<SPI::Error, CS::Error>> {
+        let mut csd = [0u8; 16];
+        self.select()?;
+        let r = self.check_r1(CMD_SEND_CSD, 0).and_then(|_| self.read_data(&mut csd));
+        self.deselect()?;
+        r?;
+        if csd[0] >> 6 != 1 {
+            return Err(Error::UnsupportedCard);
+        }
+        let c_size = (u64::from(csd[7] & 0x3F) << 16) | (u64::from(csd[8]) << 8) | u64::from(csd[9]);
+        Ok((c_size + 1) * 1024)
+    }
+
+    pub fn read_block(
+        &mut self,
+        block: u32,
+        buf: &mut [u8; BLOCK_SIZE],
+    ) -> Result<(), Error<SPI::Error, CS::Error>> {
+        self.select()?;
+        let r = self.check_r1(CMD_READ_SINGLE_BLOCK, block).and_then(|_| self.read_data(buf));
+        self.deselect()?;
+        r
+    }
+
+    pub fn write_block(
+        &mut self,
+        block: u32,
+        buf: &[u8; BLOCK_SIZE],
+    ) -> Result<(), Error<SPI::Error, CS::Error>> {
+        self.select()?;
+        let r = self.check_r1(CMD_WRITE_BLOCK, block).and_then(|_| self.write_data(buf));
+        self.deselect()?;
+        r
+    }
+
+    fn select(&mut self) -> Result<(), Error<SPI::Error, CS::Error>> {
+        self.cs.set_low().map_err(Error::Pin)
+    }
+
+    fn deselect(&mut self) -> Result<(), Error<SPI::Error, CS::Error>> {
+        self.cs.set_high().map_err(Error::Pin)?;
+        // card releases MISO line after the next clock cycles
+        self.transfer_byte(0xFF)?;
+        Ok(())
+    }
+
+    fn transfer_byte(&mut self, byte: u8) -> Result<u8, Error<SPI::Error, CS::Error>> {
+        let mut buf = [byte];
+        let r = self.spi.transfer(&mut buf).map_err(Error::Spi)?;
+        Ok(r[0])
+    }
+
+    fn command(&mut self, cmd: u8, arg: u32) -> Result<u8, Error<SPI::Error, CS::Error>> {
+        let mut frame = [
+            0x40 | cmd,
+            (arg >> 24) as u8,
+            (arg >> 16) as u8,
+            (arg >> 8) as u8,
+            arg as u8,
+            0x01,
+        ];
+        // CRC is only checked for CMD0 and CMD8 until CRC checking is enabled
+        frame[5] = match cmd {
+            CMD_GO_IDLE_STATE => 0x95,
+            CMD_SEND_IF_COND => 0x87,
+            _ => 0x01,
+        };
+        self.spi.transfer(&mut frame).map_err(Error::Spi)?;
+        // response comes after up to 8 bytes and has the top bit cleared
+        for _ in 0..8 {
+            let r = self.transfer_byte(0xFF)?;
+            if r & 0x80 == 0 {
+                return Ok(r);
+            }
+        }
+        Err(Error::Timeout)
+    }
+
+    fn check_r1(&mut self, cmd: u8, arg: u32) -> Result<(), Error<SPI::Error, CS::Error>> {
+        match self.command(cmd, arg)? {
+            0 => Ok(()),
+            r => Err(Error::Card(r)),
+        }
+    }
+
+    fn read_u32(&mut self) -> Result<u32, Error<SPI::Error, CS::Error>> {
+        let mut buf = [0xFF; 4];
+        self.spi.transfer(&mut buf).map_err(Error::Spi)?;
+        Ok(u32::from_be_bytes(buf))
+    }
+
+    fn read_data(&mut self, buf: &mut [u8]) -> Result<(), Error<SPI::Error, CS::Error>> {
+        let mut polls = 0;
+        loop {
+            match self.transfer_byte(0xFF)? {
+                DATA_START_TOKEN => break,
+                0xFF if polls < MAX_POLLS => polls += 1,
+                0xFF => return Err(Error::Timeout),
+                // data error token
+                token => return Err(Error::Card(token)),
+            }
+        }
+        for b in buf.iter_mut() {
+            *b = 0xFF;
+        }
+        self.spi.transfer(buf).map_err(Error::Spi)?;
+        // CRC is not checked
+        let mut crc = [0xFF; 2];
+        self.spi.transfer(&mut crc).map_err(Error::Spi)?;
+        Ok(())
+    }
+
+    fn write_data(&mut self, buf: &[u8; BLOCK_SIZE]) -> Result<(), Error<SPI::Error, CS::Error>> {
+        let mut data = *buf;
+        self.transfer_byte(DATA_START_TOKEN)?;
+        self.spi.transfer(&mut data).map_err(Error::Spi)?;
+        let mut crc = [0xFF; 2];
+        self.spi.transfer(&mut crc).map_err(Error::Spi)?;
+        let response = self.transfer_byte(0xFF)?;
+        if response & 0x1F != DATA_ACCEPTED {
+            return Err(Error::Card(response));
+        }
+        // card holds MISO low while it is busy programming the block
+        let mut polls = 0;
+        while self.transfer_byte(0xFF)? != 0xFF {
+            polls += 1;
+            if polls > MAX_POLLS {
+                return Err(Error::Timeout);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn convert_error<S, P>(err: Error<S, P>) -> io::Error {
+    match err {
+        Error::Timeout => io::Error::new(ErrorKind::Other, "SD card timeout"),
+        Error::Card(r) if r & R1_ADDRESS_ERROR != 0 => {
+            io::Error::new(ErrorKind::InvalidInput, "SD card address out of range")
+        }
+        Error::Card(_) => io::Error::new(ErrorKind::Other, "SD card error"),
+        Error::Spi(_) | Error::Pin(_) => io::Error::new(ErrorKind::Other, "SPI bus error"),
+        Error::UnsupportedCard => io::Error::new(ErrorKind::InvalidInput, "unsupported SD card"),
+    }
+}
+
+// Block device implementing `Read`, `Write` and `Seek` for fatfs.
+//
+// Filesystem accesses are usually whole sectors, but requests which are not block aligned are
+// handled by reading and modifying a single block buffer.
+pub struct SdDevice<SPI, CS> {
+    card: SdCard<SPI, CS>,
+    num_blocks: u64,
+    pos: u64,
+    block: [u8; BLOCK_SIZE],
+}
+
+impl<SPI, CS> SdDevice<SPI, CS>
+where
+    SPI: Transfer<u8>,
+    CS: OutputPin,
+{
+    pub fn new(mut card: SdCard<SPI, CS>) -> io::Result<Self> {
+        let num_blocks = card.num_blocks().map_err(convert_error)?;
+        Ok(SdDevice {
+            card,
+            num_blocks,
+            pos: 0,
+            block: [0; BLOCK_SIZE],
+        })
+    }
+
+    fn len(&self) -> u64 {
+        self.num_blocks * BLOCK_SIZE as u64
+    }
+
+    // Returns block index and offset in block of the current position and number of bytes of
+    // `len` that fit in the block
+    fn split_pos(&self, len: usize) -> (u32, usize, usize) {
+        let block = (self.pos / BLOCK_SIZE as u64) as u32;
+        let offset = (self.pos % BLOCK_SIZE as u64) as usize;
+        let remaining = (self.len() - self.pos) as usize;
+        let n = len.min(BLOCK_SIZE - offset).min(remaining);
+        (block, offset, n)
+    }
+}
+
+impl<SPI, CS> Read for SdDevice<SPI, CS>
+where
+    SPI: Transfer<u8>,
+    CS: OutputPin,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len() {
+            return Ok(0);
+        }
+        let (block, offset, n) = self.split_pos(buf.len());
+        self.card.read_block(block, &mut self.block).map_err(convert_error)?;
+        buf[..n].copy_from_slice(&self.block[offset..offset + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<SPI, CS> Write for SdDevice<SPI, CS>
+where
+    SPI: Transfer<u8>,
+    CS: OutputPin,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.pos >= self.len() {
+            return Err(io::Error::new(ErrorKind::NotEnoughSpace, "write beyond end of card"));
+        }
+        let (block, offset, n) = self.split_pos(buf.len());
+        if n < BLOCK_SIZE {
+            // partial block write
+            self.card.read_block(block, &mut self.block).map_err(convert_error)?;
+        }
+        self.block[offset..offset + n].copy_from_slice(&buf[..n]);
+        self.card.write_block(block, &self.block).map_err(convert_error)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // blocks are written to the card immediately
+        Ok(())
+    }
+}
+
+impl<SPI, CS> Seek for SdDevice<SPI, CS>
+where
+    SPI: Transfer<u8>,
+    CS: OutputPin,
+{
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(n) => n as i64,
+            SeekFrom::Current(n) => self.pos as i64 + n,
+            SeekFrom::End(n) => self.len() as i64 + n,
+        };
+        if new_pos < 0 || new_pos as u64 > self.len() {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "invalid seek"));
+        }
+        self.pos = new_pos as u64;
+        Ok(self.pos)
+    }
+}
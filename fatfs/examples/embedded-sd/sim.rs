@@ -0,0 +1,180 @@
+// Simulated SDHC card used to run the example on a host.
+//
+// The simulation works on the SPI byte level, so the driver goes through the same command
+// sequences as on hardware.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::rc::Rc;
+
+use hal::digital::OutputPin;
+use hal::spi::Transfer;
+use sd::BLOCK_SIZE;
+
+enum State {
+    Command(Vec<u8>),
+    WaitDataToken(u32),
+    ReceiveData(u32, Vec<u8>),
+}
+
+struct Card {
+    blocks: Vec<[u8; BLOCK_SIZE]>,
+    selected: bool,
+    idle: bool,
+    app_cmd: bool,
+    state: State,
+    output: VecDeque<u8>,
+}
+
+impl Card {
+    fn exchange(&mut self, input: u8) -> u8 {
+        let output = self.output.pop_front().unwrap_or(0xFF);
+        if self.selected {
+            self.receive(input);
+        }
+        output
+    }
+
+    fn receive(&mut self, input: u8) {
+        match self.state {
+            State::Command(ref mut frame) => {
+                if frame.is_empty() && input & 0xC0 != 0x40 {
+                    return;
+                }
+                frame.push(input);
+                if frame.len() < 6 {
+                    return;
+                }
+            }
+            State::WaitDataToken(block) => {
+                if input == 0xFE {
+                    self.state = State::ReceiveData(block, Vec::new());
+                }
+                return;
+            }
+            State::ReceiveData(block, ref mut data) => {
+                data.push(input);
+                // data and two CRC bytes
+                if data.len() == BLOCK_SIZE + 2 {
+                    self.blocks[block as usize].copy_from_slice(&data[..BLOCK_SIZE]);
+                    // data accepted and busy for a few bytes
+                    self.output.extend(&[0xE5, 0x00, 0x00, 0xFF]);
+                    self.state = State::Command(Vec::new());
+                }
+                return;
+            }
+        }
+        let frame = match ::std::mem::replace(&mut self.state, State::Command(Vec::new())) {
+            State::Command(frame) => frame,
+            _ => unreachable!(),
+        };
+        let arg = u32::from_be_bytes([frame[1], frame[2], frame[3], frame[4]]);
+        self.execute(frame[0] & 0x3F, arg);
+    }
+
+    fn execute(&mut self, cmd: u8, arg: u32) {
+        let r1 = if self.idle { 0x01 } else { 0x00 };
+        let app_cmd = self.app_cmd;
+        self.app_cmd = false;
+        // one byte gap before the response
+        self.output.push_back(0xFF);
+        match (app_cmd, cmd) {
+            (_, 0) => {
+                self.idle = true;
+                self.output.push_back(0x01);
+            }
+            (_, 8) => {
+                self.output.push_back(r1);
+                self.output.extend(&(arg & 0xFFF).to_be_bytes());
+            }
+            (_, 9) => {
+                let c_size = (self.blocks.len() / 1024 - 1) as u32;
+                let mut csd = [0u8; 16];
+                csd[0] = 0x40;
+                csd[7] = (c_size >> 16) as u8 & 0x3F;
+                csd[8] = (c_size >> 8) as u8;
+                csd[9] = c_size as u8;
+                self.output.extend(&[r1, 0xFF, 0xFE]);
+                self.output.extend(&csd);
+                self.output.extend(&[0, 0]);
+            }
+            (_, 17) if (arg as usize) < self.blocks.len() => {
+                self.output.extend(&[r1, 0xFF, 0xFE]);
+                self.output.extend(&self.blocks[arg as usize][..]);
+                self.output.extend(&[0, 0]);
+            }
+            (_, 24) if (arg as usize) < self.blocks.len() => {
+                self.output.push_back(r1);
+                self.state = State::WaitDataToken(arg);
+            }
+            (_, 55) => {
+                self.app_cmd = true;
+                self.output.push_back(r1);
+            }
+            (_, 58) => {
+                // powered up, high capacity
+                self.output.push_back(r1);
+                self.output.extend(&0xC0FF_8000u32.to_be_bytes());
+            }
+            (true, 41) => {
+                // initialization finishes on the second poll
+                self.output.push_back(r1);
+                self.idle = false;
+            }
+            (_, 17) | (_, 24) => {
+                // address error
+                self.output.push_back(r1 | 0x20);
+            }
+            _ => {
+                // illegal command
+                self.output.push_back(r1 | 0x04);
+            }
+        }
+    }
+}
+
+pub struct SimSpi(Rc<RefCell<Card>>);
+
+pub struct SimCs(Rc<RefCell<Card>>);
+
+// Creates SPI bus and chip select pin connected to a card of given size
+pub fn card(num_blocks: usize) -> (SimSpi, SimCs) {
+    let card = Rc::new(RefCell::new(Card {
+        blocks: vec![[0; BLOCK_SIZE]; num_blocks],
+        selected: false,
+        idle: false,
+        app_cmd: false,
+        state: State::Command(Vec::new()),
+        output: VecDeque::new(),
+    }));
+    (SimSpi(card.clone()), SimCs(card))
+}
+
+impl Transfer<u8> for SimSpi {
+    type Error = Infallible;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Infallible> {
+        let mut card = self.0.borrow_mut();
+        for w in words.iter_mut() {
+            *w = card.exchange(*w);
+        }
+        Ok(words)
+    }
+}
+
+impl OutputPin for SimCs {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.0.borrow_mut().selected = true;
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        let mut card = self.0.borrow_mut();
+        card.selected = false;
+        card.output.clear();
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+// Measures FAT and directory entry access paths: cluster allocation, chain traversal, free cluster
+// counting, and creating and listing directory entries.
+//
+// Run with `cargo run --release --example fat_bench`. Volumes are kept in memory and use 512 byte
+// clusters, so the time is dominated by FAT accesses.
+
+extern crate basic_io;
+extern crate fatfs;
+
+use std::time::{Duration, Instant};
+
+use basic_io::{Cursor, Read, Write};
+use fatfs::{FatType, FileSystem, FormatOptions, FsOptions};
+
+const MB: usize = 1024 * 1024;
+const ROUNDS: u32 = 5;
+
+fn format(fat_type: FatType, size: usize) -> Vec<u8> {
+    let mut data = vec![0u8; size];
+    let options = FormatOptions::new().fat_type(fat_type).bytes_per_cluster(512);
+    fatfs::format_volume(&mut Cursor::new(&mut data[..]), options).unwrap();
+    data
+}
+
+// Returns time of writing a file filling most of the volume cluster by cluster and time of
+// reading it back
+fn alloc_and_traverse(mut data: Vec<u8>) -> (Duration, Duration) {
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let clusters = fs.stats().unwrap().free_clusters as usize * 9 / 10;
+    let mut file = fs.root_dir().create_file("bench.bin").unwrap();
+    let mut buf = [0xA5u8; 512];
+    let start = Instant::now();
+    for _ in 0..clusters {
+        file.write_all(&buf).unwrap();
+    }
+    file.flush().unwrap();
+    let write_time = start.elapsed();
+    drop(file);
+    let mut file = fs.root_dir().open_file("bench.bin").unwrap();
+    let start = Instant::now();
+    while file.read(&mut buf).unwrap() > 0 {}
+    (write_time, start.elapsed())
+}
+
+// Returns time of counting free clusters when mounting
+fn count_free(data: &mut Vec<u8>) -> Duration {
+    if data[22] == 0 && data[23] == 0 {
+        // FAT32 - invalidate free cluster count in FSInfo sector
+        let fs_info = (data[48] as usize | (data[49] as usize) << 8) * 512;
+        data[fs_info + 488..fs_info + 492].copy_from_slice(&[0xFF; 4]);
+    }
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let start = Instant::now();
+    fs.stats().unwrap();
+    start.elapsed()
+}
+
+// Returns time of creating files in a directory and time of listing it repeatedly
+fn dir_entries(mut data: Vec<u8>) -> (Duration, Duration) {
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut dir = fs.root_dir().create_dir("dir").unwrap();
+    let start = Instant::now();
+    for i in 0..500 {
+        dir.create_file(&format!("F{}.TXT", i)).unwrap();
+    }
+    let create_time = start.elapsed();
+    let start = Instant::now();
+    for _ in 0..20 {
+        assert_eq!(dir.iter().count(), 502);
+    }
+    (create_time, start.elapsed())
+}
+
+fn main() {
+    let volumes = [(FatType::Fat12, 2 * MB), (FatType::Fat16, 32 * MB), (FatType::Fat32, 64 * MB)];
+    let header = ["clusters", "alloc", "traverse", "count", "create", "list"];
+    print!("{:8} {:>10}", "", header[0]);
+    for name in header[1..].iter() {
+        print!(" {:>10}", name);
+    }
+    println!();
+    for &(fat_type, size) in volumes.iter() {
+        let mut data = format(fat_type, size);
+        let total = FileSystem::new(&mut Cursor::new(&mut data[..]), FsOptions::new())
+            .unwrap()
+            .stats()
+            .unwrap()
+            .total_clusters;
+        let mut times = [Duration::new(0, 0); 5];
+        for _ in 0..ROUNDS {
+            let (alloc, traverse) = alloc_and_traverse(data.clone());
+            let (create, list) = dir_entries(data.clone());
+            times[0] += alloc;
+            times[1] += traverse;
+            times[2] += count_free(&mut data);
+            times[3] += create;
+            times[4] += list;
+        }
+        print!("{:8} {:>10}", format!("{:?}", fat_type), total);
+        for time in times.iter() {
+            print!(" {:>8.2}ms", time.as_secs_f64() * 1000.0 / ROUNDS as f64);
+        }
+        println!();
+    }
+}
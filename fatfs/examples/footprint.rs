@@ -0,0 +1,21 @@
+extern crate fatfs;
+
+use fatfs::footprint;
+
+fn main() {
+    println!("FileSystem:       {:5} bytes", footprint::FILE_SYSTEM);
+    println!("File:             {:5} bytes", footprint::FILE);
+    println!("Dir:              {:5} bytes", footprint::DIR);
+    println!("DirIter:          {:5} bytes", footprint::DIR_ITER);
+    println!("DirEntry:         {:5} bytes", footprint::DIR_ENTRY);
+    println!("max stack buffer: {:5} bytes", footprint::MAX_STACK_BUFFER);
+    print_fat_cache();
+}
+
+#[cfg(feature = "alloc")]
+fn print_fat_cache() {
+    println!("FAT cache:        {:5} bytes + cached sectors", footprint::FAT_CACHE);
+}
+
+#[cfg(not(feature = "alloc"))]
+fn print_fat_cache() {}
@@ -0,0 +1,77 @@
+/* C interface of fatfs library (enabled by "ffi" feature). See src/ffi.rs for details. */
+
+#ifndef FATFS_H
+#define FATFS_H
+
+#include <stddef.h>
+#include <stdint.h>
+#include <sys/types.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define FATFS_OK 0
+#define FATFS_ERR_INVALID_INPUT (-1)
+#define FATFS_ERR_NOT_FOUND (-2)
+#define FATFS_ERR_UNEXPECTED_EOF (-3)
+#define FATFS_ERR_WRITE_ZERO (-4)
+#define FATFS_ERR_NOT_ENOUGH_SPACE (-5)
+#define FATFS_ERR_FILE_TOO_LARGE (-6)
+#define FATFS_ERR_OTHER (-7)
+#define FATFS_ERR_ALREADY_EXISTS (-8)
+#define FATFS_ERR_CORRUPTED_FILE (-9)
+#define FATFS_ERR_TRUNCATED_VOLUME (-10)
+#define FATFS_ERR_NOT_A_DIRECTORY (-11)
+#define FATFS_ERR_IS_A_DIRECTORY (-12)
+#define FATFS_ERR_DIRECTORY_NOT_EMPTY (-13)
+#define FATFS_ERR_NAME_TOO_LONG (-14)
+#define FATFS_ERR_PATH_TOO_DEEP (-15)
+#define FATFS_ERR_TOO_MANY_ENTRIES (-16)
+#define FATFS_ERR_INTERRUPTED (-17)
+#define FATFS_ERR_DIRECTORY_FULL (-18)
+#define FATFS_ERR_FILESYSTEM_POISONED (-19)
+#define FATFS_ERR_MEDIA_CHANGED (-20)
+#define FATFS_ERR_PANIC (-21)
+#define FATFS_ERR_BUSY (-22)
+
+#define FATFS_SEEK_SET 0
+#define FATFS_SEEK_CUR 1
+#define FATFS_SEEK_END 2
+
+typedef struct fatfs_fs fatfs_fs;
+typedef struct fatfs_file fatfs_file;
+typedef struct fatfs_dir fatfs_dir;
+
+typedef struct fatfs_device {
+    void *ctx;
+    ssize_t (*read)(void *ctx, uint8_t *buf, size_t len);
+    ssize_t (*write)(void *ctx, const uint8_t *buf, size_t len);
+    int64_t (*seek)(void *ctx, int64_t offset, int whence);
+    int (*flush)(void *ctx);
+} fatfs_device;
+
+typedef struct fatfs_dirent {
+    char name[13];
+    uint8_t attributes;
+    uint64_t size;
+} fatfs_dirent;
+
+int fatfs_mount(const fatfs_device *device, fatfs_fs **out);
+int fatfs_unmount(fatfs_fs *fs);
+
+int fatfs_open(fatfs_fs *fs, const char *path, int create, fatfs_file **out);
+ssize_t fatfs_read(fatfs_file *file, uint8_t *buf, size_t len);
+ssize_t fatfs_write(fatfs_file *file, const uint8_t *buf, size_t len);
+int64_t fatfs_seek(fatfs_file *file, int64_t offset, int whence);
+int fatfs_close(fatfs_file *file);
+
+int fatfs_opendir(fatfs_fs *fs, const char *path, fatfs_dir **out);
+int fatfs_readdir(fatfs_dir *dir, fatfs_dirent *out);
+int fatfs_closedir(fatfs_dir *dir);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* FATFS_H */
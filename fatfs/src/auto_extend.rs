@@ -0,0 +1,103 @@
+use core::cmp;
+use io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::io as std_io;
+
+/// Device adapter for host files which grow lazily (enabled by `std` feature).
+///
+/// Wraps a `std::io` stream (usually `std::fs::File`) and presents it as a device of a fixed
+/// logical size. Parts of the device past the end of the backing stream read as zeros and writes
+/// beyond it extend the stream, so a volume can be formatted and used starting from an empty
+/// file. On most host filesystems skipped parts of the file are stored as holes.
+pub struct AutoExtend<T> {
+    inner: T,
+    len: u64,
+    backing_len: u64,
+    pos: u64,
+}
+
+impl<T: std_io::Read + std_io::Write + std_io::Seek> AutoExtend<T> {
+    /// Creates a device of `len` bytes backed by `inner`.
+    ///
+    /// If the backing stream is already longer the device size is the stream length.
+    pub fn new(mut inner: T, len: u64) -> std_io::Result<Self> {
+        let backing_len = inner.seek(std_io::SeekFrom::End(0))?;
+        Ok(AutoExtend {
+            inner,
+            len: cmp::max(len, backing_len),
+            backing_len,
+            pos: 0,
+        })
+    }
+
+    /// Returns length of the backing stream.
+    pub fn backing_len(&self) -> u64 {
+        self.backing_len
+    }
+
+    /// Returns the wrapped stream.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+fn convert_error(err: std_io::Error) -> io::Error {
+    let kind = match err.kind() {
+        std_io::ErrorKind::NotFound => ErrorKind::NotFound,
+        std_io::ErrorKind::AlreadyExists => ErrorKind::AlreadyExists,
+        std_io::ErrorKind::InvalidInput => ErrorKind::InvalidInput,
+        std_io::ErrorKind::UnexpectedEof => ErrorKind::UnexpectedEof,
+        std_io::ErrorKind::WriteZero => ErrorKind::WriteZero,
+        _ => ErrorKind::Other,
+    };
+    io::Error::new(kind, "backing stream error")
+}
+
+impl<T: std_io::Read + std_io::Write + std_io::Seek> Read for AutoExtend<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = if self.pos < self.backing_len {
+            let n = cmp::min(buf.len() as u64, self.backing_len - self.pos) as usize;
+            self.inner.seek(std_io::SeekFrom::Start(self.pos)).map_err(convert_error)?;
+            self.inner.read(&mut buf[..n]).map_err(convert_error)?
+        } else {
+            // not written yet
+            let n = cmp::min(buf.len() as u64, self.len.saturating_sub(self.pos)) as usize;
+            for b in &mut buf[..n] {
+                *b = 0;
+            }
+            n
+        };
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<T: std_io::Read + std_io::Write + std_io::Seek> Write for AutoExtend<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // seeking past the end and writing makes the stream grow
+        self.inner.seek(std_io::SeekFrom::Start(self.pos)).map_err(convert_error)?;
+        let n = self.inner.write(buf).map_err(convert_error)?;
+        self.pos += n as u64;
+        self.backing_len = cmp::max(self.backing_len, self.pos);
+        self.len = cmp::max(self.len, self.pos);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush().map_err(convert_error)
+    }
+}
+
+impl<T: std_io::Read + std_io::Write + std_io::Seek> Seek for AutoExtend<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(n) => n as i64,
+            SeekFrom::Current(n) => self.pos as i64 + n,
+            SeekFrom::End(n) => self.len as i64 + n,
+        };
+        if new_pos < 0 {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "invalid seek"));
+        }
+        self.pos = new_pos as u64;
+        Ok(self.pos)
+    }
+}
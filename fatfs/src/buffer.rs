@@ -0,0 +1,100 @@
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// A source of memory buffers for library caches.
+///
+/// Caches kept by the filesystem (currently the FAT cache used by `FsOptions::lazy_fat_writes`)
+/// take their buffers from the provider set by `FsOptions::buffer_provider`, so integrators decide
+/// where the memory comes from (e.g. a static array placed in a specific RAM region) and how much
+/// of it can be used.
+pub trait BufferProvider {
+    /// Returns a buffer of exactly `size` bytes or `None` if the budget is exhausted.
+    ///
+    /// Caches work without the buffer when `None` is returned, so running out of budget makes
+    /// the library slower but never makes an operation fail.
+    fn alloc(&self, size: usize) -> Option<&'static mut [u8]>;
+
+    /// Gives back a buffer returned by `alloc`.
+    ///
+    /// # Safety
+    ///
+    /// `buf` must have been returned by `alloc` of the same provider and must not have been freed
+    /// already. The caller must not keep any reference to the buffer afterwards.
+    unsafe fn free(&self, buf: &'static mut [u8]);
+}
+
+/// `BufferProvider` implementation that never provides any buffers.
+///
+/// It is used by default if `alloc` feature is disabled.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NullBufferProvider;
+
+impl BufferProvider for NullBufferProvider {
+    fn alloc(&self, _size: usize) -> Option<&'static mut [u8]> {
+        None
+    }
+
+    unsafe fn free(&self, _buf: &'static mut [u8]) {
+        // alloc never returns a buffer so there is nothing that could be freed
+    }
+}
+
+/// `BufferProvider` implementation allocating buffers on heap up to a budget (enabled by `alloc`
+/// feature).
+///
+/// Provider without a budget limit is used by default if `alloc` feature is enabled.
+#[cfg(feature = "alloc")]
+#[derive(Debug)]
+pub struct HeapBufferProvider {
+    budget: usize,
+    used: AtomicUsize,
+}
+
+#[cfg(feature = "alloc")]
+impl HeapBufferProvider {
+    /// Creates provider allocating at most `budget` bytes in total.
+    pub const fn new(budget: usize) -> Self {
+        HeapBufferProvider {
+            budget,
+            used: AtomicUsize::new(0),
+        }
+    }
+
+    /// Creates provider without a budget limit.
+    pub const fn unlimited() -> Self {
+        Self::new(usize::MAX)
+    }
+
+    /// Returns number of bytes in buffers currently allocated by this provider.
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl BufferProvider for HeapBufferProvider {
+    fn alloc(&self, size: usize) -> Option<&'static mut [u8]> {
+        let reserved = self.used.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+            used.checked_add(size).filter(|&n| n <= self.budget)
+        });
+        match reserved {
+            Ok(_) => {
+                let mut buf = Vec::new();
+                buf.resize(size, 0);
+                Some(Box::leak(buf.into_boxed_slice()))
+            }
+            Err(_) => None,
+        }
+    }
+
+    unsafe fn free(&self, buf: &'static mut [u8]) {
+        self.used.fetch_sub(buf.len(), Ordering::Relaxed);
+        // SAFE: caller guarantees that buf is a leaked box created by alloc and that ownership
+        // is given back here
+        drop(Box::from_raw(buf as *mut [u8]));
+    }
+}
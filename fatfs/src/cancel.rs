@@ -0,0 +1,74 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use io;
+use io::ErrorKind;
+
+/// A source of cancellation requests for long running operations.
+///
+/// Library polls the token set by `FsOptions::cancellation_token` inside loops which time depends
+/// on the volume size: FAT scans (free cluster search and counting), directory scans and cluster
+/// chain walks. When the token reports cancellation the operation is aborted with
+/// `ErrorKind::Interrupted` error. It allows systems guarded by a watchdog to bound the time spent
+/// in a single library call.
+///
+/// Operations are aborted between reads of FAT entries or directory entries, so filesystem
+/// metadata is never left half-updated by a single entry. An aborted operation which modifies the
+/// volume may still leave it partially done (e.g. file extended by some of the requested clusters).
+pub trait CancellationToken {
+    /// Returns `true` if current operation should be aborted.
+    fn is_cancelled(&self) -> bool;
+}
+
+/// `CancellationToken` implementation that never cancels anything.
+///
+/// It is used by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NullCancellationToken;
+
+impl CancellationToken for NullCancellationToken {
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+/// `CancellationToken` implementation backed by an atomic flag.
+///
+/// Flag can be raised from another thread or an interrupt handler (e.g. watchdog early warning
+/// interrupt). It stays raised until `reset` is called, so every operation started in the meantime
+/// fails too.
+#[derive(Debug, Default)]
+pub struct FlagCancellationToken {
+    cancelled: AtomicBool,
+}
+
+impl FlagCancellationToken {
+    /// Creates a token with the flag lowered.
+    pub const fn new() -> Self {
+        FlagCancellationToken {
+            cancelled: AtomicBool::new(false),
+        }
+    }
+
+    /// Requests cancellation of current and all future operations.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Lowers the flag so operations can run again.
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::SeqCst);
+    }
+}
+
+impl CancellationToken for FlagCancellationToken {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+pub(crate) fn check_cancelled(token: &CancellationToken) -> io::Result<()> {
+    match token.is_cancelled() {
+        true => Err(io::Error::new(ErrorKind::Interrupted, "operation cancelled")),
+        false => Ok(()),
+    }
+}
@@ -0,0 +1,471 @@
+use io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+
+use cancel::check_cancelled;
+use dir::{Dir, DOTDOT_SHORT_NAME, DOT_SHORT_NAME};
+use dir_entry::{DirEntry, DirEntryData, DIR_ENTRY_SIZE};
+use fs::{FileSystem, ReadWriteSeek};
+
+use units::Cluster;
+/// A kind of problem found by the filesystem check.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CheckIssueKind {
+    /// Cluster chain contains a cluster outside of the data area.
+    InvalidCluster,
+    /// Cluster chain contains a loop.
+    ClusterLoop,
+    /// File size does not match length of its cluster chain.
+    SizeMismatch,
+    /// Directory has no clusters allocated.
+    EmptyDirectory,
+    /// Directory does not start with "." and ".." entries.
+    MissingDotEntries,
+    /// "." or ".." entry points to an invalid cluster or is not marked as a directory.
+    InvalidDotEntry,
+    /// "." or ".." entry found outside of the beginning of a directory.
+    UnexpectedDotEntry,
+    /// Free cluster count stored in FSInfo sector does not match the FAT.
+    FreeClusterCountMismatch,
+    /// First FAT entry does not contain media descriptor or second one is not end of chain marker.
+    InvalidReservedFatEntries,
+    /// Root directory contains more than one volume label entry.
+    DuplicateVolumeLabel,
+}
+
+impl CheckIssueKind {
+    /// Returns human-readable description of the problem.
+    pub fn description(&self) -> &'static str {
+        match *self {
+            CheckIssueKind::InvalidCluster => "cluster chain contains invalid cluster",
+            CheckIssueKind::ClusterLoop => "cluster chain contains a loop",
+            CheckIssueKind::SizeMismatch => "file size does not match cluster chain length",
+            CheckIssueKind::EmptyDirectory => "directory has no clusters",
+            CheckIssueKind::MissingDotEntries => "directory is missing dot entries",
+            CheckIssueKind::InvalidDotEntry => "dot entry is invalid",
+            CheckIssueKind::UnexpectedDotEntry => "unexpected dot entry",
+            CheckIssueKind::FreeClusterCountMismatch => "free cluster count does not match FAT",
+            CheckIssueKind::InvalidReservedFatEntries => "invalid reserved FAT entries",
+            CheckIssueKind::DuplicateVolumeLabel => "duplicate volume label entry",
+        }
+    }
+}
+
+/// A problem found by the filesystem check.
+///
+/// Passed to the callback given to `FileSystem::check`.
+#[derive(Debug, Copy, Clone)]
+pub struct CheckIssue<'n> {
+    /// Kind of the problem.
+    pub kind: CheckIssueKind,
+    /// Short name of the affected entry. Empty if problem is not related to a single entry (e.g.
+    /// root directory or FSInfo sector).
+    pub name: &'n str,
+    /// True if the problem has been repaired.
+    pub repaired: bool,
+}
+
+/// Options for the filesystem check.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CheckOptions {
+    repair: bool,
+    rebuild_fs_info: bool,
+}
+
+impl CheckOptions {
+    /// Creates `CheckOptions` struct with default options. By default nothing is modified.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enables repair of found problems.
+    ///
+    /// Wrong file sizes are fixed by freeing clusters past the end of file or by shrinking the file
+    /// to the length of its cluster chain. Free cluster count is recalculated if it is wrong.
+    /// Reserved FAT entries are rewritten if they are malformed. Invalid "." and ".." entries are
+    /// rebuilt, missing ones are recreated if their slots at the beginning of the directory are
+    /// free. Volume label entries following the first one are deleted. Remaining problems are only
+    /// reported.
+    pub fn repair(mut self, repair: bool) -> Self {
+        self.repair = repair;
+        self
+    }
+
+    /// Enables unconditional recalculation of FSInfo sector fields.
+    pub fn rebuild_fs_info(mut self, rebuild_fs_info: bool) -> Self {
+        self.rebuild_fs_info = rebuild_fs_info;
+        self
+    }
+}
+
+/// Result of the filesystem check.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct CheckSummary {
+    /// Number of found problems.
+    pub issues: u32,
+    /// Number of repaired problems.
+    pub repaired: u32,
+}
+
+impl CheckSummary {
+    /// Returns true if no problems were found.
+    pub fn is_clean(&self) -> bool {
+        self.issues == 0
+    }
+}
+
+/// First difference between FAT copies found by `FileSystem::check_fat_consistency`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FatDivergence {
+    /// Index of the FAT copy which differs from the first copy.
+    pub fat: u8,
+    /// Index of the first differing sector counted from the beginning of the FAT.
+    pub sector: u32,
+}
+
+// FAT copies are compared in chunks so the buffers do not depend on sector size
+const FAT_CHUNK_SIZE: usize = 512;
+
+struct Checker<'a, 'c, IO: ReadWriteSeek + 'a> {
+    fs: &'a FileSystem<IO>,
+    options: CheckOptions,
+    report: &'c mut FnMut(&CheckIssue),
+    summary: CheckSummary,
+}
+
+impl<'a, 'c, IO: ReadWriteSeek> Checker<'a, 'c, IO> {
+    fn issue(&mut self, kind: CheckIssueKind, name: &str, repaired: bool) {
+        self.summary.issues += 1;
+        if repaired {
+            self.summary.repaired += 1;
+        }
+        (self.report)(&CheckIssue {
+            kind,
+            name,
+            repaired,
+        });
+    }
+
+    fn is_valid_cluster(&self, cluster: Cluster) -> bool {
+        cluster.0 >= 2 && cluster.0 < self.fs.total_clusters + 2
+    }
+
+    // Returns number of clusters in the chain or None if chain is broken
+    fn check_chain(&mut self, first_cluster: Cluster, name: &str) -> io::Result<Option<u32>> {
+        if !self.is_valid_cluster(first_cluster) {
+            self.issue(CheckIssueKind::InvalidCluster, name, false);
+            return Ok(None);
+        }
+        let mut num_clusters = 1;
+        for r in self.fs.cluster_iter(first_cluster) {
+            if !self.is_valid_cluster(r?) {
+                self.issue(CheckIssueKind::InvalidCluster, name, false);
+                return Ok(None);
+            }
+            num_clusters += 1;
+            if num_clusters > self.fs.total_clusters {
+                self.issue(CheckIssueKind::ClusterLoop, name, false);
+                return Ok(None);
+            }
+        }
+        Ok(Some(num_clusters))
+    }
+
+    fn check_file_size(&mut self, entry: &DirEntry<IO>, num_clusters: u32) -> io::Result<()> {
+        let cluster_size = self.fs.cluster_size() as u64;
+        let max_size = num_clusters as u64 * cluster_size;
+        let min_size = max_size.saturating_sub(cluster_size - 1);
+        let too_big = entry.len() > max_size;
+        if !too_big && (num_clusters == 0 || entry.len() >= min_size) {
+            return Ok(());
+        }
+        if self.options.repair {
+            let mut editor = entry.editor();
+            if too_big {
+                // cluster chain is too short - shrink the file
+                editor.set_size(max_size as u32);
+            } else {
+                // cluster chain is too long - free clusters past the end of file
+                let first_cluster = entry.first_cluster().unwrap(); // SAFE: num_clusters > 0
+                let needed_clusters = (entry.len() + cluster_size - 1) / cluster_size;
+                if needed_clusters == 0 {
+                    self.fs.free_cluster_chain(first_cluster)?;
+                    editor.set_first_cluster(None, self.fs.fat_type());
+                } else {
+                    let mut last_cluster = first_cluster;
+                    let mut iter = self.fs.cluster_iter(first_cluster);
+                    for _ in 1..needed_clusters {
+                        last_cluster = iter.next().unwrap()?; // SAFE: chain is long enough
+                    }
+                    self.fs.truncate_cluster_chain(last_cluster)?;
+                }
+            }
+            editor.flush(self.fs)?;
+        }
+        let repaired = self.options.repair;
+        self.issue(CheckIssueKind::SizeMismatch, entry.short_file_name(), repaired);
+        Ok(())
+    }
+
+    // Checks "." and ".." entries which must occupy first two slots of a directory
+    fn check_dot_entries(
+        &mut self,
+        entry: &DirEntry<IO>,
+        parent_cluster: Option<Cluster>,
+    ) -> io::Result<()> {
+        let fat_type = self.fs.fat_type();
+        let dir_cluster = entry.first_cluster().unwrap(); // SAFE: checked by caller
+        let dots = [(DOT_SHORT_NAME, Some(dir_cluster)), (DOTDOT_SHORT_NAME, parent_cluster)];
+        for (i, &(dot_name, cluster)) in dots.iter().enumerate() {
+            let pos = self.fs.offset_from_cluster(dir_cluster) + i as u64 * DIR_ENTRY_SIZE;
+            let raw_entry = {
+                let mut disk = self.fs.disk.borrow_mut();
+                disk.seek(SeekFrom::Start(pos))?;
+                DirEntryData::deserialize(&mut *disk)?
+            };
+            let (kind, repairable) = match raw_entry {
+                DirEntryData::File(ref data) if data.name() == &dot_name => {
+                    if data.is_dir() && data.first_cluster(fat_type) == cluster {
+                        continue;
+                    }
+                    (CheckIssueKind::InvalidDotEntry, true)
+                }
+                // slot can be reused only if it does not belong to another file
+                DirEntryData::File(ref data) if data.is_free() || data.is_end() => {
+                    (CheckIssueKind::MissingDotEntries, true)
+                }
+                DirEntryData::Lfn(ref data) if data.is_free() => {
+                    (CheckIssueKind::MissingDotEntries, true)
+                }
+                _ => (CheckIssueKind::MissingDotEntries, false),
+            };
+            let repaired = self.options.repair && repairable;
+            if repaired {
+                // dot entries share timestamps with the directory entry in parent directory
+                let mut dot_entry = entry.data.clone();
+                dot_entry.set_name(dot_name);
+                dot_entry.set_first_cluster(cluster, fat_type);
+                let mut disk = self.fs.disk.borrow_mut();
+                disk.seek(SeekFrom::Start(pos))?;
+                dot_entry.serialize(&mut *disk)?;
+            }
+            self.issue(kind, entry.short_file_name(), repaired);
+        }
+        Ok(())
+    }
+
+    fn check_dir(&mut self, dir: &Dir<IO>, dir_cluster: Option<Cluster>) -> io::Result<()> {
+        for r in dir.iter() {
+            let entry = r?;
+            let entry_name = entry.short_file_name();
+            if entry_name == "." || entry_name == ".." {
+                // dot entries at the beginning of a directory are checked by check_dot_entries
+                let expected_offset = if entry_name == "." { 0 } else { DIR_ENTRY_SIZE };
+                if dir_cluster.is_none() || entry.offset_range.0 != expected_offset {
+                    self.issue(CheckIssueKind::UnexpectedDotEntry, entry_name, false);
+                }
+                continue;
+            }
+            let num_clusters = match entry.first_cluster() {
+                Some(n) => match self.check_chain(n, entry_name)? {
+                    Some(num) => num,
+                    None => continue,
+                },
+                None => 0,
+            };
+            if entry.is_dir() {
+                if entry.first_cluster().is_none() {
+                    self.issue(CheckIssueKind::EmptyDirectory, entry_name, false);
+                    continue;
+                }
+                self.check_dot_entries(&entry, dir_cluster)?;
+                self.check_dir(&entry.to_dir(), entry.first_cluster())?;
+            } else {
+                self.check_file_size(&entry, num_clusters)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_free_clusters(&mut self) -> io::Result<()> {
+        let free_clusters = self.fs.count_free_clusters()?;
+        let repaired = self.options.repair || self.options.rebuild_fs_info;
+        match self.fs.cached_free_clusters() {
+            Some(n) if n != free_clusters => {
+                if repaired {
+                    self.fs.set_free_clusters(free_clusters);
+                }
+                self.issue(CheckIssueKind::FreeClusterCountMismatch, "", repaired);
+            }
+            _ => {}
+        }
+        if self.options.rebuild_fs_info {
+            self.fs.rebuild_fs_info(free_clusters);
+        }
+        Ok(())
+    }
+
+    fn check_reserved_fat_entries(&mut self) -> io::Result<()> {
+        if !self.fs.reserved_fat_entries_valid()? {
+            if self.options.repair {
+                self.fs.fix_reserved_fat_entries()?;
+            }
+            let repaired = self.options.repair;
+            self.issue(CheckIssueKind::InvalidReservedFatEntries, "", repaired);
+        }
+        Ok(())
+    }
+
+    // Only the first volume label entry is used as the label - others are deleted by repair
+    fn check_volume_entries(&mut self) -> io::Result<()> {
+        let root_dir = self.fs.root_dir();
+        for r in root_dir.volume_entries().skip(1) {
+            let entry = r?;
+            if self.options.repair {
+                root_dir.free_raw_entry(entry.offset())?;
+            }
+            let repaired = self.options.repair;
+            self.issue(CheckIssueKind::DuplicateVolumeLabel, entry.volume_label(), repaired);
+        }
+        Ok(())
+    }
+
+    fn check(&mut self) -> io::Result<()> {
+        self.check_reserved_fat_entries()?;
+        let root_ok = match self.fs.root_dir_cluster() {
+            Some(n) => self.check_chain(n, "")?.is_some(),
+            None => true,
+        };
+        if root_ok {
+            self.check_volume_entries()?;
+            self.check_dir(&self.fs.root_dir(), None)?;
+        }
+        self.check_free_clusters()
+    }
+}
+
+impl<IO: ReadWriteSeek> FileSystem<IO> {
+    /// Checks consistency of filesystem structures and optionally repairs found problems.
+    ///
+    /// All directories reachable from the root directory are walked and following conditions are
+    /// checked:
+    ///
+    /// * cluster chains contain only valid data clusters and do not loop,
+    /// * file sizes match lengths of their cluster chains,
+    /// * directories start with valid "." and ".." entries,
+    /// * reserved FAT entries contain media descriptor and end of chain marker,
+    /// * root directory contains at most one volume label entry,
+    /// * free cluster count maintained by the filesystem matches the FAT.
+    ///
+    /// Every found problem is passed to `report` callback. Repairs are controlled by `options`.
+    /// Changes made by repairs are written to the FSInfo sector when filesystem is unmounted.
+    pub fn check(
+        &self,
+        options: CheckOptions,
+        report: &mut FnMut(&CheckIssue),
+    ) -> io::Result<CheckSummary> {
+        let mut checker = Checker {
+            fs: self,
+            options,
+            report,
+            summary: CheckSummary::default(),
+        };
+        checker.check()?;
+        Ok(checker.summary)
+    }
+
+    /// Compares FAT copies sector by sector.
+    ///
+    /// Every copy is compared with the first one and the first found difference is returned.
+    /// Copies differ legitimately on FAT32 volumes with mirroring disabled - only the active copy
+    /// is updated then.
+    ///
+    /// If `repair_from` is given, FAT copy with that index is written over all other copies (only
+    /// differing sectors are written). The copy is not validated so the volume should be checked
+    /// by `check` afterwards. Returned difference is the one found before the repair.
+    ///
+    /// Fails with `ErrorKind::InvalidInput` if `repair_from` is not an index of a FAT copy.
+    pub fn check_fat_consistency(
+        &self,
+        repair_from: Option<u8>,
+    ) -> io::Result<Option<FatDivergence>> {
+        let (fats, first_fat_offset, fat_size) = self.fat_copies();
+        match repair_from {
+            Some(n) if n >= fats => {
+                return Err(io::Error::new(ErrorKind::InvalidInput, "invalid FAT index"));
+            }
+            _ => {}
+        }
+        // compare what is stored on the device
+        self.flush_fat()?;
+        let bytes_per_sector = self.bytes_per_sector() as u64;
+        let cancel = self.options.cancellation_token;
+        let mut reference = [0u8; FAT_CHUNK_SIZE];
+        let mut other = [0u8; FAT_CHUNK_SIZE];
+        let mut divergence = None;
+        let mut offset = 0;
+        while offset < fat_size && divergence.is_none() {
+            check_cancelled(cancel)?;
+            self.read_fat_chunk(first_fat_offset + offset, &mut reference)?;
+            for fat in 1..fats {
+                self.read_fat_chunk(first_fat_offset + fat as u64 * fat_size + offset, &mut other)?;
+                if reference != other {
+                    let sector = (offset / bytes_per_sector) as u32;
+                    divergence = Some(FatDivergence { fat, sector });
+                    break;
+                }
+            }
+            offset += FAT_CHUNK_SIZE as u64;
+        }
+        match (divergence, repair_from) {
+            (Some(_), Some(n)) => self.copy_fat(n, first_fat_offset, fat_size)?,
+            _ => {}
+        }
+        Ok(divergence)
+    }
+
+    // Writes FAT copy `src` over all other copies
+    fn copy_fat(&self, src: u8, first_fat_offset: u64, fat_size: u64) -> io::Result<()> {
+        let (fats, _, _) = self.fat_copies();
+        let cancel = self.options.cancellation_token;
+        let mut reference = [0u8; FAT_CHUNK_SIZE];
+        let mut other = [0u8; FAT_CHUNK_SIZE];
+        let mut offset = 0;
+        while offset < fat_size {
+            check_cancelled(cancel)?;
+            self.read_fat_chunk(first_fat_offset + src as u64 * fat_size + offset, &mut reference)?;
+            for fat in (0..fats).filter(|&n| n != src) {
+                let fat_offset = first_fat_offset + fat as u64 * fat_size + offset;
+                self.read_fat_chunk(fat_offset, &mut other)?;
+                if reference != other {
+                    let mut disk = self.disk.borrow_mut();
+                    disk.seek(SeekFrom::Start(fat_offset))?;
+                    disk.write_all(&reference)?;
+                }
+            }
+            offset += FAT_CHUNK_SIZE as u64;
+        }
+        Ok(())
+    }
+
+    fn read_fat_chunk(&self, offset: u64, buf: &mut [u8; FAT_CHUNK_SIZE]) -> io::Result<()> {
+        let mut disk = self.disk.borrow_mut();
+        disk.seek(SeekFrom::Start(offset))?;
+        disk.read_exact(buf)
+    }
+
+    /// Verifies invariants of filesystem structures.
+    ///
+    /// Runs `check` without repairs and returns error describing the first found problem. It is
+    /// meant to be used in tests after modifying the filesystem.
+    pub fn verify_invariants(&self) -> io::Result<()> {
+        let mut first_issue = None;
+        self.check(CheckOptions::new(), &mut |issue| {
+            if first_issue.is_none() {
+                first_issue = Some(issue.kind);
+            }
+        })?;
+        match first_issue {
+            Some(kind) => Err(io::Error::new(ErrorKind::Other, kind.description())),
+            None => Ok(()),
+        }
+    }
+}
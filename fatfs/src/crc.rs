@@ -0,0 +1,216 @@
+//! CRC protection of filesystem metadata (enabled by `crc` feature).
+//!
+//! Checksums of the FAT and of every directory cluster are stored in a hidden file in the root
+//! directory. The file is an ordinary file, so volumes stay readable by other FAT drivers.
+//! Protection is enabled per mount by `FsOptions::metadata_crc` - without it the file is neither
+//! verified nor updated.
+//!
+//! Layout of the companion file (all values are little endian):
+//!
+//! * magic `FCRC`, 4 reserved bytes, number of directory records, CRC32 of the FAT,
+//! * directory records: cluster number and CRC32 of its contents. Cluster number 0 is used for
+//!   FAT12/FAT16 root directory region.
+//!
+//! Records are stored in the order in which directories are walked from the root directory, so
+//! any change of directory structure is detected.
+
+use byteorder::LittleEndian;
+use byteorder_ext::{ReadBytesExt, WriteBytesExt};
+use io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+
+use dir::Dir;
+use dir_entry::FileAttributes;
+use fs::{write_zeros, DiskSlice, FileSystem, ReadWriteSeek, SCRATCH_BUFFER_SIZE};
+use units::Cluster;
+
+const CRC_FILE_NAME: &str = "FATCRC.SYS";
+const CRC_FILE_MAGIC: [u8; 4] = *b"FCRC";
+const CRC_FILE_HEADER_SIZE: u32 = 16;
+const CRC_RECORD_SIZE: u32 = 8;
+
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    // CRC-32 (IEEE 802.3), bitwise implementation to avoid a lookup table
+    crc = !crc;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn crc32_of(rdr: &mut Read) -> io::Result<u32> {
+    let mut crc = 0;
+    let mut buf = [0u8; SCRATCH_BUFFER_SIZE];
+    loop {
+        let n = rdr.read(&mut buf)?;
+        if n == 0 {
+            return Ok(crc);
+        }
+        crc = crc32_update(crc, &buf[..n]);
+    }
+}
+
+impl<IO: ReadWriteSeek> FileSystem<IO> {
+    fn cluster_crc(&self, cluster: Cluster) -> io::Result<u32> {
+        let offset = self.offset_from_cluster(cluster);
+        crc32_of(&mut DiskSlice::new(offset, self.cluster_size() as u64, 1, self))
+    }
+
+    fn chain_crcs(
+        &self,
+        first_cluster: Cluster,
+        f: &mut FnMut(u32, u32) -> io::Result<()>,
+    ) -> io::Result<()> {
+        f(first_cluster.0, self.cluster_crc(first_cluster)?)?;
+        for r in self.cluster_iter(first_cluster) {
+            let cluster = r?;
+            f(cluster.0, self.cluster_crc(cluster)?)?;
+        }
+        Ok(())
+    }
+
+    fn dir_crcs(&self, dir: &Dir<IO>, f: &mut FnMut(u32, u32) -> io::Result<()>) -> io::Result<()> {
+        for r in dir.iter() {
+            let e = r?;
+            let name = e.short_file_name();
+            if !e.is_dir() || name == "." || name == ".." {
+                continue;
+            }
+            match e.first_cluster() {
+                Some(n) => {
+                    self.chain_crcs(n, f)?;
+                    self.dir_crcs(&e.to_dir(), f)?;
+                }
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
+    // Calls f for every directory cluster with its number and CRC
+    fn for_each_dir_crc(&self, f: &mut FnMut(u32, u32) -> io::Result<()>) -> io::Result<()> {
+        match self.root_dir_cluster() {
+            Some(n) => self.chain_crcs(n, f)?,
+            None => f(0, crc32_of(&mut self.root_dir_slice())?)?,
+        }
+        self.dir_crcs(&self.root_dir(), f)
+    }
+
+    /// Returns None if there is no CRC file and otherwise true if stored checksums are valid.
+    pub(crate) fn verify_metadata_crc(&self) -> io::Result<Option<bool>> {
+        let mut file = match self.root_dir().open_file(CRC_FILE_NAME) {
+            Ok(f) => f,
+            Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let mut magic = [0u8; 4];
+        match file.read_exact(&mut magic) {
+            Err(ref err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(Some(false)),
+            r => r?,
+        }
+        let _reserved = file.read_u32::<LittleEndian>()?;
+        let num_records = file.read_u32::<LittleEndian>()?;
+        let fat_crc = file.read_u32::<LittleEndian>()?;
+        if magic != CRC_FILE_MAGIC || fat_crc != crc32_of(&mut self.fat_slice())? {
+            return Ok(Some(false));
+        }
+        let mut valid = true;
+        let mut num_dir_clusters = 0;
+        self.for_each_dir_crc(&mut |cluster, crc| {
+            num_dir_clusters += 1;
+            if num_dir_clusters > num_records {
+                valid = false;
+                return Ok(());
+            }
+            let stored_cluster = file.read_u32::<LittleEndian>()?;
+            let stored_crc = file.read_u32::<LittleEndian>()?;
+            valid = valid && stored_cluster == cluster && stored_crc == crc;
+            Ok(())
+        })?;
+        Ok(Some(valid && num_dir_clusters == num_records))
+    }
+
+    /// Rewrites CRC file if it is missing or outdated.
+    pub(crate) fn update_metadata_crc(&self) -> io::Result<()> {
+        if self.verify_metadata_crc()? == Some(true) {
+            return Ok(());
+        }
+        let mut root = self.root_dir();
+        // create file before counting directory clusters because it can extend root directory
+        match root.open_file(CRC_FILE_NAME) {
+            Err(ref err) if err.kind() == ErrorKind::NotFound => {
+                let attrs = FileAttributes::HIDDEN | FileAttributes::SYSTEM;
+                root.create_entry(CRC_FILE_NAME, attrs, None)?;
+            }
+            r => {
+                r?;
+            }
+        }
+        let mut num_records = 0;
+        self.for_each_dir_crc(&mut |_, _| {
+            num_records += 1;
+            Ok(())
+        })?;
+        // allocate clusters for the file using normal file API
+        let first_cluster = {
+            let mut file = root.open_file(CRC_FILE_NAME)?;
+            let size = CRC_FILE_HEADER_SIZE + num_records * CRC_RECORD_SIZE;
+            write_zeros(&mut file, size as u64)?;
+            file.truncate()?;
+            file.flush()?;
+            file.first_cluster().unwrap() // SAFE: file is not empty
+        };
+        // FAT and directories are final now - write contents directly to clusters so the
+        // directory entry is not modified anymore
+        let mut wrt = self.cluster_chain_writer(first_cluster);
+        wrt.write_all(&CRC_FILE_MAGIC)?;
+        wrt.write_u32::<LittleEndian>(0)?;
+        wrt.write_u32::<LittleEndian>(num_records)?;
+        wrt.write_u32::<LittleEndian>(crc32_of(&mut self.fat_slice())?)?;
+        self.for_each_dir_crc(&mut |cluster, crc| {
+            wrt.write_u32::<LittleEndian>(cluster)?;
+            wrt.write_u32::<LittleEndian>(crc)
+        })
+    }
+
+    fn cluster_chain_writer<'b>(&'b self, first_cluster: Cluster) -> ClusterChainWriter<'b, IO> {
+        ClusterChainWriter {
+            fs: self,
+            cluster: first_cluster,
+            offset: 0,
+        }
+    }
+}
+
+// Writes data to existing cluster chain without touching the FAT or directory entries
+struct ClusterChainWriter<'a, IO: ReadWriteSeek + 'a> {
+    fs: &'a FileSystem<IO>,
+    cluster: Cluster,
+    offset: u32,
+}
+
+impl<'a, IO: ReadWriteSeek> Write for ClusterChainWriter<'a, IO> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let cluster_size = self.fs.cluster_size();
+        if self.offset == cluster_size {
+            self.cluster = match self.fs.cluster_iter(self.cluster).next() {
+                Some(r) => r?,
+                None => return Ok(0),
+            };
+            self.offset = 0;
+        }
+        let n = ::core::cmp::min(buf.len(), (cluster_size - self.offset) as usize);
+        let offset = self.fs.offset_from_cluster(self.cluster) + self.offset as u64;
+        let mut disk = self.fs.disk.borrow_mut();
+        disk.seek(SeekFrom::Start(offset))?;
+        let written = disk.write(&buf[..n])?;
+        self.offset += written as u32;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.fs.disk.borrow_mut().flush()
+    }
+}
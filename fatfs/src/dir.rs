@@ -1,17 +1,34 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::cmp;
+use core::hash::Hasher;
+use core::iter;
 use io::{self, *};
 
-use dir_entry::{DirEntry, DirEntryData, DirFileEntryData, FileAttributes, ShortName,
-                DIR_ENTRY_SIZE};
+use cancel::check_cancelled;
+use dir_entry::{DateTime, DirEntry, DirEntryData, DirFileEntryData, FileAttributes,
+                LfnEntriesGenerator, LongName, LongNameBuilder, RawDirEntry, ShortName,
+                starts_with_ignore_case, DIR_ENTRY_FREE_FLAG, DIR_ENTRY_SIZE};
 use file::File;
-use fs::{DiskSlice, FileSystemRef};
+use fs::{write_zeros, DiskSlice, FileSystemRef, MountProfile, ReadWriteSeek, ShortNameCollision};
+use units::Cluster;
 
-#[derive(Clone)]
-pub(crate) enum DirRawStream<'a, 'b: 'a> {
-    File(File<'a, 'b>),
-    Root(DiskSlice<'a, 'b>),
+pub(crate) enum DirRawStream<'a, IO: ReadWriteSeek + 'a> {
+    File(File<'a, IO>),
+    Root(DiskSlice<'a, IO>),
 }
 
-impl<'a, 'b> DirRawStream<'a, 'b> {
+// Derived implementations of `Clone` for types generic over `IO` would require `IO: Clone`
+impl<'a, IO: ReadWriteSeek> Clone for DirRawStream<'a, IO> {
+    fn clone(&self) -> Self {
+        match self {
+            &DirRawStream::File(ref file) => DirRawStream::File(file.clone()),
+            &DirRawStream::Root(ref slice) => DirRawStream::Root(slice.clone()),
+        }
+    }
+}
+
+impl<'a, IO: ReadWriteSeek> DirRawStream<'a, IO> {
     pub(crate) fn abs_pos(&self) -> Option<u64> {
         match self {
             &DirRawStream::File(ref file) => file.abs_pos(),
@@ -19,7 +36,7 @@ impl<'a, 'b> DirRawStream<'a, 'b> {
         }
     }
 
-    pub(crate) fn first_cluster(&self) -> Option<u32> {
+    pub(crate) fn first_cluster(&self) -> Option<Cluster> {
         match self {
             &DirRawStream::File(ref file) => file.first_cluster(),
             &DirRawStream::Root(_) => None,
@@ -27,7 +44,7 @@ impl<'a, 'b> DirRawStream<'a, 'b> {
     }
 }
 
-impl<'a, 'b> Read for DirRawStream<'a, 'b> {
+impl<'a, IO: ReadWriteSeek> Read for DirRawStream<'a, IO> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         match self {
             &mut DirRawStream::File(ref mut file) => file.read(buf),
@@ -36,10 +53,13 @@ impl<'a, 'b> Read for DirRawStream<'a, 'b> {
     }
 }
 
-impl<'a, 'b> Write for DirRawStream<'a, 'b> {
+impl<'a, IO: ReadWriteSeek> Write for DirRawStream<'a, IO> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         match self {
-            &mut DirRawStream::File(ref mut file) => file.write(buf),
+            &mut DirRawStream::File(ref mut file) => {
+                let r = file.write(buf);
+                file.fs().poison_on_error(r)
+            }
             &mut DirRawStream::Root(ref mut raw) => raw.write(buf),
         }
     }
@@ -51,7 +71,7 @@ impl<'a, 'b> Write for DirRawStream<'a, 'b> {
     }
 }
 
-impl<'a, 'b> Seek for DirRawStream<'a, 'b> {
+impl<'a, IO: ReadWriteSeek> Seek for DirRawStream<'a, IO> {
     fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
         match self {
             &mut DirRawStream::File(ref mut file) => file.seek(pos),
@@ -68,32 +88,208 @@ fn split_path<'c>(path: &'c str) -> (&'c str, Option<&'c str>) {
     (comp, rest_opt)
 }
 
+/// Maximal number of 32-byte entry slots in a directory allowed by FAT specification (2 MiB of
+/// directory data).
+///
+/// Creating an entry that would not fit fails with `ErrorKind::DirectoryFull` error and
+/// iteration stops at this bound.
+pub const MAX_DIR_ENTRIES: u32 = 65536;
+
 /// FAT directory
-#[derive(Clone)]
-pub struct Dir<'a, 'b: 'a> {
-    stream: DirRawStream<'a, 'b>,
-    fs: FileSystemRef<'a, 'b>,
+///
+/// Path components passed to methods of this struct are compared with both long names and short
+/// 8.3 aliases of entries (e.g. `LONGFI~1.TXT`) ignoring case, like names are resolved by Windows.
+/// See `DirEntry::eq_name`.
+pub struct Dir<'a, IO: ReadWriteSeek + 'a> {
+    stream: DirRawStream<'a, IO>,
+    fs: FileSystemRef<'a, IO>,
+}
+
+impl<'a, IO: ReadWriteSeek> Clone for Dir<'a, IO> {
+    fn clone(&self) -> Self {
+        Dir {
+            stream: self.stream.clone(),
+            fs: self.fs,
+        }
+    }
 }
 
-impl<'a, 'b> Dir<'a, 'b> {
-    pub(crate) fn new(stream: DirRawStream<'a, 'b>, fs: FileSystemRef<'a, 'b>) -> Dir<'a, 'b> {
+impl<'a, IO: ReadWriteSeek> Dir<'a, IO> {
+    pub(crate) fn new(stream: DirRawStream<'a, IO>, fs: FileSystemRef<'a, IO>) -> Dir<'a, IO> {
         Dir { stream, fs }
     }
 
+    // Splits path into the first component and the rest checking path depth limit
+    fn split_path<'c>(&self, path: &'c str) -> io::Result<(&'c str, Option<&'c str>)> {
+        let depth = path.trim_matches('/').split('/').count();
+        if depth as u64 > self.fs.options.max_path_depth as u64 {
+            return Err(io::Error::new(ErrorKind::PathTooDeep, "path is too deep"));
+        }
+        Ok(split_path(path))
+    }
+
     /// Creates directory entries iterator
-    pub fn iter(&self) -> DirIter<'a, 'b> {
+    pub fn iter(&self) -> DirIter<'a, IO> {
         DirIter {
             stream: self.stream.clone(),
-            fs: self.fs.clone(),
+            fs: self.fs,
             err: false,
         }
     }
 
-    fn find_entry(&mut self, name: &str) -> io::Result<DirEntry<'a, 'b>> {
+    /// Creates directory entries iterator starting at the slot pointed by `cursor`.
+    ///
+    /// Meant for paginated listings: iteration can be resumed after an entry returned earlier
+    /// using `DirEntry::cursor`. If the cursor points past the end of directory the iterator is
+    /// empty.
+    pub fn iter_from(&self, cursor: DirCursor) -> io::Result<DirIter<'a, IO>> {
+        let mut stream = self.stream.clone();
+        let offset = cursor.entry_index() as u64 * DIR_ENTRY_SIZE;
+        match stream {
+            // file seek stops at the end of cluster chain
+            DirRawStream::File(ref mut file) => file.seek(SeekFrom::Start(offset))?,
+            DirRawStream::Root(ref mut raw) => {
+                let size = raw.seek(SeekFrom::End(0))?;
+                raw.seek(SeekFrom::Start(cmp::min(offset, size)))?
+            }
+        };
+        Ok(DirIter {
+            stream,
+            fs: self.fs,
+            err: false,
+        })
+    }
+
+    /// Creates directory entries iterator returning only entries which attributes contain all bits
+    /// of `mask` and none of the bits of `skip`.
+    ///
+    /// Entries are filtered while the directory is scanned so no additional passes are needed.
+    pub fn iter_filtered(
+        &self,
+        mask: FileAttributes,
+        skip: FileAttributes,
+    ) -> DirFilterIter<'a, IO> {
+        DirFilterIter {
+            iter: self.iter(),
+            mask,
+            skip,
+        }
+    }
+
+    /// Creates iterator of entries which are not directories.
+    pub fn files(&self) -> DirFilterIter<'a, IO> {
+        self.iter_filtered(FileAttributes::empty(), FileAttributes::DIRECTORY)
+    }
+
+    /// Creates iterator of subdirectory entries.
+    ///
+    /// Special entries "." and ".." are returned too if directory is not the root directory.
+    pub fn dirs(&self) -> DirFilterIter<'a, IO> {
+        self.iter_filtered(FileAttributes::DIRECTORY, FileAttributes::empty())
+    }
+
+    /// Reads all directory entries into memory (enabled by `alloc` feature).
+    ///
+    /// Entries are read once so the returned listing is not affected by later modifications of the
+    /// directory. Modifying directory while iterating it with `iter` can make the iterator skip
+    /// or repeat entries.
+    #[cfg(feature = "alloc")]
+    pub fn snapshot(&self) -> io::Result<Vec<DirEntry<'a, IO>>> {
+        self.iter().collect()
+    }
+
+    /// Creates raw directory entries iterator.
+    ///
+    /// Unlike `iter` it returns every 32-byte entry including long name, deleted and volume label
+    /// entries. Iteration stops after the entry marking end of directory. Meant for debugging.
+    pub fn iter_raw(&self) -> DirRawIter<'a, IO> {
+        DirRawIter {
+            stream: self.stream.clone(),
+            done: false,
+        }
+    }
+
+    /// Creates iterator of volume label entries (entries with VOLUME_ID attribute).
+    ///
+    /// Such entries are skipped by `iter`. A valid volume has at most one of them, in the root
+    /// directory, but some tools leave more behind (see `FileSystem::check`).
+    pub fn volume_entries(&self) -> DirVolumeIter<'a, IO> {
+        DirVolumeIter {
+            iter: self.iter_raw(),
+        }
+    }
+
+    /// Creates iterator of entries which long or short name starts with `prefix` ignoring case.
+    ///
+    /// Case is ignored the same way as in `DirEntry::eq_name` (also for non-ASCII characters).
+    ///
+    /// Meant for name completion. Directory is scanned once and the scan ends on the entry marking
+    /// end of directory.
+    pub fn find_prefix<'p>(&self, prefix: &'p str) -> DirPrefixIter<'a, 'p, IO> {
+        DirPrefixIter {
+            iter: self.iter(),
+            prefix,
+        }
+    }
+
+    /// Computes digest of the directory tree using hasher `H`.
+    ///
+    /// Digest covers names, sizes and contents of all files and subdirectories, so it can be
+    /// compared with a digest of an expected tree computed the same way elsewhere. Every entry
+    /// (except "." and "..") is hashed with a new hasher by writing its name (long name if
+    /// present) as UTF-8 followed by a zero byte, then for files byte 0, size as 64-bit little
+    /// endian and contents, and for directories byte 1 and the subdirectory digest as 64-bit
+    /// little endian. Entry digests are summed (wrapping), so the result does not depend on the
+    /// order of entries on disk, and the directory digest is hash of the number of entries and
+    /// the sum, both as 64-bit little endian.
+    ///
+    /// File contents are streamed through a small buffer, so `H` must give the same result no
+    /// matter how data is split between `write` calls.
+    pub fn tree_digest<H: Hasher + Default>(&self) -> io::Result<u64> {
+        let mut count = 0u64;
+        let mut sum = 0u64;
+        for r in self.iter() {
+            let e = r?;
+            let name = e.file_name();
+            if name == "." || name == ".." {
+                continue;
+            }
+            let mut hasher = H::default();
+            let mut buf = [0u8; 4];
+            for c in name.chars() {
+                hasher.write(c.encode_utf8(&mut buf).as_bytes());
+            }
+            hasher.write(&[0]);
+            if e.is_dir() {
+                let digest = e.to_dir().tree_digest::<H>()?;
+                hasher.write(&[1]);
+                hasher.write(&digest.to_le_bytes());
+            } else {
+                hasher.write(&[0]);
+                hasher.write(&e.len().to_le_bytes());
+                let mut file = e.to_file();
+                let mut buf = [0u8; 256];
+                loop {
+                    let n = file.read(&mut buf)?;
+                    if n == 0 {
+                        break;
+                    }
+                    hasher.write(&buf[..n]);
+                }
+            }
+            count += 1;
+            sum = sum.wrapping_add(hasher.finish());
+        }
+        let mut hasher = H::default();
+        hasher.write(&count.to_le_bytes());
+        hasher.write(&sum.to_le_bytes());
+        Ok(hasher.finish())
+    }
+
+    fn find_entry(&self, name: &str) -> io::Result<DirEntry<'a, IO>> {
         for r in self.iter() {
             let e = r?;
-            // compare name ignoring case
-            if e.file_name().eq_ignore_ascii_case(name) {
+            if e.eq_name(name) {
                 return Ok(e);
             }
         }
@@ -101,8 +297,8 @@ impl<'a, 'b> Dir<'a, 'b> {
     }
 
     /// Opens existing directory
-    pub fn open_dir(&mut self, path: &str) -> io::Result<Dir<'a, 'b>> {
-        let (name, rest_opt) = split_path(path);
+    pub fn open_dir(&mut self, path: &str) -> io::Result<Dir<'a, IO>> {
+        let (name, rest_opt) = self.split_path(path)?;
         let e = self.find_entry(name)?;
         match rest_opt {
             Some(rest) => e.to_dir().open_dir(rest),
@@ -111,65 +307,137 @@ impl<'a, 'b> Dir<'a, 'b> {
     }
 
     /// Opens existing file.
-    pub fn open_file(&mut self, path: &str) -> io::Result<File<'a, 'b>> {
-        let (name, rest_opt) = split_path(path);
+    pub fn open_file(&mut self, path: &str) -> io::Result<File<'a, IO>> {
+        let (name, rest_opt) = self.split_path(path)?;
         let e = self.find_entry(name)?;
         match rest_opt {
             Some(rest) => e.to_dir().open_file(rest),
-            None => Ok(e.to_file()),
+            None => {
+                if self.fs.options.paranoid {
+                    e.validate_chain()?;
+                }
+                Ok(e.to_file())
+            }
         }
     }
 
     /// Creates new file or opens existing without truncating.
-    pub fn create_file(&mut self, path: &str) -> io::Result<File<'a, 'b>> {
-        let (name, rest_opt) = split_path(path);
+    pub fn create_file(&mut self, path: &str) -> io::Result<File<'a, IO>> {
+        self.open_or_create_file(path).map(CreateOutcome::into_inner)
+    }
+
+    /// Creates new file or opens existing without truncating and reports which one happened.
+    ///
+    /// Lookup and creation are done in one call, so unlike checking `open_file` result first the
+    /// outcome cannot be wrong because of another handle creating the file in between.
+    pub fn open_or_create_file(&mut self, path: &str) -> io::Result<CreateOutcome<File<'a, IO>>> {
+        let (name, rest_opt) = self.split_path(path)?;
         let r = self.find_entry(name);
         match rest_opt {
-            Some(rest) => r?.to_dir().create_file(rest),
+            Some(rest) => r?.to_dir().open_or_create_file(rest),
             None => match r {
                 Err(ref err) if err.kind() == ErrorKind::NotFound => {
-                    Ok(
-                        self.create_entry(name, FileAttributes::from_bits_truncate(0), None)?
-                            .to_file(),
-                    )
+                    let entry =
+                        self.create_entry(name, FileAttributes::from_bits_truncate(0), None)?;
+                    Ok(CreateOutcome::Created(entry.to_file()))
                 }
                 Err(err) => Err(err),
-                Ok(e) => Ok(e.to_file()),
+                Ok(e) => Ok(CreateOutcome::Opened(e.to_file())),
             },
         }
     }
 
     /// Creates new directory or opens existing.
-    pub fn create_dir(&mut self, path: &str) -> io::Result<Dir<'a, 'b>> {
-        let (name, rest_opt) = split_path(path);
+    pub fn create_dir(&mut self, path: &str) -> io::Result<Dir<'a, IO>> {
+        self.open_or_create_dir(path).map(CreateOutcome::into_inner)
+    }
+
+    /// Creates new directory or opens existing and reports which one happened.
+    ///
+    /// See `open_or_create_file`.
+    pub fn open_or_create_dir(&mut self, path: &str) -> io::Result<CreateOutcome<Dir<'a, IO>>> {
+        let (name, rest_opt) = self.split_path(path)?;
         let r = self.find_entry(name);
         match rest_opt {
-            Some(rest) => r?.to_dir().create_dir(rest),
+            Some(rest) => r?.to_dir().open_or_create_dir(rest),
             None => {
                 match r {
                     Err(ref err) if err.kind() == ErrorKind::NotFound => {
-                        // alloc cluster for directory data
+                        // alloc cluster for directory data - it must be zeroed so the entries
+                        // following "." and ".." are end markers
                         let cluster = self.fs.alloc_cluster(None)?;
+                        self.fs.zero_cluster(cluster)?;
                         // create entry in parent directory
-                        let entry =
-                            self.create_entry(name, FileAttributes::DIRECTORY, Some(cluster))?;
-                        let mut dir = entry.to_dir();
-                        // create special entries "." and ".."
-                        dir.create_entry(".", FileAttributes::DIRECTORY, entry.first_cluster())?;
-                        dir.create_entry(
-                            "..",
+                        let entry = match self.create_entry(
+                            name,
                             FileAttributes::DIRECTORY,
-                            self.stream.first_cluster(),
-                        )?;
-                        Ok(dir)
+                            Some(cluster),
+                        ) {
+                            Ok(entry) => entry,
+                            Err(err) => {
+                                // do not leak directory data if entry cannot be created
+                                self.fs.free_cluster_chain(cluster)?;
+                                return Err(err);
+                            }
+                        };
+                        let mut dir = entry.to_dir();
+                        // create special entries "." and ".." - they share timestamps with the
+                        // directory entry in parent directory
+                        let mut dot_entry = entry.data.clone();
+                        dot_entry.set_name(DOT_SHORT_NAME);
+                        dir.write_entry(".", dot_entry)?;
+                        let mut dotdot_entry = entry.data.clone();
+                        dotdot_entry.set_name(DOTDOT_SHORT_NAME);
+                        let parent_cluster = self.first_cluster();
+                        dotdot_entry.set_first_cluster(parent_cluster, self.fs.fat_type());
+                        dir.write_entry("..", dotdot_entry)?;
+                        Ok(CreateOutcome::Created(dir))
                     }
                     Err(err) => Err(err),
-                    Ok(e) => Ok(e.to_dir()),
+                    Ok(e) => Ok(CreateOutcome::Opened(e.to_dir())),
                 }
             }
         }
     }
 
+    /// Returns first cluster of directory or None for root directory.
+    ///
+    /// Note: FAT32 root directory has a cluster chain but it is referenced as cluster 0 in ".."
+    /// entries so None is returned for it too.
+    pub(crate) fn first_cluster(&self) -> Option<Cluster> {
+        match self.stream.first_cluster() {
+            n if n == self.fs.root_dir_cluster() => None,
+            n => n,
+        }
+    }
+
+    /// Counts used and free 32-byte entry slots in space allocated to this directory.
+    ///
+    /// Long name, volume label and "." and ".." entries count as used. Deleted entries and all
+    /// slots starting from the entry marking end of directory count as free. Slots of clusters
+    /// not allocated yet are not counted.
+    pub fn slot_usage(&self) -> io::Result<DirSlotUsage> {
+        let mut stream = self.stream.clone();
+        stream.seek(SeekFrom::Start(0))?;
+        let mut usage = DirSlotUsage::default();
+        let mut after_end = false;
+        loop {
+            check_cancelled(self.fs.options.cancellation_token)?;
+            let mut data = [0u8; DIR_ENTRY_SIZE as usize];
+            match stream.read_exact(&mut data) {
+                Err(ref err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(usage),
+                Err(err) => return Err(err),
+                _ => {}
+            }
+            after_end = after_end || data[0] == 0;
+            if after_end || data[0] == DIR_ENTRY_FREE_FLAG {
+                usage.free += 1;
+            } else {
+                usage.used += 1;
+            }
+        }
+    }
+
     fn is_empty(&mut self) -> io::Result<bool> {
         // check if directory contains no files
         for r in self.iter() {
@@ -185,53 +453,497 @@ impl<'a, 'b> Dir<'a, 'b> {
 
     /// Removes existing file or directory.
     ///
-    /// Make sure there is no reference to this file (no File instance) or filesystem corruption
-    /// can happen.
+    /// Directory must be empty, otherwise `ErrorKind::DirectoryNotEmpty` error is returned.
+    /// Directory opened by a `Dir` handle is not removed - `ErrorKind::Busy` error is returned
+    /// (only with `alloc` feature).
+    ///
+    /// Open `File` objects of the removed file fail with `ErrorKind::NotFound` error afterwards.
+    /// Without `alloc` feature open files are not tracked - drop them before the file is removed,
+    /// otherwise they can write to clusters reused by other files.
     pub fn remove(&mut self, path: &str) -> io::Result<()> {
-        let (name, rest_opt) = split_path(path);
+        self.remove_entry(path, None)
+    }
+
+    /// Removes existing file.
+    ///
+    /// Fails with `ErrorKind::IsADirectory` error if `path` refers to a directory.
+    ///
+    /// Open `File` objects of the removed file fail with `ErrorKind::NotFound` error afterwards.
+    /// Without `alloc` feature open files are not tracked - drop them before the file is removed,
+    /// otherwise they can write to clusters reused by other files.
+    pub fn remove_file(&mut self, path: &str) -> io::Result<()> {
+        self.remove_entry(path, Some(false))
+    }
+
+    /// Removes existing empty directory.
+    ///
+    /// Fails with `ErrorKind::NotADirectory` error if `path` refers to a file, with
+    /// `ErrorKind::DirectoryNotEmpty` error if the directory contains any entries and with
+    /// `ErrorKind::Busy` error if the directory is opened by a `Dir` handle (only with `alloc`
+    /// feature).
+    pub fn remove_dir(&mut self, path: &str) -> io::Result<()> {
+        self.remove_entry(path, Some(true))
+    }
+
+    /// Removes existing file after overwriting its data.
+    ///
+    /// Every cluster of the file is overwritten `passes` times before it is freed, alternating
+    /// 0xFF and 0x00 patterns so the last pass writes zeros, and the device is flushed after each
+    /// pass. Long and short name entries of the file are cleared except for the deletion marker.
+    /// With zero `passes` only the entries are cleared. Overwriting in place does not reach copies
+    /// kept by wear leveling of flash devices.
+    ///
+    /// Fails with `ErrorKind::IsADirectory` error if `path` refers to a directory.
+    ///
+    /// Open `File` objects of the removed file fail with `ErrorKind::NotFound` error afterwards.
+    /// Without `alloc` feature open files are not tracked - drop them before the file is removed,
+    /// otherwise they can write to clusters reused by other files.
+    pub fn remove_secure(&mut self, path: &str, passes: u32) -> io::Result<()> {
+        let (name, rest_opt) = self.split_path(path)?;
+        let e = self.find_entry(name)?;
+        match rest_opt {
+            Some(rest) => {
+                if !e.is_dir() {
+                    return Err(io::Error::new(ErrorKind::NotADirectory, "not a directory"));
+                }
+                return e.to_dir().remove_secure(rest, passes);
+            }
+            None => {}
+        }
+        if e.is_dir() {
+            return Err(io::Error::new(ErrorKind::IsADirectory, "is a directory"));
+        }
+        match e.first_cluster() {
+            Some(first_cluster) => {
+                for pass in 0..passes {
+                    let pattern = if (passes - pass) % 2 == 0 { 0xFF } else { 0x00 };
+                    let next_clusters = self.fs.cluster_iter(first_cluster);
+                    for r in iter::once(Ok(first_cluster)).chain(next_clusters) {
+                        self.fs.fill_cluster(r?, pattern)?;
+                    }
+                    self.fs.disk.borrow_mut().flush()?;
+                }
+                self.fs.free_cluster_chain(first_cluster)?;
+            }
+            None => {}
+        }
+        self.free_entry(&e)?;
+        self.clear_free_entry(&e)
+    }
+
+    #[cfg(feature = "splice")]
+    pub(crate) fn append_to_file(&mut self, path: &str, dst: &mut File<'a, IO>) -> io::Result<()> {
+        let (name, rest_opt) = self.split_path(path)?;
         let e = self.find_entry(name)?;
         match rest_opt {
-            Some(rest) => e.to_dir().remove(rest),
+            Some(rest) => {
+                if !e.is_dir() {
+                    return Err(io::Error::new(ErrorKind::NotADirectory, "not a directory"));
+                }
+                return e.to_dir().append_to_file(rest, dst);
+            }
+            None => {}
+        }
+        if e.is_dir() {
+            return Err(io::Error::new(ErrorKind::IsADirectory, "is a directory"));
+        }
+        if dst.entry_pos() == Some(e.entry_pos) {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "cannot append file to itself"));
+        }
+        let dst_size = dst.file_size()?;
+        if e.len() > self.fs.max_file_size().saturating_sub(dst_size) as u64 {
+            return Err(io::Error::new(ErrorKind::FileTooLarge, "maximal file size exceeded"));
+        }
+        e.validate_chain()?;
+        {
+            let mut src = e.to_file();
+            if dst_size % self.fs.cluster_size() == 0 {
+                // detach the chain before linking it - if linking fails clusters are leaked
+                // instead of being referenced by two entries
+                match src.split_chain(0)? {
+                    Some(chain) => {
+                        src.flush()?;
+                        dst.append_chain(chain)?;
+                    }
+                    None => {}
+                }
+            } else {
+                let pos = dst.seek(SeekFrom::Current(0))?;
+                dst.seek(SeekFrom::End(0))?;
+                let mut buf = [0u8; 512];
+                loop {
+                    let n = src.read(&mut buf)?;
+                    if n == 0 {
+                        break;
+                    }
+                    dst.write_all(&buf[..n])?;
+                }
+                dst.seek(SeekFrom::Start(pos))?;
+                src.seek(SeekFrom::Start(0))?;
+                src.truncate()?;
+                src.flush()?;
+            }
+        }
+        self.free_entry(&e)
+    }
+
+    fn remove_entry(&mut self, path: &str, expect_dir: Option<bool>) -> io::Result<()> {
+        let (name, rest_opt) = self.split_path(path)?;
+        let e = self.find_entry(name)?;
+        match rest_opt {
+            Some(rest) => {
+                if !e.is_dir() {
+                    return Err(io::Error::new(ErrorKind::NotADirectory, "not a directory"));
+                }
+                e.to_dir().remove_entry(rest, expect_dir)
+            }
             None => {
+                match expect_dir {
+                    Some(true) if !e.is_dir() => {
+                        return Err(io::Error::new(ErrorKind::NotADirectory, "not a directory"));
+                    }
+                    Some(false) if e.is_dir() => {
+                        return Err(io::Error::new(ErrorKind::IsADirectory, "is a directory"));
+                    }
+                    _ => {}
+                }
+                self.check_not_busy(&e)?;
                 // in case of directory check if it is empty
                 if e.is_dir() && !e.to_dir().is_empty()? {
                     return Err(io::Error::new(
-                        ErrorKind::NotFound,
+                        ErrorKind::DirectoryNotEmpty,
                         "removing non-empty directory is denied",
                     ));
                 }
                 // free directory data
                 match e.first_cluster() {
-                    Some(n) => self.fs.cluster_iter(n).free()?,
+                    Some(n) => self.fs.free_cluster_chain(n)?,
                     _ => {}
                 }
                 // free long and short name entries
+                self.free_entry(&e)
+            }
+        }
+    }
+
+    /// Removes all files for which `f` returns false.
+    ///
+    /// Directory is scanned once and entries of removed files are freed during the scan. Their
+    /// clusters are freed together after the scan (with `alloc` feature - otherwise right after
+    /// the entry), so the free cluster count is updated once. Subdirectories are not passed to `f`
+    /// and are never removed. Returns number of removed files.
+    pub fn retain(&mut self, f: &mut FnMut(&DirEntry<IO>) -> bool) -> io::Result<u32> {
+        #[cfg(feature = "alloc")]
+        let mut chains = Vec::new();
+        let mut removed = 0;
+        for r in self.iter() {
+            let e = r?;
+            if e.is_dir() || f(&e) {
+                continue;
+            }
+            // entry is freed first so it never points to freed clusters
+            self.free_entry(&e)?;
+            match e.first_cluster() {
+                #[cfg(feature = "alloc")]
+                Some(n) => chains.push(n),
+                #[cfg(not(feature = "alloc"))]
+                Some(n) => self.fs.free_cluster_chain(n)?,
+                None => {}
+            }
+            removed += 1;
+        }
+        #[cfg(feature = "alloc")]
+        self.fs.free_cluster_chains(&chains)?;
+        if removed > 0 {
+            let now = self.fs.options.time_provider.get_current_date_time();
+            self.set_modified(now)?;
+        }
+        Ok(removed)
+    }
+
+    fn free_entry(&self, e: &DirEntry<IO>) -> io::Result<()> {
+        // open handles of the entry must not write it back
+        #[cfg(feature = "alloc")]
+        self.fs.open_entries.borrow_mut().entry_removed(e.entry_pos);
+        let mut stream = self.stream.clone();
+        stream.seek(SeekFrom::Start(e.offset_range.0 as u64))?;
+        let num = (e.offset_range.1 - e.offset_range.0) as usize / DIR_ENTRY_SIZE as usize;
+        for _ in 0..num {
+            let mut data = DirEntryData::deserialize(&mut stream)?;
+            data.set_free();
+            stream.seek(SeekFrom::Current(-(DIR_ENTRY_SIZE as i64)))?;
+            data.serialize(&mut stream)?;
+        }
+        Ok(())
+    }
+
+    // Zeroes all bytes except the deletion marker in entries of a removed file
+    fn clear_free_entry(&self, e: &DirEntry<IO>) -> io::Result<()> {
+        let mut stream = self.stream.clone();
+        stream.seek(SeekFrom::Start(e.offset_range.0 as u64))?;
+        let num = (e.offset_range.1 - e.offset_range.0) as usize / DIR_ENTRY_SIZE as usize;
+        for _ in 0..num {
+            stream.seek(SeekFrom::Current(1))?;
+            write_zeros(&mut stream, DIR_ENTRY_SIZE - 1)?;
+        }
+        Ok(())
+    }
+
+    // Replaces, creates or removes (if `label` is None) entry with VOLUME_ID attribute. Removing
+    // frees all volume label entries so a duplicate does not become the label on next mount.
+    pub(crate) fn set_volume_entry(&mut self, label: Option<[u8; 11]>) -> io::Result<()> {
+        let now = self.fs.options.time_provider.get_current_date_time();
+        let label = match label {
+            Some(label) => label,
+            None => {
+                for r in self.volume_entries() {
+                    let e = r?;
+                    self.free_raw_entry(e.offset())?;
+                }
+                return Ok(());
+            }
+        };
+        // reuse existing entry, otherwise the first free (possibly deleted) slot
+        let mut stream = match self.volume_entries().next() {
+            Some(r) => {
                 let mut stream = self.stream.clone();
-                stream.seek(SeekFrom::Start(e.offset_range.0 as u64))?;
-                let num = (e.offset_range.1 - e.offset_range.0) as usize / DIR_ENTRY_SIZE as usize;
-                for _ in 0..num {
-                    let mut data = DirEntryData::deserialize(&mut stream)?;
-                    data.set_free();
-                    stream.seek(SeekFrom::Current(-(DIR_ENTRY_SIZE as i64)))?;
-                    data.serialize(&mut stream)?;
+                stream.seek(SeekFrom::Start(r?.offset()))?;
+                stream
+            }
+            None => self.find_free_entries(1)?,
+        };
+        let mut raw_entry = DirFileEntryData::new(label, FileAttributes::VOLUME_ID);
+        raw_entry.set_modified(now);
+        raw_entry.serialize(&mut stream)
+    }
+
+    // Marks a single entry as deleted
+    pub(crate) fn free_raw_entry(&self, offset: u64) -> io::Result<()> {
+        let mut stream = self.stream.clone();
+        stream.seek(SeekFrom::Start(offset))?;
+        stream.write_all(&[DIR_ENTRY_FREE_FLAG])
+    }
+
+    /// Renames or moves existing file or directory.
+    ///
+    /// `dst_dir` can be this directory or any other directory on the same filesystem. When a
+    /// directory is moved to a new parent its ".." entry is updated to point to the new parent.
+    /// Entry names are generated again in the destination directory, so a short name colliding
+    /// with an existing entry there is resolved according to `FsOptions::short_name_collision`.
+    /// If the new name matches the entry itself ignoring case, the entry keeps its short name
+    /// and only its long name is changed.
+    ///
+    /// Fails with `ErrorKind::AlreadyExists` error if destination name is already used, with
+    /// `ErrorKind::NotADirectory` error if a file is used as a path component and with
+    /// `ErrorKind::InvalidInput` error if a directory would be moved into itself. With `alloc`
+    /// feature renaming a directory fails with `ErrorKind::Busy` error if the directory or any
+    /// file or directory inside it is open. Open files can be renamed - their handles follow the
+    /// entry.
+    pub fn rename(&self, src_path: &str, dst_dir: &Dir<'a, IO>, dst_path: &str) -> io::Result<()> {
+        // traverse source path
+        let (src_name, rest_opt) = self.split_path(src_path)?;
+        match rest_opt {
+            Some(rest) => {
+                let src_subdir = self.find_entry(src_name)?;
+                if !src_subdir.is_dir() {
+                    return Err(io::Error::new(ErrorKind::NotADirectory, "not a directory"));
+                }
+                return src_subdir.to_dir().rename(rest, dst_dir, dst_path);
+            }
+            None => {}
+        }
+        // traverse destination path
+        let (dst_name, rest_opt) = self.split_path(dst_path)?;
+        match rest_opt {
+            Some(rest) => {
+                let dst_subdir = dst_dir.find_entry(dst_name)?;
+                if !dst_subdir.is_dir() {
+                    return Err(io::Error::new(ErrorKind::NotADirectory, "not a directory"));
                 }
-                Ok(())
+                return self.rename(src_name, &dst_subdir.to_dir(), rest);
+            }
+            None => {}
+        }
+        self.rename_internal(src_name, dst_dir, dst_name)
+    }
+
+    fn rename_internal(
+        &self,
+        src_name: &str,
+        dst_dir: &Dir<'a, IO>,
+        dst_name: &str,
+    ) -> io::Result<()> {
+        let e = self.find_entry(src_name)?;
+        // check if destination name is unused - destination can only match the entry itself
+        // (e.g. when case of the name changes)
+        match dst_dir.find_entry(dst_name) {
+            Ok(ref dst) if dst.entry_pos == e.entry_pos => {
+                return self.rename_in_place(&e, dst_name)
+            }
+            Ok(_) => {
+                return Err(io::Error::new(
+                    ErrorKind::AlreadyExists,
+                    "destination already exists",
+                ))
+            }
+            Err(ref err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        let parent_changed = self.first_cluster() != dst_dir.first_cluster();
+        if e.is_dir() && parent_changed {
+            dst_dir.check_not_inside(e.first_cluster())?;
+        }
+        self.check_not_busy(&e)?;
+        validate_long_name(dst_name, self.fs.options.max_name_length)?;
+        let mut dst_dir = dst_dir.clone();
+        let short_name = dst_dir.make_short_name_unique(dst_name, generate_short_name(dst_name))?;
+        // write new entry before freeing the old one so the file is not lost if writing fails
+        let mut raw_entry = e.data.clone();
+        raw_entry.set_name(short_name);
+        let new_entry = dst_dir.write_entry(dst_name, raw_entry)?;
+        // open handles follow the entry to its new location
+        #[cfg(feature = "alloc")]
+        self.fs
+            .open_entries
+            .borrow_mut()
+            .entry_moved(e.entry_pos, new_entry.entry_pos, short_name);
+        self.free_entry(&e)?;
+        if e.is_dir() && parent_changed {
+            // ".." entry must point to the new parent
+            let parent_cluster = dst_dir.first_cluster();
+            for r in new_entry.to_dir().iter() {
+                let dot_entry = r?;
+                if dot_entry.short_file_name() == ".." {
+                    let mut editor = dot_entry.editor();
+                    editor.set_first_cluster(parent_cluster, self.fs.fat_type());
+                    editor.flush(self.fs)?;
+                    break;
+                }
+            }
+        }
+        // both directories are modified
+        let now = self.fs.options.time_provider.get_current_date_time();
+        dst_dir.set_modified(now)?;
+        if parent_changed {
+            self.clone().set_modified(now)?;
+        }
+        Ok(())
+    }
+
+    // Renames entry to a name matching it ignoring case, keeping its short name. LFN entries are
+    // overwritten if the new name takes the same number of them - otherwise the entry is written
+    // again like in a regular rename.
+    fn rename_in_place(&self, e: &DirEntry<'a, IO>, name: &str) -> io::Result<()> {
+        if e.file_name() == name {
+            return Ok(());
+        }
+        validate_long_name(name, self.fs.options.max_name_length)?;
+        let lfn_gen = LfnEntriesGenerator::new(name, e.data.name());
+        let num_lfn_entries = match lfn_gen {
+            Some(ref lfn_gen) => lfn_gen.len(),
+            None => 0,
+        };
+        if num_lfn_entries == 0 && e.slot_count() == 1 {
+            // name is stored in the short name entry which keeps only upper case
+            return Ok(());
+        }
+        if num_lfn_entries + 1 == e.slot_count() as usize {
+            let mut stream = self.stream.clone();
+            stream.seek(SeekFrom::Start(e.offset_range.0))?;
+            for lfn_entry in lfn_gen.into_iter().flat_map(|lfn_gen| lfn_gen) {
+                lfn_entry.serialize(&mut stream)?;
+            }
+        } else {
+            #[allow(unused_variables)]
+            let new_entry = self.clone().write_entry(name, e.data.clone())?;
+            // open handles must not write the entry back to its old slot
+            #[cfg(feature = "alloc")]
+            self.fs
+                .open_entries
+                .borrow_mut()
+                .entry_moved(e.entry_pos, new_entry.entry_pos, *e.data.name());
+            self.free_entry(e)?;
+        }
+        let now = self.fs.options.time_provider.get_current_date_time();
+        self.clone().set_modified(now)
+    }
+
+    // Fails with `ErrorKind::Busy` error if `e` is a directory used by an open handle or
+    // containing entries used by open handles. Open files can be removed and renamed - their
+    // handles follow the entry or fail after it is removed.
+    #[cfg(feature = "alloc")]
+    fn check_not_busy(&self, e: &DirEntry<'a, IO>) -> io::Result<()> {
+        if e.is_dir() {
+            let busy = self.fs.open_entries.borrow().is_open(e.entry_pos);
+            if busy || e.to_dir().has_open_entries()? {
+                return Err(io::Error::new(ErrorKind::Busy, "directory is in use"));
+            }
+        }
+        Ok(())
+    }
+
+    // Open handles are not tracked without `alloc` feature
+    #[cfg(not(feature = "alloc"))]
+    fn check_not_busy(&self, _e: &DirEntry<'a, IO>) -> io::Result<()> {
+        Ok(())
+    }
+
+    // Checks if any entry in this directory or its subdirectories is used by an open handle
+    #[cfg(feature = "alloc")]
+    fn has_open_entries(&self) -> io::Result<bool> {
+        for r in self.iter() {
+            let e = r?;
+            let name = e.raw_short_name();
+            if name == DOT_SHORT_NAME || name == DOTDOT_SHORT_NAME {
+                continue;
+            }
+            if self.fs.open_entries.borrow().is_open(e.entry_pos) {
+                return Ok(true);
+            }
+            if e.is_dir() && e.to_dir().has_open_entries()? {
+                return Ok(true);
             }
         }
+        Ok(false)
     }
 
-    fn find_free_entries(&mut self, num_entries: usize) -> io::Result<DirRawStream<'a, 'b>> {
+    // Fails if this directory is the directory starting at `cluster` or its descendant
+    fn check_not_inside(&self, cluster: Option<Cluster>) -> io::Result<()> {
+        let mut dir = self.clone();
+        // limit number of steps in case ".." entries form a loop
+        for _ in 0..self.fs.total_clusters {
+            let dir_cluster = match dir.first_cluster() {
+                Some(n) => n,
+                None => return Ok(()),
+            };
+            if Some(dir_cluster) == cluster {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "cannot move directory into itself",
+                ));
+            }
+            dir = dir.find_entry("..")?.to_dir();
+        }
+        Err(io::Error::new(ErrorKind::Other, "directory tree contains a loop"))
+    }
+
+    fn find_free_entries(&mut self, num_entries: usize) -> io::Result<DirRawStream<'a, IO>> {
         let mut stream = self.stream.clone();
         let mut first_free = 0;
         let mut num_free = 0;
         let mut i = 0;
         loop {
+            check_cancelled(self.fs.options.cancellation_token)?;
+            if i >= MAX_DIR_ENTRIES as usize {
+                return Err(io::Error::new(ErrorKind::DirectoryFull, "directory is full"));
+            }
             let raw_entry = DirEntryData::deserialize(&mut stream)?;
             if raw_entry.is_end() {
                 // first unused entry - all remaining space can be used
                 if num_free == 0 {
                     first_free = i;
                 }
+                self.check_entries_limit(first_free + num_entries)?;
                 stream.seek(io::SeekFrom::Start(first_free as u64 * DIR_ENTRY_SIZE))?;
                 return Ok(stream);
             } else if raw_entry.is_free() {
@@ -242,6 +954,7 @@ impl<'a, 'b> Dir<'a, 'b> {
                 num_free += 1;
                 if num_free == num_entries {
                     // enough space for new file
+                    self.check_entries_limit(first_free + num_entries)?;
                     stream.seek(io::SeekFrom::Start(first_free as u64 * DIR_ENTRY_SIZE))?;
                     return Ok(stream);
                 }
@@ -253,34 +966,120 @@ impl<'a, 'b> Dir<'a, 'b> {
         }
     }
 
+    fn check_entries_limit(&self, num_entries: usize) -> io::Result<()> {
+        if num_entries as u64 > self.fs.options.max_dir_entries as u64 {
+            return Err(io::Error::new(ErrorKind::TooManyEntries, "too many directory entries"));
+        }
+        if num_entries as u64 > MAX_DIR_ENTRIES as u64 {
+            return Err(io::Error::new(ErrorKind::DirectoryFull, "directory is full"));
+        }
+        Ok(())
+    }
+
+    // Writes LFN entries if the short name does not store the name exactly. Returns stream
+    // positioned at the short name entry, position of the first entry and the long name.
     fn create_lfn_entries(
         &mut self,
-        _name: &str,
-        _short_name: &[u8],
-    ) -> io::Result<(DirRawStream<'a, 'b>, u64)> {
-        let mut stream = self.find_free_entries(1)?;
+        name: &str,
+        short_name: &[u8; 11],
+    ) -> io::Result<(DirRawStream<'a, IO>, u64, LongName)> {
+        let lfn_gen = LfnEntriesGenerator::new(name, short_name);
+        let num_lfn_entries = match lfn_gen {
+            Some(ref lfn_gen) => lfn_gen.len(),
+            None => 0,
+        };
+        let mut stream = self.find_free_entries(num_lfn_entries + 1)?;
         let start_pos = stream.seek(io::SeekFrom::Current(0))?;
-        Ok((stream, start_pos))
+        match lfn_gen {
+            Some(lfn_gen) => {
+                let long_name = lfn_gen.long_name();
+                for lfn_entry in lfn_gen {
+                    lfn_entry.serialize(&mut stream)?;
+                }
+                Ok((stream, start_pos, long_name))
+            }
+            None => Ok((stream, start_pos, LongName::empty())),
+        }
     }
 
-    fn create_entry(
+    pub(crate) fn create_entry(
         &mut self,
         name: &str,
         attrs: FileAttributes,
-        first_cluster: Option<u32>,
-    ) -> io::Result<DirEntry<'a, 'b>> {
+        first_cluster: Option<Cluster>,
+    ) -> io::Result<DirEntry<'a, IO>> {
         // check if name doesn't contain unsupported characters
-        validate_long_name(name)?;
+        validate_long_name(name, self.fs.options.max_name_length)?;
+        self.check_name_unused(name)?;
         // generate short name
-        let short_name = generate_short_name(name);
-        // generate long entries
-        let (mut stream, start_pos) = self.create_lfn_entries(&name, &short_name)?;
-        // create and write short name entry
+        let short_name = self.make_short_name_unique(name, generate_short_name(name))?;
+        // create short name entry with all timestamps set to current time - returned entry holds
+        // exactly the values stored on the device
         let mut raw_entry = DirFileEntryData::new(short_name, attrs);
         raw_entry.set_first_cluster(first_cluster, self.fs.fat_type());
-        raw_entry.reset_created();
-        raw_entry.reset_accessed();
-        raw_entry.reset_modified();
+        let now = self.fs.options.time_provider.get_current_date_time().clamp();
+        raw_entry.set_created(now);
+        raw_entry.set_accessed(now.date);
+        raw_entry.set_modified(now);
+        let entry = self.write_entry(name, raw_entry)?;
+        // adding entry modifies the directory
+        self.set_modified(now)?;
+        Ok(entry)
+    }
+
+    // Fails with `ErrorKind::AlreadyExists` if long or short name of any entry is equal to `name`
+    // ignoring case. Callers usually looked the name up before but the directory could have been
+    // modified through another handle in the meantime.
+    fn check_name_unused(&self, name: &str) -> io::Result<()> {
+        for r in self.iter() {
+            if r?.eq_name(name) {
+                return Err(io::Error::new(ErrorKind::AlreadyExists, "file already exists"));
+            }
+        }
+        Ok(())
+    }
+
+    fn short_name_exists(&self, short_name: &[u8; 11]) -> io::Result<bool> {
+        for r in self.iter() {
+            if r?.data.name() == short_name {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn make_short_name_unique(&self, name: &str, short_name: [u8; 11]) -> io::Result<[u8; 11]> {
+        if !self.short_name_exists(&short_name)? {
+            return Ok(short_name);
+        }
+        let (hash, max_tail) = match self.fs.options.short_name_collision {
+            ShortNameCollision::Error => {
+                return Err(io::Error::new(
+                    ErrorKind::AlreadyExists,
+                    "short file name already exists",
+                ))
+            }
+            ShortNameCollision::NumericTail => (None, 999999),
+            ShortNameCollision::HashTail => (Some(long_name_hash(name)), 9),
+        };
+        for n in 1..max_tail + 1 {
+            let candidate = short_name_with_tail(&short_name, hash, n);
+            if !self.short_name_exists(&candidate)? {
+                return Ok(candidate);
+            }
+        }
+        Err(io::Error::new(ErrorKind::Other, "cannot generate unique short file name"))
+    }
+
+    fn write_entry(
+        &mut self,
+        name: &str,
+        raw_entry: DirFileEntryData,
+    ) -> io::Result<DirEntry<'a, IO>> {
+        // generate long entries
+        let (mut stream, start_pos, long_name) =
+            self.create_lfn_entries(&name, raw_entry.name())?;
+        // write short name entry
         raw_entry.serialize(&mut stream)?;
         let end_pos = stream.seek(io::SeekFrom::Current(0))?;
         let abs_pos = stream.abs_pos().map(|p| p - DIR_ENTRY_SIZE);
@@ -289,47 +1088,136 @@ impl<'a, 'b> Dir<'a, 'b> {
         return Ok(DirEntry {
             data: raw_entry,
             short_name,
+            long_name,
             fs: self.fs,
             entry_pos: abs_pos.unwrap(), // SAFE: abs_pos is absent only for empty file
             offset_range: (start_pos, end_pos),
         });
     }
+
+    fn set_modified(&mut self, date_time: DateTime) -> io::Result<()> {
+        // root directory has no entry so there is no timestamp to update
+        match self.stream {
+            DirRawStream::File(ref mut file) => {
+                // time provider can return timestamps which cannot be stored
+                file.set_modified(date_time.clamp())?;
+                file.flush_dir_entry()
+            }
+            DirRawStream::Root(_) => Ok(()),
+        }
+    }
+}
+
+/// Result of `Dir::open_or_create_file` and `Dir::open_or_create_dir`.
+#[derive(Clone, Debug)]
+pub enum CreateOutcome<T> {
+    /// Entry did not exist and was created.
+    Created(T),
+    /// Entry already existed and was opened.
+    Opened(T),
+}
+
+impl<T> CreateOutcome<T> {
+    /// Returns true if entry was created.
+    pub fn is_created(&self) -> bool {
+        match self {
+            &CreateOutcome::Created(_) => true,
+            &CreateOutcome::Opened(_) => false,
+        }
+    }
+
+    /// Returns created or opened object.
+    pub fn into_inner(self) -> T {
+        match self {
+            CreateOutcome::Created(x) | CreateOutcome::Opened(x) => x,
+        }
+    }
+}
+
+/// Position of a 32-byte entry slot in a directory.
+///
+/// Returned by `DirEntry::cursor` and used by `Dir::iter_from` to resume iteration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DirCursor {
+    entry_index: u32,
+}
+
+impl DirCursor {
+    /// Creates cursor pointing at `entry_index`-th raw entry slot of a directory.
+    pub fn from_entry_index(entry_index: u32) -> Self {
+        DirCursor { entry_index }
+    }
+
+    /// Returns index of the raw entry slot this cursor points at.
+    pub fn entry_index(&self) -> u32 {
+        self.entry_index
+    }
 }
 
 /// Directory entries iterator.
-#[derive(Clone)]
-pub struct DirIter<'a, 'b: 'a> {
-    stream: DirRawStream<'a, 'b>,
-    fs: FileSystemRef<'a, 'b>,
+pub struct DirIter<'a, IO: ReadWriteSeek + 'a> {
+    stream: DirRawStream<'a, IO>,
+    fs: FileSystemRef<'a, IO>,
     err: bool,
 }
 
-impl<'a, 'b> DirIter<'a, 'b> {
-    fn read_dir_entry(&mut self) -> io::Result<Option<DirEntry<'a, 'b>>> {
+impl<'a, IO: ReadWriteSeek> Clone for DirIter<'a, IO> {
+    fn clone(&self) -> Self {
+        DirIter {
+            stream: self.stream.clone(),
+            fs: self.fs,
+            err: self.err,
+        }
+    }
+}
+
+impl<'a, IO: ReadWriteSeek> DirIter<'a, IO> {
+    fn read_dir_entry(&mut self) -> io::Result<Option<DirEntry<'a, IO>>> {
         let mut offset = self.stream.seek(SeekFrom::Current(0))?;
         let mut begin_offset = offset;
+        let max_offset = self.fs.options.max_dir_entries as u64 * DIR_ENTRY_SIZE;
+        let mut lfn = LongNameBuilder::new();
+        let strict = self.fs.options.profile == MountProfile::Strict;
         loop {
+            check_cancelled(self.fs.options.cancellation_token)?;
+            // entries beyond the limit of FAT specification are never used
+            if offset >= MAX_DIR_ENTRIES as u64 * DIR_ENTRY_SIZE {
+                return Ok(None);
+            }
             let raw_entry = DirEntryData::deserialize(&mut self.stream)?;
             offset += DIR_ENTRY_SIZE;
+            // bound the scan of huge or corrupted directories
+            if !raw_entry.is_end() && offset > max_offset {
+                return Err(io::Error::new(ErrorKind::TooManyEntries, "too many directory entries"));
+            }
             match raw_entry {
                 DirEntryData::File(data) => {
                     // Check if this is end of dif
                     if data.is_end() {
+                        lfn.clear();
+                        check_orphaned_lfn(&lfn, strict)?;
                         return Ok(None);
                     }
                     // Check if this is deleted or volume ID entry
                     if data.is_free() || data.is_volume() {
                         begin_offset = offset;
+                        lfn.clear();
                         continue;
                     }
                     // Get entry position on volume
                     let abs_pos = self.stream.abs_pos().map(|p| p - DIR_ENTRY_SIZE);
-                    // Check if LFN checksum is valid
+                    if strict && !short_name_valid(data.name()) {
+                        return Err(io::Error::new(ErrorKind::CorruptedFile, "invalid short name"));
+                    }
+                    // Long name is used only if LFN checksum is valid
+                    let long_name = lfn.finish(data.name());
+                    check_orphaned_lfn(&lfn, strict)?;
                     // Return directory entry
                     let short_name = ShortName::new(data.name());
                     return Ok(Some(DirEntry {
                         data,
                         short_name,
+                        long_name,
                         fs: self.fs,
                         entry_pos: abs_pos.unwrap(), // SAFE: abs_pos is empty only for empty file
                         offset_range: (begin_offset, offset),
@@ -339,16 +1227,35 @@ impl<'a, 'b> DirIter<'a, 'b> {
                     // Check if this is deleted entry
                     if data.is_free() {
                         begin_offset = offset;
+                        lfn.clear();
                         continue;
                     }
+                    lfn.process(&data);
                 }
             }
         }
     }
 }
 
-impl<'a, 'b> Iterator for DirIter<'a, 'b> {
-    type Item = io::Result<DirEntry<'a, 'b>>;
+fn check_orphaned_lfn(lfn: &LongNameBuilder, strict: bool) -> io::Result<()> {
+    if strict && lfn.is_orphaned() {
+        return Err(io::Error::new(ErrorKind::CorruptedFile, "orphaned long name entries"));
+    }
+    Ok(())
+}
+
+// Checks characters allowed in short names by the specification (lowercase letters are not
+// checked as some implementations store them)
+fn short_name_valid(name: &[u8; 11]) -> bool {
+    // first byte 0x05 stands for 0xE5
+    let first_valid = name[0] != b' ' && (name[0] == 0x05 || name[0] >= 0x20);
+    first_valid
+        && name[1..].iter().all(|&c| c >= 0x20)
+        && name.iter().all(|&c| !b"\"*+,/:;<=>?[\\]|".contains(&c))
+}
+
+impl<'a, IO: ReadWriteSeek> Iterator for DirIter<'a, IO> {
+    type Item = io::Result<DirEntry<'a, IO>>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.err {
@@ -366,17 +1273,196 @@ impl<'a, 'b> Iterator for DirIter<'a, 'b> {
     }
 }
 
+/// Iterator of directory entries matching a name prefix.
+///
+/// Returned by `Dir::find_prefix`.
+pub struct DirPrefixIter<'a, 'p, IO: ReadWriteSeek + 'a> {
+    iter: DirIter<'a, IO>,
+    prefix: &'p str,
+}
+
+impl<'a, 'p, IO: ReadWriteSeek> Clone for DirPrefixIter<'a, 'p, IO> {
+    fn clone(&self) -> Self {
+        DirPrefixIter {
+            iter: self.iter.clone(),
+            prefix: self.prefix,
+        }
+    }
+}
+
+impl<'a, 'p, IO: ReadWriteSeek> Iterator for DirPrefixIter<'a, 'p, IO> {
+    type Item = io::Result<DirEntry<'a, IO>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.iter.next() {
+                Some(Ok(e)) => {
+                    let matches = starts_with_ignore_case(e.file_name().chars(), self.prefix)
+                        || starts_with_ignore_case(e.short_file_name().chars(), self.prefix);
+                    if matches {
+                        return Some(Ok(e));
+                    }
+                }
+                r => return r,
+            }
+        }
+    }
+}
+
+/// Iterator of directory entries filtered by attributes.
+///
+/// Returned by `Dir::iter_filtered`, `Dir::files` and `Dir::dirs`.
+pub struct DirFilterIter<'a, IO: ReadWriteSeek + 'a> {
+    iter: DirIter<'a, IO>,
+    mask: FileAttributes,
+    skip: FileAttributes,
+}
+
+impl<'a, IO: ReadWriteSeek> Clone for DirFilterIter<'a, IO> {
+    fn clone(&self) -> Self {
+        DirFilterIter {
+            iter: self.iter.clone(),
+            mask: self.mask,
+            skip: self.skip,
+        }
+    }
+}
+
+impl<'a, IO: ReadWriteSeek> Iterator for DirFilterIter<'a, IO> {
+    type Item = io::Result<DirEntry<'a, IO>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.iter.next() {
+                Some(Ok(e)) => {
+                    let attrs = e.attributes();
+                    if attrs.contains(self.mask) && !attrs.intersects(self.skip) {
+                        return Some(Ok(e));
+                    }
+                }
+                r => return r,
+            }
+        }
+    }
+}
+
+/// Numbers of used and free entry slots in a directory.
+///
+/// Returned by `Dir::slot_usage`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DirSlotUsage {
+    /// Number of slots holding file, long name, volume label and dot entries
+    pub used: u32,
+    /// Number of deleted and never used slots
+    pub free: u32,
+}
+
+/// Raw directory entries iterator.
+pub struct DirRawIter<'a, IO: ReadWriteSeek + 'a> {
+    stream: DirRawStream<'a, IO>,
+    done: bool,
+}
+
+impl<'a, IO: ReadWriteSeek> Clone for DirRawIter<'a, IO> {
+    fn clone(&self) -> Self {
+        DirRawIter {
+            stream: self.stream.clone(),
+            done: self.done,
+        }
+    }
+}
+
+impl<'a, IO: ReadWriteSeek> DirRawIter<'a, IO> {
+    fn read_raw_entry(&mut self) -> io::Result<Option<RawDirEntry>> {
+        let offset = self.stream.seek(SeekFrom::Current(0))?;
+        let mut data = [0u8; DIR_ENTRY_SIZE as usize];
+        match self.stream.read_exact(&mut data) {
+            // entries can occupy all clusters of directory so there is no zero entry at the end
+            Err(ref err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+            _ => {}
+        }
+        let abs_pos = self.stream.abs_pos().map(|p| p - DIR_ENTRY_SIZE);
+        Ok(Some(RawDirEntry {
+            data,
+            offset,
+            abs_pos,
+        }))
+    }
+}
+
+impl<'a, IO: ReadWriteSeek> Iterator for DirRawIter<'a, IO> {
+    type Item = io::Result<RawDirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.read_raw_entry() {
+            Ok(Some(e)) => {
+                self.done = e.is_end();
+                Some(Ok(e))
+            }
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Iterator of volume label entries.
+///
+/// Returned by `Dir::volume_entries`.
+pub struct DirVolumeIter<'a, IO: ReadWriteSeek + 'a> {
+    iter: DirRawIter<'a, IO>,
+}
+
+impl<'a, IO: ReadWriteSeek> Clone for DirVolumeIter<'a, IO> {
+    fn clone(&self) -> Self {
+        DirVolumeIter {
+            iter: self.iter.clone(),
+        }
+    }
+}
+
+impl<'a, IO: ReadWriteSeek> Iterator for DirVolumeIter<'a, IO> {
+    type Item = io::Result<RawDirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.iter.next() {
+                Some(Ok(e)) => {
+                    if !e.is_end() && !e.is_free() && e.is_volume() {
+                        return Some(Ok(e));
+                    }
+                }
+                r => return r,
+            }
+        }
+    }
+}
+
+pub(crate) const DOT_SHORT_NAME: [u8; 11] = *b".          ";
+pub(crate) const DOTDOT_SHORT_NAME: [u8; 11] = *b"..         ";
+
 fn copy_short_name_part(dst: &mut [u8], src: &str) {
     let mut j = 0;
     for c in src.chars() {
         if j == dst.len() {
             break;
         }
-        // replace characters allowed in long name but disallowed in short
+        // skip dots and spaces and replace other characters allowed in long name but disallowed
+        // in short (the full name is kept in LFN entries)
         let c2 = match c {
-            '.' | ' ' | '+' | ',' | ';' | '=' | '[' | ']' => '?',
+            '.' | ' ' => continue,
+            '+' | ',' | ';' | '=' | '[' | ']' => '_',
             _ if c < '\u{80}' => c,
-            _ => '?',
+            _ => '_',
         };
         // short name is always uppercase
         let upper = c2.to_uppercase().next().unwrap(); // SAFE: uppercase must return at least one character
@@ -401,20 +1487,67 @@ fn generate_short_name(name: &str) -> [u8; 11] {
             copy_short_name_part(&mut short_name[0..8], &name);
         }
     }
-    // FIXME: make sure short name is unique...
     short_name
 }
 
-fn validate_long_name(name: &str) -> io::Result<()> {
+fn long_name_hash(name: &str) -> u16 {
+    // FNV-1a folded to 16 bits, long names are compared ignoring case
+    let mut hash: u32 = 0x811C9DC5;
+    for b in name.bytes() {
+        hash ^= b.to_ascii_uppercase() as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    (hash >> 16) as u16 ^ hash as u16
+}
+
+// Builds short name with "~N" tail, optionally replacing end of the base name with a hash
+fn short_name_with_tail(short_name: &[u8; 11], hash: Option<u16>, n: u32) -> [u8; 11] {
+    let mut base = [b' '; 8];
+    let mut base_len = short_name[..8].iter().position(|&c| c == b' ').unwrap_or(8);
+    base[..base_len].copy_from_slice(&short_name[..base_len]);
+    match hash {
+        Some(hash) => {
+            base_len = cmp::min(base_len, 2);
+            for i in 0..4 {
+                let digit = (hash >> (12 - i * 4)) & 0xF;
+                base[base_len + i] = b"0123456789ABCDEF"[digit as usize];
+            }
+            base_len += 4;
+        }
+        None => {}
+    }
+    // format tail from the end of buffer
+    let mut tail = [b'~'; 8];
+    let mut tail_len = 1;
+    let mut x = n;
+    loop {
+        tail[8 - tail_len] = b'0' + (x % 10) as u8;
+        tail_len += 1;
+        x /= 10;
+        if x == 0 {
+            break;
+        }
+    }
+    let keep = cmp::min(base_len, 8 - tail_len);
+    let mut result = *short_name;
+    result[..keep].copy_from_slice(&base[..keep]);
+    result[keep..keep + tail_len].copy_from_slice(&tail[8 - tail_len..]);
+    for c in result[keep + tail_len..8].iter_mut() {
+        *c = b' ';
+    }
+    result
+}
+
+fn validate_long_name(name: &str, max_length: u32) -> io::Result<()> {
     if name.len() == 0 {
         return Err(io::Error::new(
             ErrorKind::InvalidInput,
             "filename cannot be empty",
         ));
     }
-    if name.len() > 255 {
+    if name.chars().count() > max_length as usize {
         return Err(io::Error::new(
-            ErrorKind::InvalidInput,
+            ErrorKind::NameTooLong,
             "filename is too long",
         ));
     }
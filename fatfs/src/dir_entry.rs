@@ -1,12 +1,12 @@
-use byteorder::LittleEndian;
-use byteorder_ext::{ReadBytesExt, WriteBytesExt};
-use core::{fmt, str};
-use io::Cursor;
+use byteorder::{ByteOrder, LittleEndian};
+use core::{char, cmp, fmt, iter, slice, str};
 use io::{self, *};
 
-use dir::{Dir, DirRawStream};
+use dir::{Dir, DirCursor, DirRawStream};
 use file::File;
-use fs::{FatType, FileSystemRef};
+use fs::{FatType, FileSystemRef, ReadWriteSeek};
+use table::valid_chain_length;
+use units::Cluster;
 
 bitflags! {
     /// FAT file attributes
@@ -25,6 +25,81 @@ bitflags! {
 pub(crate) const DIR_ENTRY_SIZE: u64 = 32;
 pub(crate) const DIR_ENTRY_FREE_FLAG: u8 = 0xE5;
 
+const LFN_ENTRY_LAST_FLAG: u8 = 0x40;
+const LFN_ORDER_MASK: u8 = 0x3F;
+const MAX_LFN_ENTRIES: usize = 20;
+// Number of UCS-2 characters stored in one LFN entry
+const LFN_PART_LEN: usize = 13;
+pub(crate) const LFN_BUFFER_LEN: usize = MAX_LFN_ENTRIES * LFN_PART_LEN;
+
+/// Computes checksum of short name stored in long file name entries.
+///
+/// Every LFN entry belonging to a file stores checksum of the file short name (11 bytes, padded
+/// with spaces and without the dot) so LFN entries orphaned by drivers not supporting long names
+/// can be detected.
+pub fn lfn_checksum(short_name: &[u8; 11]) -> u8 {
+    let mut chksum = 0u8;
+    for &b in short_name {
+        chksum = ((chksum & 1) << 7).wrapping_add(chksum >> 1).wrapping_add(b);
+    }
+    chksum
+}
+
+/// A problem found in a sequence of long file name entries.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LfnSequenceError {
+    /// Sequence is empty or consists of more than 20 entries.
+    InvalidLength,
+    /// Entry is not a long name entry or its reserved fields are not zero.
+    InvalidEntry,
+    /// Entries are not numbered from the last part of the name down to the first one.
+    InvalidOrder,
+    /// Stored checksum does not match the short name.
+    ChecksumMismatch,
+}
+
+impl LfnSequenceError {
+    /// Returns human-readable description of the problem.
+    pub fn description(&self) -> &'static str {
+        match *self {
+            LfnSequenceError::InvalidLength => "invalid number of long name entries",
+            LfnSequenceError::InvalidEntry => "invalid long name entry",
+            LfnSequenceError::InvalidOrder => "invalid order of long name entries",
+            LfnSequenceError::ChecksumMismatch => "long name checksum does not match short name",
+        }
+    }
+}
+
+/// Validates raw long file name entries belonging to a short name entry.
+///
+/// `lfn_entries` must be given in on-disk order, i.e. the entry holding the last part of the name
+/// comes first. `short_name` is the name field (first 11 bytes) of the short name entry following
+/// the sequence.
+pub fn validate_lfn_sequence(
+    lfn_entries: &[[u8; DIR_ENTRY_SIZE as usize]],
+    short_name: &[u8; 11],
+) -> core::result::Result<(), LfnSequenceError> {
+    let len = lfn_entries.len();
+    if len == 0 || len > MAX_LFN_ENTRIES {
+        return Err(LfnSequenceError::InvalidLength);
+    }
+    let checksum = lfn_checksum(short_name);
+    for (i, entry) in lfn_entries.iter().enumerate() {
+        // attributes, entry type and first cluster fields
+        if entry[11] != FileAttributes::LFN.bits() || entry[12] != 0 || entry[26..28] != [0, 0] {
+            return Err(LfnSequenceError::InvalidEntry);
+        }
+        let expected_order = (len - i) as u8 | if i == 0 { LFN_ENTRY_LAST_FLAG } else { 0 };
+        if entry[0] & (LFN_ORDER_MASK | LFN_ENTRY_LAST_FLAG) != expected_order {
+            return Err(LfnSequenceError::InvalidOrder);
+        }
+        if entry[13] != checksum {
+            return Err(LfnSequenceError::ChecksumMismatch);
+        }
+    }
+    Ok(())
+}
+
 /// Decoded file short name
 #[derive(Clone, Debug, Default)]
 pub(crate) struct ShortName {
@@ -67,6 +142,230 @@ impl ShortName {
     }
 }
 
+/// File long name as stored in LFN entries
+///
+/// Only UCS-2 units are kept - they are decoded to UTF-8 when the name is used (see `EntryName`).
+#[derive(Clone)]
+pub(crate) struct LongName {
+    units: [u16; LFN_BUFFER_LEN],
+    len: u16,
+}
+
+impl LongName {
+    pub(crate) fn empty() -> Self {
+        LongName {
+            units: [0; LFN_BUFFER_LEN],
+            len: 0,
+        }
+    }
+
+    fn from_ucs2(units: &[u16]) -> Self {
+        let mut long_name = LongName::empty();
+        long_name.units[..units.len()].copy_from_slice(units);
+        long_name.len = units.len() as u16;
+        long_name
+    }
+
+    fn units(&self) -> Option<&[u16]> {
+        match self.len {
+            0 => None,
+            len => Some(&self.units[..len as usize]),
+        }
+    }
+}
+
+/// Name of a directory entry.
+///
+/// Returned by `DirEntry::file_name` and `DirEntry::long_file_name`. Long names are decoded from
+/// UCS-2 units when the name is formatted, compared or iterated, so no UTF-8 copy of the name is
+/// stored in `DirEntry`. Unpaired surrogates are decoded as U+FFFD. Use `Display` implementation
+/// (e.g. `to_string`) to get the name as a string.
+#[derive(Clone, Copy)]
+pub struct EntryName<'a> {
+    inner: EntryNameInner<'a>,
+}
+
+#[derive(Clone, Copy)]
+enum EntryNameInner<'a> {
+    Short(&'a str),
+    Long(&'a [u16]),
+}
+
+impl<'a> EntryName<'a> {
+    fn short(name: &'a str) -> Self {
+        EntryName {
+            inner: EntryNameInner::Short(name),
+        }
+    }
+
+    fn long(units: &'a [u16]) -> Self {
+        EntryName {
+            inner: EntryNameInner::Long(units),
+        }
+    }
+
+    /// Returns iterator of characters of the name.
+    pub fn chars(&self) -> EntryNameChars<'a> {
+        let inner = match self.inner {
+            EntryNameInner::Short(name) => EntryNameCharsInner::Short(name.chars()),
+            EntryNameInner::Long(units) => {
+                EntryNameCharsInner::Long(char::decode_utf16(units.iter().cloned()))
+            }
+        };
+        EntryNameChars { inner }
+    }
+}
+
+impl<'a> fmt::Display for EntryName<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.inner {
+            EntryNameInner::Short(name) => f.write_str(name),
+            EntryNameInner::Long(_) => {
+                for c in self.chars() {
+                    fmt::Write::write_char(f, c)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<'a> fmt::Debug for EntryName<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Write::write_char(f, '"')?;
+        for c in self.chars() {
+            for e in c.escape_debug() {
+                fmt::Write::write_char(f, e)?;
+            }
+        }
+        fmt::Write::write_char(f, '"')
+    }
+}
+
+impl<'a, 'b> PartialEq<EntryName<'b>> for EntryName<'a> {
+    fn eq(&self, other: &EntryName<'b>) -> bool {
+        self.chars().eq(other.chars())
+    }
+}
+
+impl<'a> PartialEq<str> for EntryName<'a> {
+    fn eq(&self, other: &str) -> bool {
+        match self.inner {
+            EntryNameInner::Short(name) => name == other,
+            EntryNameInner::Long(_) => self.chars().eq(other.chars()),
+        }
+    }
+}
+
+impl<'a, 'b> PartialEq<&'b str> for EntryName<'a> {
+    fn eq(&self, other: &&'b str) -> bool {
+        *self == **other
+    }
+}
+
+/// Iterator of characters of an entry name.
+///
+/// Returned by `EntryName::chars`.
+#[derive(Clone)]
+pub struct EntryNameChars<'a> {
+    inner: EntryNameCharsInner<'a>,
+}
+
+#[derive(Clone)]
+enum EntryNameCharsInner<'a> {
+    Short(str::Chars<'a>),
+    Long(char::DecodeUtf16<iter::Cloned<slice::Iter<'a, u16>>>),
+}
+
+impl<'a> Iterator for EntryNameChars<'a> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        match self.inner {
+            EntryNameCharsInner::Short(ref mut chars) => chars.next(),
+            EntryNameCharsInner::Long(ref mut chars) => {
+                chars.next().map(|r| r.unwrap_or(char::REPLACEMENT_CHARACTER))
+            }
+        }
+    }
+}
+
+// Assembles long name from LFN entries preceding a short name entry. Entries are stored on disk
+// starting from the last part of the name so parts are collected in a buffer before decoding.
+pub(crate) struct LongNameBuilder {
+    buf: [u16; LFN_BUFFER_LEN],
+    // number of entries in the sequence or 0 if no valid sequence is being read
+    len: u8,
+    // order of the next expected entry
+    next_order: u8,
+    checksum: u8,
+    // set when entries not belonging to any valid sequence were seen
+    orphaned: bool,
+}
+
+impl LongNameBuilder {
+    pub(crate) fn new() -> Self {
+        LongNameBuilder {
+            buf: [0; LFN_BUFFER_LEN],
+            len: 0,
+            next_order: 0,
+            checksum: 0,
+            orphaned: false,
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        // sequence interrupted before its short name entry
+        self.orphaned |= self.len != 0;
+        self.len = 0;
+        self.next_order = 0;
+    }
+
+    pub(crate) fn is_orphaned(&self) -> bool {
+        self.orphaned
+    }
+
+    pub(crate) fn process(&mut self, data: &DirLfnEntryData) {
+        let order = data.order & LFN_ORDER_MASK;
+        let valid = data.entry_type == 0 && data.reserved_0 == 0 && order != 0;
+        if data.order & LFN_ENTRY_LAST_FLAG != 0 {
+            // first entry of a new sequence
+            self.clear();
+            if !valid || order as usize > MAX_LFN_ENTRIES {
+                self.orphaned = true;
+                return;
+            }
+            self.len = order;
+            self.checksum = data.checksum;
+        } else if !valid || order != self.next_order || data.checksum != self.checksum {
+            // entry is orphaned or out of order
+            self.clear();
+            self.orphaned = true;
+            return;
+        }
+        let pos = (order as usize - 1) * LFN_PART_LEN;
+        self.buf[pos..pos + 5].copy_from_slice(&data.name_0);
+        self.buf[pos + 5..pos + 11].copy_from_slice(&data.name_1);
+        self.buf[pos + 11..pos + 13].copy_from_slice(&data.name_2);
+        self.next_order = order - 1;
+    }
+
+    // Returns long name if a complete sequence belonging to the short name was read
+    pub(crate) fn finish(&mut self, short_name: &[u8; 11]) -> LongName {
+        let complete = self.len != 0 && self.next_order == 0;
+        let len = self.len as usize * LFN_PART_LEN;
+        self.len = 0;
+        if !complete || self.checksum != lfn_checksum(short_name) {
+            self.orphaned |= len != 0;
+            return LongName::empty();
+        }
+        // name is terminated by zero unless it fills all entries
+        let units = &self.buf[..len];
+        let name_len = units.iter().position(|&c| c == 0).unwrap_or(len);
+        LongName::from_ucs2(&units[..name_len])
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Clone, Debug, Default)]
 pub(crate) struct DirFileEntryData {
@@ -97,7 +396,15 @@ impl DirFileEntryData {
         &self.name
     }
 
-    pub(crate) fn first_cluster(&self, fat_type: FatType) -> Option<u32> {
+    pub(crate) fn set_name(&mut self, name: [u8; 11]) {
+        self.name = name;
+    }
+
+    pub(crate) fn set_attrs(&mut self, attrs: FileAttributes) {
+        self.attrs = attrs;
+    }
+
+    pub(crate) fn first_cluster(&self, fat_type: FatType) -> Option<Cluster> {
         let first_cluster_hi = if fat_type == FatType::Fat32 {
             self.first_cluster_hi
         } else {
@@ -107,12 +414,12 @@ impl DirFileEntryData {
         if n == 0 {
             None
         } else {
-            Some(n)
+            Some(Cluster(n))
         }
     }
 
-    pub(crate) fn set_first_cluster(&mut self, cluster: Option<u32>, fat_type: FatType) {
-        let n = cluster.unwrap_or(0);
+    pub(crate) fn set_first_cluster(&mut self, cluster: Option<Cluster>, fat_type: FatType) {
+        let n = cluster.map_or(0, |n| n.0);
         if fat_type == FatType::Fat32 {
             self.first_cluster_hi = (n >> 16) as u16;
         }
@@ -139,59 +446,82 @@ impl DirFileEntryData {
         !self.is_dir()
     }
 
-    fn created(&self) -> DateTime {
-        DateTime::from_u16(self.create_date, self.create_time_1)
+    pub(crate) fn created(&self) -> DateTime {
+        let mut date_time = DateTime::from_u16(self.create_date, self.create_time_1);
+        // fine resolution field holds 10 ms units (0-199) added to the 2 second resolution time
+        date_time.time.sec += cmp::min(self.create_time_0, 199) as u16 / 100;
+        date_time
     }
 
-    fn accessed(&self) -> Date {
+    pub(crate) fn accessed(&self) -> Date {
         Date::from_u16(self.access_date)
     }
 
-    fn modified(&self) -> DateTime {
+    pub(crate) fn modified(&self) -> DateTime {
         DateTime::from_u16(self.modify_date, self.modify_time)
     }
 
-    fn set_created(&mut self, date_time: DateTime) {
+    pub(crate) fn set_created(&mut self, date_time: DateTime) {
+        let date_time = date_time.clamp();
         self.create_date = date_time.date.to_u16();
         self.create_time_1 = date_time.time.to_u16();
+        self.create_time_0 = (date_time.time.sec % 2 * 100) as u8;
     }
 
-    fn set_accessed(&mut self, date: Date) {
+    pub(crate) fn set_accessed(&mut self, date: Date) {
         self.access_date = date.to_u16();
     }
 
-    fn set_modified(&mut self, date_time: DateTime) {
+    pub(crate) fn set_modified(&mut self, date_time: DateTime) {
+        let date_time = date_time.clamp();
         self.modify_date = date_time.date.to_u16();
         self.modify_time = date_time.time.to_u16();
     }
 
-    pub(crate) fn reset_created(&mut self) {
-        // nop - user controls timestamps manually
+    pub(crate) fn reset_modified(&mut self, time_provider: &TimeProvider) {
+        self.set_modified(time_provider.get_current_date_time());
     }
 
-    pub(crate) fn reset_accessed(&mut self) -> bool {
-        // nop - user controls timestamps manually
-        false
+    pub(crate) fn from_bytes(buf: &[u8; DIR_ENTRY_SIZE as usize]) -> DirFileEntryData {
+        let mut name = [0u8; 11];
+        name.copy_from_slice(&buf[0..11]);
+        DirFileEntryData {
+            name,
+            attrs: FileAttributes::from_bits_truncate(buf[11]),
+            reserved_0: buf[12],
+            create_time_0: buf[13],
+            create_time_1: LittleEndian::read_u16(&buf[14..]),
+            create_date: LittleEndian::read_u16(&buf[16..]),
+            access_date: LittleEndian::read_u16(&buf[18..]),
+            first_cluster_hi: LittleEndian::read_u16(&buf[20..]),
+            modify_time: LittleEndian::read_u16(&buf[22..]),
+            modify_date: LittleEndian::read_u16(&buf[24..]),
+            first_cluster_lo: LittleEndian::read_u16(&buf[26..]),
+            size: LittleEndian::read_u32(&buf[28..]),
+        }
     }
 
-    pub(crate) fn reset_modified(&mut self) {
-        // nop - user controls timestamps manually
+    pub(crate) fn to_bytes(&self, buf: &mut [u8; DIR_ENTRY_SIZE as usize]) {
+        buf[0..11].copy_from_slice(&self.name);
+        buf[11] = self.attrs.bits();
+        buf[12] = self.reserved_0;
+        buf[13] = self.create_time_0;
+        LittleEndian::write_u16(&mut buf[14..], self.create_time_1);
+        LittleEndian::write_u16(&mut buf[16..], self.create_date);
+        LittleEndian::write_u16(&mut buf[18..], self.access_date);
+        LittleEndian::write_u16(&mut buf[20..], self.first_cluster_hi);
+        LittleEndian::write_u16(&mut buf[22..], self.modify_time);
+        LittleEndian::write_u16(&mut buf[24..], self.modify_date);
+        LittleEndian::write_u16(&mut buf[26..], self.first_cluster_lo);
+        LittleEndian::write_u32(&mut buf[28..], self.size);
     }
 
-    pub(crate) fn serialize(&self, wrt: &mut Write) -> io::Result<()> {
-        wrt.write_all(&self.name)?;
-        wrt.write_u8(self.attrs.bits())?;
-        wrt.write_u8(self.reserved_0)?;
-        wrt.write_u8(self.create_time_0)?;
-        wrt.write_u16::<LittleEndian>(self.create_time_1)?;
-        wrt.write_u16::<LittleEndian>(self.create_date)?;
-        wrt.write_u16::<LittleEndian>(self.access_date)?;
-        wrt.write_u16::<LittleEndian>(self.first_cluster_hi)?;
-        wrt.write_u16::<LittleEndian>(self.modify_time)?;
-        wrt.write_u16::<LittleEndian>(self.modify_date)?;
-        wrt.write_u16::<LittleEndian>(self.first_cluster_lo)?;
-        wrt.write_u32::<LittleEndian>(self.size)?;
-        Ok(())
+    pub(crate) fn serialize<W: Write + ?Sized>(&self, wrt: &mut W) -> io::Result<()> {
+        // Entry is written in a single operation so it is never partially updated on disk.
+        // Entries are aligned to their size so the write never crosses a sector boundary.
+        let mut buf = [0u8; DIR_ENTRY_SIZE as usize];
+        self.to_bytes(&mut buf);
+        wrt.write_all(&buf)
     }
 
     pub(crate) fn is_free(&self) -> bool {
@@ -225,22 +555,37 @@ pub(crate) struct DirLfnEntryData {
 }
 
 impl DirLfnEntryData {
-    pub(crate) fn serialize(&self, wrt: &mut Write) -> io::Result<()> {
-        wrt.write_u8(self.order)?;
-        for ch in self.name_0.iter() {
-            wrt.write_u16::<LittleEndian>(*ch)?;
-        }
-        wrt.write_u8(self.attrs.bits())?;
-        wrt.write_u8(self.entry_type)?;
-        wrt.write_u8(self.checksum)?;
-        for ch in self.name_1.iter() {
-            wrt.write_u16::<LittleEndian>(*ch)?;
-        }
-        wrt.write_u16::<LittleEndian>(self.reserved_0)?;
-        for ch in self.name_2.iter() {
-            wrt.write_u16::<LittleEndian>(*ch)?;
-        }
-        Ok(())
+    pub(crate) fn from_bytes(buf: &[u8; DIR_ENTRY_SIZE as usize]) -> DirLfnEntryData {
+        let mut data = DirLfnEntryData {
+            order: buf[0],
+            attrs: FileAttributes::from_bits_truncate(buf[11]),
+            entry_type: buf[12],
+            checksum: buf[13],
+            reserved_0: LittleEndian::read_u16(&buf[26..]),
+            ..Default::default()
+        };
+        LittleEndian::read_u16_into(&buf[1..11], &mut data.name_0);
+        LittleEndian::read_u16_into(&buf[14..26], &mut data.name_1);
+        LittleEndian::read_u16_into(&buf[28..32], &mut data.name_2);
+        data
+    }
+
+    pub(crate) fn to_bytes(&self, buf: &mut [u8; DIR_ENTRY_SIZE as usize]) {
+        buf[0] = self.order;
+        LittleEndian::write_u16_into(&self.name_0, &mut buf[1..11]);
+        buf[11] = self.attrs.bits();
+        buf[12] = self.entry_type;
+        buf[13] = self.checksum;
+        LittleEndian::write_u16_into(&self.name_1, &mut buf[14..26]);
+        LittleEndian::write_u16(&mut buf[26..], self.reserved_0);
+        LittleEndian::write_u16_into(&self.name_2, &mut buf[28..32]);
+    }
+
+    pub(crate) fn serialize<W: Write + ?Sized>(&self, wrt: &mut W) -> io::Result<()> {
+        // see DirFileEntryData::serialize
+        let mut buf = [0u8; DIR_ENTRY_SIZE as usize];
+        self.to_bytes(&mut buf);
+        wrt.write_all(&buf)
     }
 
     pub(crate) fn is_free(&self) -> bool {
@@ -256,6 +601,77 @@ impl DirLfnEntryData {
     }
 }
 
+// Generates LFN entries storing a long name. Entries are returned in on-disk order, i.e. starting
+// from the last part of the name.
+pub(crate) struct LfnEntriesGenerator {
+    units: [u16; LFN_BUFFER_LEN],
+    name_len: usize,
+    checksum: u8,
+    // order of the next entry or 0 when all entries were returned
+    next_order: u8,
+    num_entries: u8,
+}
+
+impl LfnEntriesGenerator {
+    // Returns None if the short name stores the name exactly (ignoring case) so no LFN entries
+    // are needed. Name must be validated already - it cannot exceed the maximal name length.
+    pub(crate) fn new(name: &str, short_name: &[u8; 11]) -> Option<Self> {
+        if eq_ignore_case(ShortName::new(short_name).to_str().chars(), name) {
+            return None;
+        }
+        // unused units after the zero terminator are filled with 0xFFFF
+        let mut units = [0xFFFFu16; LFN_BUFFER_LEN];
+        let mut name_len = 0;
+        for (dst, src) in units.iter_mut().zip(name.encode_utf16()) {
+            *dst = src;
+            name_len += 1;
+        }
+        // name is terminated by zero unless it fills all entries
+        if name_len % LFN_PART_LEN != 0 {
+            units[name_len] = 0;
+        }
+        let num_entries = ((name_len + LFN_PART_LEN - 1) / LFN_PART_LEN) as u8;
+        Some(LfnEntriesGenerator {
+            units,
+            name_len,
+            checksum: lfn_checksum(short_name),
+            next_order: num_entries,
+            num_entries,
+        })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.num_entries as usize
+    }
+
+    pub(crate) fn long_name(&self) -> LongName {
+        LongName::from_ucs2(&self.units[..self.name_len])
+    }
+}
+
+impl Iterator for LfnEntriesGenerator {
+    type Item = DirLfnEntryData;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next_order == 0 {
+            return None;
+        }
+        let order = self.next_order;
+        self.next_order -= 1;
+        let pos = (order as usize - 1) * LFN_PART_LEN;
+        let mut data = DirLfnEntryData {
+            order: if order == self.num_entries { order | LFN_ENTRY_LAST_FLAG } else { order },
+            attrs: FileAttributes::LFN,
+            checksum: self.checksum,
+            ..Default::default()
+        };
+        data.name_0.copy_from_slice(&self.units[pos..pos + 5]);
+        data.name_1.copy_from_slice(&self.units[pos + 5..pos + 11]);
+        data.name_2.copy_from_slice(&self.units[pos + 11..pos + 13]);
+        Some(data)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub(crate) enum DirEntryData {
     File(DirFileEntryData),
@@ -263,16 +679,16 @@ pub(crate) enum DirEntryData {
 }
 
 impl DirEntryData {
-    pub(crate) fn serialize(&mut self, wrt: &mut Write) -> io::Result<()> {
-        match self {
-            &mut DirEntryData::File(ref mut file) => file.serialize(wrt),
-            &mut DirEntryData::Lfn(ref mut lfn) => lfn.serialize(wrt),
-        }
+    pub(crate) fn serialize<W: Write + ?Sized>(&mut self, wrt: &mut W) -> io::Result<()> {
+        // see DirFileEntryData::serialize
+        let mut buf = [0u8; DIR_ENTRY_SIZE as usize];
+        self.to_bytes(&mut buf);
+        wrt.write_all(&buf)
     }
 
-    pub(crate) fn deserialize(rdr: &mut Read) -> io::Result<DirEntryData> {
-        let mut name = [0; 11];
-        match rdr.read_exact(&mut name) {
+    pub(crate) fn deserialize<R: Read + ?Sized>(rdr: &mut R) -> io::Result<DirEntryData> {
+        let mut buf = [0u8; DIR_ENTRY_SIZE as usize];
+        match rdr.read_exact(&mut buf) {
             Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => {
                 // entries can occupy all clusters of directory so there is no zero entry at the end
                 // handle it here by returning non-existing empty entry
@@ -283,40 +699,22 @@ impl DirEntryData {
             Err(err) => return Err(err),
             _ => {}
         }
-        let attrs = FileAttributes::from_bits_truncate(rdr.read_u8()?);
+        Ok(DirEntryData::from_bytes(&buf))
+    }
+
+    pub(crate) fn from_bytes(buf: &[u8; DIR_ENTRY_SIZE as usize]) -> DirEntryData {
+        let attrs = FileAttributes::from_bits_truncate(buf[11]);
         if attrs & FileAttributes::LFN == FileAttributes::LFN {
-            // read long name entry
-            let mut data = DirLfnEntryData {
-                attrs,
-                ..Default::default()
-            };
-            // use cursor to divide name into order and LFN name_0
-            let mut cur = Cursor::new(&name);
-            data.order = cur.read_u8()?;
-            cur.read_u16_into::<LittleEndian>(&mut data.name_0)?;
-            data.entry_type = rdr.read_u8()?;
-            data.checksum = rdr.read_u8()?;
-            rdr.read_u16_into::<LittleEndian>(&mut data.name_1)?;
-            data.reserved_0 = rdr.read_u16::<LittleEndian>()?;
-            rdr.read_u16_into::<LittleEndian>(&mut data.name_2)?;
-            Ok(DirEntryData::Lfn(data))
+            DirEntryData::Lfn(DirLfnEntryData::from_bytes(buf))
         } else {
-            // read short name entry
-            let data = DirFileEntryData {
-                name,
-                attrs,
-                reserved_0: rdr.read_u8()?,
-                create_time_0: rdr.read_u8()?,
-                create_time_1: rdr.read_u16::<LittleEndian>()?,
-                create_date: rdr.read_u16::<LittleEndian>()?,
-                access_date: rdr.read_u16::<LittleEndian>()?,
-                first_cluster_hi: rdr.read_u16::<LittleEndian>()?,
-                modify_time: rdr.read_u16::<LittleEndian>()?,
-                modify_date: rdr.read_u16::<LittleEndian>()?,
-                first_cluster_lo: rdr.read_u16::<LittleEndian>()?,
-                size: rdr.read_u32::<LittleEndian>()?,
-            };
-            Ok(DirEntryData::File(data))
+            DirEntryData::File(DirFileEntryData::from_bytes(buf))
+        }
+    }
+
+    pub(crate) fn to_bytes(&self, buf: &mut [u8; DIR_ENTRY_SIZE as usize]) {
+        match self {
+            &DirEntryData::File(ref file) => file.to_bytes(buf),
+            &DirEntryData::Lfn(ref lfn) => lfn.to_bytes(buf),
         }
     }
 
@@ -342,6 +740,23 @@ impl DirEntryData {
     }
 }
 
+// Range of years which can be stored in FAT (7-bit offset from 1980)
+const MIN_YEAR: u16 = 1980;
+const MAX_YEAR: u16 = 2107;
+
+fn is_leap_year(year: u16) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: u16, month: u16) -> u16 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
 /// DOS compatible date
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Date {
@@ -351,6 +766,56 @@ pub struct Date {
 }
 
 impl Date {
+    /// Creates new date.
+    ///
+    /// Fails with `ErrorKind::InvalidInput` if date is invalid or cannot be stored in FAT (year
+    /// outside of range 1980-2107).
+    pub fn new(year: u16, month: u16, day: u16) -> io::Result<Self> {
+        let date = Date { year, month, day };
+        if !date.is_valid() {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "invalid date"));
+        }
+        Ok(date)
+    }
+
+    /// Checks if date is valid and can be stored in FAT.
+    pub fn is_valid(&self) -> bool {
+        self.year >= MIN_YEAR
+            && self.year <= MAX_YEAR
+            && self.month >= 1
+            && self.month <= 12
+            && self.day >= 1
+            && self.day <= days_in_month(self.year, self.month)
+    }
+
+    /// Returns the nearest date which can be stored in FAT.
+    ///
+    /// Dates before 1980 become 1980-01-01 and dates after 2107 become 2107-12-31. Out of range
+    /// month and day are limited to the valid range.
+    pub fn clamp(&self) -> Self {
+        if self.year < MIN_YEAR {
+            Date {
+                year: MIN_YEAR,
+                month: 1,
+                day: 1,
+            }
+        } else if self.year > MAX_YEAR {
+            Date {
+                year: MAX_YEAR,
+                month: 12,
+                day: 31,
+            }
+        } else {
+            let month = clamp(self.month, 1, 12);
+            let day = clamp(self.day, 1, days_in_month(self.year, month));
+            Date {
+                year: self.year,
+                month,
+                day,
+            }
+        }
+    }
+
     pub(crate) fn from_u16(dos_date: u16) -> Self {
         let (year, month, day) = (
             (dos_date >> 9) + 1980,
@@ -361,7 +826,8 @@ impl Date {
     }
 
     fn to_u16(&self) -> u16 {
-        ((self.year - 1980) << 9) | (self.month << 5) | self.day
+        let date = self.clamp();
+        ((date.year - MIN_YEAR) << 9) | (date.month << 5) | date.day
     }
 }
 
@@ -374,6 +840,33 @@ pub struct Time {
 }
 
 impl Time {
+    /// Creates new time.
+    ///
+    /// Fails with `ErrorKind::InvalidInput` if time is invalid. Note: FAT stores seconds with
+    /// 2 second resolution so odd seconds are rounded down when stored. Creation time is the
+    /// exception - it has an additional field keeping odd seconds.
+    pub fn new(hour: u16, min: u16, sec: u16) -> io::Result<Self> {
+        let time = Time { hour, min, sec };
+        if !time.is_valid() {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "invalid time"));
+        }
+        Ok(time)
+    }
+
+    /// Checks if time is valid.
+    pub fn is_valid(&self) -> bool {
+        self.hour < 24 && self.min < 60 && self.sec < 60
+    }
+
+    /// Returns time with out of range fields limited to the valid range.
+    pub fn clamp(&self) -> Self {
+        Time {
+            hour: cmp::min(self.hour, 23),
+            min: cmp::min(self.min, 59),
+            sec: cmp::min(self.sec, 59),
+        }
+    }
+
     pub(crate) fn from_u16(dos_time: u16) -> Self {
         let (hour, min, sec) = (
             dos_time >> 11,
@@ -384,7 +877,8 @@ impl Time {
     }
 
     fn to_u16(&self) -> u16 {
-        (self.hour << 11) | (self.min << 5) | (self.sec / 2)
+        let time = self.clamp();
+        (time.hour << 11) | (time.min << 5) | (time.sec / 2)
     }
 }
 
@@ -396,6 +890,70 @@ pub struct DateTime {
 }
 
 impl DateTime {
+    /// Checks if date and time are valid and can be stored in FAT.
+    pub fn is_valid(&self) -> bool {
+        self.date.is_valid() && self.time.is_valid()
+    }
+
+    /// Returns the nearest date and time which can be stored in FAT.
+    ///
+    /// Timestamps before 1980 become 1980-01-01 00:00:00 and timestamps after 2107 become
+    /// 2107-12-31 23:59:59. Out of range fields are limited to the valid range.
+    pub fn clamp(&self) -> Self {
+        let time = if self.date.year < MIN_YEAR {
+            Time {
+                hour: 0,
+                min: 0,
+                sec: 0,
+            }
+        } else if self.date.year > MAX_YEAR {
+            Time {
+                hour: 23,
+                min: 59,
+                sec: 59,
+            }
+        } else {
+            self.time.clamp()
+        };
+        DateTime {
+            date: self.date.clamp(),
+            time,
+        }
+    }
+
+    /// Converts Unix timestamp (seconds since 1970-01-01 00:00:00) to date and time.
+    ///
+    /// FAT does not store time zone so the result is in the same time zone as the timestamp
+    /// (usually UTC). Timestamps outside of range supported by FAT are clamped (see `clamp`).
+    pub fn from_unix_epoch(secs: i64) -> Self {
+        let max_secs = days_from_civil(MAX_YEAR, 12, 31) * SECS_PER_DAY + SECS_PER_DAY - 1;
+        let secs = cmp::max(0, cmp::min(secs, max_secs));
+        let (year, month, day) = civil_from_days(secs / SECS_PER_DAY);
+        let secs_of_day = (secs % SECS_PER_DAY) as u32;
+        let date_time = DateTime {
+            date: Date { year, month, day },
+            time: Time {
+                hour: (secs_of_day / 3600) as u16,
+                min: (secs_of_day / 60 % 60) as u16,
+                sec: (secs_of_day % 60) as u16,
+            },
+        };
+        date_time.clamp()
+    }
+
+    /// Converts date and time to Unix timestamp (seconds since 1970-01-01 00:00:00).
+    ///
+    /// Invalid date and time are clamped first (see `clamp`).
+    pub fn to_unix_epoch(&self) -> i64 {
+        let date_time = self.clamp();
+        let date = date_time.date;
+        let time = date_time.time;
+        days_from_civil(date.year, date.month, date.day) * SECS_PER_DAY
+            + time.hour as i64 * 3600
+            + time.min as i64 * 60
+            + time.sec as i64
+    }
+
     pub(crate) fn from_u16(dos_date: u16, dos_time: u16) -> Self {
         DateTime {
             date: Date::from_u16(dos_date),
@@ -404,11 +962,103 @@ impl DateTime {
     }
 }
 
+const SECS_PER_DAY: i64 = 24 * 60 * 60;
+
+// Days since 1970-01-01 in proleptic Gregorian calendar, valid for years since 1 AD (algorithm from
+// http://howardhinnant.github.io/date_algorithms.html). Eras are 400-year cycles starting on
+// March 1st so leap day is the last day of a year.
+fn days_from_civil(year: u16, month: u16, day: u16) -> i64 {
+    let year = year as i64 - if month <= 2 { 1 } else { 0 };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let month_from_march = (month as i64 + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// Inverse of days_from_civil for non-negative number of days
+fn civil_from_days(days: i64) -> (u16, u16, u16) {
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = (month_from_march + 2) % 12 + 1;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year as u16, month as u16, day as u16)
+}
+
+fn clamp(val: u16, min: u16, max: u16) -> u16 {
+    cmp::max(min, cmp::min(val, max))
+}
+
+/// A current date and time provider.
+///
+/// Library calls this trait methods when it needs to update file timestamps. Implementation can
+/// use RTC or any other time source available on the platform.
+pub trait TimeProvider {
+    fn get_current_date(&self) -> Date;
+    fn get_current_date_time(&self) -> DateTime;
+}
+
+/// `TimeProvider` implementation that always returns DOS minimal date-time (1980-01-01 00:00:00).
+///
+/// It is used by default because there is no portable time source in `no_std` environment.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NullTimeProvider;
+
+impl TimeProvider for NullTimeProvider {
+    fn get_current_date(&self) -> Date {
+        Date {
+            year: 1980,
+            month: 1,
+            day: 1,
+        }
+    }
+
+    fn get_current_date_time(&self) -> DateTime {
+        DateTime {
+            date: self.get_current_date(),
+            time: Time {
+                hour: 0,
+                min: 0,
+                sec: 0,
+            },
+        }
+    }
+}
+
+// Converts character to upper case using only one-to-one mappings (FAT up-case tables cannot
+// map a character to several characters, e.g. 'ß' to "SS")
+fn to_fat_uppercase(c: char) -> char {
+    let mut upper = c.to_uppercase();
+    match (upper.next(), upper.next()) {
+        (Some(u), None) => u,
+        _ => c,
+    }
+}
+
+fn eq_ignore_case<I: Iterator<Item = char>>(a: I, b: &str) -> bool {
+    a.map(to_fat_uppercase).eq(b.chars().map(to_fat_uppercase))
+}
+
+pub(crate) fn starts_with_ignore_case<I: Iterator<Item = char>>(name: I, prefix: &str) -> bool {
+    let mut name = name.map(to_fat_uppercase);
+    prefix.chars().map(to_fat_uppercase).all(|p| name.next() == Some(p))
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct DirEntryEditor {
     data: DirFileEntryData,
     pos: u64,
     dirty: bool,
+    // record in the table of open entries - set for editors owned by file handles
+    #[cfg(feature = "alloc")]
+    handle: Option<u32>,
 }
 
 impl DirEntryEditor {
@@ -417,6 +1067,46 @@ impl DirEntryEditor {
             data,
             pos,
             dirty: false,
+            #[cfg(feature = "alloc")]
+            handle: None,
+        }
+    }
+
+    // Registers the editor in the table of open entries, so it follows the entry when it is
+    // renamed and fails when it is removed
+    #[cfg(feature = "alloc")]
+    pub(crate) fn register<IO: ReadWriteSeek>(&mut self, fs: FileSystemRef<IO>) {
+        let id = fs.open_entries.borrow_mut().open(self.pos, self.data.name);
+        self.handle = Some(id);
+    }
+
+    // Returns a copy of the editor registered as another handle of the same entry
+    #[cfg(feature = "alloc")]
+    pub(crate) fn clone_handle<IO: ReadWriteSeek>(&self, fs: FileSystemRef<IO>) -> Self {
+        match self.handle {
+            Some(id) => fs.open_entries.borrow_mut().clone_handle(id),
+            None => {}
+        }
+        self.clone()
+    }
+
+    #[cfg(feature = "alloc")]
+    pub(crate) fn unregister<IO: ReadWriteSeek>(&mut self, fs: FileSystemRef<IO>) {
+        match self.handle.take() {
+            Some(id) => fs.open_entries.borrow_mut().close(id),
+            None => {}
+        }
+    }
+
+    // Returns an error if the entry was removed since the editor has been registered
+    #[cfg(feature = "alloc")]
+    pub(crate) fn check_not_removed<IO: ReadWriteSeek>(
+        &self,
+        fs: FileSystemRef<IO>,
+    ) -> io::Result<()> {
+        match self.handle {
+            Some(id) if fs.open_entries.borrow().get(id).is_none() => Err(entry_removed_error()),
+            _ => Ok(()),
         }
     }
 
@@ -424,13 +1114,29 @@ impl DirEntryEditor {
         &self.data
     }
 
-    pub(crate) fn set_first_cluster(&mut self, first_cluster: Option<u32>, fat_type: FatType) {
+    #[cfg(feature = "splice")]
+    pub(crate) fn pos(&self) -> u64 {
+        self.pos
+    }
+
+    pub(crate) fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub(crate) fn set_first_cluster(&mut self, first_cluster: Option<Cluster>, fat_type: FatType) {
         if first_cluster != self.data.first_cluster(fat_type) {
             self.data.set_first_cluster(first_cluster, fat_type);
             self.dirty = true;
         }
     }
 
+    pub(crate) fn set_attrs(&mut self, attrs: FileAttributes) {
+        if attrs != self.data.attrs {
+            self.data.set_attrs(attrs);
+            self.dirty = true;
+        }
+    }
+
     pub(crate) fn set_size(&mut self, size: u32) {
         match self.data.size() {
             Some(n) if size != n => {
@@ -462,12 +1168,12 @@ impl DirEntryEditor {
         }
     }
 
-    pub(crate) fn reset_modified(&mut self) {
-        self.data.reset_modified();
+    pub(crate) fn reset_modified(&mut self, time_provider: &TimeProvider) {
+        self.data.reset_modified(time_provider);
         self.dirty = true;
     }
 
-    pub(crate) fn flush(&mut self, fs: FileSystemRef) -> io::Result<()> {
+    pub(crate) fn flush<IO: ReadWriteSeek>(&mut self, fs: FileSystemRef<IO>) -> io::Result<()> {
         if self.dirty {
             self.write(fs)?;
             self.dirty = false;
@@ -475,32 +1181,171 @@ impl DirEntryEditor {
         Ok(())
     }
 
-    fn write(&self, fs: FileSystemRef) -> io::Result<()> {
+    fn write<IO: ReadWriteSeek>(&mut self, fs: FileSystemRef<IO>) -> io::Result<()> {
+        // follow the entry if it was renamed
+        #[cfg(feature = "alloc")]
+        match self.handle {
+            Some(id) => match fs.open_entries.borrow().get(id) {
+                Some((pos, name)) => {
+                    self.pos = pos;
+                    self.data.name = name;
+                }
+                None => return Err(entry_removed_error()),
+            },
+            None => {}
+        }
         let mut disk = fs.disk.borrow_mut();
+        // Make sure entry was not removed or moved since it has been read. Otherwise writing it
+        // would resurrect the old entry or overwrite an unrelated one.
+        let mut name = [0u8; 11];
         disk.seek(io::SeekFrom::Start(self.pos))?;
-        self.data.serialize(&mut *disk)
+        disk.read_exact(&mut name)?;
+        if name != self.data.name {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "directory entry was moved or removed",
+            ));
+        }
+        disk.seek(io::SeekFrom::Start(self.pos))?;
+        let r = self.data.serialize(&mut *disk);
+        drop(disk);
+        fs.poison_on_error(r)
+    }
+}
+
+#[cfg(feature = "alloc")]
+fn entry_removed_error() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "file was removed")
+}
+
+/// Raw 32-byte directory entry.
+///
+/// Returned by DirRawIter. Includes long name, deleted and volume label entries.
+#[derive(Clone, Copy)]
+pub struct RawDirEntry {
+    pub(crate) data: [u8; DIR_ENTRY_SIZE as usize],
+    pub(crate) offset: u64,
+    pub(crate) abs_pos: Option<u64>,
+}
+
+impl RawDirEntry {
+    /// Returns raw entry bytes.
+    pub fn bytes(&self) -> &[u8; DIR_ENTRY_SIZE as usize] {
+        &self.data
+    }
+
+    /// Returns offset of entry from the beginning of directory.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns offset of entry from the beginning of volume.
+    pub fn abs_pos(&self) -> Option<u64> {
+        self.abs_pos
+    }
+
+    /// Returns true if entry marks the end of directory.
+    pub fn is_end(&self) -> bool {
+        self.data[0] == 0
+    }
+
+    /// Returns true if entry is deleted.
+    pub fn is_free(&self) -> bool {
+        self.data[0] == DIR_ENTRY_FREE_FLAG
+    }
+
+    /// Returns true if entry is a part of long file name.
+    pub fn is_lfn(&self) -> bool {
+        FileAttributes::from_bits_truncate(self.data[11]) == FileAttributes::LFN
+    }
+
+    /// Returns true if entry holds volume label (has VOLUME_ID attribute and is not a part of
+    /// long file name).
+    pub fn is_volume(&self) -> bool {
+        let attrs = FileAttributes::from_bits_truncate(self.data[11]);
+        attrs.contains(FileAttributes::VOLUME_ID) && !self.is_lfn()
+    }
+
+    /// Returns label stored in a volume label entry without trailing spaces.
+    ///
+    /// Empty string is returned if the label is not valid UTF-8.
+    pub fn volume_label(&self) -> &str {
+        str::from_utf8(&self.data[..11]).unwrap_or("").trim_right()
     }
 }
 
 /// FAT directory entry.
 ///
 /// Returned by DirIter.
-#[derive(Clone)]
-pub struct DirEntry<'a, 'b: 'a> {
+pub struct DirEntry<'a, IO: ReadWriteSeek + 'a> {
     pub(crate) data: DirFileEntryData,
     pub(crate) short_name: ShortName,
+    pub(crate) long_name: LongName,
     pub(crate) entry_pos: u64,
     pub(crate) offset_range: (u64, u64),
-    pub(crate) fs: FileSystemRef<'a, 'b>,
+    pub(crate) fs: FileSystemRef<'a, IO>,
+}
+
+// Derived implementation would require `IO: Clone`
+impl<'a, IO: ReadWriteSeek> Clone for DirEntry<'a, IO> {
+    fn clone(&self) -> Self {
+        DirEntry {
+            data: self.data.clone(),
+            short_name: self.short_name.clone(),
+            long_name: self.long_name.clone(),
+            entry_pos: self.entry_pos,
+            offset_range: self.offset_range,
+            fs: self.fs,
+        }
+    }
 }
 
-impl<'a, 'b> DirEntry<'a, 'b> {
+impl<'a, IO: ReadWriteSeek> DirEntry<'a, IO> {
     pub fn short_file_name(&self) -> &str {
         self.short_name.to_str()
     }
 
-    pub fn file_name(&self) -> &str {
-        self.short_file_name()
+    /// Returns long file name or `None` if the entry has no valid long name.
+    ///
+    /// Long names are read from LFN entries preceding the short name entry. Entries with a wrong
+    /// order or checksum (e.g. left behind by drivers not supporting long names) are ignored.
+    pub fn long_file_name(&self) -> Option<EntryName> {
+        self.long_name.units().map(EntryName::long)
+    }
+
+    /// Returns short name exactly as stored in the directory entry: 8 bytes of base name and
+    /// 3 bytes of extension padded with spaces, in OEM encoding.
+    ///
+    /// Unlike `short_file_name` no bytes are replaced, so archival tools can preserve the original
+    /// encoding. Note: first byte 0x05 stands for 0xE5 (it is kept as stored).
+    pub fn raw_short_name(&self) -> [u8; 11] {
+        *self.data.name()
+    }
+
+    /// Returns UCS-2 code units of long file name in name order or `None` if the entry has no
+    /// valid long name.
+    ///
+    /// Units are returned as stored in LFN entries without the terminator and padding. Unlike
+    /// `long_file_name` unpaired surrogates are not replaced.
+    pub fn raw_lfn_units(&self) -> Option<&[u16]> {
+        self.long_name.units()
+    }
+
+    /// Returns long file name if present and short file name otherwise.
+    pub fn file_name(&self) -> EntryName {
+        match self.long_file_name() {
+            Some(name) => name,
+            None => EntryName::short(self.short_file_name()),
+        }
+    }
+
+    /// Checks if long or short name of this entry is equal to `name` ignoring case.
+    ///
+    /// Case is ignored for all characters with a single character upper case form like in FAT
+    /// implementations, not only for ASCII ones. Comparison does not allocate memory.
+    pub fn eq_name(&self, name: &str) -> bool {
+        eq_ignore_case(self.file_name().chars(), name)
+            || eq_ignore_case(self.short_file_name().chars(), name)
     }
 
     /// Returns file attributes
@@ -508,6 +1353,30 @@ impl<'a, 'b> DirEntry<'a, 'b> {
         self.data.attrs
     }
 
+    /// Changes attributes of this entry and writes it to the device.
+    ///
+    /// Only `READ_ONLY`, `HIDDEN`, `SYSTEM` and `ARCHIVE` attributes can be set or cleared -
+    /// `DIRECTORY` attribute is kept as it was. Fails with `ErrorKind::InvalidInput` error if
+    /// `attrs` contains other attributes. Short name entry is updated in place, so a `File` object
+    /// opened for this entry before the call overwrites the change when it is flushed.
+    ///
+    /// Note: attributes are not interpreted by the library, e.g. read-only files can still be
+    /// written.
+    pub fn set_attributes(&mut self, attrs: FileAttributes) -> io::Result<()> {
+        let settable = FileAttributes::READ_ONLY
+            | FileAttributes::HIDDEN
+            | FileAttributes::SYSTEM
+            | FileAttributes::ARCHIVE;
+        if !settable.contains(attrs) {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "attributes cannot be set"));
+        }
+        let mut editor = self.editor();
+        editor.set_attrs(attrs | (self.data.attrs & FileAttributes::DIRECTORY));
+        editor.flush(self.fs)?;
+        self.data = editor.inner().clone();
+        Ok(())
+    }
+
     /// Checks if entry belongs to directory.
     pub fn is_dir(&self) -> bool {
         self.data.is_dir()
@@ -518,18 +1387,114 @@ impl<'a, 'b> DirEntry<'a, 'b> {
         self.data.is_file()
     }
 
-    pub(crate) fn first_cluster(&self) -> Option<u32> {
+    /// Returns range of byte offsets occupied by this entry in its directory.
+    ///
+    /// Offsets are relative to the beginning of directory data. The range starts at the first long
+    /// file name entry preceding the short name entry (if any) and ends after the short name entry.
+    pub fn offset_range(&self) -> (u64, u64) {
+        self.offset_range
+    }
+
+    /// Returns number of 32-byte directory entry slots occupied by this entry including long file
+    /// name entries.
+    pub fn slot_count(&self) -> u32 {
+        ((self.offset_range.1 - self.offset_range.0) / DIR_ENTRY_SIZE) as u32
+    }
+
+    /// Returns cursor pointing at the slot following this entry.
+    ///
+    /// Pass it to `Dir::iter_from` to continue iteration after this entry.
+    pub fn cursor(&self) -> DirCursor {
+        DirCursor::from_entry_index((self.offset_range.1 / DIR_ENTRY_SIZE) as u32)
+    }
+
+    pub(crate) fn first_cluster(&self) -> Option<Cluster> {
         self.data.first_cluster(self.fs.fat_type())
     }
 
-    fn editor(&self) -> DirEntryEditor {
+    // Checks that cluster chain of a file is valid and matches its size (paranoid mode)
+    pub(crate) fn validate_chain(&self) -> io::Result<()> {
+        let num_clusters = match self.first_cluster() {
+            Some(n) => {
+                let fat = &mut self.fs.fat_slice();
+                let ops = self.fs.fat_ops;
+                let cancel = self.fs.options.cancellation_token;
+                match valid_chain_length(fat, ops, n, self.fs.total_clusters, cancel)? {
+                    Some(len) => len as u64,
+                    None => {
+                        return Err(io::Error::new(
+                            ErrorKind::CorruptedFile,
+                            "invalid cluster chain",
+                        ))
+                    }
+                }
+            }
+            None => 0,
+        };
+        let cluster_size = self.fs.cluster_size() as u64;
+        if num_clusters != (self.data.size as u64 + cluster_size - 1) / cluster_size {
+            return Err(io::Error::new(
+                ErrorKind::CorruptedFile,
+                "cluster chain length does not match file size",
+            ));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn editor(&self) -> DirEntryEditor {
         DirEntryEditor::new(self.data.clone(), self.entry_pos)
     }
 
+    /// Changes first cluster and size of this entry (enabled by `recovery` feature).
+    ///
+    /// Meant for undelete and data recovery tools. The new cluster chain is validated before the
+    /// entry is written: it must consist of allocated clusters, end with end of chain marker and
+    /// be exactly as long as needed for `size` bytes. Directories must have size 0 and a non-empty
+    /// chain.
+    ///
+    /// Note: clusters of the old chain are not freed and the new chain is not checked for being
+    /// used by other entries.
+    #[cfg(feature = "recovery")]
+    pub fn relink(&mut self, first_cluster: Option<Cluster>, size: u32) -> io::Result<()> {
+        let fat_type = self.fs.fat_type();
+        let num_clusters = match first_cluster {
+            Some(n) => {
+                let fat = &mut self.fs.fat_slice();
+                let ops = self.fs.fat_ops;
+                let cancel = self.fs.options.cancellation_token;
+                match valid_chain_length(fat, ops, n, self.fs.total_clusters, cancel)? {
+                    Some(len) => len as u64,
+                    None => {
+                        return Err(io::Error::new(ErrorKind::InvalidInput, "invalid cluster chain"))
+                    }
+                }
+            }
+            None => 0,
+        };
+        let cluster_size = self.fs.cluster_size() as u64;
+        let valid = if self.is_dir() {
+            size == 0 && num_clusters > 0
+        } else {
+            num_clusters == (size as u64 + cluster_size - 1) / cluster_size
+        };
+        if !valid {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "cluster chain length does not match size",
+            ));
+        }
+        let mut editor = self.editor();
+        editor.set_first_cluster(first_cluster, fat_type);
+        editor.set_size(size);
+        editor.flush(self.fs)?;
+        self.data = editor.inner().clone();
+        Ok(())
+    }
+
     /// Returns File struct for this entry.
     ///
     /// Panics if this is not a file.
-    pub fn to_file(&self) -> File<'a, 'b> {
+    pub fn to_file(&self) -> File<'a, IO> {
         assert!(!self.is_dir(), "Not a file entry");
         File::new(self.first_cluster(), Some(self.editor()), self.fs)
     }
@@ -537,7 +1502,7 @@ impl<'a, 'b> DirEntry<'a, 'b> {
     /// Returns Dir struct for this entry.
     ///
     /// Panics if this is not a directory.
-    pub fn to_dir(&self) -> Dir<'a, 'b> {
+    pub fn to_dir(&self) -> Dir<'a, IO> {
         assert!(self.is_dir(), "Not a directory entry");
         match self.first_cluster() {
             Some(n) => {
@@ -553,6 +1518,20 @@ impl<'a, 'b> DirEntry<'a, 'b> {
         self.data.size as u64
     }
 
+    /// Returns file size rounded up to whole clusters or 0 for directory.
+    ///
+    /// It is the space taken by a file with a valid cluster chain. The chain itself is not read.
+    pub fn allocated_len(&self) -> u64 {
+        let cluster_size = self.fs.cluster_size() as u64;
+        (self.len() + cluster_size - 1) / cluster_size * cluster_size
+    }
+
+    /// Returns number of bytes allocated to the file but not used by its data (slack space) or 0
+    /// for directory.
+    pub fn slack(&self) -> u64 {
+        self.allocated_len() - self.len()
+    }
+
     /// Returns file creation date and time.
     pub fn created(&self) -> DateTime {
         self.data.created()
@@ -569,7 +1548,7 @@ impl<'a, 'b> DirEntry<'a, 'b> {
     }
 }
 
-impl<'a, 'b> fmt::Debug for DirEntry<'a, 'b> {
+impl<'a, IO: ReadWriteSeek> fmt::Debug for DirEntry<'a, IO> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.data.fmt(f)
     }
@@ -0,0 +1,107 @@
+use buffer::BufferProvider;
+
+// Maximal number of cached sectors - when the cache is full FAT sectors are written directly to
+// the device
+pub(crate) const MAX_CACHED_SECTORS: usize = 32;
+
+// FAT sectors modified in lazy FAT write mode (see `FsOptions::lazy_fat_writes`). Sectors are
+// indexed by their offset in FAT and written to all FAT copies when filesystem is flushed.
+// Sector buffers come from the buffer provider and are given back when the cache is cleared. The
+// index is a fixed size table so no memory is allocated outside of the provider.
+pub(crate) struct FatCache {
+    // offsets of cached sectors in ascending order
+    offsets: [u64; MAX_CACHED_SECTORS],
+    buffers: [Option<&'static mut [u8]>; MAX_CACHED_SECTORS],
+    len: usize,
+    provider: &'static BufferProvider,
+}
+
+impl FatCache {
+    pub(crate) fn new(provider: &'static BufferProvider) -> Self {
+        FatCache {
+            offsets: [0; MAX_CACHED_SECTORS],
+            buffers: Default::default(),
+            len: 0,
+            provider,
+        }
+    }
+
+    fn find(&self, offset: u64) -> Result<usize, usize> {
+        self.offsets[..self.len].binary_search(&offset)
+    }
+
+    pub(crate) fn get(&self, offset: u64) -> Option<&[u8]> {
+        match self.find(offset) {
+            Ok(i) => self.buffers[i].as_ref().map(|b| &b[..]),
+            Err(_) => None,
+        }
+    }
+
+    pub(crate) fn get_mut(&mut self, offset: u64) -> Option<&mut [u8]> {
+        match self.find(offset) {
+            Ok(i) => self.buffers[i].as_mut().map(|b| &mut b[..]),
+            Err(_) => None,
+        }
+    }
+
+    // Returns a buffer for a new cached sector or None if the cache is full or provider budget is
+    // exhausted
+    pub(crate) fn alloc_sector(&self, size: usize) -> Option<&'static mut [u8]> {
+        if self.len == MAX_CACHED_SECTORS {
+            return None;
+        }
+        self.provider.alloc(size)
+    }
+
+    // Adds a sector buffer returned by `alloc_sector` to the cache
+    pub(crate) fn insert(&mut self, offset: u64, sector: &'static mut [u8]) {
+        let i = match self.find(offset) {
+            Ok(_) => panic!("sector is cached already"),
+            Err(i) => i,
+        };
+        // alloc_sector fails when the cache is full
+        debug_assert!(self.len < MAX_CACHED_SECTORS);
+        for j in (i..self.len).rev() {
+            self.offsets[j + 1] = self.offsets[j];
+            self.buffers[j + 1] = self.buffers[j].take();
+        }
+        self.offsets[i] = offset;
+        self.buffers[i] = Some(sector);
+        self.len += 1;
+    }
+
+    pub(crate) fn free_sector(&self, sector: &'static mut [u8]) {
+        // SAFE: sector was returned by alloc_sector and it is not used afterwards
+        unsafe { self.provider.free(sector) };
+    }
+
+    // Returns cached sectors in ascending order of offsets
+    pub(crate) fn iter<'a>(&'a self) -> impl Iterator<Item = (u64, &'a [u8])> + 'a {
+        let buffers = self.buffers[..self.len].iter();
+        self.offsets[..self.len]
+            .iter()
+            .zip(buffers)
+            .filter_map(|(&offset, b)| b.as_ref().map(|b| (offset, &b[..])))
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub(crate) fn clear(&mut self) {
+        for i in 0..self.len {
+            match self.buffers[i].take() {
+                // SAFE: buffers were returned by alloc_sector and the cache is their only owner
+                Some(sector) => unsafe { self.provider.free(sector) },
+                None => {}
+            }
+        }
+        self.len = 0;
+    }
+}
+
+impl Drop for FatCache {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
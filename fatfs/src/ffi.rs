@@ -0,0 +1,400 @@
+//! C ABI for using the library from C code.
+//!
+//! Enabled by `ffi` feature which makes the library depend on `std`. To produce a library that can
+//! be linked with C code build the crate with `cargo rustc --features ffi --crate-type staticlib`
+//! (or `cdylib`). Function declarations are available in `include/fatfs.h`.
+//!
+//! All objects are represented by opaque handles. Functions return `FATFS_OK` (zero) on success
+//! and negative error code on failure. Files and directories must be closed before filesystem
+//! they belong to is unmounted. Panics are not propagated to C code - function that panicked
+//! returns `FATFS_ERR_PANIC` and handles passed to it should not be used anymore (except for
+//! closing them).
+
+// types are named like C types they represent
+#![allow(non_camel_case_types)]
+
+use core::{ptr, slice, str};
+use std::boxed::Box;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int, c_void};
+use std::panic::{self, AssertUnwindSafe};
+
+use io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+
+use dir::{Dir, DirIter};
+use file::File;
+use fs::{FileSystem, FsOptions};
+
+pub const FATFS_OK: c_int = 0;
+pub const FATFS_ERR_INVALID_INPUT: c_int = -1;
+pub const FATFS_ERR_NOT_FOUND: c_int = -2;
+pub const FATFS_ERR_UNEXPECTED_EOF: c_int = -3;
+pub const FATFS_ERR_WRITE_ZERO: c_int = -4;
+pub const FATFS_ERR_NOT_ENOUGH_SPACE: c_int = -5;
+pub const FATFS_ERR_FILE_TOO_LARGE: c_int = -6;
+pub const FATFS_ERR_OTHER: c_int = -7;
+pub const FATFS_ERR_ALREADY_EXISTS: c_int = -8;
+pub const FATFS_ERR_CORRUPTED_FILE: c_int = -9;
+pub const FATFS_ERR_TRUNCATED_VOLUME: c_int = -10;
+pub const FATFS_ERR_NOT_A_DIRECTORY: c_int = -11;
+pub const FATFS_ERR_IS_A_DIRECTORY: c_int = -12;
+pub const FATFS_ERR_DIRECTORY_NOT_EMPTY: c_int = -13;
+pub const FATFS_ERR_NAME_TOO_LONG: c_int = -14;
+pub const FATFS_ERR_PATH_TOO_DEEP: c_int = -15;
+pub const FATFS_ERR_TOO_MANY_ENTRIES: c_int = -16;
+pub const FATFS_ERR_INTERRUPTED: c_int = -17;
+pub const FATFS_ERR_DIRECTORY_FULL: c_int = -18;
+pub const FATFS_ERR_FILESYSTEM_POISONED: c_int = -19;
+pub const FATFS_ERR_MEDIA_CHANGED: c_int = -20;
+pub const FATFS_ERR_PANIC: c_int = -21;
+pub const FATFS_ERR_BUSY: c_int = -22;
+
+pub const FATFS_SEEK_SET: c_int = 0;
+pub const FATFS_SEEK_CUR: c_int = 1;
+pub const FATFS_SEEK_END: c_int = 2;
+
+/// Storage device implemented by C code.
+///
+/// `read` and `write` callbacks return number of transferred bytes (at most `len`) or negative
+/// value on error.
+/// `seek` returns new position from the beginning of device or negative value on error. `flush`
+/// returns zero on success.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct fatfs_device {
+    pub ctx: *mut c_void,
+    pub read: extern "C" fn(ctx: *mut c_void, buf: *mut u8, len: usize) -> isize,
+    pub write: extern "C" fn(ctx: *mut c_void, buf: *const u8, len: usize) -> isize,
+    pub seek: extern "C" fn(ctx: *mut c_void, offset: i64, whence: c_int) -> i64,
+    pub flush: extern "C" fn(ctx: *mut c_void) -> c_int,
+}
+
+/// Directory entry returned by `fatfs_readdir`.
+#[repr(C)]
+pub struct fatfs_dirent {
+    /// Null terminated short file name.
+    pub name: [c_char; 13],
+    pub attributes: u8,
+    pub size: u64,
+}
+
+/// Opaque filesystem handle.
+pub struct fatfs_fs {
+    // fs borrows device so it must be dropped first
+    fs: Option<FileSystem<&'static mut fatfs_device>>,
+    device: *mut fatfs_device,
+}
+
+/// Opaque file handle.
+pub struct fatfs_file {
+    file: File<'static, &'static mut fatfs_device>,
+}
+
+/// Opaque directory iterator handle.
+pub struct fatfs_dir {
+    iter: DirIter<'static, &'static mut fatfs_device>,
+}
+
+fn device_error() -> io::Error {
+    io::Error::new(ErrorKind::Other, "device error")
+}
+
+// Converts value returned by read or write callback to number of transferred bytes
+fn transferred_len(n: isize, len: usize) -> io::Result<usize> {
+    match n {
+        n if n < 0 => Err(device_error()),
+        n if n as usize > len => Err(io::Error::new(ErrorKind::Other, "invalid transfer length")),
+        n => Ok(n as usize),
+    }
+}
+
+impl Read for fatfs_device {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        transferred_len((self.read)(self.ctx, buf.as_mut_ptr(), buf.len()), buf.len())
+    }
+}
+
+impl Write for fatfs_device {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        transferred_len((self.write)(self.ctx, buf.as_ptr(), buf.len()), buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match (self.flush)(self.ctx) {
+            0 => Ok(()),
+            _ => Err(device_error()),
+        }
+    }
+}
+
+impl Seek for fatfs_device {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (offset, whence) = match pos {
+            SeekFrom::Start(n) => (n as i64, FATFS_SEEK_SET),
+            SeekFrom::Current(n) => (n, FATFS_SEEK_CUR),
+            SeekFrom::End(n) => (n, FATFS_SEEK_END),
+        };
+        match (self.seek)(self.ctx, offset, whence) {
+            n if n < 0 => Err(device_error()),
+            n => Ok(n as u64),
+        }
+    }
+}
+
+fn error_code(err: &io::Error) -> c_int {
+    match err.kind() {
+        ErrorKind::InvalidInput => FATFS_ERR_INVALID_INPUT,
+        ErrorKind::NotFound => FATFS_ERR_NOT_FOUND,
+        ErrorKind::UnexpectedEof => FATFS_ERR_UNEXPECTED_EOF,
+        ErrorKind::WriteZero => FATFS_ERR_WRITE_ZERO,
+        ErrorKind::NotEnoughSpace => FATFS_ERR_NOT_ENOUGH_SPACE,
+        ErrorKind::FileTooLarge => FATFS_ERR_FILE_TOO_LARGE,
+        ErrorKind::AlreadyExists => FATFS_ERR_ALREADY_EXISTS,
+        ErrorKind::CorruptedFile => FATFS_ERR_CORRUPTED_FILE,
+        ErrorKind::TruncatedVolume => FATFS_ERR_TRUNCATED_VOLUME,
+        ErrorKind::NotADirectory => FATFS_ERR_NOT_A_DIRECTORY,
+        ErrorKind::IsADirectory => FATFS_ERR_IS_A_DIRECTORY,
+        ErrorKind::DirectoryNotEmpty => FATFS_ERR_DIRECTORY_NOT_EMPTY,
+        ErrorKind::NameTooLong => FATFS_ERR_NAME_TOO_LONG,
+        ErrorKind::PathTooDeep => FATFS_ERR_PATH_TOO_DEEP,
+        ErrorKind::TooManyEntries => FATFS_ERR_TOO_MANY_ENTRIES,
+        ErrorKind::Interrupted => FATFS_ERR_INTERRUPTED,
+        ErrorKind::DirectoryFull => FATFS_ERR_DIRECTORY_FULL,
+        ErrorKind::FilesystemPoisoned => FATFS_ERR_FILESYSTEM_POISONED,
+        ErrorKind::MediaChanged => FATFS_ERR_MEDIA_CHANGED,
+        ErrorKind::Busy => FATFS_ERR_BUSY,
+        ErrorKind::Other => FATFS_ERR_OTHER,
+    }
+}
+
+fn result_code(r: io::Result<()>) -> c_int {
+    match r {
+        Ok(()) => FATFS_OK,
+        Err(err) => error_code(&err),
+    }
+}
+
+// Runs body of exported function and returns `err` if it panics (unwinding into C code is undefined
+// behaviour)
+fn catch_panic<T, F: FnOnce() -> T>(err: T, f: F) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(r) => r,
+        Err(_) => err,
+    }
+}
+
+unsafe fn path_arg<'p>(path: *const c_char) -> Option<&'p str> {
+    if path.is_null() {
+        return None;
+    }
+    str::from_utf8(CStr::from_ptr(path).to_bytes()).ok()
+}
+
+unsafe fn fs_arg(fs: *mut fatfs_fs) -> Option<&'static FileSystem<&'static mut fatfs_device>> {
+    if fs.is_null() {
+        return None;
+    }
+    (*fs).fs.as_ref().map(|fs| &*(fs as *const FileSystem<&'static mut fatfs_device>))
+}
+
+/// Mounts filesystem stored on `device`. On success handle is stored in `out`.
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_mount(
+    device: *const fatfs_device,
+    out: *mut *mut fatfs_fs,
+) -> c_int {
+    catch_panic(FATFS_ERR_PANIC, || {
+        if device.is_null() || out.is_null() {
+            return FATFS_ERR_INVALID_INPUT;
+        }
+        let device = Box::into_raw(Box::new(*device));
+        // filesystem is read from the beginning of device
+        let r = (*device).seek(SeekFrom::Start(0));
+        match r.and_then(|_| FileSystem::new(&mut *device, FsOptions::new())) {
+            Ok(fs) => {
+                *out = Box::into_raw(Box::new(fatfs_fs {
+                    fs: Some(fs),
+                    device,
+                }));
+                FATFS_OK
+            }
+            Err(err) => {
+                drop(Box::from_raw(device));
+                error_code(&err)
+            }
+        }
+    })
+}
+
+/// Unmounts filesystem and releases its handle. Handle is released even if an error is returned.
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_unmount(fs: *mut fatfs_fs) -> c_int {
+    catch_panic(FATFS_ERR_PANIC, || {
+        if fs.is_null() {
+            return FATFS_ERR_INVALID_INPUT;
+        }
+        let mut handle = Box::from_raw(fs);
+        let r = match handle.fs.take() {
+            Some(fs) => fs.unmount(),
+            None => Ok(()),
+        };
+        drop(Box::from_raw(handle.device));
+        result_code(r)
+    })
+}
+
+/// Opens file at `path`. If `create` is nonzero file is created if it does not exist.
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_open(
+    fs: *mut fatfs_fs,
+    path: *const c_char,
+    create: c_int,
+    out: *mut *mut fatfs_file,
+) -> c_int {
+    catch_panic(FATFS_ERR_PANIC, || {
+        let (fs, path) = match (fs_arg(fs), path_arg(path)) {
+            (Some(fs), Some(path)) if !out.is_null() => (fs, path),
+            _ => return FATFS_ERR_INVALID_INPUT,
+        };
+        let r = if create != 0 {
+            fs.root_dir().create_file(path)
+        } else {
+            fs.root_dir().open_file(path)
+        };
+        match r {
+            Ok(file) => {
+                *out = Box::into_raw(Box::new(fatfs_file { file }));
+                FATFS_OK
+            }
+            Err(err) => error_code(&err),
+        }
+    })
+}
+
+/// Reads up to `len` bytes from file. Returns number of bytes read (zero at the end of file).
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_read(file: *mut fatfs_file, buf: *mut u8, len: usize) -> isize {
+    catch_panic(FATFS_ERR_PANIC as isize, || {
+        if file.is_null() || (buf.is_null() && len > 0) {
+            return FATFS_ERR_INVALID_INPUT as isize;
+        }
+        let buf = if len > 0 { slice::from_raw_parts_mut(buf, len) } else { &mut [] };
+        match (*file).file.read(buf) {
+            Ok(n) => n as isize,
+            Err(err) => error_code(&err) as isize,
+        }
+    })
+}
+
+/// Writes up to `len` bytes to file. Returns number of bytes written.
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_write(file: *mut fatfs_file, buf: *const u8, len: usize) -> isize {
+    catch_panic(FATFS_ERR_PANIC as isize, || {
+        if file.is_null() || (buf.is_null() && len > 0) {
+            return FATFS_ERR_INVALID_INPUT as isize;
+        }
+        let buf = if len > 0 { slice::from_raw_parts(buf, len) } else { &[] };
+        match (*file).file.write(buf) {
+            Ok(n) => n as isize,
+            Err(err) => error_code(&err) as isize,
+        }
+    })
+}
+
+/// Changes position in file. Returns new position or negative error code.
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_seek(file: *mut fatfs_file, offset: i64, whence: c_int) -> i64 {
+    catch_panic(FATFS_ERR_PANIC as i64, || {
+        if file.is_null() {
+            return FATFS_ERR_INVALID_INPUT as i64;
+        }
+        let pos = match whence {
+            FATFS_SEEK_SET if offset >= 0 => SeekFrom::Start(offset as u64),
+            FATFS_SEEK_CUR => SeekFrom::Current(offset),
+            FATFS_SEEK_END => SeekFrom::End(offset),
+            _ => return FATFS_ERR_INVALID_INPUT as i64,
+        };
+        match (*file).file.seek(pos) {
+            Ok(n) => n as i64,
+            Err(err) => error_code(&err) as i64,
+        }
+    })
+}
+
+/// Flushes file and releases its handle. Handle cannot be used after this call even if an error is
+/// returned.
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_close(file: *mut fatfs_file) -> c_int {
+    catch_panic(FATFS_ERR_PANIC, || {
+        if file.is_null() {
+            return FATFS_ERR_INVALID_INPUT;
+        }
+        let mut handle = Box::from_raw(file);
+        // handle is released in both cases - failed flush is only logged when file is dropped
+        result_code(handle.file.flush())
+    })
+}
+
+/// Opens directory at `path` for reading its entries. Empty path or "/" opens root directory.
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_opendir(
+    fs: *mut fatfs_fs,
+    path: *const c_char,
+    out: *mut *mut fatfs_dir,
+) -> c_int {
+    catch_panic(FATFS_ERR_PANIC, || {
+        let (fs, path) = match (fs_arg(fs), path_arg(path)) {
+            (Some(fs), Some(path)) if !out.is_null() => (fs, path.trim_matches('/')),
+            _ => return FATFS_ERR_INVALID_INPUT,
+        };
+        let r: io::Result<Dir<&'static mut fatfs_device>> = if path.is_empty() {
+            Ok(fs.root_dir())
+        } else {
+            fs.root_dir().open_dir(path)
+        };
+        match r {
+            Ok(dir) => {
+                *out = Box::into_raw(Box::new(fatfs_dir { iter: dir.iter() }));
+                FATFS_OK
+            }
+            Err(err) => error_code(&err),
+        }
+    })
+}
+
+/// Reads next directory entry into `out`. Returns 1 if entry was read and 0 at the end of
+/// directory.
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_readdir(dir: *mut fatfs_dir, out: *mut fatfs_dirent) -> c_int {
+    catch_panic(FATFS_ERR_PANIC, || {
+        if dir.is_null() || out.is_null() {
+            return FATFS_ERR_INVALID_INPUT;
+        }
+        match (*dir).iter.next() {
+            Some(Ok(entry)) => {
+                let out = &mut *out;
+                let name = entry.short_file_name().as_bytes();
+                out.name = [0; 13];
+                let dst = out.name.as_mut_ptr();
+                ptr::copy_nonoverlapping(name.as_ptr() as *const c_char, dst, name.len());
+                out.attributes = entry.attributes().bits();
+                out.size = entry.len();
+                1
+            }
+            Some(Err(err)) => error_code(&err),
+            None => 0,
+        }
+    })
+}
+
+/// Releases directory iterator handle.
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_closedir(dir: *mut fatfs_dir) -> c_int {
+    catch_panic(FATFS_ERR_PANIC, || {
+        if dir.is_null() {
+            return FATFS_ERR_INVALID_INPUT;
+        }
+        drop(Box::from_raw(dir));
+        FATFS_OK
+    })
+}
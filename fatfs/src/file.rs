@@ -1,30 +1,64 @@
 use core::cmp;
 use io::{self, *};
 
-use dir_entry::{Date, DateTime, DirEntryEditor};
-use fs::FileSystemRef;
+#[cfg(feature = "splice")]
+use dir::Dir;
+use dir_entry::{Date, DateTime, DirEntryEditor, NullTimeProvider, TimeProvider};
+use fs::{FileSystemRef, ReadWriteSeek};
+use table::ClusterIterator;
+use units::Cluster;
 
 /// FAT file used for reading and writing.
-#[derive(Clone)]
-pub struct File<'a, 'b: 'a> {
+///
+/// File is flushed automatically when dropped but errors are only logged then - call `flush` to
+/// handle them.
+///
+/// An open file follows its directory entry when it is renamed or moved. If the file is removed,
+/// all further operations fail with `ErrorKind::NotFound`. Open files are tracked only if `alloc`
+/// feature is enabled - otherwise only flushing of the directory entry is checked.
+pub struct File<'a, IO: ReadWriteSeek + 'a> {
     // Note first_cluster is None if file is empty
-    first_cluster: Option<u32>,
+    first_cluster: Option<Cluster>,
     // Note: if offset points between clusters current_cluster is the previous cluster
-    current_cluster: Option<u32>,
+    current_cluster: Option<Cluster>,
     // current position in this file
     offset: u32,
     // file dir entry editor - None for root dir
     entry: Option<DirEntryEditor>,
     // file-system reference
-    fs: FileSystemRef<'a, 'b>,
+    fs: FileSystemRef<'a, IO>,
 }
 
-impl<'a, 'b> File<'a, 'b> {
+// Derived implementation would require `IO: Clone`
+impl<'a, IO: ReadWriteSeek> Clone for File<'a, IO> {
+    fn clone(&self) -> Self {
+        #[cfg(feature = "alloc")]
+        let entry = self.entry.as_ref().map(|e| e.clone_handle(self.fs));
+        #[cfg(not(feature = "alloc"))]
+        let entry = self.entry.clone();
+        File {
+            first_cluster: self.first_cluster,
+            current_cluster: self.current_cluster,
+            offset: self.offset,
+            entry,
+            fs: self.fs,
+        }
+    }
+}
+
+impl<'a, IO: ReadWriteSeek> File<'a, IO> {
     pub(crate) fn new(
-        first_cluster: Option<u32>,
+        first_cluster: Option<Cluster>,
         entry: Option<DirEntryEditor>,
-        fs: FileSystemRef<'a, 'b>,
+        fs: FileSystemRef<'a, IO>,
     ) -> Self {
+        #[cfg(feature = "alloc")]
+        let mut entry = entry;
+        #[cfg(feature = "alloc")]
+        match entry {
+            Some(ref mut e) => e.register(fs),
+            None => {}
+        }
         File {
             first_cluster,
             entry,
@@ -34,11 +68,21 @@ impl<'a, 'b> File<'a, 'b> {
         }
     }
 
+    // Fails if the file was removed - its clusters may belong to another file already
+    fn check_not_removed(&self) -> io::Result<()> {
+        #[cfg(feature = "alloc")]
+        match self.entry {
+            Some(ref e) => e.check_not_removed(self.fs)?,
+            None => {}
+        }
+        Ok(())
+    }
+
     fn update_size(&mut self) {
         let offset = self.offset;
         match self.entry {
             Some(ref mut e) => {
-                e.reset_modified();
+                e.reset_modified(self.fs.options.time_provider);
                 if e.inner().size().map_or(false, |s| offset > s) {
                     e.set_size(offset);
                 }
@@ -48,30 +92,48 @@ impl<'a, 'b> File<'a, 'b> {
     }
 
     /// Truncate file in current position.
+    ///
+    /// Truncating to zero frees all clusters of the file, so an empty file never has clusters
+    /// allocated.
     pub fn truncate(&mut self) -> io::Result<()> {
+        self.check_not_removed()?;
         match self.entry {
-            Some(ref mut e) => {
-                e.set_size(self.offset);
-                if self.offset == 0 {
-                    e.set_first_cluster(None, self.fs.fat_type());
-                }
-            }
+            Some(ref mut e) => e.set_size(self.offset),
             _ => {}
         }
         if self.offset > 0 {
             debug_assert!(self.current_cluster.is_some());
             // if offset is not 0 current cluster cannot be empty
             self.fs
-                .cluster_iter(self.current_cluster.unwrap())
-                .truncate() // SAFE
+                .truncate_cluster_chain(self.current_cluster.unwrap()) // SAFE
         } else {
             debug_assert!(self.current_cluster.is_none());
-            match self.first_cluster {
-                Some(n) => self.fs.cluster_iter(n).free()?,
-                _ => {}
+            // detach the chain before freeing it - if freeing fails clusters are leaked instead
+            // of being still referenced by the entry
+            match self.take_first_cluster() {
+                Some(n) => self.fs.free_cluster_chain(n),
+                None => Ok(()),
+            }
+        }
+    }
+
+    fn take_first_cluster(&mut self) -> Option<Cluster> {
+        match self.entry {
+            Some(ref mut e) => e.set_first_cluster(None, self.fs.fat_type()),
+            _ => {}
+        }
+        self.first_cluster.take()
+    }
+
+    // Frees cluster allocated for a write which failed so the space is not leaked
+    fn free_new_cluster(&mut self, cluster: Cluster) -> io::Result<()> {
+        match self.current_cluster {
+            Some(n) => self.fs.truncate_cluster_chain(n),
+            None => {
+                debug_assert_eq!(self.first_cluster, Some(cluster));
+                self.take_first_cluster();
+                self.fs.free_cluster_chain(cluster)
             }
-            self.first_cluster = None;
-            Ok(())
         }
     }
 
@@ -81,7 +143,11 @@ impl<'a, 'b> File<'a, 'b> {
         match self.current_cluster {
             Some(n) => {
                 let cluster_size = self.fs.cluster_size();
-                let offset_in_cluster = self.offset % cluster_size;
+                let offset_in_cluster = match self.offset % cluster_size {
+                    // offset is at the end of current cluster
+                    0 if self.offset > 0 => cluster_size,
+                    x => x,
+                };
                 let offset_in_fs = self.fs.offset_from_cluster(n) + (offset_in_cluster as u64);
                 Some(offset_in_fs)
             }
@@ -97,34 +163,108 @@ impl<'a, 'b> File<'a, 'b> {
         Ok(())
     }
 
+    /// Checks if file has changes that have not been flushed yet.
+    ///
+    /// Directory entry changes (size, first cluster and timestamps) are kept in the file object -
+    /// file data is written to the device immediately. In lazy FAT write mode allocated clusters
+    /// are kept in the FAT cache shared by the whole filesystem, so pending FAT sectors make every
+    /// file dirty. Changes are written by `flush` method (the directory entry is also written when
+    /// the file object is dropped).
+    pub fn is_dirty(&self) -> bool {
+        let entry_dirty = match self.entry {
+            Some(ref e) => e.is_dirty(),
+            None => false,
+        };
+        entry_dirty || self.fs.has_pending_fat_writes()
+    }
+
+    /// Returns date and time of creation for this file.
+    ///
+    /// Returned value includes changes that have not been flushed yet, so timestamps of a file
+    /// returned by `Dir::create_file` can be read without looking the entry up again.
+    pub fn created(&self) -> DateTime {
+        match self.entry {
+            Some(ref e) => e.inner().created(),
+            None => NullTimeProvider.get_current_date_time(),
+        }
+    }
+
+    /// Returns date of last access for this file.
+    pub fn accessed(&self) -> Date {
+        match self.entry {
+            Some(ref e) => e.inner().accessed(),
+            None => NullTimeProvider.get_current_date(),
+        }
+    }
+
+    /// Returns date and time of last modification for this file.
+    pub fn modified(&self) -> DateTime {
+        match self.entry {
+            Some(ref e) => e.inner().modified(),
+            None => NullTimeProvider.get_current_date_time(),
+        }
+    }
+
     /// Set date and time of creation for this file.
     ///
-    /// Note: if chrono feature is enabled (default) library automatically updates all timestamps
-    pub fn set_created(&mut self, date_time: DateTime) {
+    /// Note: library sets creation time using `TimeProvider` from `FsOptions` when file is created
+    ///
+    /// Fails with `ErrorKind::InvalidInput` if timestamp cannot be stored in FAT.
+    pub fn set_created(&mut self, date_time: DateTime) -> io::Result<()> {
+        if !date_time.is_valid() {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "invalid date or time"));
+        }
         match self.entry {
             Some(ref mut e) => e.set_created(date_time),
             _ => {}
         }
+        Ok(())
     }
 
     /// Set date of last access for this file.
     ///
-    /// Note: if chrono feature is enabled (default) library automatically updates all timestamps
-    pub fn set_accessed(&mut self, date: Date) {
+    /// Note: library updates access date on reads only if enabled in `FsOptions`
+    ///
+    /// Fails with `ErrorKind::InvalidInput` if date cannot be stored in FAT.
+    pub fn set_accessed(&mut self, date: Date) -> io::Result<()> {
+        if !date.is_valid() {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "invalid date"));
+        }
         match self.entry {
             Some(ref mut e) => e.set_accessed(date),
             _ => {}
         }
+        Ok(())
     }
 
     /// Set date and time of last modification for this file.
     ///
-    /// Note: if chrono feature is enabled (default) library automatically updates all timestamps
-    pub fn set_modified(&mut self, date_time: DateTime) {
+    /// Note: library updates modification time using `TimeProvider` from `FsOptions` on every write
+    ///
+    /// Fails with `ErrorKind::InvalidInput` if timestamp cannot be stored in FAT.
+    pub fn set_modified(&mut self, date_time: DateTime) -> io::Result<()> {
+        if !date_time.is_valid() {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "invalid date or time"));
+        }
         match self.entry {
             Some(ref mut e) => e.set_modified(date_time),
             _ => {}
         }
+        Ok(())
+    }
+
+    fn update_accessed(&mut self) {
+        if !self.fs.options.update_accessed_date {
+            return;
+        }
+        match self.entry {
+            // editor is marked dirty only if date changes so entry is written at most once a day
+            Some(ref mut e) if !e.inner().is_dir() => {
+                let date = self.fs.options.time_provider.get_current_date().clamp();
+                e.set_accessed(date);
+            }
+            _ => {}
+        }
     }
 
     fn bytes_left_in_file(&self) -> Option<usize> {
@@ -134,7 +274,30 @@ impl<'a, 'b> File<'a, 'b> {
         }
     }
 
-    fn set_first_cluster(&mut self, cluster: u32) {
+    // Returns number of clusters following `cluster` in its chain which are physically consecutive
+    // to it, checking at most `max_clusters` clusters
+    fn contiguous_clusters_after(&self, cluster: Cluster, max_clusters: usize) -> io::Result<u32> {
+        let mut count = 0;
+        for r in self.fs.cluster_iter(cluster).take(max_clusters) {
+            if r?.0 != cluster.0 + count + 1 {
+                break;
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn bytes_left_until_max_size(&self) -> usize {
+        match self.entry {
+            // directories are not limited by file size
+            Some(ref e) if e.inner().size().is_some() => {
+                self.fs.max_file_size().saturating_sub(self.offset) as usize
+            }
+            _ => usize::max_value(),
+        }
+    }
+
+    fn set_first_cluster(&mut self, cluster: Cluster) {
         self.first_cluster = Some(cluster);
         match self.entry {
             Some(ref mut e) => e.set_first_cluster(self.first_cluster, self.fs.fat_type()),
@@ -142,22 +305,249 @@ impl<'a, 'b> File<'a, 'b> {
         }
     }
 
-    pub(crate) fn first_cluster(&self) -> Option<u32> {
+    /// Returns iterator over clusters allocated for this file in chain order.
+    ///
+    /// Iterator yields `ErrorKind::CorruptedFile` error if the chain contains an invalid cluster
+    /// number or a loop, so it can be used on damaged filesystems (e.g. by integrity scanners or
+    /// defragmentation tools).
+    pub fn clusters(&self) -> FileClusters<'a, IO> {
+        FileClusters {
+            first_cluster: self.first_cluster,
+            iter: self.first_cluster.map(|n| self.fs.cluster_iter(n)),
+            fs: self.fs,
+            num_clusters: 0,
+            err: false,
+        }
+    }
+
+    #[cfg(feature = "splice")]
+    pub(crate) fn entry_pos(&self) -> Option<u64> {
+        self.entry.as_ref().map(|e| e.pos())
+    }
+
+    #[cfg(feature = "splice")]
+    pub(crate) fn file_size(&self) -> io::Result<u32> {
+        match self.entry {
+            Some(ref e) => match e.inner().size() {
+                Some(size) => Ok(size),
+                None => Err(io::Error::new(ErrorKind::IsADirectory, "not a file")),
+            },
+            None => Err(io::Error::new(ErrorKind::IsADirectory, "not a file")),
+        }
+    }
+
+    #[cfg(feature = "splice")]
+    fn set_file_size(&mut self, size: u32) {
+        match self.entry {
+            Some(ref mut e) => {
+                e.reset_modified(self.fs.options.time_provider);
+                e.set_size(size);
+            }
+            None => {}
+        }
+    }
+
+    /// Detaches clusters of this file starting with cluster `at_cluster` (counted from 0)
+    /// (enabled by `splice` feature).
+    ///
+    /// The file is truncated to `at_cluster` whole clusters and detached part is returned without
+    /// copying any data, so it can be attached to another file with `append_chain`. Returns `None`
+    /// if the file is not longer than `at_cluster` clusters. If position is past the new end of
+    /// the file it is moved to the end.
+    ///
+    /// Together with `append_chain` it allows concatenating files and removing data from the
+    /// middle of a file by rewriting FAT entries only.
+    #[cfg(feature = "splice")]
+    pub fn split_chain(&mut self, at_cluster: u32) -> io::Result<Option<ClusterChain>> {
+        self.check_not_removed()?;
+        let size = self.file_size()?;
+        let cluster_size = self.fs.cluster_size();
+        let new_size = at_cluster as u64 * cluster_size as u64;
+        if new_size >= size as u64 {
+            return Ok(None);
+        }
+        let new_size = new_size as u32;
+        let (last_cluster, first_cluster) = if at_cluster == 0 {
+            match self.take_first_cluster() {
+                Some(n) => (None, n),
+                None => return Err(io::Error::new(ErrorKind::CorruptedFile, "missing cluster")),
+            }
+        } else {
+            let mut clusters = self.clusters().skip(at_cluster as usize - 1);
+            let last_cluster = match clusters.next() {
+                Some(r) => r?,
+                None => return Err(io::Error::new(ErrorKind::CorruptedFile, "chain too short")),
+            };
+            let first_cluster = match clusters.next() {
+                Some(r) => r?,
+                None => return Err(io::Error::new(ErrorKind::CorruptedFile, "chain too short")),
+            };
+            self.fs.set_next_cluster(last_cluster, None)?;
+            (Some(last_cluster), first_cluster)
+        };
+        self.set_file_size(new_size);
+        if self.offset > new_size {
+            self.offset = new_size;
+            self.current_cluster = last_cluster;
+        }
+        Ok(Some(ClusterChain {
+            first_cluster,
+            len: size - new_size,
+        }))
+    }
+
+    /// Attaches `chain` to the end of this file (enabled by `splice` feature).
+    ///
+    /// File size is increased by the length of the chain and data is not copied. The file size
+    /// must be a multiple of the cluster size (e.g. after `split_chain`), otherwise
+    /// `ErrorKind::InvalidInput` is returned. Fails with `ErrorKind::FileTooLarge` if the result
+    /// would exceed maximal file size. Position is not changed.
+    #[cfg(feature = "splice")]
+    pub fn append_chain(&mut self, chain: ClusterChain) -> io::Result<()> {
+        self.check_not_removed()?;
+        let size = self.file_size()?;
+        let cluster_size = self.fs.cluster_size();
+        if size % cluster_size != 0 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "file size is not a multiple of cluster size",
+            ));
+        }
+        if chain.len > self.fs.max_file_size().saturating_sub(size) {
+            return Err(io::Error::new(ErrorKind::FileTooLarge, "maximal file size exceeded"));
+        }
+        let mut last_cluster = None;
+        let mut num_clusters = 0;
+        for r in self.clusters() {
+            last_cluster = Some(r?);
+            num_clusters += 1;
+        }
+        if num_clusters != size / cluster_size {
+            return Err(io::Error::new(
+                ErrorKind::CorruptedFile,
+                "cluster chain length does not match file size",
+            ));
+        }
+        match last_cluster {
+            Some(n) => self.fs.set_next_cluster(n, Some(chain.first_cluster))?,
+            None => self.set_first_cluster(chain.first_cluster),
+        }
+        self.set_file_size(size + chain.len);
+        Ok(())
+    }
+
+    /// Appends contents of file `src_path` in `dir` to the end of this file and removes the
+    /// source file (enabled by `splice` feature).
+    ///
+    /// If size of this file is a multiple of the cluster size, cluster chain of the source file is
+    /// linked to this file (see `append_chain`) so only metadata is written regardless of the
+    /// source size. Otherwise data of the source file is copied. Position is not changed.
+    ///
+    /// Fails with `ErrorKind::InvalidInput` if `src_path` refers to this file and with
+    /// `ErrorKind::FileTooLarge` if the result would exceed maximal file size. Make sure there is
+    /// no other File instance for the source file or filesystem corruption can happen.
+    #[cfg(feature = "splice")]
+    pub fn append_file(&mut self, dir: &mut Dir<'a, IO>, src_path: &str) -> io::Result<()> {
+        dir.append_to_file(src_path, self)
+    }
+
+    pub(crate) fn fs(&self) -> FileSystemRef<'a, IO> {
+        self.fs
+    }
+
+    pub(crate) fn first_cluster(&self) -> Option<Cluster> {
+        self.first_cluster
+    }
+}
+
+/// Cluster chain detached from a file by `File::split_chain` (enabled by `splice` feature).
+///
+/// The chain is not referenced by any directory entry. It should be passed to
+/// `File::append_chain` or `FileSystem::free_chain`, otherwise its clusters are lost until the
+/// filesystem is repaired.
+#[cfg(feature = "splice")]
+#[derive(Debug)]
+pub struct ClusterChain {
+    pub(crate) first_cluster: Cluster,
+    len: u32,
+}
+
+#[cfg(feature = "splice")]
+impl ClusterChain {
+    /// Returns number of the first cluster in the chain.
+    pub fn first_cluster(&self) -> Cluster {
         self.first_cluster
     }
+
+    /// Returns number of data bytes stored in the chain.
+    pub fn len(&self) -> u64 {
+        self.len as u64
+    }
+}
+
+/// Iterator over clusters of a file.
+///
+/// Returned by `File::clusters`.
+pub struct FileClusters<'a, IO: ReadWriteSeek + 'a> {
+    // yielded before clusters from iter
+    first_cluster: Option<Cluster>,
+    iter: Option<ClusterIterator<'a, IO>>,
+    fs: FileSystemRef<'a, IO>,
+    num_clusters: u32,
+    err: bool,
+}
+
+impl<'a, IO: ReadWriteSeek> FileClusters<'a, IO> {
+    fn check_cluster(&mut self, cluster: Cluster) -> io::Result<Cluster> {
+        if cluster.0 < 2 || cluster.0 >= self.fs.total_clusters + 2 {
+            return Err(io::Error::new(ErrorKind::CorruptedFile, "invalid cluster number in chain"));
+        }
+        // chain longer than number of clusters must contain a loop
+        if self.num_clusters == self.fs.total_clusters {
+            return Err(io::Error::new(ErrorKind::CorruptedFile, "cluster chain contains a loop"));
+        }
+        self.num_clusters += 1;
+        Ok(cluster)
+    }
+}
+
+impl<'a, IO: ReadWriteSeek> Iterator for FileClusters<'a, IO> {
+    type Item = io::Result<Cluster>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.err {
+            return None;
+        }
+        let r = match self.first_cluster.take() {
+            Some(n) => Ok(n),
+            None => match self.iter.as_mut().and_then(|iter| iter.next()) {
+                Some(r) => r,
+                None => return None,
+            },
+        };
+        let r = r.and_then(|n| self.check_cluster(n));
+        self.err = r.is_err();
+        Some(r)
+    }
 }
 
-impl<'a, 'b> Drop for File<'a, 'b> {
+impl<'a, IO: ReadWriteSeek> Drop for File<'a, IO> {
     fn drop(&mut self) {
         match self.flush() {
-            Err(err) => panic!("flush failed {}", err),
+            Err(err) => error!("flush failed {}", err),
             _ => {}
         }
+        #[cfg(feature = "alloc")]
+        match self.entry {
+            Some(ref mut e) => e.unregister(self.fs),
+            None => {}
+        }
     }
 }
 
-impl<'a, 'b> Read for File<'a, 'b> {
+impl<'a, IO: ReadWriteSeek> Read for File<'a, IO> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.check_not_removed()?;
         let cluster_size = self.fs.cluster_size();
         let current_cluster_opt = if self.offset % cluster_size == 0 {
             // next cluster
@@ -177,18 +567,34 @@ impl<'a, 'b> Read for File<'a, 'b> {
         };
         let current_cluster = match current_cluster_opt {
             Some(n) => n,
-            None => return Ok(0),
+            None if buf.is_empty() || self.bytes_left_in_file().map_or(true, |n| n == 0) => {
+                return Ok(0)
+            }
+            None => {
+                // size in directory entry claims there is more data than the cluster chain holds
+                return Err(io::Error::new(
+                    ErrorKind::CorruptedFile,
+                    "cluster chain is shorter than file size",
+                ));
+            }
         };
         let offset_in_cluster = self.offset % cluster_size;
         let bytes_left_in_cluster = (cluster_size - offset_in_cluster) as usize;
         let bytes_left_in_file = self.bytes_left_in_file().unwrap_or(bytes_left_in_cluster);
-        let read_size = cmp::min(
-            cmp::min(buf.len(), bytes_left_in_cluster),
-            bytes_left_in_file,
-        );
-        if read_size == 0 {
+        let max_read_size = cmp::min(buf.len(), bytes_left_in_file);
+        if max_read_size == 0 {
             return Ok(0);
         }
+        // read following clusters in the same disk operation if they are physically consecutive
+        let contiguous_bytes = if max_read_size > bytes_left_in_cluster {
+            let clusters_needed =
+                (max_read_size - bytes_left_in_cluster - 1) / cluster_size as usize + 1;
+            let clusters = self.contiguous_clusters_after(current_cluster, clusters_needed)?;
+            bytes_left_in_cluster + clusters as usize * cluster_size as usize
+        } else {
+            bytes_left_in_cluster
+        };
+        let read_size = cmp::min(max_read_size, contiguous_bytes);
         let offset_in_fs =
             self.fs.offset_from_cluster(current_cluster) + (offset_in_cluster as u64);
         let read_bytes = {
@@ -200,23 +606,35 @@ impl<'a, 'b> Read for File<'a, 'b> {
             return Ok(0);
         }
         self.offset += read_bytes as u32;
-        self.current_cluster = Some(current_cluster);
+        // current cluster is the one containing the last read byte
+        let clusters_advanced = match read_bytes.checked_sub(bytes_left_in_cluster) {
+            Some(n) => (n + cluster_size as usize - 1) / cluster_size as usize,
+            None => 0,
+        };
+        self.current_cluster = Some(Cluster(current_cluster.0 + clusters_advanced as u32));
+        self.update_accessed();
 
         Ok(read_bytes)
     }
 }
 
-impl<'a, 'b> Write for File<'a, 'b> {
+impl<'a, IO: ReadWriteSeek> Write for File<'a, IO> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.check_not_removed()?;
         let cluster_size = self.fs.cluster_size();
         let offset_in_cluster = self.offset % cluster_size;
         let bytes_left_in_cluster = (cluster_size - offset_in_cluster) as usize;
-        let write_size = cmp::min(buf.len(), bytes_left_in_cluster);
+        let bytes_left_until_max = self.bytes_left_until_max_size();
+        if bytes_left_until_max == 0 && !buf.is_empty() {
+            return Err(io::Error::new(ErrorKind::FileTooLarge, "maximal file size reached"));
+        }
+        let write_size = cmp::min(cmp::min(buf.len(), bytes_left_in_cluster), bytes_left_until_max);
         // Exit early if we are going to write no data
         if write_size == 0 {
             return Ok(0);
         }
         // Get cluster for write possibly allocating new one
+        let mut new_cluster_allocated = false;
         let current_cluster = if self.offset % cluster_size == 0 {
             // next cluster
             let next_cluster = match self.current_cluster {
@@ -235,6 +653,7 @@ impl<'a, 'b> Write for File<'a, 'b> {
                 None => {
                     // end of chain reached - allocate new cluster
                     let new_cluster = self.fs.alloc_cluster(self.current_cluster)?;
+                    new_cluster_allocated = true;
                     if self.first_cluster.is_none() {
                         self.set_first_cluster(new_cluster);
                     }
@@ -243,13 +662,7 @@ impl<'a, 'b> Write for File<'a, 'b> {
                         .map_or(true, |e| e.inner().size().is_none())
                     {
                         // zero new directory cluster
-                        let abs_pos = self.fs.offset_from_cluster(new_cluster);
-                        let mut disk = self.fs.disk.borrow_mut();
-                        disk.seek(SeekFrom::Start(abs_pos))?;
-                        for _ in 0..cluster_size / 32 {
-                            let zero = [0u8; 32];
-                            disk.write(&zero)?;
-                        }
+                        self.fs.zero_cluster(new_cluster)?;
                     }
                     new_cluster
                 }
@@ -263,14 +676,21 @@ impl<'a, 'b> Write for File<'a, 'b> {
         };
         let offset_in_fs =
             self.fs.offset_from_cluster(current_cluster) + (offset_in_cluster as u64);
-        let written_bytes = {
+        let write_result = {
             let mut disk = self.fs.disk.borrow_mut();
-            disk.seek(SeekFrom::Start(offset_in_fs))?;
-            disk.write(&buf[..write_size])?
+            disk.seek(SeekFrom::Start(offset_in_fs))
+                .and_then(|_| disk.write(&buf[..write_size]))
+        };
+        let written_bytes = match write_result {
+            Ok(n) if n > 0 => n,
+            r => {
+                // nothing was written - do not leave the new cluster allocated
+                if new_cluster_allocated {
+                    self.free_new_cluster(current_cluster)?;
+                }
+                return r;
+            }
         };
-        if written_bytes == 0 {
-            return Ok(0);
-        }
         // some bytes were writter - update position and optionally size
         self.offset += written_bytes as u32;
         self.current_cluster = Some(current_cluster);
@@ -279,13 +699,17 @@ impl<'a, 'b> Write for File<'a, 'b> {
     }
 
     fn flush(&mut self) -> io::Result<()> {
+        // FAT chain is stored before the entry refers to it, so an interrupted flush leaves lost
+        // clusters instead of an entry pointing to free clusters
+        self.fs.flush_fat()?;
+        self.fs.disk.borrow_mut().flush()?;
         self.flush_dir_entry()?;
         let mut disk = self.fs.disk.borrow_mut();
         disk.flush()
     }
 }
 
-impl<'a, 'b> Seek for File<'a, 'b> {
+impl<'a, IO: ReadWriteSeek> Seek for File<'a, IO> {
     fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
         let mut new_pos = match pos {
             SeekFrom::Current(x) => self.offset as i64 + x,
@@ -0,0 +1,84 @@
+use core::ops::Deref;
+
+use fs::{FileSystem, FsOptions, ReadWriteSeek};
+use io::{self, ErrorKind, Read, Seek, SeekFrom};
+use units::{Cluster, Sector};
+
+/// A `FileSystem` with sector size fixed at compile time (enabled by `fixed` feature).
+///
+/// Meant for MCU builds where the storage has a known sector size (e.g. 512 bytes for SD cards).
+/// Mounting fails with `ErrorKind::InvalidInput` error if the volume uses a different sector size,
+/// so sector buffers can be placed on stack with their exact size (see `sector_buffer`) and sector
+/// reads of this type use the constant in offset math. All other operations are available
+/// through `Deref` to `FileSystem`.
+///
+/// Note: only methods of this type are specialized. Operations reached through `Deref` (file,
+/// directory and FAT accesses) run the same code as in `FileSystem` and compute offsets from the
+/// sector size read from BPB, so they do not get faster or smaller.
+///
+/// `SECTOR` must be a power of two between 512 and 4096 - other values fail to compile.
+pub struct FixedSectorFileSystem<IO: ReadWriteSeek, const SECTOR: usize> {
+    fs: FileSystem<IO>,
+}
+
+impl<IO: ReadWriteSeek, const SECTOR: usize> FixedSectorFileSystem<IO, SECTOR> {
+    const VALID_SECTOR: () = assert!(
+        SECTOR.is_power_of_two() && SECTOR >= 512 && SECTOR <= 4096,
+        "invalid sector size"
+    );
+
+    /// Sector size of volumes this type can mount.
+    pub const SECTOR_SIZE: usize = SECTOR;
+
+    /// Creates new filesystem object instance like `FileSystem::new`.
+    ///
+    /// Fails with `ErrorKind::InvalidInput` error if sector size of the volume is not `SECTOR`.
+    pub fn new(disk: IO, options: FsOptions) -> io::Result<Self> {
+        let () = Self::VALID_SECTOR;
+        let fs = FileSystem::new(disk, options)?;
+        if fs.bytes_per_sector() as usize != SECTOR {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "unexpected sector size"));
+        }
+        Ok(FixedSectorFileSystem { fs })
+    }
+
+    /// Returns a zeroed buffer for a single sector.
+    pub fn sector_buffer() -> [u8; SECTOR] {
+        [0; SECTOR]
+    }
+
+    /// Returns offset of a sector from the beginning of the volume.
+    pub fn offset_from_sector(&self, sector: Sector) -> u64 {
+        sector.0 * SECTOR as u64
+    }
+
+    /// Returns offset of a cluster from the beginning of the volume.
+    ///
+    /// Panics if `cluster` is not a data cluster (cluster numbers start from 2).
+    pub fn offset_from_cluster(&self, cluster: Cluster) -> u64 {
+        self.offset_from_sector(self.fs.sector_from_cluster(cluster))
+    }
+
+    /// Reads raw contents of a sector.
+    ///
+    /// The device is read directly, so FAT sectors modified in lazy FAT write mode are returned
+    /// as they were before the modification until the filesystem is flushed.
+    pub fn read_sector(&self, sector: Sector, buf: &mut [u8; SECTOR]) -> io::Result<()> {
+        let mut disk = self.fs.disk.borrow_mut();
+        disk.seek(SeekFrom::Start(self.offset_from_sector(sector)))?;
+        disk.read_exact(buf)
+    }
+
+    /// Unmounts the filesystem and returns the underlying device (see `FileSystem::into_inner`).
+    pub fn into_inner(self) -> io::Result<IO> {
+        self.fs.into_inner()
+    }
+}
+
+impl<IO: ReadWriteSeek, const SECTOR: usize> Deref for FixedSectorFileSystem<IO, SECTOR> {
+    type Target = FileSystem<IO>;
+
+    fn deref(&self) -> &FileSystem<IO> {
+        &self.fs
+    }
+}
@@ -0,0 +1,59 @@
+//! Static memory footprint report (enabled by `footprint` feature).
+//!
+//! Constants in this module are sizes in bytes of library objects for the target the crate is
+//! built for, so integrators of RTOS and other memory constrained systems can budget RAM without
+//! running any code. Run `cargo run --example footprint --features footprint` (with `--target`
+//! and other features as needed) to print all of them.
+//!
+//! The library allocates no memory on its own unless `alloc` feature is used. Besides objects
+//! listed here, no library function places more than `MAX_STACK_BUFFER` bytes of buffers on
+//! stack.
+//!
+//! Object sizes depend on the storage type the filesystem is created with. Constants are computed
+//! for storage passed as `&mut ReadWriteSeek` trait object (see `Storage`); owned storage objects
+//! add their own size to `FILE_SYSTEM` instead.
+
+use core::mem::size_of;
+
+use dir::{Dir, DirIter};
+use dir_entry::{DirEntry, LFN_BUFFER_LEN};
+use file::File;
+use fs::{FileSystem, ReadWriteSeek};
+#[cfg(feature = "alloc")]
+use fat_cache::{FatCache, MAX_CACHED_SECTORS};
+
+/// Storage type used to compute sizes in this module.
+pub type Storage = &'static mut ReadWriteSeek;
+
+/// Size of `FileSystem` object.
+pub const FILE_SYSTEM: usize = size_of::<FileSystem<Storage>>();
+
+/// Size of `File` object.
+pub const FILE: usize = size_of::<File<'static, Storage>>();
+
+/// Size of `Dir` object.
+pub const DIR: usize = size_of::<Dir<'static, Storage>>();
+
+/// Size of `DirIter` object.
+pub const DIR_ITER: usize = size_of::<DirIter<'static, Storage>>();
+
+/// Size of `DirEntry` object.
+pub const DIR_ENTRY: usize = size_of::<DirEntry<'static, Storage>>();
+
+/// Total size of temporary buffers placed on stack by a single library function.
+///
+/// It is taken by UCS-2 units of a long file name and their copy kept for the returned
+/// `DirEntry` when an entry is created. Buffers used when FAT copies are compared (two chunks of
+/// 512 bytes) and when the boot sector or FSInfo sector is read or written are smaller.
+pub const MAX_STACK_BUFFER: usize = 2 * LFN_BUFFER_LEN * size_of::<u16>();
+
+/// Size of FAT cache used by `FsOptions::lazy_fat_writes` without cached sectors.
+///
+/// Every cached FAT sector additionally takes a sector size buffer from
+/// `FsOptions::buffer_provider`. At most `MAX_FAT_CACHE_SECTORS` sectors are cached.
+#[cfg(feature = "alloc")]
+pub const FAT_CACHE: usize = size_of::<FatCache>();
+
+/// Maximal number of FAT sectors cached by `FsOptions::lazy_fat_writes`.
+#[cfg(feature = "alloc")]
+pub const MAX_FAT_CACHE_SECTORS: usize = MAX_CACHED_SECTORS;
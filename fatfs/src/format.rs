@@ -0,0 +1,416 @@
+use byteorder::LittleEndian;
+use byteorder_ext::WriteBytesExt;
+use core::cmp;
+use io::{self, ErrorKind, SeekFrom};
+
+use dir_entry::DIR_ENTRY_SIZE;
+use fs::{write_zeros, BiosParameterBlock, BootRecord, FatType, FsInfoSector, ReadWriteSeek};
+
+const KB: u64 = 1024;
+const MB: u64 = KB * 1024;
+const GB: u64 = MB * 1024;
+
+/// A volume formatting options.
+///
+/// Options are specified as an argument for `format_volume` function. Parameters which are not
+/// set explicitly are chosen based on volume size.
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    pub(crate) bytes_per_sector: u16,
+    pub(crate) total_sectors: Option<u32>,
+    pub(crate) bytes_per_cluster: Option<u32>,
+    pub(crate) fat_type: Option<FatType>,
+    pub(crate) fats: u8,
+    pub(crate) root_entries: u16,
+    pub(crate) media: u8,
+    pub(crate) volume_id: u32,
+    pub(crate) volume_label: [u8; 11],
+    pub(crate) data_alignment: Option<u32>,
+    pub(crate) erase_block_size: Option<u32>,
+}
+
+impl FormatOptions {
+    /// Creates `FormatOptions` struct with default options.
+    pub fn new() -> Self {
+        FormatOptions {
+            bytes_per_sector: 512,
+            total_sectors: None,
+            bytes_per_cluster: None,
+            fat_type: None,
+            fats: 2,
+            root_entries: 512,
+            media: 0xF8,
+            volume_id: 0,
+            volume_label: *b"NO NAME    ",
+            data_alignment: None,
+            erase_block_size: None,
+        }
+    }
+
+    /// Creates `FormatOptions` struct with options recommended for an EFI System Partition.
+    ///
+    /// ESP uses FAT32 with 512 byte sectors and data region aligned to 1 MiB. Cluster size is
+    /// chosen based on volume size (4 KiB for volumes below 8 GiB, smaller if the volume is too
+    /// small for FAT32 with 4 KiB clusters).
+    pub fn esp() -> Self {
+        Self::new()
+            .fat_type(FatType::Fat32)
+            .bytes_per_sector(512)
+            .data_alignment(MB as u32)
+    }
+
+    /// Changes sector size (power of two between 512 and 4096). Default is 512.
+    pub fn bytes_per_sector(mut self, bytes_per_sector: u16) -> Self {
+        self.bytes_per_sector = bytes_per_sector;
+        self
+    }
+
+    /// Changes number of sectors used by the volume. By default whole device is used.
+    pub fn total_sectors(mut self, total_sectors: u32) -> Self {
+        self.total_sectors = Some(total_sectors);
+        self
+    }
+
+    /// Changes cluster size (power of two, at most 128 sectors). By default it is chosen based on
+    /// volume size.
+    pub fn bytes_per_cluster(mut self, bytes_per_cluster: u32) -> Self {
+        self.bytes_per_cluster = Some(bytes_per_cluster);
+        self
+    }
+
+    /// Changes FAT type. By default it is chosen based on volume size.
+    pub fn fat_type(mut self, fat_type: FatType) -> Self {
+        self.fat_type = Some(fat_type);
+        self
+    }
+
+    /// Changes number of FAT copies (1 or 2). Default is 2.
+    pub fn fats(mut self, fats: u8) -> Self {
+        self.fats = fats;
+        self
+    }
+
+    /// Changes number of root directory entries (FAT12/FAT16 only). Default is 512.
+    pub fn root_entries(mut self, root_entries: u16) -> Self {
+        self.root_entries = root_entries;
+        self
+    }
+
+    /// Changes media descriptor. Default is 0xF8 (fixed disk).
+    pub fn media(mut self, media: u8) -> Self {
+        self.media = media;
+        self
+    }
+
+    /// Changes volume identifier (serial number). Default is 0.
+    pub fn volume_id(mut self, volume_id: u32) -> Self {
+        self.volume_id = volume_id;
+        self
+    }
+
+    /// Aligns start of the data region to `data_alignment` bytes (power of two, at least sector
+    /// size) by adding padding to reserved sectors. By default data region is not aligned.
+    ///
+    /// Offset is relative to the beginning of the volume so the volume itself should be aligned
+    /// (e.g. partition starting at 1 MiB).
+    pub fn data_alignment(mut self, data_alignment: u32) -> Self {
+        self.data_alignment = Some(data_alignment);
+        self
+    }
+
+    /// Aligns the first FAT and the data region to erase blocks of `erase_block_size` bytes
+    /// (power of two, at least sector size). By default volume is not aligned to erase blocks.
+    ///
+    /// Flash memory (e.g. SD cards) is much slower when writes are not aligned to erase blocks
+    /// (usually 4 MiB). Reserved sectors are padded to a whole erase block and padding needed to
+    /// align the data region is added to FATs (and the root directory on FAT12/FAT16).
+    pub fn erase_block_size(mut self, erase_block_size: u32) -> Self {
+        self.erase_block_size = Some(erase_block_size);
+        self
+    }
+
+    /// Changes volume label stored in the boot sector. Label is truncated to 11 bytes.
+    /// Default is "NO NAME".
+    pub fn volume_label(mut self, volume_label: &str) -> Self {
+        self.volume_label = [b' '; 11];
+        let len = cmp::min(volume_label.len(), 11);
+        self.volume_label[..len].copy_from_slice(&volume_label.as_bytes()[..len]);
+        self
+    }
+}
+
+fn invalid_input(msg: &'static str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidInput, msg)
+}
+
+#[derive(Debug)]
+struct Layout {
+    fat_type: FatType,
+    sectors_per_cluster: u8,
+    reserved_sectors: u16,
+    root_entries: u16,
+    sectors_per_fat: u32,
+    total_clusters: u32,
+}
+
+fn default_fat_type(total_bytes: u64) -> FatType {
+    if total_bytes < 16 * MB {
+        FatType::Fat12
+    } else if total_bytes <= 512 * MB {
+        FatType::Fat16
+    } else {
+        FatType::Fat32
+    }
+}
+
+fn default_fat32_cluster_size(total_bytes: u64) -> u64 {
+    if total_bytes < 8 * GB {
+        4 * KB
+    } else if total_bytes < 16 * GB {
+        8 * KB
+    } else if total_bytes < 32 * GB {
+        16 * KB
+    } else {
+        32 * KB
+    }
+}
+
+fn compute_layout(
+    options: &FormatOptions,
+    total_sectors: u32,
+    fat_type: FatType,
+    sectors_per_cluster: u32,
+) -> Layout {
+    let bytes_per_sector = options.bytes_per_sector as u32;
+    let (min_reserved_sectors, root_entries) = match fat_type {
+        FatType::Fat32 => (32, 0),
+        _ => (1, options.root_entries),
+    };
+    let root_dir_bytes = root_entries as u32 * DIR_ENTRY_SIZE as u32;
+    let root_dir_sectors = (root_dir_bytes + bytes_per_sector - 1) / bytes_per_sector;
+    let fats = options.fats as u32;
+    let data_alignment_sectors = options.data_alignment.map_or(1, |n| n / bytes_per_sector);
+    let erase_block_sectors = options.erase_block_size.map_or(1, |n| n / bytes_per_sector);
+    // both values are powers of two so the bigger one is a multiple of the other one
+    let alignment_sectors = cmp::max(data_alignment_sectors, erase_block_sectors);
+    // FAT starts on erase block boundary
+    let min_reserved_sectors = round_up(min_reserved_sectors, erase_block_sectors);
+    // FAT size depends on number of clusters which depends on FAT size - start from the smallest
+    // possible FAT and grow it until all clusters fit
+    let mut sectors_per_fat = 1;
+    loop {
+        let metadata_sectors = min_reserved_sectors + fats * sectors_per_fat + root_dir_sectors;
+        // pad metadata so data region starts on alignment boundary
+        let padding = round_up(metadata_sectors, alignment_sectors) - metadata_sectors;
+        let (reserved_sectors, fat_padding, root_dir_padding) = if erase_block_sectors > 1 {
+            // keep FAT aligned - FAT32 metadata size is even so root directory is never padded
+            (min_reserved_sectors, padding / fats, padding % fats)
+        } else {
+            (min_reserved_sectors + padding, 0, 0)
+        };
+        let used_sectors = metadata_sectors + padding;
+        let data_sectors = total_sectors.saturating_sub(used_sectors);
+        let total_clusters = data_sectors / sectors_per_cluster;
+        let fat_bytes = ((total_clusters as u64 + 2) * fat_type.bits_per_entry() as u64 + 7) / 8;
+        let needed = ((fat_bytes + bytes_per_sector as u64 - 1) / bytes_per_sector as u64) as u32;
+        if needed <= sectors_per_fat {
+            let root_entries = if root_dir_padding > 0 {
+                (root_dir_sectors + root_dir_padding) * bytes_per_sector / DIR_ENTRY_SIZE as u32
+            } else {
+                root_entries as u32
+            };
+            return Layout {
+                fat_type,
+                sectors_per_cluster: sectors_per_cluster as u8,
+                reserved_sectors: reserved_sectors as u16,
+                root_entries: root_entries as u16,
+                sectors_per_fat: sectors_per_fat + fat_padding,
+                total_clusters,
+            };
+        }
+        sectors_per_fat = needed;
+    }
+}
+
+fn round_up(n: u32, alignment: u32) -> u32 {
+    (n + alignment - 1) / alignment * alignment
+}
+
+fn determine_layout(options: &FormatOptions, total_sectors: u32) -> io::Result<Layout> {
+    let bytes_per_sector = options.bytes_per_sector as u64;
+    let total_bytes = total_sectors as u64 * bytes_per_sector;
+    let fat_type = options.fat_type.unwrap_or(default_fat_type(total_bytes));
+    for alignment in options.data_alignment.iter().chain(options.erase_block_size.iter()) {
+        let alignment = *alignment as u64;
+        if !alignment.is_power_of_two() || alignment < bytes_per_sector {
+            return Err(invalid_input("invalid alignment"));
+        }
+        // reserved sectors count is a 16-bit field
+        if alignment / bytes_per_sector > 0x8000 {
+            return Err(invalid_input("alignment too big"));
+        }
+    }
+    let candidates = match options.bytes_per_cluster {
+        Some(n) => {
+            let n = n as u64;
+            if !n.is_power_of_two() || n < bytes_per_sector || n / bytes_per_sector > 128 {
+                return Err(invalid_input("invalid cluster size"));
+            }
+            [n / bytes_per_sector; 8]
+        }
+        None => {
+            // try cluster sizes from preferred one down (FAT32) or from the smallest one up
+            let first = match fat_type {
+                FatType::Fat32 => default_fat32_cluster_size(total_bytes) / bytes_per_sector,
+                _ => 1,
+            };
+            let mut candidates = [0u64; 8];
+            for (i, c) in candidates.iter_mut().enumerate() {
+                *c = match fat_type {
+                    FatType::Fat32 => cmp::max(first >> i, 1),
+                    _ => cmp::min(first << i, 128),
+                };
+            }
+            candidates
+        }
+    };
+    for &sectors_per_cluster in candidates.iter() {
+        let layout = compute_layout(options, total_sectors, fat_type, sectors_per_cluster as u32);
+        let clusters = layout.total_clusters;
+        if clusters >= fat_type.min_clusters() && clusters <= fat_type.max_clusters() {
+            return Ok(layout);
+        }
+    }
+    Err(invalid_input("volume size is not supported by selected FAT type and cluster size"))
+}
+
+fn write_fat_header(disk: &mut ReadWriteSeek, layout: &Layout, media: u8) -> io::Result<()> {
+    let fat_type = layout.fat_type;
+    let mask = fat_type.entry_mask();
+    // first entry contains media descriptor, second one is end of chain with clean shutdown and
+    // no I/O error bits set
+    let entry_0 = (mask & !0xFF) | media as u32;
+    let entry_1 = mask;
+    match fat_type {
+        FatType::Fat12 => {
+            let packed = entry_0 | (entry_1 << 12);
+            disk.write_all(&[packed as u8, (packed >> 8) as u8, (packed >> 16) as u8])
+        }
+        FatType::Fat16 => {
+            disk.write_u16::<LittleEndian>(entry_0 as u16)?;
+            disk.write_u16::<LittleEndian>(entry_1 as u16)
+        }
+        FatType::Fat32 => {
+            disk.write_u32::<LittleEndian>(entry_0)?;
+            disk.write_u32::<LittleEndian>(entry_1)?;
+            // root directory cluster
+            disk.write_u32::<LittleEndian>(mask)
+        }
+    }
+}
+
+/// Creates a new FAT filesystem on the device.
+///
+/// Parameters which are not specified in `options` are chosen based on the volume size. Existing
+/// data is not wiped - only the boot sector, FATs and the root directory are written.
+pub fn format_volume<T: ReadWriteSeek>(disk: &mut T, options: FormatOptions) -> io::Result<()> {
+    let bytes_per_sector = options.bytes_per_sector;
+    if !bytes_per_sector.is_power_of_two() || bytes_per_sector < 512 || bytes_per_sector > 4096 {
+        return Err(invalid_input("invalid sector size"));
+    }
+    if options.fats < 1 || options.fats > 2 {
+        return Err(invalid_input("invalid number of FATs"));
+    }
+    let total_sectors = match options.total_sectors {
+        Some(n) => n,
+        None => {
+            let size = disk.seek(SeekFrom::End(0))?;
+            cmp::min(size / bytes_per_sector as u64, u32::max_value() as u64) as u32
+        }
+    };
+    let layout = determine_layout(&options, total_sectors)?;
+    let fat_type = layout.fat_type;
+    let is_fat32 = fat_type == FatType::Fat32;
+    let total_sectors_fit_16 = total_sectors < 0x10000 && !is_fat32;
+
+    let mut boot = BootRecord::default();
+    boot.bootjmp = if is_fat32 { [0xEB, 0x58, 0x90] } else { [0xEB, 0x3C, 0x90] };
+    boot.oem_name = *b"MSWIN4.1";
+    boot.boot_sig = [0x55, 0xAA];
+    boot.bpb = BiosParameterBlock {
+        bytes_per_sector,
+        sectors_per_cluster: layout.sectors_per_cluster,
+        reserved_sectors: layout.reserved_sectors,
+        fats: options.fats,
+        root_entries: layout.root_entries,
+        total_sectors_16: if total_sectors_fit_16 { total_sectors as u16 } else { 0 },
+        media: options.media,
+        sectors_per_fat_16: if is_fat32 { 0 } else { layout.sectors_per_fat as u16 },
+        sectors_per_track: 32,
+        heads: 64,
+        hidden_sectors: 0,
+        total_sectors_32: if total_sectors_fit_16 { 0 } else { total_sectors },
+        sectors_per_fat_32: if is_fat32 { layout.sectors_per_fat } else { 0 },
+        extended_flags: 0,
+        fs_version: 0,
+        root_dir_first_cluster: if is_fat32 { 2 } else { 0 },
+        fs_info_sector: if is_fat32 { 1 } else { 0 },
+        backup_boot_sector: if is_fat32 { 6 } else { 0 },
+        reserved_0: [0; 12],
+        drive_num: 0x80,
+        reserved_1: 0,
+        ext_sig: 0x29,
+        volume_id: options.volume_id,
+        volume_label: options.volume_label,
+        fs_type_label: match fat_type {
+            FatType::Fat12 => *b"FAT12   ",
+            FatType::Fat16 => *b"FAT16   ",
+            FatType::Fat32 => *b"FAT32   ",
+        },
+    };
+
+    let sector_size = bytes_per_sector as u64;
+    // reserved sectors
+    disk.seek(SeekFrom::Start(0))?;
+    write_zeros(disk, layout.reserved_sectors as u64 * sector_size)?;
+    disk.seek(SeekFrom::Start(0))?;
+    boot.serialize(disk)?;
+    if is_fat32 {
+        let fs_info = FsInfoSector {
+            // root directory occupies the first cluster
+            free_cluster_count: Some(layout.total_clusters - 1),
+            next_free_cluster: Some(3),
+            dirty: false,
+        };
+        let backup_sector = boot.bpb.backup_boot_sector as u64;
+        disk.seek(SeekFrom::Start(sector_size))?;
+        fs_info.serialize(disk)?;
+        disk.seek(SeekFrom::Start(backup_sector * sector_size))?;
+        boot.serialize(disk)?;
+        disk.seek(SeekFrom::Start((backup_sector + 1) * sector_size))?;
+        fs_info.serialize(disk)?;
+    }
+
+    // FATs
+    let fat_size = layout.sectors_per_fat as u64 * sector_size;
+    for i in 0..options.fats as u64 {
+        let fat_offset = layout.reserved_sectors as u64 * sector_size + i * fat_size;
+        disk.seek(SeekFrom::Start(fat_offset))?;
+        write_zeros(disk, fat_size)?;
+        disk.seek(SeekFrom::Start(fat_offset))?;
+        write_fat_header(disk, &layout, options.media)?;
+    }
+
+    // root directory (root directory region or first cluster)
+    let root_dir_offset =
+        layout.reserved_sectors as u64 * sector_size + options.fats as u64 * fat_size;
+    let root_dir_size = if is_fat32 {
+        layout.sectors_per_cluster as u64 * sector_size
+    } else {
+        layout.root_entries as u64 * DIR_ENTRY_SIZE
+    };
+    disk.seek(SeekFrom::Start(root_dir_offset))?;
+    write_zeros(disk, root_dir_size)?;
+    disk.seek(SeekFrom::Start(0))?;
+    disk.flush()
+}
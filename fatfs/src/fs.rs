@@ -1,13 +1,38 @@
-use byteorder::LittleEndian;
-use byteorder_ext::ReadBytesExt;
-use core::cell::RefCell;
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+use byteorder::{ByteOrder, LittleEndian};
+use core::cell::{Cell, RefCell};
 use core::cmp;
+use core::iter;
 use io::{self, *};
 
+use buffer::BufferProvider;
+#[cfg(not(feature = "alloc"))]
+use buffer::NullBufferProvider;
+#[cfg(feature = "alloc")]
+use buffer::HeapBufferProvider;
+use cancel::{check_cancelled, CancellationToken, NullCancellationToken};
 use dir::{Dir, DirRawStream};
-use dir_entry::DIR_ENTRY_SIZE;
+use dir_entry::{NullTimeProvider, TimeProvider, DIR_ENTRY_SIZE};
 use file::File;
-use table::{alloc_cluster, read_fat_flags, ClusterIterator};
+#[cfg(feature = "splice")]
+use file::ClusterChain;
+#[cfg(feature = "alloc")]
+use fat_cache::FatCache;
+#[cfg(feature = "alloc")]
+use format::{format_volume, FormatOptions};
+#[cfg(feature = "alloc")]
+use memory::MemoryDisk;
+#[cfg(feature = "alloc")]
+use open_entries::OpenEntries;
+#[cfg(feature = "alloc")]
+use overlay::{OverlayDisk, OverlayStore};
+use table::{alloc_cluster, count_free_clusters, fix_reserved_fat_entries, is_free_cluster,
+            read_fat_flags, reserved_fat_entries_valid, write_fat_io_error_flag, ClusterIterator,
+            FatOps, RESERVED_FAT_ENTRIES};
+#[cfg(feature = "splice")]
+use table::set_next_cluster;
+use units::{Cluster, Sector};
 
 use core::str;
 
@@ -15,6 +40,37 @@ use core::str;
 //   http://wiki.osdev.org/FAT
 //   https://www.win.tue.nl/~aeb/linux/fs/fat/fat-1.html
 
+// File size is stored in a 32-bit field of directory entry
+const MAX_FILE_SIZE: u32 = 0xFFFFFFFF;
+
+// Long file name is limited to 255 UTF-16 characters
+pub(crate) const MAX_NAME_LENGTH: u32 = 255;
+
+// Size of buffers allocated on stack for zeroing and checksumming data. Together with boot
+// sector buffers it bounds stack temporaries of the library independently of sector and cluster
+// size.
+pub(crate) const SCRATCH_BUFFER_SIZE: usize = 128;
+
+// End of chain marker is selected by 3 lowest bits of FAT entry
+const MAX_END_OF_CHAIN_MARKER: u8 = 7;
+
+// Boot and FSInfo sectors are decoded from the first 512 bytes of a sector independently of
+// sector size
+pub(crate) const BOOT_SECTOR_SIZE: usize = 512;
+
+pub(crate) fn write_zeros<W: Write + ?Sized>(wrt: &mut W, mut len: u64) -> io::Result<()> {
+    let zeros = [0u8; SCRATCH_BUFFER_SIZE];
+    while len > 0 {
+        let n = cmp::min(len, zeros.len() as u64) as usize;
+        wrt.write_all(&zeros[..n])?;
+        len -= n as u64;
+    }
+    Ok(())
+}
+
+/// A type of FAT filesystem.
+///
+/// FAT type is determined by the number of data clusters on the volume.
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum FatType {
     Fat12,
@@ -24,21 +80,95 @@ pub enum FatType {
 
 impl FatType {
     fn from_clusters(total_clusters: u32) -> FatType {
-        if total_clusters < 4085 {
+        if total_clusters < FatType::Fat16.min_clusters() {
             FatType::Fat12
-        } else if total_clusters < 65525 {
+        } else if total_clusters < FatType::Fat32.min_clusters() {
             FatType::Fat16
         } else {
             FatType::Fat32
         }
     }
+
+    /// Returns number of bits occupied by a single FAT entry.
+    ///
+    /// Note: only lower 28 bits of FAT32 entry are used - top 4 bits are reserved.
+    pub fn bits_per_entry(&self) -> u32 {
+        match *self {
+            FatType::Fat12 => 12,
+            FatType::Fat16 => 16,
+            FatType::Fat32 => 32,
+        }
+    }
+
+    /// Returns minimal number of data clusters for a volume of this type.
+    pub fn min_clusters(&self) -> u32 {
+        match *self {
+            FatType::Fat12 => 1,
+            FatType::Fat16 => 4085,
+            FatType::Fat32 => 65525,
+        }
+    }
+
+    /// Returns maximal number of data clusters for a volume of this type.
+    pub fn max_clusters(&self) -> u32 {
+        match *self {
+            FatType::Fat12 => 4084,
+            FatType::Fat16 => 65524,
+            FatType::Fat32 => 0x0FFFFFF4,
+        }
+    }
+
+    /// Returns mask of FAT entry bits used for cluster numbers.
+    pub fn entry_mask(&self) -> u32 {
+        match *self {
+            FatType::Fat12 => 0x0FFF,
+            FatType::Fat16 => 0xFFFF,
+            FatType::Fat32 => 0x0FFFFFFF,
+        }
+    }
+
+    /// Returns FAT entry value marking a bad cluster.
+    pub fn bad_cluster(&self) -> u32 {
+        self.entry_mask() - 8
+    }
+
+    /// Returns inclusive range of FAT entry values marking end of cluster chain.
+    ///
+    /// Any value from the range is accepted when reading. The last one is written by default - see
+    /// `FsOptions::end_of_chain_marker`.
+    pub fn end_of_chain(&self) -> (u32, u32) {
+        (self.entry_mask() - 7, self.entry_mask())
+    }
 }
 
+/// A FAT volume status flags retrieved from the Boot Sector and the allocation table second entry.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct FsStatusFlags {
+    /// Volume was not unmounted cleanly - filesystem may be inconsistent
     pub dirty: bool,
+    /// Disk I/O error was encountered during last use - some sectors may be unreadable
     pub io_error: bool,
 }
 
+// Progress of `FileSystem::scan_free_space_incremental` - clusters below `next_cluster` were
+// scanned and `free_clusters` of them are free. Zero `next_cluster` means no scan in progress.
+#[derive(Copy, Clone, Default)]
+struct FreeSpaceScan {
+    next_cluster: u32,
+    free_clusters: u32,
+}
+
+/// Filesystem usage statistics.
+#[derive(Copy, Clone, Debug)]
+pub struct FileSystemStats {
+    /// Cluster size in bytes
+    pub cluster_size: u32,
+    /// Number of total clusters in filesystem usable for file allocation
+    pub total_clusters: u32,
+    /// Number of free clusters
+    pub free_clusters: u32,
+}
+
 pub trait ReadSeek: Read + Seek {}
 impl<T> ReadSeek for T
 where
@@ -53,6 +183,242 @@ where
 {
 }
 
+/// A hook called before a failed device access is retried.
+///
+/// Implementations can sleep or reset the device. See `RetryPolicy`.
+pub trait RetryBackoff {
+    /// Waits before retry number `attempt` (starting from 1).
+    fn backoff(&self, attempt: u32);
+}
+
+// `RetryBackoff` implementation retrying immediately
+struct NoBackoff;
+
+impl RetryBackoff for NoBackoff {
+    fn backoff(&self, _attempt: u32) {}
+}
+
+/// A policy of retrying device reads and writes which returned an error.
+///
+/// Flaky devices (e.g. SD cards) can fail transiently. Every failed read, write or flush is retried
+/// up to the configured number of times before the error is returned to the caller. Device is
+/// seeked back to the original position before each retry.
+#[derive(Copy, Clone)]
+pub struct RetryPolicy {
+    retries: u32,
+    backoff: &'static RetryBackoff,
+}
+
+impl RetryPolicy {
+    /// Creates policy retrying failed accesses `retries` times without waiting in between.
+    pub fn new(retries: u32) -> Self {
+        RetryPolicy {
+            retries,
+            backoff: &NoBackoff,
+        }
+    }
+
+    /// Changes hook called before every retry.
+    pub fn backoff(mut self, backoff: &'static RetryBackoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+}
+
+/// A source of identifiers of the medium inserted in a device with removable media.
+///
+/// Identifier can be e.g. the serial number from CID register of an SD card or a counter
+/// incremented by a card detect interrupt. It is read when the filesystem is mounted and compared
+/// before every device read and write, so it should be cheap to get. After a change all accesses
+/// fail with `ErrorKind::MediaChanged` error until `FileSystem::remount` is called. See
+/// `FsOptions::media_id_provider`.
+pub trait MediaIdProvider {
+    /// Returns identifier of the current medium or `None` if no medium is present.
+    fn media_id(&self) -> Option<u64>;
+}
+
+/// `MediaIdProvider` implementation which never reports a media change.
+///
+/// It is used by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NullMediaIdProvider;
+
+impl MediaIdProvider for NullMediaIdProvider {
+    fn media_id(&self) -> Option<u64> {
+        None
+    }
+}
+
+// Device wrapper retrying failed accesses according to `RetryPolicy`
+pub(crate) struct Disk<IO: ReadWriteSeek> {
+    // taken out by `FileSystem::into_inner`
+    device: Option<IO>,
+    retry_policy: RetryPolicy,
+    // position tracked to restore it before retrying a failed access
+    pos: u64,
+    // set when a write failed after all retries
+    pub(crate) io_error: bool,
+    // device is smaller than the volume (degraded read-only mount)
+    truncated: bool,
+    // writing of metadata failed - further writes are rejected
+    poisoned: bool,
+    media: &'static MediaIdProvider,
+    // identifier of the medium the filesystem was mounted from
+    media_id: Option<u64>,
+    // set when the medium identifier changed - all accesses are rejected until remount
+    media_changed: bool,
+}
+
+impl<IO: ReadWriteSeek> Disk<IO> {
+    fn new(device: IO, retry_policy: RetryPolicy, media: &'static MediaIdProvider) -> Self {
+        Disk {
+            device: Some(device),
+            retry_policy,
+            pos: 0,
+            io_error: false,
+            truncated: false,
+            poisoned: false,
+            media,
+            media_id: media.media_id(),
+            media_changed: false,
+        }
+    }
+
+    // Resets state of the previous medium before mounting again
+    fn reset(&mut self) {
+        self.io_error = false;
+        self.truncated = false;
+        self.poisoned = false;
+        self.media_id = self.media.media_id();
+        self.media_changed = false;
+    }
+
+    fn check_media(&mut self) -> io::Result<()> {
+        if !self.media_changed && self.media.media_id() != self.media_id {
+            warn!("medium was changed");
+            self.media_changed = true;
+        }
+        if self.media_changed {
+            return Err(media_changed_error());
+        }
+        Ok(())
+    }
+
+    fn device(&mut self) -> &mut IO {
+        // SAFE: device is taken only by `FileSystem::into_inner` which consumes the filesystem
+        self.device.as_mut().unwrap()
+    }
+
+    fn device_size(&mut self) -> io::Result<u64> {
+        let pos = self.pos;
+        let size = self.device().seek(SeekFrom::End(0))?;
+        self.device().seek(SeekFrom::Start(pos))?;
+        Ok(size)
+    }
+
+    fn with_retries<T, F>(&mut self, mut op: F) -> io::Result<T>
+    where
+        F: FnMut(&mut IO) -> io::Result<T>,
+    {
+        let mut attempt = 0;
+        loop {
+            let err = match op(self.device()) {
+                Ok(r) => return Ok(r),
+                Err(err) => err,
+            };
+            if attempt == self.retry_policy.retries {
+                return Err(err);
+            }
+            attempt += 1;
+            warn!("device access failed ({}), retry {}", err, attempt);
+            self.retry_policy.backoff.backoff(attempt);
+            let pos = self.pos;
+            self.device().seek(SeekFrom::Start(pos))?;
+        }
+    }
+
+    fn check_write_result<T>(&mut self, r: io::Result<T>) -> io::Result<T> {
+        if r.is_err() && !self.io_error {
+            error!("device write failed, volume will be marked as having I/O errors");
+            self.io_error = true;
+        }
+        r
+    }
+}
+
+fn poisoned_error() -> Error {
+    Error::new(ErrorKind::FilesystemPoisoned, "filesystem is poisoned")
+}
+
+fn media_changed_error() -> Error {
+    Error::new(ErrorKind::MediaChanged, "medium was changed")
+}
+
+impl<IO: ReadWriteSeek> Read for Disk<IO> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.check_media()?;
+        let n = self.with_retries(|disk| disk.read(buf))?;
+        if n == 0 && !buf.is_empty() && self.truncated {
+            return Err(Error::new(ErrorKind::TruncatedVolume, "read beyond end of device"));
+        }
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<IO: ReadWriteSeek> Write for Disk<IO> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.check_media()?;
+        if self.truncated {
+            return Err(Error::new(ErrorKind::TruncatedVolume, "truncated volume is read-only"));
+        }
+        if self.poisoned {
+            return Err(poisoned_error());
+        }
+        let r = self.with_retries(|disk| disk.write(buf));
+        let n = self.check_write_result(r)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let r = self.with_retries(|disk| disk.flush());
+        self.check_write_result(r)
+    }
+}
+
+impl<IO: ReadWriteSeek> Seek for Disk<IO> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = self.device().seek(pos)?;
+        Ok(self.pos)
+    }
+}
+
+// Converts label to the padded upper case form stored on disk or None if label is empty
+fn encode_volume_label(label: &str) -> io::Result<Option<[u8; 11]>> {
+    const INVALID_CHARS: &[u8] = b"\"*+,./:;<=>?[\\]|";
+    if label.len() > 11 {
+        return Err(Error::new(ErrorKind::InvalidInput, "volume label is too long"));
+    }
+    let mut raw_label = [b' '; 11];
+    for (i, c) in label.bytes().enumerate() {
+        if c < 0x20 || c >= 0x7F || INVALID_CHARS.contains(&c) {
+            return Err(Error::new(ErrorKind::InvalidInput, "invalid character in volume label"));
+        }
+        raw_label[i] = c.to_ascii_uppercase();
+    }
+    if label.trim_right().is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(raw_label))
+    }
+}
+
+// Checks if label means the volume has no label
+fn label_is_empty(label: &[u8; 11]) -> bool {
+    label == b"NO NAME    " || label.iter().all(|&c| c == b' ' || c == 0)
+}
+
 pub(crate) fn strip_non_ascii(slice: &mut [u8]) {
     for c in slice {
         if *c < 0x20 || *c >= 0x80 {
@@ -61,53 +427,180 @@ pub(crate) fn strip_non_ascii(slice: &mut [u8]) {
     }
 }
 
+/// Basic volume information returned by `FileSystem::peek_volume_info`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VolumeInfo {
+    /// Volume serial number (volume ID) from BPB.
+    pub volume_id: u32,
+    /// Volume label from BPB padded with spaces.
+    pub volume_label: [u8; 11],
+    /// Type of File Allocation Table.
+    pub fat_type: FatType,
+    /// Maximal size of a file on the volume - 4 GiB - 1 byte (limit of the size field in
+    /// directory entry) or less if the data region is smaller.
+    ///
+    /// Mounted filesystem can be configured with a lower limit (see `FsOptions::max_file_size`).
+    pub max_file_size: u32,
+}
+
+impl VolumeInfo {
+    /// Decodes volume information from the first 512 bytes of a boot sector.
+    ///
+    /// Meant for callers which already keep the boot sector in memory (e.g. in a sector cache).
+    /// Fails if the boot sector signature or BPB is invalid.
+    pub fn from_boot_sector(sector: &[u8; 512]) -> io::Result<VolumeInfo> {
+        let boot = BootRecord::from_bytes(sector)?;
+        boot.check_signature()?;
+        let bpb = boot.bpb;
+        let total_clusters = bpb.total_clusters();
+        let cluster_size = bpb.bytes_per_sector as u64 * bpb.sectors_per_cluster as u64;
+        Ok(VolumeInfo {
+            volume_id: bpb.volume_id,
+            volume_label: bpb.volume_label,
+            fat_type: FatType::from_clusters(total_clusters),
+            max_file_size: cmp::min(total_clusters as u64 * cluster_size, MAX_FILE_SIZE as u64)
+                as u32,
+        })
+    }
+
+    /// Returns volume label without trailing spaces.
+    pub fn volume_label(&self) -> &str {
+        str::from_utf8(&self.volume_label).unwrap_or("").trim_right()
+    }
+}
+
+// Reads boot sector and returns BPB stored in it
+fn read_boot_sector(rdr: &mut Read, profile: MountProfile) -> io::Result<BiosParameterBlock> {
+    let boot = BootRecord::deserialize(rdr)?;
+    boot.check_signature()?;
+    if profile == MountProfile::Strict {
+        check_boot_record_strict(&boot)?;
+    }
+    Ok(boot.bpb)
+}
+
+// Checks fields which relaxed mounting does not care about
+fn check_boot_record_strict(boot: &BootRecord) -> io::Result<()> {
+    // layout checks are shared with detection
+    if detect_fat_type(boot).is_none() {
+        return Err(Error::new(ErrorKind::Other, "boot sector is not valid"));
+    }
+    let bpb = &boot.bpb;
+    if bpb.total_sectors_16 != 0 && bpb.total_sectors_32 != 0 {
+        return Err(Error::new(ErrorKind::Other, "both total sector fields are set in BPB"));
+    }
+    if bpb.sectors_per_fat_16 == 0 {
+        // FAT32 extended BPB
+        if bpb.total_sectors_16 != 0 || bpb.fs_version != 0 || bpb.reserved_0 != [0; 12] {
+            return Err(Error::new(ErrorKind::Other, "reserved fields are not zero in BPB"));
+        }
+    }
+    Ok(())
+}
+
+/// Checks if device looks like it contains a FAT filesystem and returns its FAT type.
+///
+/// Only the boot sector is read. It must have a valid signature and jump instruction and its BPB
+/// must describe a consistent volume layout. Nothing is written and the device position is
+/// restored, so it can be used by auto-mount logic to probe a device before trying other
+/// filesystems. Device errors are treated as absence of a FAT filesystem.
+pub fn detect(disk: &mut ReadSeek) -> Option<FatType> {
+    let pos = match disk.seek(SeekFrom::Current(0)) {
+        Ok(n) => n,
+        Err(_) => return None,
+    };
+    let boot = disk.seek(SeekFrom::Start(0)).and_then(|_| BootRecord::deserialize(disk));
+    match disk.seek(SeekFrom::Start(pos)) {
+        Ok(_) => {}
+        Err(_) => return None,
+    }
+    match boot {
+        Ok(ref boot) => detect_fat_type(boot),
+        Err(_) => None,
+    }
+}
+
+fn detect_fat_type(boot: &BootRecord) -> Option<FatType> {
+    let bpb = &boot.bpb;
+    let valid_jump = (boot.bootjmp[0] == 0xEB && boot.bootjmp[2] == 0x90)
+        || boot.bootjmp[0] == 0xE9;
+    let valid_sector_size = bpb.bytes_per_sector.is_power_of_two()
+        && bpb.bytes_per_sector >= 512
+        && bpb.bytes_per_sector <= 4096;
+    let valid_media = bpb.media == 0xF0 || bpb.media >= 0xF8;
+    if boot.boot_sig != [0x55, 0xAA]
+        || !valid_jump
+        || !valid_sector_size
+        || !bpb.sectors_per_cluster.is_power_of_two()
+        || !valid_media
+        || bpb.sectors_per_fat() == 0
+        || bpb.first_data_sector() >= bpb.total_sectors()
+    {
+        return None;
+    }
+    let total_clusters = bpb.total_clusters();
+    let fat_type = FatType::from_clusters(total_clusters);
+    let valid_root_dir = match fat_type {
+        FatType::Fat12 | FatType::Fat16 => bpb.root_entries != 0,
+        FatType::Fat32 => bpb.root_entries == 0 && bpb.sectors_per_fat_16 == 0,
+    };
+    // FAT must have an entry for every cluster
+    let fat_bits = bpb.sectors_per_fat() as u64 * bpb.bytes_per_sector as u64 * 8;
+    let needed_bits = (total_clusters as u64 + 2) * fat_type.bits_per_entry() as u64;
+    if total_clusters == 0 || !valid_root_dir || fat_bits < needed_bits {
+        return None;
+    }
+    Some(fat_type)
+}
+
 #[allow(dead_code)]
 #[derive(Default, Debug, Clone)]
-struct BiosParameterBlock {
-    bytes_per_sector: u16,
-    sectors_per_cluster: u8,
-    reserved_sectors: u16,
-    fats: u8,
-    root_entries: u16,
-    total_sectors_16: u16,
-    media: u8,
-    sectors_per_fat_16: u16,
-    sectors_per_track: u16,
-    heads: u16,
-    hidden_sectors: u32,
-    total_sectors_32: u32,
+pub(crate) struct BiosParameterBlock {
+    pub(crate) bytes_per_sector: u16,
+    pub(crate) sectors_per_cluster: u8,
+    pub(crate) reserved_sectors: u16,
+    pub(crate) fats: u8,
+    pub(crate) root_entries: u16,
+    pub(crate) total_sectors_16: u16,
+    pub(crate) media: u8,
+    pub(crate) sectors_per_fat_16: u16,
+    pub(crate) sectors_per_track: u16,
+    pub(crate) heads: u16,
+    pub(crate) hidden_sectors: u32,
+    pub(crate) total_sectors_32: u32,
 
     // Extended BIOS Parameter Block
-    sectors_per_fat_32: u32,
-    extended_flags: u16,
-    fs_version: u16,
-    root_dir_first_cluster: u32,
-    fs_info_sector: u16,
-    backup_boot_sector: u16,
-    reserved_0: [u8; 12],
-    drive_num: u8,
-    reserved_1: u8,
-    ext_sig: u8,
-    volume_id: u32,
-    volume_label: [u8; 11],
-    fs_type_label: [u8; 8],
+    pub(crate) sectors_per_fat_32: u32,
+    pub(crate) extended_flags: u16,
+    pub(crate) fs_version: u16,
+    pub(crate) root_dir_first_cluster: u32,
+    pub(crate) fs_info_sector: u16,
+    pub(crate) backup_boot_sector: u16,
+    pub(crate) reserved_0: [u8; 12],
+    pub(crate) drive_num: u8,
+    pub(crate) reserved_1: u8,
+    pub(crate) ext_sig: u8,
+    pub(crate) volume_id: u32,
+    pub(crate) volume_label: [u8; 11],
+    pub(crate) fs_type_label: [u8; 8],
 }
 
 impl BiosParameterBlock {
-    fn deserialize(rdr: &mut Read) -> io::Result<BiosParameterBlock> {
+    // Decodes BPB from boot sector
+    fn from_bytes(sector: &[u8; BOOT_SECTOR_SIZE]) -> io::Result<BiosParameterBlock> {
         let mut bpb: BiosParameterBlock = Default::default();
-        bpb.bytes_per_sector = rdr.read_u16::<LittleEndian>()?;
-        bpb.sectors_per_cluster = rdr.read_u8()?;
-        bpb.reserved_sectors = rdr.read_u16::<LittleEndian>()?;
-        bpb.fats = rdr.read_u8()?;
-        bpb.root_entries = rdr.read_u16::<LittleEndian>()?;
-        bpb.total_sectors_16 = rdr.read_u16::<LittleEndian>()?;
-        bpb.media = rdr.read_u8()?;
-        bpb.sectors_per_fat_16 = rdr.read_u16::<LittleEndian>()?;
-        bpb.sectors_per_track = rdr.read_u16::<LittleEndian>()?;
-        bpb.heads = rdr.read_u16::<LittleEndian>()?;
-        bpb.hidden_sectors = rdr.read_u32::<LittleEndian>()?;
-        bpb.total_sectors_32 = rdr.read_u32::<LittleEndian>()?;
+        bpb.bytes_per_sector = LittleEndian::read_u16(&sector[11..]);
+        bpb.sectors_per_cluster = sector[13];
+        bpb.reserved_sectors = LittleEndian::read_u16(&sector[14..]);
+        bpb.fats = sector[16];
+        bpb.root_entries = LittleEndian::read_u16(&sector[17..]);
+        bpb.total_sectors_16 = LittleEndian::read_u16(&sector[19..]);
+        bpb.media = sector[21];
+        bpb.sectors_per_fat_16 = LittleEndian::read_u16(&sector[22..]);
+        bpb.sectors_per_track = LittleEndian::read_u16(&sector[24..]);
+        bpb.heads = LittleEndian::read_u16(&sector[26..]);
+        bpb.hidden_sectors = LittleEndian::read_u32(&sector[28..]);
+        bpb.total_sectors_32 = LittleEndian::read_u32(&sector[32..]);
 
         // sanity checks
         if bpb.bytes_per_sector < 512 {
@@ -132,28 +625,24 @@ impl BiosParameterBlock {
             return Err(Error::new(ErrorKind::Other, "invalid fats value in BPB"));
         }
 
-        if bpb.sectors_per_fat_16 == 0 {
-            bpb.sectors_per_fat_32 = rdr.read_u32::<LittleEndian>()?;
-            bpb.extended_flags = rdr.read_u16::<LittleEndian>()?;
-            bpb.fs_version = rdr.read_u16::<LittleEndian>()?;
-            bpb.root_dir_first_cluster = rdr.read_u32::<LittleEndian>()?;
-            bpb.fs_info_sector = rdr.read_u16::<LittleEndian>()?;
-            bpb.backup_boot_sector = rdr.read_u16::<LittleEndian>()?;
-            rdr.read_exact(&mut bpb.reserved_0)?;
-            bpb.drive_num = rdr.read_u8()?;
-            bpb.reserved_1 = rdr.read_u8()?;
-            bpb.ext_sig = rdr.read_u8()?; // 0x29
-            bpb.volume_id = rdr.read_u32::<LittleEndian>()?;
-            rdr.read_exact(&mut bpb.volume_label)?;
-            rdr.read_exact(&mut bpb.fs_type_label)?;
+        let ext = if bpb.sectors_per_fat_16 == 0 {
+            bpb.sectors_per_fat_32 = LittleEndian::read_u32(&sector[36..]);
+            bpb.extended_flags = LittleEndian::read_u16(&sector[40..]);
+            bpb.fs_version = LittleEndian::read_u16(&sector[42..]);
+            bpb.root_dir_first_cluster = LittleEndian::read_u32(&sector[44..]);
+            bpb.fs_info_sector = LittleEndian::read_u16(&sector[48..]);
+            bpb.backup_boot_sector = LittleEndian::read_u16(&sector[50..]);
+            bpb.reserved_0.copy_from_slice(&sector[52..64]);
+            &sector[64..90]
         } else {
-            bpb.drive_num = rdr.read_u8()?;
-            bpb.reserved_1 = rdr.read_u8()?;
-            bpb.ext_sig = rdr.read_u8()?; // 0x29
-            bpb.volume_id = rdr.read_u32::<LittleEndian>()?;
-            rdr.read_exact(&mut bpb.volume_label)?;
-            rdr.read_exact(&mut bpb.fs_type_label)?;
-        }
+            &sector[36..62]
+        };
+        bpb.drive_num = ext[0];
+        bpb.reserved_1 = ext[1];
+        bpb.ext_sig = ext[2]; // 0x29
+        bpb.volume_id = LittleEndian::read_u32(&ext[3..]);
+        bpb.volume_label.copy_from_slice(&ext[7..18]);
+        bpb.fs_type_label.copy_from_slice(&ext[18..26]);
         // Strip non-ascii characters from volume label
         strip_non_ascii(&mut bpb.volume_label);
         if bpb.ext_sig != 0x29 {
@@ -165,6 +654,88 @@ impl BiosParameterBlock {
         Ok(bpb)
     }
 
+    fn total_sectors(&self) -> u32 {
+        if self.total_sectors_16 == 0 {
+            self.total_sectors_32
+        } else {
+            self.total_sectors_16 as u32
+        }
+    }
+
+    fn sectors_per_fat(&self) -> u32 {
+        if self.sectors_per_fat_16 == 0 {
+            self.sectors_per_fat_32
+        } else {
+            self.sectors_per_fat_16 as u32
+        }
+    }
+
+    // Checks if both BPBs describe the same volume with the same layout
+    fn same_volume(&self, other: &BiosParameterBlock) -> bool {
+        self.volume_id == other.volume_id
+            && self.bytes_per_sector == other.bytes_per_sector
+            && self.sectors_per_cluster == other.sectors_per_cluster
+            && self.reserved_sectors == other.reserved_sectors
+            && self.fats == other.fats
+            && self.root_entries == other.root_entries
+            && self.total_sectors() == other.total_sectors()
+            && self.sectors_per_fat() == other.sectors_per_fat()
+            && self.root_dir_first_cluster == other.root_dir_first_cluster
+            && self.fs_info_sector == other.fs_info_sector
+    }
+
+    fn root_dir_sectors(&self) -> u32 {
+        let root_dir_bytes = self.root_entries as u32 * DIR_ENTRY_SIZE as u32;
+        (root_dir_bytes + (self.bytes_per_sector as u32 - 1)) / self.bytes_per_sector as u32
+    }
+
+    fn first_data_sector(&self) -> u32 {
+        // saturating arithmetic keeps garbage BPB values from overflowing
+        (self.fats as u32)
+            .saturating_mul(self.sectors_per_fat())
+            .saturating_add(self.reserved_sectors as u32 + self.root_dir_sectors())
+    }
+
+    fn total_clusters(&self) -> u32 {
+        let data_sectors = self.total_sectors().saturating_sub(self.first_data_sector());
+        data_sectors / self.sectors_per_cluster as u32
+    }
+
+    // Encodes BPB into boot sector leaving other bytes untouched
+    fn to_bytes(&self, sector: &mut [u8; BOOT_SECTOR_SIZE]) {
+        LittleEndian::write_u16(&mut sector[11..], self.bytes_per_sector);
+        sector[13] = self.sectors_per_cluster;
+        LittleEndian::write_u16(&mut sector[14..], self.reserved_sectors);
+        sector[16] = self.fats;
+        LittleEndian::write_u16(&mut sector[17..], self.root_entries);
+        LittleEndian::write_u16(&mut sector[19..], self.total_sectors_16);
+        sector[21] = self.media;
+        LittleEndian::write_u16(&mut sector[22..], self.sectors_per_fat_16);
+        LittleEndian::write_u16(&mut sector[24..], self.sectors_per_track);
+        LittleEndian::write_u16(&mut sector[26..], self.heads);
+        LittleEndian::write_u32(&mut sector[28..], self.hidden_sectors);
+        LittleEndian::write_u32(&mut sector[32..], self.total_sectors_32);
+
+        let ext = if self.sectors_per_fat_16 == 0 {
+            LittleEndian::write_u32(&mut sector[36..], self.sectors_per_fat_32);
+            LittleEndian::write_u16(&mut sector[40..], self.extended_flags);
+            LittleEndian::write_u16(&mut sector[42..], self.fs_version);
+            LittleEndian::write_u32(&mut sector[44..], self.root_dir_first_cluster);
+            LittleEndian::write_u16(&mut sector[48..], self.fs_info_sector);
+            LittleEndian::write_u16(&mut sector[50..], self.backup_boot_sector);
+            sector[52..64].copy_from_slice(&self.reserved_0);
+            &mut sector[64..90]
+        } else {
+            &mut sector[36..62]
+        };
+        ext[0] = self.drive_num;
+        ext[1] = self.reserved_1;
+        ext[2] = self.ext_sig;
+        LittleEndian::write_u32(&mut ext[3..], self.volume_id);
+        ext[7..18].copy_from_slice(&self.volume_label);
+        ext[18..26].copy_from_slice(&self.fs_type_label);
+    }
+
     fn mirroring_enabled(&self) -> bool {
         self.extended_flags & 0x80 == 0
     }
@@ -181,56 +752,580 @@ impl BiosParameterBlock {
     }
 }
 
+// Boot sector without boot code - it is never used by the library so it is skipped when reading
+// and zeroed when writing to keep the structure small
 #[allow(dead_code)]
-struct BootRecord {
-    bootjmp: [u8; 3],
-    oem_name: [u8; 8],
-    bpb: BiosParameterBlock,
-    boot_code: [u8; 448],
-    boot_sig: [u8; 2],
+#[derive(Default)]
+pub(crate) struct BootRecord {
+    pub(crate) bootjmp: [u8; 3],
+    pub(crate) oem_name: [u8; 8],
+    pub(crate) bpb: BiosParameterBlock,
+    pub(crate) boot_sig: [u8; 2],
 }
 
 impl BootRecord {
-    fn deserialize(rdr: &mut Read) -> io::Result<BootRecord> {
+    pub(crate) fn from_bytes(sector: &[u8; BOOT_SECTOR_SIZE]) -> io::Result<BootRecord> {
         let mut boot: BootRecord = Default::default();
-        rdr.read_exact(&mut boot.bootjmp)?;
-        rdr.read_exact(&mut boot.oem_name)?;
-        boot.bpb = BiosParameterBlock::deserialize(rdr)?;
+        boot.bootjmp.copy_from_slice(&sector[0..3]);
+        boot.oem_name.copy_from_slice(&sector[3..11]);
+        boot.bpb = BiosParameterBlock::from_bytes(sector)?;
+        boot.boot_sig.copy_from_slice(&sector[510..512]);
+        Ok(boot)
+    }
 
-        if boot.bpb.sectors_per_fat_16 == 0 {
-            rdr.read_exact(&mut boot.boot_code[0..420])?;
-        } else {
-            rdr.read_exact(&mut boot.boot_code[0..448])?;
+    // Encodes boot sector with zeroed boot code
+    pub(crate) fn to_bytes(&self, sector: &mut [u8; BOOT_SECTOR_SIZE]) {
+        *sector = [0; BOOT_SECTOR_SIZE];
+        sector[0..3].copy_from_slice(&self.bootjmp);
+        sector[3..11].copy_from_slice(&self.oem_name);
+        self.bpb.to_bytes(sector);
+        sector[510..512].copy_from_slice(&self.boot_sig);
+    }
+
+    fn check_signature(&self) -> io::Result<()> {
+        if self.boot_sig != [0x55, 0xAA] {
+            return Err(Error::new(ErrorKind::Other, "invalid signature"));
         }
-        rdr.read_exact(&mut boot.boot_sig)?;
-        Ok(boot)
+        Ok(())
+    }
+
+    pub(crate) fn deserialize(rdr: &mut Read) -> io::Result<BootRecord> {
+        let mut sector = [0u8; BOOT_SECTOR_SIZE];
+        rdr.read_exact(&mut sector)?;
+        Self::from_bytes(&sector)
+    }
+
+    pub(crate) fn serialize(&self, wrt: &mut Write) -> io::Result<()> {
+        let mut sector = [0u8; BOOT_SECTOR_SIZE];
+        self.to_bytes(&mut sector);
+        wrt.write_all(&sector)
     }
 }
 
-impl Default for BootRecord {
-    fn default() -> BootRecord {
-        BootRecord {
-            bootjmp: Default::default(),
-            oem_name: Default::default(),
-            bpb: Default::default(),
-            boot_code: [0; 448],
-            boot_sig: Default::default(),
+#[derive(Default, Debug, Clone)]
+pub(crate) struct FsInfoSector {
+    pub(crate) free_cluster_count: Option<u32>,
+    pub(crate) next_free_cluster: Option<u32>,
+    pub(crate) dirty: bool,
+}
+
+impl FsInfoSector {
+    const LEAD_SIG: u32 = 0x41615252;
+    const STRUC_SIG: u32 = 0x61417272;
+    const TRAIL_SIG: u32 = 0xAA550000;
+
+    // Decodes FSInfo sector or returns None if its signatures are invalid
+    fn from_bytes(sector: &[u8; BOOT_SECTOR_SIZE]) -> Option<FsInfoSector> {
+        let lead_sig = LittleEndian::read_u32(&sector[0..]);
+        let struc_sig = LittleEndian::read_u32(&sector[484..]);
+        let free_cluster_count = match LittleEndian::read_u32(&sector[488..]) {
+            0xFFFFFFFF => None,
+            n => Some(n),
+        };
+        let next_free_cluster = match LittleEndian::read_u32(&sector[492..]) {
+            0xFFFFFFFF => None,
+            n => Some(n),
+        };
+        let trail_sig = LittleEndian::read_u32(&sector[508..]);
+        if lead_sig != Self::LEAD_SIG
+            || struc_sig != Self::STRUC_SIG
+            || trail_sig != Self::TRAIL_SIG
+        {
+            return None;
         }
+        Some(FsInfoSector {
+            free_cluster_count,
+            next_free_cluster,
+            dirty: false,
+        })
+    }
+
+    fn to_bytes(&self, sector: &mut [u8; BOOT_SECTOR_SIZE]) {
+        *sector = [0; BOOT_SECTOR_SIZE];
+        LittleEndian::write_u32(&mut sector[0..], Self::LEAD_SIG);
+        LittleEndian::write_u32(&mut sector[484..], Self::STRUC_SIG);
+        LittleEndian::write_u32(&mut sector[488..], self.free_cluster_count.unwrap_or(0xFFFFFFFF));
+        LittleEndian::write_u32(&mut sector[492..], self.next_free_cluster.unwrap_or(0xFFFFFFFF));
+        LittleEndian::write_u32(&mut sector[508..], Self::TRAIL_SIG);
+    }
+
+    fn deserialize(rdr: &mut Read) -> io::Result<Option<FsInfoSector>> {
+        let mut sector = [0u8; BOOT_SECTOR_SIZE];
+        rdr.read_exact(&mut sector)?;
+        Ok(Self::from_bytes(&sector))
+    }
+
+    pub(crate) fn serialize(&self, wrt: &mut Write) -> io::Result<()> {
+        let mut sector = [0u8; BOOT_SECTOR_SIZE];
+        self.to_bytes(&mut sector);
+        wrt.write_all(&sector)
+    }
+
+    fn validate(&mut self, total_clusters: u32) {
+        // values are only a hint so drop them if they are out of range
+        if self.free_cluster_count.map_or(false, |n| n > total_clusters) {
+            self.free_cluster_count = None;
+        }
+        let max_cluster = total_clusters + 2;
+        if self.next_free_cluster.map_or(false, |n| n < 2 || n >= max_cluster) {
+            self.next_free_cluster = None;
+        }
+    }
+
+    fn add_free_clusters(&mut self, free_clusters: i32) {
+        match self.free_cluster_count {
+            Some(n) => {
+                self.free_cluster_count = Some((n as i32 + free_clusters) as u32);
+                self.dirty = true;
+            }
+            None => {}
+        }
+    }
+
+    fn forget_free_cluster_count(&mut self) {
+        self.free_cluster_count = None;
+        self.dirty = true;
     }
+
+    fn set_free_cluster_count(&mut self, free_cluster_count: u32) {
+        self.free_cluster_count = Some(free_cluster_count);
+        self.dirty = true;
+    }
+
+    fn set_next_free_cluster(&mut self, cluster: u32) {
+        self.next_free_cluster = Some(cluster);
+        self.dirty = true;
+    }
+}
+
+/// A policy of handling short file name collisions.
+///
+/// Short name is generated from the long name and different long names can produce the same
+/// short name (e.g. `LONGFILE.TXT` for both `longfile1.txt` and `longfile2.txt`).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ShortNameCollision {
+    /// Fail with `ErrorKind::AlreadyExists` error.
+    Error,
+    /// Append Windows-style numeric tail (e.g. `LONGFI~1.TXT`).
+    NumericTail,
+    /// Replace end of the name with a hash of the long name and append a tail (e.g.
+    /// `LO1F3A~1.TXT`) like Windows NT does. Generated name does not depend on order in which
+    /// files were created so it is suited for reproducible image builds.
+    HashTail,
+}
+
+/// A set of rules used when on-disk structures are parsed.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MountProfile {
+    /// Reject any deviation from the specification.
+    ///
+    /// Mounting fails if boot sector fields are out of spec (jump instruction, sector and cluster
+    /// sizes, media descriptor, reserved fields, FAT too small for the number of clusters),
+    /// FSInfo signatures are invalid or reserved FAT entries do not match the media descriptor.
+    /// Cluster chains containing free, bad or out of range clusters and orphaned long name
+    /// entries are reported as `ErrorKind::CorruptedFile` errors.
+    Strict,
+    /// Accept deviations which do not prevent reading the volume.
+    ///
+    /// Volumes written by quirky implementations (e.g. cameras and phones) are mounted and
+    /// problems are logged or ignored: invalid FSInfo sectors are not used, cluster chains end on
+    /// a free or bad cluster and orphaned long name entries are skipped.
+    Relaxed,
+}
+
+#[cfg(feature = "alloc")]
+static DEFAULT_BUFFER_PROVIDER: HeapBufferProvider = HeapBufferProvider::unlimited();
+
+/// A FAT filesystem mount options.
+///
+/// Options are specified as an argument for `FileSystem::new` method.
+#[derive(Copy, Clone)]
+pub struct FsOptions {
+    pub(crate) time_provider: &'static TimeProvider,
+    pub(crate) buffer_provider: &'static BufferProvider,
+    pub(crate) cancellation_token: &'static CancellationToken,
+    pub(crate) max_file_size: u32,
+    pub(crate) max_name_length: u32,
+    pub(crate) max_path_depth: u32,
+    pub(crate) max_dir_entries: u32,
+    pub(crate) short_name_collision: ShortNameCollision,
+    pub(crate) profile: MountProfile,
+    pub(crate) retry_policy: RetryPolicy,
+    pub(crate) media_id_provider: &'static MediaIdProvider,
+    pub(crate) update_accessed_date: bool,
+    pub(crate) allow_truncated: bool,
+    pub(crate) paranoid: bool,
+    pub(crate) end_of_chain_marker: u8,
+    #[cfg(feature = "alloc")]
+    pub(crate) lazy_fat_writes: bool,
+    #[cfg(feature = "crc")]
+    pub(crate) metadata_crc: bool,
+    #[cfg(feature = "crc")]
+    pub(crate) verify_metadata_crc: bool,
 }
 
-pub(crate) type FileSystemRef<'a, 'b> = &'a FileSystem<'b>;
+impl FsOptions {
+    /// Creates `FsOptions` struct with default options.
+    pub fn new() -> Self {
+        FsOptions {
+            time_provider: &NullTimeProvider,
+            #[cfg(feature = "alloc")]
+            buffer_provider: &DEFAULT_BUFFER_PROVIDER,
+            #[cfg(not(feature = "alloc"))]
+            buffer_provider: &NullBufferProvider,
+            cancellation_token: &NullCancellationToken,
+            max_file_size: MAX_FILE_SIZE,
+            max_name_length: MAX_NAME_LENGTH,
+            max_path_depth: u32::MAX,
+            max_dir_entries: u32::MAX,
+            short_name_collision: ShortNameCollision::NumericTail,
+            profile: MountProfile::Relaxed,
+            retry_policy: RetryPolicy::new(0),
+            media_id_provider: &NullMediaIdProvider,
+            update_accessed_date: false,
+            allow_truncated: false,
+            paranoid: false,
+            end_of_chain_marker: MAX_END_OF_CHAIN_MARKER,
+            #[cfg(feature = "alloc")]
+            lazy_fat_writes: false,
+            #[cfg(feature = "crc")]
+            metadata_crc: false,
+            #[cfg(feature = "crc")]
+            verify_metadata_crc: true,
+        }
+    }
+
+    /// Changes default time provider used for setting file timestamps.
+    pub fn time_provider(mut self, time_provider: &'static TimeProvider) -> Self {
+        self.time_provider = time_provider;
+        self
+    }
+
+    /// Changes provider of buffers used by caches.
+    ///
+    /// Default is `HeapBufferProvider` without a budget limit if `alloc` feature is enabled and
+    /// `NullBufferProvider` otherwise.
+    pub fn buffer_provider(mut self, buffer_provider: &'static BufferProvider) -> Self {
+        self.buffer_provider = buffer_provider;
+        self
+    }
+
+    /// Changes end of chain marker written to the FAT. `marker` selects one of 8 values from the
+    /// range returned by `FatType::end_of_chain` - 0 is the first one (e.g. 0xFFF8 on FAT16) and 7
+    /// the last one (e.g. 0xFFFF). Markers from the whole range are accepted when reading
+    /// regardless of this option. It allows byte-exact comparison with images created by other
+    /// implementations.
+    ///
+    /// Default is 7. Bigger values are ignored.
+    pub fn end_of_chain_marker(mut self, marker: u8) -> Self {
+        self.end_of_chain_marker = cmp::min(marker, MAX_END_OF_CHAIN_MARKER);
+        self
+    }
+
+    /// Changes token polled by long running operations (FAT, directory and cluster chain scans).
+    /// Operations fail with `ErrorKind::Interrupted` error once the token reports cancellation.
+    ///
+    /// Default is `NullCancellationToken` which never cancels.
+    pub fn cancellation_token(mut self, cancellation_token: &'static CancellationToken) -> Self {
+        self.cancellation_token = cancellation_token;
+        self
+    }
+
+    /// Changes maximal size of files. Writes that would make a file bigger fail with
+    /// `ErrorKind::FileTooLarge` error.
+    ///
+    /// Default is the limit imposed by FAT format: 4 GiB - 1 byte.
+    pub fn max_file_size(mut self, max_file_size: u32) -> Self {
+        self.max_file_size = max_file_size;
+        self
+    }
+
+    /// Changes maximal length of file names in characters. Creating or renaming a file with a
+    /// longer name fails with `ErrorKind::NameTooLong` error.
+    ///
+    /// Default is the limit imposed by FAT format: 255 characters. Bigger values are ignored.
+    pub fn max_name_length(mut self, max_name_length: u32) -> Self {
+        self.max_name_length = cmp::min(max_name_length, MAX_NAME_LENGTH);
+        self
+    }
+
+    /// Changes maximal number of components in paths passed to `Dir` methods. Using a longer path
+    /// fails with `ErrorKind::PathTooDeep` error.
+    ///
+    /// Default is no limit.
+    pub fn max_path_depth(mut self, max_path_depth: u32) -> Self {
+        self.max_path_depth = max_path_depth;
+        self
+    }
+
+    /// Changes maximal number of 32-byte entry slots in a directory. Creating an entry that would
+    /// not fit fails with `ErrorKind::TooManyEntries` error. Iteration fails with the same error
+    /// when an entry other than the end marker is found beyond the limit, so scanning a huge or
+    /// corrupted directory is bounded.
+    ///
+    /// Default is no limit other than `MAX_DIR_ENTRIES` from FAT specification.
+    pub fn max_dir_entries(mut self, max_dir_entries: u32) -> Self {
+        self.max_dir_entries = max_dir_entries;
+        self
+    }
+
+    /// Changes rules used when on-disk structures are parsed.
+    ///
+    /// Default is `MountProfile::Relaxed`.
+    pub fn profile(mut self, profile: MountProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Changes handling of short file name collisions.
+    ///
+    /// Default is `ShortNameCollision::NumericTail`.
+    pub fn short_name_collision(mut self, short_name_collision: ShortNameCollision) -> Self {
+        self.short_name_collision = short_name_collision;
+        self
+    }
+
+    /// Changes policy of retrying failed device reads and writes.
+    ///
+    /// Default is `RetryPolicy::new(0)` - errors are returned immediately.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Changes source of removable medium identifiers used to detect media changes.
+    ///
+    /// Default is `NullMediaIdProvider` - changes are not detected.
+    pub fn media_id_provider(mut self, media_id_provider: &'static MediaIdProvider) -> Self {
+        self.media_id_provider = media_id_provider;
+        self
+    }
+
+    /// Enables updating of file access date when file is read (disabled by default).
+    ///
+    /// FAT stores only the date of last access so the directory entry is modified at most once a
+    /// day for every file. Change is written when file is flushed or dropped. Keep it disabled on
+    /// flash devices if access dates are not needed.
+    pub fn update_accessed_date(mut self, update_accessed_date: bool) -> Self {
+        self.update_accessed_date = update_accessed_date;
+        self
+    }
+
+    /// Allows mounting volumes stored on devices smaller than the volume size in BPB (disabled
+    /// by default).
+    ///
+    /// Such devices are usually truncated images. By default mounting them fails with
+    /// `ErrorKind::TruncatedVolume` error. If enabled the filesystem is mounted read-only: all
+    /// writes and reads of sectors missing on the device fail with `ErrorKind::TruncatedVolume`.
+    pub fn allow_truncated(mut self, allow_truncated: bool) -> Self {
+        self.allow_truncated = allow_truncated;
+        self
+    }
+
+    /// Enables paranoid mode (disabled by default).
+    ///
+    /// In paranoid mode `Dir::open_file` walks the whole cluster chain of the file before
+    /// returning it. Opening fails with `ErrorKind::CorruptedFile` error if the chain contains
+    /// free, bad or out of range clusters, contains a loop or its length does not match the file
+    /// size. The walk is bounded by the number of clusters on the volume. It makes opening big
+    /// files slower but corruption is reported when file is opened instead of in the middle of
+    /// reading it.
+    pub fn paranoid(mut self, paranoid: bool) -> Self {
+        self.paranoid = paranoid;
+        self
+    }
+
+    /// Enables lazy FAT writes (disabled by default).
+    ///
+    /// Modified FAT sectors are kept in memory and written to all FAT copies in ascending order
+    /// by `File::flush`, `FileSystem::flush` and when filesystem is unmounted. It greatly reduces
+    /// number of device writes when big files are created, but changes made since the last flush
+    /// are lost if the device is removed. Sector buffers are taken from the buffer provider and
+    /// when it has none left or 32 sectors are cached FAT sectors are written directly to the
+    /// device.
+    #[cfg(feature = "alloc")]
+    pub fn lazy_fat_writes(mut self, lazy_fat_writes: bool) -> Self {
+        self.lazy_fat_writes = lazy_fat_writes;
+        self
+    }
+
+    /// Enables CRC protection of filesystem metadata (disabled by default, requires `crc` feature).
+    ///
+    /// Checksums of the FAT and directories are stored in a hidden `FATCRC.SYS` file in the root
+    /// directory. The file is updated when filesystem is flushed or unmounted and checksums are
+    /// verified when filesystem is mounted (see `verify_metadata_crc`). Volumes without the file
+    /// are mounted normally.
+    #[cfg(feature = "crc")]
+    pub fn metadata_crc(mut self, metadata_crc: bool) -> Self {
+        self.metadata_crc = metadata_crc;
+        self
+    }
+
+    /// Enables verification of metadata checksums when filesystem is mounted (enabled by default).
+    ///
+    /// Only used if CRC protection is enabled by `metadata_crc`. If checksums are invalid mounting
+    /// fails. Disabling verification allows mounting volumes modified by other drivers -
+    /// checksums are updated when filesystem is unmounted.
+    #[cfg(feature = "crc")]
+    pub fn verify_metadata_crc(mut self, verify_metadata_crc: bool) -> Self {
+        self.verify_metadata_crc = verify_metadata_crc;
+        self
+    }
+}
+
+pub(crate) type FileSystemRef<'a, IO> = &'a FileSystem<IO>;
+
+// Location of root directory computed once when filesystem is mounted
+#[derive(Copy, Clone, Debug)]
+enum RootDirLocation {
+    // FAT12/FAT16 fixed region (offset and size in bytes)
+    Region(u64, u64),
+    // FAT32 cluster chain
+    Cluster(Cluster),
+}
 
 /// FAT filesystem main struct.
-pub struct FileSystem<'a> {
-    pub(crate) disk: RefCell<&'a mut ReadWriteSeek>,
+///
+/// `IO` is the storage device. It is owned by the filesystem, so a reference to a device (e.g.
+/// `&mut Cursor<&mut [u8]>`) can be passed to keep using it after the filesystem is dropped.
+pub struct FileSystem<IO: ReadWriteSeek> {
+    pub(crate) disk: RefCell<Disk<IO>>,
+    pub(crate) options: FsOptions,
     fat_type: FatType,
+    pub(crate) fat_ops: FatOps<IO>,
+    bpb: BiosParameterBlock,
+    first_data_sector: u32,
+    root_dir_location: RootDirLocation,
+    pub(crate) total_clusters: u32,
+    fs_info: RefCell<FsInfoSector>,
+    free_scan: Cell<FreeSpaceScan>,
+    // label from BPB or from root directory entry if BPB has no label
+    volume_label: [u8; 11],
+    // set when mounting succeeded - volumes rejected while mounting are not modified on drop
+    mounted: bool,
+    #[cfg(feature = "alloc")]
+    fat_cache: RefCell<FatCache>,
+    // directory entries of open files
+    #[cfg(feature = "alloc")]
+    pub(crate) open_entries: RefCell<OpenEntries>,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> FileSystem<OverlayDisk<'a>> {
+    /// Creates new filesystem object on top of read-only `base` device.
+    ///
+    /// All modifications are stored in `overlay` and the base device is never written. It allows
+    /// tests and simulations to modify golden images without touching them.
+    pub fn new_overlay<T: ReadSeek>(
+        base: &'a mut T,
+        overlay: &'a mut OverlayStore,
+        options: FsOptions,
+    ) -> io::Result<Self> {
+        Self::new(OverlayDisk::new(base, overlay), options)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl FileSystem<MemoryDisk> {
+    /// Creates new filesystem object on a freshly formatted volume of `size` bytes kept in memory.
+    ///
+    /// It is a convenience helper for tests and temporary storage - contents are lost when the
+    /// filesystem object is dropped.
+    pub fn new_in_memory(size: u64, format_options: FormatOptions) -> io::Result<Self> {
+        if size > usize::max_value() as u64 {
+            return Err(Error::new(ErrorKind::InvalidInput, "volume too big"));
+        }
+        let mut disk = MemoryDisk::new(size as usize);
+        format_volume(&mut disk, format_options)?;
+        Self::new(disk, FsOptions::new())
+    }
+}
+
+// Functions independent of the storage type. They are implemented for a single type so they can
+// be called as `FileSystem::f` without type annotations.
+impl<'a> FileSystem<&'a mut ReadWriteSeek> {
+    /// Reads basic information about a volume without mounting it.
+    ///
+    /// Only the boot sector is read. The device is positioned at its beginning afterwards, so it
+    /// can be passed to `FileSystem::new` if the volume turns out to be the expected one.
+    pub fn peek_volume_info(disk: &mut ReadSeek) -> io::Result<VolumeInfo> {
+        let mut sector = [0u8; BOOT_SECTOR_SIZE];
+        disk.seek(SeekFrom::Start(0))?;
+        disk.read_exact(&mut sector)?;
+        disk.seek(SeekFrom::Start(0))?;
+        VolumeInfo::from_boot_sector(&sector)
+    }
+}
+
+// Volume parameters read from boot sector and FSInfo sector when the filesystem is mounted
+struct MountedVolume {
     bpb: BiosParameterBlock,
+    fat_type: FatType,
     first_data_sector: u32,
-    root_dir_sectors: u32,
+    root_dir_location: RootDirLocation,
+    total_clusters: u32,
+    fs_info: FsInfoSector,
 }
 
-impl<'a> FileSystem<'a> {
+fn read_volume<IO: ReadWriteSeek>(
+    disk: &mut Disk<IO>,
+    options: &FsOptions,
+) -> io::Result<MountedVolume> {
+    let bpb = read_boot_sector(disk, options.profile)?;
+    let root_dir_sectors = bpb.root_dir_sectors();
+    let first_data_sector = bpb.first_data_sector();
+    let total_clusters = bpb.total_clusters();
+    let fat_type = FatType::from_clusters(total_clusters);
+
+    let volume_size = bpb.total_sectors() as u64 * bpb.bytes_per_sector as u64;
+    let device_size = disk.device_size()?;
+    if device_size < volume_size {
+        if !options.allow_truncated {
+            return Err(Error::new(ErrorKind::TruncatedVolume, "device is smaller than volume"));
+        }
+        warn!("device is smaller than volume, mounting read-only");
+        disk.truncated = true;
+    }
+
+    // Read FSInfo sector (FAT32 only)
+    let mut fs_info = if fat_type == FatType::Fat32 && bpb.fs_info_sector != 0 {
+        let fs_info_offset = bpb.fs_info_sector as u64 * bpb.bytes_per_sector as u64;
+        disk.seek(SeekFrom::Start(fs_info_offset))?;
+        match FsInfoSector::deserialize(disk)? {
+            Some(fs_info) => fs_info,
+            None if options.profile == MountProfile::Strict => {
+                return Err(Error::new(ErrorKind::Other, "invalid FSInfo signature"));
+            }
+            // FSInfo contents are only a hint - ignore invalid sector
+            None => FsInfoSector::default(),
+        }
+    } else {
+        FsInfoSector::default()
+    };
+    fs_info.validate(total_clusters);
+
+    let root_dir_location = match fat_type {
+        FatType::Fat12 | FatType::Fat16 => {
+            let bytes_per_sector = bpb.bytes_per_sector as u64;
+            let root_dir_sector = (first_data_sector - root_dir_sectors) as u64;
+            RootDirLocation::Region(
+                root_dir_sector * bytes_per_sector,
+                root_dir_sectors as u64 * bytes_per_sector,
+            )
+        }
+        FatType::Fat32 => RootDirLocation::Cluster(Cluster(bpb.root_dir_first_cluster)),
+    };
+    Ok(MountedVolume {
+        bpb,
+        fat_type,
+        first_data_sector,
+        root_dir_location,
+        total_clusters,
+        fs_info,
+    })
+}
+
+impl<IO: ReadWriteSeek> FileSystem<IO> {
     /// Creates new filesystem object instance.
     ///
     /// Supplied disk parameter cannot be seeked. If there is a need to read a fragment of disk image (e.g. partition)
@@ -238,47 +1333,115 @@ impl<'a> FileSystem<'a> {
     ///
     /// Note: creating multiple filesystem objects with one underlying device/disk image can
     /// cause filesystem corruption.
-    pub fn new<T: ReadWriteSeek>(disk: &'a mut T) -> io::Result<FileSystem<'a>> {
+    pub fn new(mut disk: IO, options: FsOptions) -> io::Result<Self> {
         // Make sure given image is not seeked
         debug_assert!(disk.seek(SeekFrom::Current(0))? == 0);
+        let mut disk = Disk::new(disk, options.retry_policy, options.media_id_provider);
+        let volume = read_volume(&mut disk, &options)?;
+        let volume_label = volume.bpb.volume_label;
+        let mut fs = FileSystem {
+            disk: RefCell::new(disk),
+            options,
+            fat_type: volume.fat_type,
+            fat_ops: FatOps::new(volume.fat_type),
+            bpb: volume.bpb,
+            first_data_sector: volume.first_data_sector,
+            root_dir_location: volume.root_dir_location,
+            total_clusters: volume.total_clusters,
+            fs_info: RefCell::new(volume.fs_info),
+            free_scan: Cell::new(FreeSpaceScan::default()),
+            volume_label,
+            mounted: false,
+            #[cfg(feature = "alloc")]
+            fat_cache: RefCell::new(FatCache::new(options.buffer_provider)),
+            #[cfg(feature = "alloc")]
+            open_entries: RefCell::new(OpenEntries::new()),
+        };
+        fs.check_mounted_volume()?;
+        #[cfg(feature = "crc")]
+        {
+            let verify = options.metadata_crc && options.verify_metadata_crc;
+            if verify && fs.verify_metadata_crc()? == Some(false) {
+                // fs is not mounted yet so drop does not seal corrupted metadata with new
+                // checksums
+                return Err(Error::new(ErrorKind::Other, "metadata checksum mismatch"));
+            }
+        }
+        fs.mounted = true;
+        Ok(fs)
+    }
 
-        // Read boot sector
-        let bpb = {
-            let boot = BootRecord::deserialize(disk)?;
-            if boot.boot_sig != [0x55, 0xAA] {
-                return Err(Error::new(ErrorKind::Other, "invalid signature"));
+    // Checks parts of the volume read after the boot sector and FSInfo sector
+    fn check_mounted_volume(&mut self) -> io::Result<()> {
+        if !self.reserved_fat_entries_valid()? {
+            if self.options.profile == MountProfile::Strict {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    "reserved FAT entries do not match media descriptor",
+                ));
             }
-            boot.bpb
-        };
+            warn!("reserved FAT entries do not match media descriptor");
+        }
+        // some systems (e.g. older Windows versions) set label only in the root directory
+        if label_is_empty(&self.volume_label) {
+            match self.read_volume_label_entry()? {
+                Some(label) => self.volume_label = label,
+                None => {}
+            }
+        }
+        Ok(())
+    }
 
-        let total_sectors = if bpb.total_sectors_16 == 0 {
-            bpb.total_sectors_32
-        } else {
-            bpb.total_sectors_16 as u32
-        };
-        let sectors_per_fat = if bpb.sectors_per_fat_16 == 0 {
-            bpb.sectors_per_fat_32
-        } else {
-            bpb.sectors_per_fat_16 as u32
+    /// Mounts the volume again, e.g. after `ErrorKind::MediaChanged` error.
+    ///
+    /// Boot sector and FSInfo sector are read again and all cached state (FAT cache, free space
+    /// counters, volume label, I/O error and poisoned flags) is reset, so a `FileSystem` object
+    /// kept by the application (e.g. in a static variable) can be used with the new medium.
+    /// Medium identifier is read again from `MediaIdProvider`. Pending changes are discarded - call
+    /// `flush` first if the medium was not changed.
+    ///
+    /// If mounting fails all operations fail with `ErrorKind::MediaChanged` error until `remount`
+    /// succeeds.
+    pub fn remount(&mut self) -> io::Result<()> {
+        let r = self.remount_internal();
+        if r.is_err() {
+            self.disk.get_mut().media_changed = true;
+        }
+        r
+    }
+
+    fn remount_internal(&mut self) -> io::Result<()> {
+        #[cfg(feature = "alloc")]
+        self.fat_cache.get_mut().clear();
+        self.free_scan.set(FreeSpaceScan::default());
+        let volume = {
+            let disk = self.disk.get_mut();
+            disk.reset();
+            disk.seek(SeekFrom::Start(0))?;
+            read_volume(disk, &self.options)?
         };
-        let root_dir_bytes = bpb.root_entries as u32 * DIR_ENTRY_SIZE as u32;
-        let root_dir_sectors =
-            (root_dir_bytes + (bpb.bytes_per_sector as u32 - 1)) / bpb.bytes_per_sector as u32;
-        let first_data_sector =
-            bpb.reserved_sectors as u32 + (bpb.fats as u32 * sectors_per_fat) + root_dir_sectors;
-        let fat_sectors = bpb.fats as u32 * sectors_per_fat;
-        let data_sectors =
-            total_sectors - (bpb.reserved_sectors as u32 + fat_sectors + root_dir_sectors as u32);
-        let total_clusters = data_sectors / bpb.sectors_per_cluster as u32;
-        let fat_type = FatType::from_clusters(total_clusters);
-
-        Ok(FileSystem {
-            disk: RefCell::new(disk),
-            fat_type,
-            bpb: bpb,
-            first_data_sector,
-            root_dir_sectors,
-        })
+        self.fat_type = volume.fat_type;
+        self.fat_ops = FatOps::new(volume.fat_type);
+        self.bpb = volume.bpb;
+        self.first_data_sector = volume.first_data_sector;
+        self.root_dir_location = volume.root_dir_location;
+        self.total_clusters = volume.total_clusters;
+        *self.fs_info.get_mut() = volume.fs_info;
+        self.volume_label = self.bpb.volume_label;
+        self.check_mounted_volume()?;
+        #[cfg(feature = "crc")]
+        {
+            let verify = self.options.metadata_crc && self.options.verify_metadata_crc;
+            if verify && self.verify_metadata_crc()? == Some(false) {
+                return Err(Error::new(ErrorKind::Other, "metadata checksum mismatch"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks if volume serial number (volume ID) is equal to `serial`.
+    pub fn matches_serial(&self, serial: u32) -> bool {
+        self.bpb.volume_id == serial
     }
 
     /// Returns type of used File Allocation Table (FAT).
@@ -291,51 +1454,180 @@ impl<'a> FileSystem<'a> {
         self.bpb.volume_id
     }
 
-    /// Returns volume label from BPB in Boot Sector.
+    /// Returns maximal size of a file on this filesystem (configured in `FsOptions`).
+    pub fn max_file_size(&self) -> u32 {
+        self.options.max_file_size
+    }
+
+    /// Returns volume label without trailing spaces.
     ///
-    /// Note: File with VOLUME_ID attribute in root directory is ignored by this library.
-    /// Only label from BPB is used.
+    /// Label from BPB in Boot Sector is used. If BPB has no label (it is empty or "NO NAME"), the
+    /// entry with VOLUME_ID attribute in root directory is used instead - it is read once when the
+    /// filesystem is mounted.
     pub fn volume_label(&self) -> &str {
-        str::from_utf8(&self.bpb.volume_label)
-            .unwrap_or("")
-            .trim_right()
+        str::from_utf8(&self.volume_label).unwrap_or("").trim_right()
     }
 
-    /// Returns root directory object allowing futher penetration of filesystem structure.
-    pub fn root_dir<'b>(&'b self) -> Dir<'b, 'a> {
-        let root_rdr = {
-            match self.fat_type {
-                FatType::Fat12 | FatType::Fat16 => DirRawStream::Root(DiskSlice::from_sectors(
-                    self.first_data_sector - self.root_dir_sectors,
-                    self.root_dir_sectors,
-                    1,
-                    self,
-                )),
-                _ => {
-                    DirRawStream::File(File::new(Some(self.bpb.root_dir_first_cluster), None, self))
-                }
+    /// Changes volume label.
+    ///
+    /// Label is converted to upper case and stored in BPB of the Boot Sector (and its backup on
+    /// FAT32 volumes) and in the root directory entry with VOLUME_ID attribute, which is created
+    /// if missing. Empty label removes the root directory entry and sets BPB label to "NO NAME".
+    ///
+    /// Fails with `ErrorKind::InvalidInput` if label is longer than 11 characters or contains
+    /// characters not allowed in short names. BPB is left unchanged if the volume has no extended
+    /// boot signature.
+    pub fn set_volume_label(&mut self, label: &str) -> io::Result<()> {
+        let raw_label = encode_volume_label(label)?;
+        let bpb_label = match raw_label {
+            Some(l) => l,
+            None => *b"NO NAME    ",
+        };
+        if self.bpb.ext_sig == 0x29 {
+            let label_offset = if self.fat_type == FatType::Fat32 { 71 } else { 43 };
+            let sectors = [Sector(0), Sector(self.bpb.backup_boot_sector as u64)];
+            let has_backup = self.fat_type == FatType::Fat32 && sectors[1].0 != 0;
+            let num_sectors = if has_backup { 2 } else { 1 };
+            let mut disk = self.disk.borrow_mut();
+            for &sector in sectors[..num_sectors].iter() {
+                disk.seek(SeekFrom::Start(self.offset_from_sector(sector) + label_offset))?;
+                disk.write_all(&bpb_label)?;
+            }
+            self.bpb.volume_label = bpb_label;
+        }
+        self.root_dir().set_volume_entry(raw_label)?;
+        self.volume_label = bpb_label;
+        Ok(())
+    }
+
+    // Returns label stored in root directory entry with VOLUME_ID attribute
+    fn read_volume_label_entry(&self) -> io::Result<Option<[u8; 11]>> {
+        for r in self.root_dir().iter_raw() {
+            let entry = r?;
+            if entry.is_end() {
+                break;
+            }
+            if !entry.is_free() && entry.is_volume() {
+                let mut label = [0u8; 11];
+                label.copy_from_slice(&entry.bytes()[..11]);
+                strip_non_ascii(&mut label);
+                return Ok(Some(label));
             }
+        }
+        Ok(None)
+    }
+
+    /// Returns root directory object allowing futher penetration of filesystem structure.
+    pub fn root_dir<'b>(&'b self) -> Dir<'b, IO> {
+        let root_rdr = match self.root_dir_location {
+            RootDirLocation::Region(..) => DirRawStream::Root(self.root_dir_slice()),
+            RootDirLocation::Cluster(n) => DirRawStream::File(File::new(Some(n), None, self)),
         };
         Dir::new(root_rdr, self)
     }
 
-    pub(crate) fn offset_from_sector(&self, sector: u32) -> u64 {
-        (sector as u64) * self.bpb.bytes_per_sector as u64
+    // Returns FAT12/FAT16 root directory region
+    pub(crate) fn root_dir_slice<'b>(&'b self) -> DiskSlice<'b, IO> {
+        match self.root_dir_location {
+            RootDirLocation::Region(offset, size) => DiskSlice::new(offset, size, 1, self),
+            RootDirLocation::Cluster(_) => panic!("FAT32 has no root directory region"),
+        }
+    }
+
+    /// Returns size of a sector in bytes.
+    pub fn bytes_per_sector(&self) -> u16 {
+        self.bpb.bytes_per_sector
     }
 
-    pub(crate) fn sector_from_cluster(&self, cluster: u32) -> u32 {
-        ((cluster - 2) * self.bpb.sectors_per_cluster as u32) + self.first_data_sector
+    /// Returns number of sectors in a cluster.
+    pub fn sectors_per_cluster(&self) -> u8 {
+        self.bpb.sectors_per_cluster
     }
 
-    pub(crate) fn cluster_size(&self) -> u32 {
+    /// Returns size of a cluster in bytes.
+    pub fn cluster_size(&self) -> u32 {
         self.bpb.sectors_per_cluster as u32 * self.bpb.bytes_per_sector as u32
     }
 
-    pub(crate) fn offset_from_cluster(&self, cluser: u32) -> u64 {
-        self.offset_from_sector(self.sector_from_cluster(cluser))
+    /// Returns number of the first sector of data region (sector of cluster 2).
+    pub fn first_data_sector(&self) -> Sector {
+        Sector(self.first_data_sector as u64)
+    }
+
+    /// Returns offset of a sector from the beginning of the volume.
+    pub fn offset_from_sector(&self, sector: Sector) -> u64 {
+        sector.0 * self.bpb.bytes_per_sector as u64
+    }
+
+    /// Returns number of the first sector of a cluster.
+    ///
+    /// Panics if `cluster` is not a data cluster (cluster numbers start from 2).
+    pub fn sector_from_cluster(&self, cluster: Cluster) -> Sector {
+        assert!(cluster.0 >= 2, "invalid cluster number");
+        let sector = (cluster.0 - 2) as u64 * self.bpb.sectors_per_cluster as u64;
+        Sector(sector + self.first_data_sector as u64)
+    }
+
+    /// Returns offset of a cluster from the beginning of the volume.
+    ///
+    /// Panics if `cluster` is not a data cluster (cluster numbers start from 2).
+    pub fn offset_from_cluster(&self, cluster: Cluster) -> u64 {
+        self.offset_from_sector(self.sector_from_cluster(cluster))
+    }
+
+    /// Returns cluster containing byte at given offset from the beginning of the volume or `None`
+    /// if the offset is outside of data region.
+    pub fn cluster_from_offset(&self, offset: u64) -> Option<Cluster> {
+        let data_offset = self.offset_from_sector(self.first_data_sector());
+        if offset < data_offset {
+            return None;
+        }
+        let cluster = (offset - data_offset) / self.cluster_size() as u64;
+        if cluster < self.total_clusters as u64 {
+            Some(Cluster(cluster as u32 + 2))
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn root_dir_cluster(&self) -> Option<Cluster> {
+        match self.root_dir_location {
+            RootDirLocation::Cluster(n) => Some(n),
+            RootDirLocation::Region(..) => None,
+        }
+    }
+
+    pub(crate) fn zero_cluster(&self, cluster: Cluster) -> io::Result<()> {
+        let zero = [0u8; DIR_ENTRY_SIZE as usize];
+        let abs_pos = self.offset_from_cluster(cluster);
+        let mut disk = self.disk.borrow_mut();
+        disk.seek(SeekFrom::Start(abs_pos))?;
+        for _ in 0..self.cluster_size() / DIR_ENTRY_SIZE as u32 {
+            disk.write_all(&zero)?;
+        }
+        Ok(())
+    }
+
+    // Overwrites whole cluster with `pattern` byte
+    pub(crate) fn fill_cluster(&self, cluster: Cluster, pattern: u8) -> io::Result<()> {
+        let buf = [pattern; SCRATCH_BUFFER_SIZE];
+        let abs_pos = self.offset_from_cluster(cluster);
+        let mut disk = self.disk.borrow_mut();
+        disk.seek(SeekFrom::Start(abs_pos))?;
+        for _ in 0..self.cluster_size() / SCRATCH_BUFFER_SIZE as u32 {
+            disk.write_all(&buf)?;
+        }
+        Ok(())
+    }
+
+    // Returns number of FAT copies, offset of the first one and size of a single copy
+    pub(crate) fn fat_copies(&self) -> (u8, u64, u64) {
+        let bytes_per_sector = self.bpb.bytes_per_sector as u64;
+        let fat_size = self.bpb.sectors_per_fat() as u64 * bytes_per_sector;
+        (self.bpb.fats, self.bpb.reserved_sectors as u64 * bytes_per_sector, fat_size)
     }
 
-    fn fat_slice<'b>(&'b self) -> DiskSlice<'b, 'a> {
+    pub(crate) fn fat_slice<'b>(&'b self) -> DiskSlice<'b, IO> {
         let sectors_per_fat = if self.bpb.sectors_per_fat_16 == 0 {
             self.bpb.sectors_per_fat_32
         } else {
@@ -350,46 +1642,508 @@ impl<'a> FileSystem<'a> {
                 (self.bpb.reserved_sectors as u32) + active_fat * sectors_per_fat;
             (fat_first_sector, 1)
         };
-        DiskSlice::from_sectors(fat_first_sector, sectors_per_fat, mirrors, self)
+        #[allow(unused_mut)]
+        let mut fat = DiskSlice::from_sectors(fat_first_sector, sectors_per_fat, mirrors, self);
+        #[cfg(feature = "alloc")]
+        {
+            fat.lazy = self.options.lazy_fat_writes;
+        }
+        fat
     }
 
-    pub(crate) fn cluster_iter<'b>(&'b self, cluster: u32) -> ClusterIterator<'b, 'a> {
+    /// Returns iterator over clusters following given cluster in its cluster chain.
+    ///
+    /// Iteration stops at the end of chain marker. Loops in the chain are not detected so callers
+    /// should limit number of iterations to the total number of clusters. Meant for debugging.
+    pub fn cluster_iter<'b>(&'b self, cluster: Cluster) -> ClusterIterator<'b, IO> {
         let disk_slice = self.fat_slice();
-        ClusterIterator::new(disk_slice, self.fat_type, cluster)
+        let strict = self.options.profile == MountProfile::Strict;
+        let cancel = self.options.cancellation_token;
+        let end_cluster = Cluster(self.total_clusters + RESERVED_FAT_ENTRIES);
+        ClusterIterator::new(disk_slice, self.fat_ops, cluster, strict, end_cluster, cancel)
     }
 
-    pub(crate) fn alloc_cluster(&self, prev_cluster: Option<u32>) -> io::Result<u32> {
+    pub(crate) fn alloc_cluster(&self, prev_cluster: Option<Cluster>) -> io::Result<Cluster> {
         let mut disk_slice = self.fat_slice();
-        alloc_cluster(&mut disk_slice, self.fat_type, prev_cluster)
+        let hint = self.fs_info.borrow().next_free_cluster;
+        let cluster = alloc_cluster(
+            &mut disk_slice,
+            self.fat_ops,
+            prev_cluster,
+            hint.map(Cluster),
+            self.total_clusters,
+            self.options.cancellation_token,
+        )?;
+        let mut fs_info = self.fs_info.borrow_mut();
+        fs_info.set_next_free_cluster(cluster.0 + 1);
+        fs_info.add_free_clusters(-1);
+        let mut scan = self.free_scan.get();
+        if cluster.0 < scan.next_cluster {
+            scan.free_clusters -= 1;
+            self.free_scan.set(scan);
+        }
+        Ok(cluster)
+    }
+
+    pub(crate) fn truncate_cluster_chain(&self, cluster: Cluster) -> io::Result<()> {
+        let scanned_freed = self.count_scanned(self.cluster_iter(cluster))?;
+        let r = self.cluster_iter(cluster).truncate();
+        let num_free = self.forget_free_clusters_on_error(r)?;
+        self.fs_info.borrow_mut().add_free_clusters(num_free as i32);
+        self.add_scanned_free_clusters(scanned_freed);
+        Ok(())
+    }
+
+    pub(crate) fn free_cluster_chain(&self, cluster: Cluster) -> io::Result<()> {
+        self.free_cluster_chains(&[cluster])
+    }
+
+    // Frees chains starting at `clusters` - free cluster counters are updated once for all of
+    // them
+    pub(crate) fn free_cluster_chains(&self, clusters: &[Cluster]) -> io::Result<()> {
+        let mut scanned_freed = 0;
+        for &cluster in clusters {
+            let chain = iter::once(Ok(cluster)).chain(self.cluster_iter(cluster));
+            scanned_freed += self.count_scanned(chain)?;
+        }
+        let mut num_free = 0;
+        for &cluster in clusters {
+            let r = self.cluster_iter(cluster).free();
+            num_free += self.forget_free_clusters_on_error(r)?;
+        }
+        self.fs_info.borrow_mut().add_free_clusters(num_free as i32);
+        self.add_scanned_free_clusters(scanned_freed);
+        Ok(())
+    }
+
+    // Chain could have been freed partially so the number of free clusters is no longer known
+    fn forget_free_clusters_on_error(&self, r: io::Result<u32>) -> io::Result<u32> {
+        if r.is_err() {
+            self.fs_info.borrow_mut().forget_free_cluster_count();
+            self.free_scan.set(FreeSpaceScan::default());
+        }
+        r
+    }
+
+    #[cfg(feature = "splice")]
+    pub(crate) fn set_next_cluster(
+        &self,
+        cluster: Cluster,
+        next: Option<Cluster>,
+    ) -> io::Result<()> {
+        set_next_cluster(&mut self.fat_slice(), self.fat_ops, cluster, next)
+    }
+
+    /// Frees clusters of a chain detached by `File::split_chain` (enabled by `splice` feature).
+    #[cfg(feature = "splice")]
+    pub fn free_chain(&self, chain: ClusterChain) -> io::Result<()> {
+        self.free_cluster_chain(chain.first_cluster)
+    }
+
+    // Counts clusters already passed by the incremental free space scan. Chain is walked only
+    // when a scan is in progress.
+    fn count_scanned<I>(&self, clusters: I) -> io::Result<u32>
+    where
+        I: Iterator<Item = io::Result<Cluster>>,
+    {
+        let next_cluster = self.free_scan.get().next_cluster;
+        let mut count = 0;
+        if next_cluster > RESERVED_FAT_ENTRIES {
+            for r in clusters {
+                if r?.0 < next_cluster {
+                    count += 1;
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    fn add_scanned_free_clusters(&self, free_clusters: u32) {
+        let mut scan = self.free_scan.get();
+        scan.free_clusters += free_clusters;
+        self.free_scan.set(scan);
+    }
+
+    /// Advances incremental scan of the FAT counting free clusters by at most `budget_clusters`
+    /// FAT entries.
+    ///
+    /// Meant to be called repeatedly from an idle loop instead of scanning the whole FAT at once
+    /// in `stats`. Scan progress is kept in the filesystem object and clusters allocated or freed
+    /// in the already scanned part are accounted for, so the filesystem can be used between
+    /// calls. When the scan reaches the end of the FAT the number of free clusters is returned
+    /// and stored as the value reported by `stats` (and written to FSInfo sector on FAT32). The
+    /// next call starts a new scan. Returns `None` while the scan is not finished.
+    pub fn scan_free_space_incremental(&self, budget_clusters: u32) -> io::Result<Option<u32>> {
+        let mut scan = self.free_scan.get();
+        if scan.next_cluster == 0 {
+            scan.next_cluster = RESERVED_FAT_ENTRIES;
+        }
+        let end_cluster = self.total_clusters + RESERVED_FAT_ENTRIES;
+        let cancel = self.options.cancellation_token;
+        let mut fat = self.fat_slice();
+        for _ in 0..budget_clusters {
+            if scan.next_cluster == end_cluster {
+                break;
+            }
+            check_cancelled(cancel)?;
+            if is_free_cluster(&mut fat, self.fat_ops, Cluster(scan.next_cluster))? {
+                scan.free_clusters += 1;
+            }
+            scan.next_cluster += 1;
+        }
+        if scan.next_cluster < end_cluster {
+            self.free_scan.set(scan);
+            return Ok(None);
+        }
+        self.free_scan.set(FreeSpaceScan::default());
+        self.fs_info.borrow_mut().set_free_cluster_count(scan.free_clusters);
+        Ok(Some(scan.free_clusters))
+    }
+
+    /// Returns filesystem statistics like number of total and free clusters.
+    ///
+    /// For FAT32 volumes number of free clusters from FSInfo sector is returned if it is valid.
+    /// Otherwise whole FAT is scanned once and the result is maintained incrementally afterwards.
+    pub fn stats(&self) -> io::Result<FileSystemStats> {
+        let free_cluster_count = self.fs_info.borrow().free_cluster_count;
+        let free_clusters = match free_cluster_count {
+            Some(n) => n,
+            None => self.recalc_free_clusters()?,
+        };
+        Ok(FileSystemStats {
+            cluster_size: self.cluster_size(),
+            total_clusters: self.total_clusters,
+            free_clusters,
+        })
+    }
+
+    fn recalc_free_clusters(&self) -> io::Result<u32> {
+        let free_cluster_count = self.count_free_clusters()?;
+        self.fs_info.borrow_mut().set_free_cluster_count(free_cluster_count);
+        Ok(free_cluster_count)
+    }
+
+    pub(crate) fn count_free_clusters(&self) -> io::Result<u32> {
+        let mut fat = self.fat_slice();
+        let cancel = self.options.cancellation_token;
+        count_free_clusters(&mut fat, self.fat_ops, self.total_clusters, cancel)
+    }
+
+    pub(crate) fn cached_free_clusters(&self) -> Option<u32> {
+        self.fs_info.borrow().free_cluster_count
+    }
+
+    pub(crate) fn set_free_clusters(&self, free_clusters: u32) {
+        self.fs_info.borrow_mut().set_free_cluster_count(free_clusters);
+    }
+
+    pub(crate) fn rebuild_fs_info(&self, free_clusters: u32) {
+        let mut fs_info = self.fs_info.borrow_mut();
+        fs_info.set_free_cluster_count(free_clusters);
+        // next free cluster is only a hint - mark it as unknown so search starts from the beginning
+        fs_info.next_free_cluster = None;
+    }
+
+    fn flush_fs_info(&self) -> io::Result<()> {
+        let mut fs_info = self.fs_info.borrow_mut();
+        if self.fat_type == FatType::Fat32 && fs_info.dirty && self.bpb.fs_info_sector != 0 {
+            let offset = self.offset_from_sector(Sector(self.bpb.fs_info_sector as u64));
+            let r = {
+                let mut disk = self.disk.borrow_mut();
+                disk.seek(SeekFrom::Start(offset)).and_then(|_| fs_info.serialize(&mut *disk))
+            };
+            self.poison_on_error(r)?;
+            fs_info.dirty = false;
+        }
+        Ok(())
     }
 
+    // Checks if there are FAT sectors modified in lazy FAT write mode that were not written yet
+    pub(crate) fn has_pending_fat_writes(&self) -> bool {
+        #[cfg(feature = "alloc")]
+        {
+            !self.fat_cache.borrow().is_empty()
+        }
+        #[cfg(not(feature = "alloc"))]
+        {
+            false
+        }
+    }
+
+    // Writes FAT sectors modified in lazy FAT write mode
+    pub(crate) fn flush_fat(&self) -> io::Result<()> {
+        #[cfg(feature = "alloc")]
+        {
+            let mut fat_cache = self.fat_cache.borrow_mut();
+            let mut fat = self.fat_slice();
+            fat.lazy = false;
+            for (offset, sector) in fat_cache.iter() {
+                fat.seek(SeekFrom::Start(offset))?;
+                fat.write_all(sector)?;
+            }
+            // sectors are kept if write fails so flush can be retried
+            fat_cache.clear();
+        }
+        Ok(())
+    }
+
+    /// Writes all pending changes of filesystem metadata to the device.
+    ///
+    /// Writes FAT sectors modified in lazy FAT write mode, CRC file (if enabled by
+    /// `FsOptions::metadata_crc`) and FSInfo sector and flushes the device. Changes of open files
+    /// are written by `File::flush`.
+    pub fn flush(&self) -> io::Result<()> {
+        // CRC file update allocates clusters so it must be done first
+        #[cfg(feature = "crc")]
+        {
+            if self.options.metadata_crc && !self.is_read_only() {
+                self.update_metadata_crc()?;
+            }
+        }
+        self.flush_fat()?;
+        self.flush_fs_info()?;
+        self.disk.borrow_mut().flush()
+    }
+
+    /// Lends the underlying device to `f` without unmounting the filesystem.
+    ///
+    /// Meant for vendor specific commands (e.g. secure erase of free sectors or health queries)
+    /// issued through the device driver. Pending metadata changes are flushed like in `flush` and
+    /// FAT cache is emptied before `f` is called. Afterwards the boot sector is read again and if
+    /// it no longer describes the same volume an error is returned. Position of the device does
+    /// not need to be preserved by `f`. If the filesystem is poisoned (see `is_poisoned`) pending
+    /// changes are discarded instead so the volume can be repaired by `f`.
+    ///
+    /// Note: `f` must not modify filesystem structures or data of open files.
+    pub fn with_disk<F, R>(&self, f: F) -> io::Result<R>
+    where
+        F: FnOnce(&mut IO) -> io::Result<R>,
+    {
+        if self.is_poisoned() {
+            // pending changes cannot be written - the device is lent in its current state so it
+            // can be repaired
+            #[cfg(feature = "alloc")]
+            self.fat_cache.borrow_mut().clear();
+        } else {
+            self.flush()?;
+        }
+        let mut disk = self.disk.borrow_mut();
+        let r = f(disk.device())?;
+        disk.seek(SeekFrom::Start(0))?;
+        let bpb = read_boot_sector(&mut *disk, self.options.profile)?;
+        if !self.bpb.same_volume(&bpb) {
+            return Err(Error::new(ErrorKind::Other, "volume changed while device was lent"));
+        }
+        Ok(r)
+    }
+
+    /// Unmounts the filesystem.
+    ///
+    /// Updates FSInfo sector if needed. Filesystem is unmounted automatically when dropped but
+    /// errors are only logged then. If unmounting fails it is not attempted again.
+    pub fn unmount(mut self) -> io::Result<()> {
+        // do not unmount again on drop, also if unmounting fails
+        self.mounted = false;
+        self.unmount_internal()
+    }
+
+    /// Unmounts the filesystem and returns the storage device.
+    ///
+    /// Works like `unmount` but gives the device back, e.g. to mount it again or to pass it to
+    /// other code. The device is seeked to the beginning, so it can be passed to `FileSystem::new`
+    /// right away. If unmounting fails the device is dropped together with the filesystem.
+    pub fn into_inner(mut self) -> io::Result<IO> {
+        // do not unmount again on drop, also if unmounting fails
+        self.mounted = false;
+        self.unmount_internal()?;
+        let disk = self.disk.get_mut();
+        disk.seek(SeekFrom::Start(0))?;
+        // SAFE: device is present until it is taken here
+        Ok(disk.device.take().unwrap())
+    }
+
+    /// Moves the filesystem to the heap and leaks it, returning a reference valid for the rest of
+    /// the program.
+    ///
+    /// Objects returned by the filesystem (`File<'static, IO>`, `Dir<'static, IO>`) can then be
+    /// stored in other structures and kept open across function boundaries, e.g. in a kernel VFS
+    /// layer. The filesystem is never unmounted, so `flush` should be called before the device is
+    /// powered off.
+    #[cfg(feature = "alloc")]
+    pub fn leak(self) -> &'static Self
+    where
+        IO: 'static,
+    {
+        Box::leak(Box::new(self))
+    }
+
+    fn unmount_internal(&self) -> io::Result<()> {
+        if self.is_read_only() {
+            return Ok(());
+        }
+        // changes made to the previous medium must not be written to the new one
+        self.disk.borrow_mut().check_media()?;
+        if self.is_poisoned() {
+            // pending changes are dropped - only mark the volume as needing a check (best effort)
+            self.disk.borrow_mut().poisoned = false;
+            #[allow(unused_mut)]
+            let mut fat = self.fat_slice();
+            #[cfg(feature = "alloc")]
+            {
+                fat.lazy = false;
+            }
+            let _ = write_fat_io_error_flag(&mut fat, self.fat_ops, true);
+            self.disk.borrow_mut().poisoned = true;
+            return Err(poisoned_error());
+        }
+        let io_error = self.disk.borrow().io_error;
+        if io_error {
+            // best effort - device may be unable to store the flag
+            write_fat_io_error_flag(&mut self.fat_slice(), self.fat_ops, true)?;
+        }
+        // CRC file update allocates clusters so it must be done before FSInfo is written. FAT
+        // checksum covers the I/O error flag so the flag is written first.
+        #[cfg(feature = "crc")]
+        {
+            if self.options.metadata_crc {
+                self.update_metadata_crc()?;
+            }
+        }
+        self.flush_fs_info()?;
+        self.flush_fat()?;
+        let mut disk = self.disk.borrow_mut();
+        disk.flush()
+    }
+
+    /// Returns true if filesystem was mounted read-only because the device is truncated.
+    ///
+    /// See `FsOptions::allow_truncated`.
+    pub fn is_read_only(&self) -> bool {
+        self.disk.borrow().truncated
+    }
+
+    /// Returns true if writing of filesystem metadata (FAT, directory entries or FSInfo sector)
+    /// failed.
+    ///
+    /// Structures on the device can be inconsistent then, so all further writes fail with
+    /// `ErrorKind::FilesystemPoisoned` error instead of building on top of them. Reading is still
+    /// possible. Pending changes are not written when the filesystem is unmounted, only the I/O
+    /// error flag is set in FAT (see `read_status_flags`).
+    pub fn is_poisoned(&self) -> bool {
+        self.disk.borrow().poisoned
+    }
+
+    /// Clears the poisoned state (see `is_poisoned`).
+    ///
+    /// Meant to be called after the volume was checked and repaired by external tools, e.g.
+    /// through `with_disk`. FAT changes not yet written in lazy FAT write mode are discarded and
+    /// the free cluster count is computed again when needed.
+    pub fn clear_poison(&self) {
+        #[cfg(feature = "alloc")]
+        self.fat_cache.borrow_mut().clear();
+        let mut fs_info = self.fs_info.borrow_mut();
+        fs_info.free_cluster_count = None;
+        fs_info.next_free_cluster = None;
+        self.free_scan.set(FreeSpaceScan::default());
+        self.disk.borrow_mut().poisoned = false;
+    }
+
+    // Marks the filesystem as poisoned if writing of metadata failed
+    pub(crate) fn poison_on_error<T>(&self, r: io::Result<T>) -> io::Result<T> {
+        match r {
+            Err(ref err)
+                if err.kind() != ErrorKind::FilesystemPoisoned
+                    && err.kind() != ErrorKind::TruncatedVolume
+                    && err.kind() != ErrorKind::MediaChanged =>
+            {
+                error!("metadata write failed ({}), filesystem is poisoned", err);
+                self.disk.borrow_mut().poisoned = true;
+            }
+            _ => {}
+        }
+        r
+    }
+
+    /// Returns status flags for this volume.
+    ///
+    /// Flags are combined from the Boot Sector and the second FAT entry (the latter is not
+    /// available for FAT12). `io_error` is also set if a device write failed since the filesystem
+    /// was mounted - the flag is stored in FAT when filesystem is unmounted.
     pub fn read_status_flags(&self) -> io::Result<FsStatusFlags> {
         let bpb_status = self.bpb.status_flags();
-        let fat_status = read_fat_flags(&mut self.fat_slice(), self.fat_type)?;
+        let fat_status = read_fat_flags(&mut self.fat_slice(), self.fat_ops)?;
         Ok(FsStatusFlags {
             dirty: bpb_status.dirty || fat_status.dirty,
-            io_error: bpb_status.io_error || fat_status.io_error,
+            io_error: bpb_status.io_error || fat_status.io_error || self.disk.borrow().io_error,
         })
     }
+
+    /// Clears I/O error flag stored in FAT (e.g. after the device was checked or replaced).
+    ///
+    /// Flag stored in the Boot Sector by other systems is not modified.
+    pub fn clear_io_error(&self) -> io::Result<()> {
+        self.disk.borrow_mut().io_error = false;
+        write_fat_io_error_flag(&mut self.fat_slice(), self.fat_ops, false)
+    }
+
+    // Checks if FAT[0] contains media descriptor from BPB and FAT[1] is end of chain marker
+    pub(crate) fn reserved_fat_entries_valid(&self) -> io::Result<bool> {
+        reserved_fat_entries_valid(&mut self.fat_slice(), self.fat_ops, self.bpb.media)
+    }
+
+    pub(crate) fn fix_reserved_fat_entries(&self) -> io::Result<()> {
+        fix_reserved_fat_entries(&mut self.fat_slice(), self.fat_ops, self.bpb.media)
+    }
+}
+
+impl<IO: ReadWriteSeek> Drop for FileSystem<IO> {
+    fn drop(&mut self) {
+        if !self.mounted {
+            return;
+        }
+        match self.unmount_internal() {
+            Err(err) => error!("unmount failed {}", err),
+            _ => {}
+        }
+    }
 }
 
-#[derive(Clone)]
-pub(crate) struct DiskSlice<'a, 'b: 'a> {
+pub(crate) struct DiskSlice<'a, IO: ReadWriteSeek + 'a> {
     begin: u64,
     size: u64,
     offset: u64,
     mirrors: u8,
-    fs: &'a FileSystem<'b>,
+    fs: &'a FileSystem<IO>,
+    // accesses go through FAT cache (lazy FAT write mode)
+    #[cfg(feature = "alloc")]
+    lazy: bool,
+}
+
+// Derived implementation would require `IO: Clone`
+impl<'a, IO: ReadWriteSeek> Clone for DiskSlice<'a, IO> {
+    fn clone(&self) -> Self {
+        DiskSlice {
+            begin: self.begin,
+            size: self.size,
+            offset: self.offset,
+            mirrors: self.mirrors,
+            fs: self.fs,
+            #[cfg(feature = "alloc")]
+            lazy: self.lazy,
+        }
+    }
 }
 
-impl<'a, 'b> DiskSlice<'a, 'b> {
-    pub(crate) fn new(begin: u64, size: u64, mirrors: u8, fs: FileSystemRef<'a, 'b>) -> Self {
+impl<'a, IO: ReadWriteSeek> DiskSlice<'a, IO> {
+    pub(crate) fn new(begin: u64, size: u64, mirrors: u8, fs: FileSystemRef<'a, IO>) -> Self {
         DiskSlice {
             begin,
             size,
             mirrors,
             fs,
             offset: 0,
+            #[cfg(feature = "alloc")]
+            lazy: false,
         }
     }
 
@@ -397,7 +2151,7 @@ impl<'a, 'b> DiskSlice<'a, 'b> {
         first_sector: u32,
         sector_count: u32,
         mirrors: u8,
-        fs: FileSystemRef<'a, 'b>,
+        fs: FileSystemRef<'a, IO>,
     ) -> Self {
         let bytes_per_sector = fs.bpb.bytes_per_sector as u64;
         Self::new(
@@ -411,12 +2165,89 @@ impl<'a, 'b> DiskSlice<'a, 'b> {
     pub(crate) fn abs_pos(&self) -> u64 {
         self.begin + self.offset
     }
+
+    // Returns FAT entry value written as end of chain marker
+    pub(crate) fn end_of_chain(&self, fat_type: FatType) -> u32 {
+        let (eoc_min, _) = fat_type.end_of_chain();
+        eoc_min + self.fs.options.end_of_chain_marker as u32
+    }
+
+    // Limits access size so it does not cross sector boundary. Unaligned multi-byte accesses
+    // (e.g. FAT12 entries) are split by read_exact/write_all into accesses of single sectors
+    // which is required by block devices.
+    fn access_size(&self, len: usize) -> usize {
+        let bytes_per_sector = self.fs.bpb.bytes_per_sector as u64;
+        let bytes_left_in_sector = bytes_per_sector - (self.begin + self.offset) % bytes_per_sector;
+        let bytes_left_in_slice = self.size - self.offset;
+        cmp::min(cmp::min(bytes_left_in_slice, bytes_left_in_sector), len as u64) as usize
+    }
+
+    // Returns offset of current sector in slice and offset in that sector
+    #[cfg(feature = "alloc")]
+    fn sector_offsets(&self) -> (u64, usize) {
+        let bytes_per_sector = self.fs.bpb.bytes_per_sector as u64;
+        let offset_in_sector = (self.begin + self.offset) % bytes_per_sector;
+        (self.offset - offset_in_sector, offset_in_sector as usize)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn read_cached(&mut self, buf: &mut [u8]) -> io::Result<Option<usize>> {
+        let (sector_offset, offset_in_sector) = self.sector_offsets();
+        let read_size = self.access_size(buf.len());
+        match self.fs.fat_cache.borrow().get(sector_offset) {
+            Some(sector) => {
+                buf[..read_size]
+                    .copy_from_slice(&sector[offset_in_sector..offset_in_sector + read_size]);
+            }
+            None => return Ok(None),
+        }
+        self.offset += read_size as u64;
+        Ok(Some(read_size))
+    }
+
+    // Returns None if sector is not cached and there is no buffer for it
+    #[cfg(feature = "alloc")]
+    fn write_cached(&mut self, buf: &[u8]) -> io::Result<Option<usize>> {
+        let (sector_offset, offset_in_sector) = self.sector_offsets();
+        let write_size = self.access_size(buf.len());
+        let mut fat_cache = self.fs.fat_cache.borrow_mut();
+        if fat_cache.get(sector_offset).is_none() {
+            let sector = match fat_cache.alloc_sector(self.fs.bpb.bytes_per_sector as usize) {
+                Some(sector) => sector,
+                None => return Ok(None),
+            };
+            let mut disk = self.fs.disk.borrow_mut();
+            let r = disk
+                .seek(SeekFrom::Start(self.begin + sector_offset))
+                .and_then(|_| disk.read_exact(sector));
+            match r {
+                Ok(()) => fat_cache.insert(sector_offset, sector),
+                Err(err) => {
+                    fat_cache.free_sector(sector);
+                    return Err(err);
+                }
+            }
+        }
+        let sector = fat_cache.get_mut(sector_offset).unwrap(); // SAFE: inserted above
+        sector[offset_in_sector..offset_in_sector + write_size].copy_from_slice(&buf[..write_size]);
+        self.offset += write_size as u64;
+        Ok(Some(write_size))
+    }
 }
 
-impl<'a, 'b> Read for DiskSlice<'a, 'b> {
+impl<'a, IO: ReadWriteSeek> Read for DiskSlice<'a, IO> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        #[cfg(feature = "alloc")]
+        {
+            if self.lazy {
+                match self.read_cached(buf)? {
+                    Some(n) => return Ok(n),
+                    None => {}
+                }
+            }
+        }
         let offset = self.begin + self.offset;
-        let read_size = cmp::min((self.size - self.offset) as usize, buf.len());
+        let read_size = self.access_size(buf.len());
         let mut disk = self.fs.disk.borrow_mut();
         disk.seek(SeekFrom::Start(offset))?;
         let size = disk.read(&mut buf[..read_size])?;
@@ -425,14 +2256,26 @@ impl<'a, 'b> Read for DiskSlice<'a, 'b> {
     }
 }
 
-impl<'a, 'b> Write for DiskSlice<'a, 'b> {
+impl<'a, IO: ReadWriteSeek> Write for DiskSlice<'a, IO> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        #[cfg(feature = "alloc")]
+        {
+            if self.lazy {
+                match self.write_cached(buf)? {
+                    Some(n) => return Ok(n),
+                    None => {}
+                }
+            }
+        }
         let offset = self.begin + self.offset;
-        let write_size = cmp::min((self.size - self.offset) as usize, buf.len());
+        let write_size = self.access_size(buf.len());
         for i in 0..self.mirrors {
-            let mut disk = self.fs.disk.borrow_mut();
-            disk.seek(SeekFrom::Start(offset + i as u64 * self.size))?;
-            disk.write_all(&buf[..write_size])?;
+            let r = {
+                let mut disk = self.fs.disk.borrow_mut();
+                disk.seek(SeekFrom::Start(offset + i as u64 * self.size))
+                    .and_then(|_| disk.write_all(&buf[..write_size]))
+            };
+            self.fs.poison_on_error(r)?;
         }
         self.offset += write_size as u64;
         Ok(write_size)
@@ -444,7 +2287,7 @@ impl<'a, 'b> Write for DiskSlice<'a, 'b> {
     }
 }
 
-impl<'a, 'b> Seek for DiskSlice<'a, 'b> {
+impl<'a, IO: ReadWriteSeek> Seek for DiskSlice<'a, IO> {
     fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
         let new_offset = match pos {
             SeekFrom::Current(x) => self.offset as i64 + x,
@@ -3,19 +3,68 @@
 extern crate byteorder;
 #[macro_use]
 extern crate bitflags;
+#[macro_use]
+extern crate log;
 extern crate basic_io;
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
+mod buffer;
+mod cancel;
+mod check;
 mod dir;
 mod dir_entry;
 mod file;
+mod format;
 mod fs;
 mod table;
+mod units;
+mod wipe;
+
+#[cfg(feature = "std")]
+mod auto_extend;
+#[cfg(feature = "crc")]
+mod crc;
+#[cfg(feature = "alloc")]
+mod fat_cache;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "fixed")]
+mod fixed;
+#[cfg(feature = "footprint")]
+pub mod footprint;
+#[cfg(feature = "alloc")]
+mod memory;
+#[cfg(feature = "alloc")]
+mod open_entries;
+#[cfg(feature = "alloc")]
+mod overlay;
+#[cfg(feature = "alloc")]
+mod writer;
 
 mod byteorder_core_io;
 use basic_io as io;
 use byteorder_core_io as byteorder_ext;
 
+pub use buffer::*;
+pub use cancel::*;
+pub use check::*;
 pub use dir::*;
 pub use dir_entry::*;
 pub use file::*;
+pub use format::*;
 pub use fs::*;
+pub use table::ClusterIterator;
+pub use units::{Cluster, Sector};
+#[cfg(feature = "std")]
+pub use auto_extend::AutoExtend;
+#[cfg(feature = "fixed")]
+pub use fixed::FixedSectorFileSystem;
+#[cfg(feature = "alloc")]
+pub use memory::MemoryDisk;
+#[cfg(feature = "alloc")]
+pub use overlay::{OverlayDisk, OverlayStore};
+#[cfg(feature = "alloc")]
+pub use writer::ClusterAlignedWriter;
@@ -0,0 +1,64 @@
+use alloc::vec::Vec;
+use core::cmp;
+use io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+
+/// Fixed size storage kept in memory (used by `FileSystem::new_in_memory`).
+pub struct MemoryDisk {
+    data: Vec<u8>,
+    pos: u64,
+}
+
+impl MemoryDisk {
+    pub(crate) fn new(size: usize) -> Self {
+        let mut data = Vec::new();
+        data.resize(size, 0);
+        MemoryDisk { data, pos: 0 }
+    }
+
+    fn remaining(&self) -> &[u8] {
+        let start = cmp::min(self.pos, self.data.len() as u64) as usize;
+        &self.data[start..]
+    }
+}
+
+impl Read for MemoryDisk {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = {
+            let src = self.remaining();
+            let n = cmp::min(buf.len(), src.len());
+            buf[..n].copy_from_slice(&src[..n]);
+            n
+        };
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Write for MemoryDisk {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let start = cmp::min(self.pos, self.data.len() as u64) as usize;
+        let n = cmp::min(buf.len(), self.data.len() - start);
+        self.data[start..start + n].copy_from_slice(&buf[..n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for MemoryDisk {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(n) => n as i64,
+            SeekFrom::Current(n) => self.pos as i64 + n,
+            SeekFrom::End(n) => self.data.len() as i64 + n,
+        };
+        if new_pos < 0 {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "invalid seek"));
+        }
+        self.pos = new_pos as u64;
+        Ok(self.pos)
+    }
+}
@@ -0,0 +1,94 @@
+use alloc::vec::Vec;
+
+// Directory entry used by open file and directory handles
+struct OpenEntry {
+    id: u32,
+    // position of the short name entry on the volume
+    pos: u64,
+    name: [u8; 11],
+    handles: u32,
+    removed: bool,
+}
+
+// Table of directory entries used by open handles. Handles of the same entry share a record, which
+// follows the entry when it is renamed or moved and is marked as removed when the entry is freed,
+// so handles never write their entry to a slot which now belongs to another file.
+pub(crate) struct OpenEntries {
+    entries: Vec<OpenEntry>,
+    next_id: u32,
+}
+
+impl OpenEntries {
+    pub(crate) fn new() -> Self {
+        OpenEntries {
+            entries: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    // Registers a new handle of the entry at `pos` and returns identifier of its record
+    pub(crate) fn open(&mut self, pos: u64, name: [u8; 11]) -> u32 {
+        match self.entries.iter_mut().find(|e| e.pos == pos && !e.removed) {
+            Some(e) => {
+                e.handles += 1;
+                return e.id;
+            }
+            None => {}
+        }
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.entries.push(OpenEntry {
+            id,
+            pos,
+            name,
+            handles: 1,
+            removed: false,
+        });
+        id
+    }
+
+    pub(crate) fn clone_handle(&mut self, id: u32) {
+        match self.entries.iter_mut().find(|e| e.id == id) {
+            Some(e) => e.handles += 1,
+            None => {}
+        }
+    }
+
+    pub(crate) fn close(&mut self, id: u32) {
+        match self.entries.iter().position(|e| e.id == id) {
+            Some(i) => {
+                self.entries[i].handles -= 1;
+                if self.entries[i].handles == 0 {
+                    self.entries.swap_remove(i);
+                }
+            }
+            None => {}
+        }
+    }
+
+    // Returns current position and short name of the entry or None if it was removed
+    pub(crate) fn get(&self, id: u32) -> Option<(u64, [u8; 11])> {
+        match self.entries.iter().find(|e| e.id == id) {
+            Some(e) if !e.removed => Some((e.pos, e.name)),
+            _ => None,
+        }
+    }
+
+    // Checks if the entry at `pos` is used by any open handle
+    pub(crate) fn is_open(&self, pos: u64) -> bool {
+        self.entries.iter().any(|e| e.pos == pos && !e.removed)
+    }
+
+    pub(crate) fn entry_removed(&mut self, pos: u64) {
+        for e in self.entries.iter_mut().filter(|e| e.pos == pos) {
+            e.removed = true;
+        }
+    }
+
+    pub(crate) fn entry_moved(&mut self, old_pos: u64, new_pos: u64, new_name: [u8; 11]) {
+        for e in self.entries.iter_mut().filter(|e| e.pos == old_pos && !e.removed) {
+            e.pos = new_pos;
+            e.name = new_name;
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use alloc::boxed::Box;
+use alloc::collections::btree_map::{BTreeMap, Entry};
+use core::cmp;
+use io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+
+use fs::ReadSeek;
+
+const BLOCK_SIZE: usize = 512;
+
+/// In-memory copy-on-write store used by `FileSystem::new_overlay`.
+///
+/// Every block modified through the filesystem is kept in the store so the base device is never
+/// written. The store can be used again to mount the modified volume or cleared to go back to the
+/// base image.
+#[derive(Clone, Default)]
+pub struct OverlayStore {
+    blocks: BTreeMap<u64, Box<[u8; BLOCK_SIZE]>>,
+}
+
+impl OverlayStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns number of bytes of the base device that have been modified (rounded up to 512 byte
+    /// blocks).
+    pub fn modified_size(&self) -> u64 {
+        self.blocks.len() as u64 * BLOCK_SIZE as u64
+    }
+
+    /// Discards all modifications.
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+    }
+}
+
+/// Storage combining read-only base device and `OverlayStore` (used by
+/// `FileSystem::new_overlay`).
+pub struct OverlayDisk<'a> {
+    base: &'a mut ReadSeek,
+    store: &'a mut OverlayStore,
+    pos: u64,
+}
+
+impl<'a> OverlayDisk<'a> {
+    pub(crate) fn new(base: &'a mut ReadSeek, store: &'a mut OverlayStore) -> Self {
+        OverlayDisk {
+            base,
+            store,
+            pos: 0,
+        }
+    }
+}
+
+impl<'a> Read for OverlayDisk<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let block = self.pos / BLOCK_SIZE as u64;
+        let offset_in_block = (self.pos % BLOCK_SIZE as u64) as usize;
+        let read_size = cmp::min(buf.len(), BLOCK_SIZE - offset_in_block);
+        let read_bytes = match self.store.blocks.get(&block) {
+            Some(data) => {
+                let src = &data[offset_in_block..offset_in_block + read_size];
+                buf[..read_size].copy_from_slice(src);
+                read_size
+            }
+            None => {
+                self.base.seek(SeekFrom::Start(self.pos))?;
+                self.base.read(&mut buf[..read_size])?
+            }
+        };
+        self.pos += read_bytes as u64;
+        Ok(read_bytes)
+    }
+}
+
+impl<'a> Write for OverlayDisk<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let block = self.pos / BLOCK_SIZE as u64;
+        let offset_in_block = (self.pos % BLOCK_SIZE as u64) as usize;
+        let write_size = cmp::min(buf.len(), BLOCK_SIZE - offset_in_block);
+        let data = match self.store.blocks.entry(block) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => {
+                // copy block from base on first write (part past the end of base stays zeroed)
+                let mut data = Box::new([0u8; BLOCK_SIZE]);
+                self.base.seek(SeekFrom::Start(block * BLOCK_SIZE as u64))?;
+                let mut filled = 0;
+                while filled < BLOCK_SIZE {
+                    match self.base.read(&mut data[filled..])? {
+                        0 => break,
+                        n => filled += n,
+                    }
+                }
+                e.insert(data)
+            }
+        };
+        data[offset_in_block..offset_in_block + write_size].copy_from_slice(&buf[..write_size]);
+        self.pos += write_size as u64;
+        Ok(write_size)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // base is never written and store is in memory
+        Ok(())
+    }
+}
+
+impl<'a> Seek for OverlayDisk<'a> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(n) => n as i64,
+            SeekFrom::Current(n) => self.pos as i64 + n,
+            SeekFrom::End(n) => self.base.seek(SeekFrom::End(n))? as i64,
+        };
+        if new_pos < 0 {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "invalid seek"));
+        }
+        self.pos = new_pos as u64;
+        Ok(self.pos)
+    }
+}
@@ -1,7 +1,9 @@
 use byteorder::LittleEndian;
+use cancel::{check_cancelled, CancellationToken};
 use byteorder_ext::{ReadBytesExt, WriteBytesExt};
-use fs::{DiskSlice, FatType, FsStatusFlags, ReadSeek};
+use fs::{DiskSlice, FatType, FsStatusFlags, ReadWriteSeek};
 use io::{self, *};
+use units::Cluster;
 
 struct Fat<T> {
     #[allow(dead_code)]
@@ -12,6 +14,9 @@ type Fat12 = Fat<u8>;
 type Fat16 = Fat<u16>;
 type Fat32 = Fat<u32>;
 
+// First two FAT entries are reserved and do not describe data clusters
+pub(crate) const RESERVED_FAT_ENTRIES: u32 = 2;
+
 #[derive(Debug, Clone, Copy)]
 enum FatValue {
     Free,
@@ -21,89 +26,280 @@ enum FatValue {
 }
 
 trait FatTrait {
-    fn get(fat: &mut ReadSeek, cluster: u32) -> io::Result<FatValue>;
-    fn set(fat: &mut DiskSlice, cluster: u32, value: FatValue) -> io::Result<()>;
-    fn find_free(fat: &mut ReadSeek, hint_cluster: u32) -> io::Result<u32>;
-    fn get_raw(fat: &mut ReadSeek, cluster: u32) -> io::Result<u32>;
+    fn get<IO: ReadWriteSeek>(fat: &mut DiskSlice<IO>, cluster: Cluster) -> io::Result<FatValue>;
+    fn set<IO: ReadWriteSeek>(
+        fat: &mut DiskSlice<IO>,
+        cluster: Cluster,
+        value: FatValue,
+    ) -> io::Result<()>;
+    fn find_free<IO: ReadWriteSeek>(
+        fat: &mut DiskSlice<IO>,
+        start_cluster: Cluster,
+        end_cluster: Cluster,
+        cancel: &CancellationToken,
+    ) -> io::Result<Cluster>;
+    fn get_raw<IO: ReadWriteSeek>(fat: &mut DiskSlice<IO>, cluster: Cluster) -> io::Result<u32>;
+    fn count_free<IO: ReadWriteSeek>(
+        fat: &mut DiskSlice<IO>,
+        end_cluster: u32,
+        cancel: &CancellationToken,
+    ) -> io::Result<u32>;
 }
 
-fn read_fat(fat: &mut ReadSeek, fat_type: FatType, cluster: u32) -> io::Result<FatValue> {
-    match fat_type {
-        FatType::Fat12 => Fat12::get(fat, cluster),
-        FatType::Fat16 => Fat16::get(fat, cluster),
-        FatType::Fat32 => Fat32::get(fat, cluster),
-    }
+// FAT accessors of one FAT type resolved when filesystem is mounted, so traversing a cluster
+// chain, allocating clusters and counting free clusters do not dispatch on FAT type for every
+// cluster
+pub(crate) struct FatOps<IO: ReadWriteSeek> {
+    fat_type: FatType,
+    get_raw: fn(&mut DiskSlice<IO>, Cluster) -> io::Result<u32>,
+    get: fn(&mut DiskSlice<IO>, Cluster) -> io::Result<FatValue>,
+    set: fn(&mut DiskSlice<IO>, Cluster, FatValue) -> io::Result<()>,
+    find_free: fn(&mut DiskSlice<IO>, Cluster, Cluster, &CancellationToken) -> io::Result<Cluster>,
+    count_free: fn(&mut DiskSlice<IO>, u32, &CancellationToken) -> io::Result<u32>,
 }
 
-fn write_fat(
-    fat: &mut DiskSlice,
-    fat_type: FatType,
-    cluster: u32,
-    value: FatValue,
-) -> io::Result<()> {
-    match fat_type {
-        FatType::Fat12 => Fat12::set(fat, cluster, value),
-        FatType::Fat16 => Fat16::set(fat, cluster, value),
-        FatType::Fat32 => Fat32::set(fat, cluster, value),
+// Derived implementations would require `IO: Clone`
+impl<IO: ReadWriteSeek> Clone for FatOps<IO> {
+    fn clone(&self) -> Self {
+        *self
     }
 }
 
-fn get_next_cluster(
-    fat: &mut ReadSeek,
-    fat_type: FatType,
-    cluster: u32,
-) -> io::Result<Option<u32>> {
-    let val = read_fat(fat, fat_type, cluster)?;
-    match val {
-        FatValue::Data(n) => Ok(Some(n)),
-        _ => Ok(None),
+impl<IO: ReadWriteSeek> Copy for FatOps<IO> {}
+
+impl<IO: ReadWriteSeek> FatOps<IO> {
+    pub(crate) fn new(fat_type: FatType) -> FatOps<IO> {
+        match fat_type {
+            FatType::Fat12 => Self::of::<Fat12>(fat_type),
+            FatType::Fat16 => Self::of::<Fat16>(fat_type),
+            FatType::Fat32 => Self::of::<Fat32>(fat_type),
+        }
+    }
+
+    fn of<T: FatTrait>(fat_type: FatType) -> FatOps<IO> {
+        FatOps {
+            fat_type,
+            get_raw: T::get_raw,
+            get: T::get,
+            set: T::set,
+            find_free: T::find_free,
+            count_free: T::count_free,
+        }
     }
 }
 
-fn find_free_cluster(fat: &mut ReadSeek, fat_type: FatType, cluster: u32) -> io::Result<u32> {
-    match fat_type {
-        FatType::Fat12 => Fat12::find_free(fat, cluster),
-        FatType::Fat16 => Fat16::find_free(fat, cluster),
-        FatType::Fat32 => Fat32::find_free(fat, cluster),
+impl FatValue {
+    fn from_raw(raw: u32, fat_type: FatType) -> FatValue {
+        let (eoc_min, _) = fat_type.end_of_chain();
+        match raw {
+            0 => FatValue::Free,
+            n if n == fat_type.bad_cluster() => FatValue::Bad,
+            n if n >= eoc_min => FatValue::EndOfChain,
+            n => FatValue::Data(n),
+        }
+    }
+
+    fn to_raw<IO: ReadWriteSeek>(&self, fat: &DiskSlice<IO>, fat_type: FatType) -> u32 {
+        match *self {
+            FatValue::Free => 0,
+            FatValue::Bad => fat_type.bad_cluster(),
+            FatValue::EndOfChain => fat.end_of_chain(fat_type),
+            FatValue::Data(n) => n,
+        }
     }
 }
 
-pub(crate) fn alloc_cluster(
-    fat: &mut DiskSlice,
-    fat_type: FatType,
-    prev_cluster: Option<u32>,
-) -> io::Result<u32> {
-    let new_cluster = find_free_cluster(fat, fat_type, 2)?;
-    write_fat(fat, fat_type, new_cluster, FatValue::EndOfChain)?;
+fn no_free_cluster_error() -> io::Error {
+    io::Error::new(ErrorKind::NotEnoughSpace, "no free cluster")
+}
+
+fn invalid_chain_error() -> io::Error {
+    io::Error::new(ErrorKind::CorruptedFile, "invalid cluster chain")
+}
+
+pub(crate) fn alloc_cluster<IO: ReadWriteSeek>(
+    fat: &mut DiskSlice<IO>,
+    ops: FatOps<IO>,
+    prev_cluster: Option<Cluster>,
+    hint: Option<Cluster>,
+    total_clusters: u32,
+    cancel: &CancellationToken,
+) -> io::Result<Cluster> {
+    // search from hint to the end of FAT and then wrap around to the first data cluster
+    let first_cluster = Cluster(RESERVED_FAT_ENTRIES);
+    let end_cluster = Cluster(total_clusters + RESERVED_FAT_ENTRIES);
+    let start_cluster = match hint {
+        Some(n) if n >= first_cluster && n < end_cluster => n,
+        _ => first_cluster,
+    };
+    let new_cluster = match (ops.find_free)(fat, start_cluster, end_cluster, cancel) {
+        Ok(n) => n,
+        Err(ref err)
+            if err.kind() == ErrorKind::NotEnoughSpace && start_cluster > first_cluster =>
+        {
+            (ops.find_free)(fat, first_cluster, start_cluster, cancel)?
+        }
+        Err(err) => return Err(err),
+    };
+    (ops.set)(fat, new_cluster, FatValue::EndOfChain)?;
     match prev_cluster {
-        Some(n) => write_fat(fat, fat_type, n, FatValue::Data(new_cluster))?,
+        Some(n) => (ops.set)(fat, n, FatValue::Data(new_cluster.0))?,
         None => {}
     }
     Ok(new_cluster)
 }
 
-pub(crate) fn read_fat_flags(fat: &mut DiskSlice, fat_type: FatType) -> io::Result<FsStatusFlags> {
-    // check MSB (except in FAT12)
-    let val = match fat_type {
-        FatType::Fat12 => 0xFFF,
-        FatType::Fat16 => Fat16::get_raw(fat, 1)?,
-        FatType::Fat32 => Fat32::get_raw(fat, 1)?,
-    };
-    let dirty = match fat_type {
-        FatType::Fat12 => false,
-        FatType::Fat16 => val & (1 << 15) == 0,
-        FatType::Fat32 => val & (1 << 27) == 0,
+// Links `cluster` to `next` or marks it as the end of its chain
+#[cfg(feature = "splice")]
+pub(crate) fn set_next_cluster<IO: ReadWriteSeek>(
+    fat: &mut DiskSlice<IO>,
+    ops: FatOps<IO>,
+    cluster: Cluster,
+    next: Option<Cluster>,
+) -> io::Result<()> {
+    let value = match next {
+        Some(n) => FatValue::Data(n.0),
+        None => FatValue::EndOfChain,
     };
-    let io_error = match fat_type {
-        FatType::Fat12 => false,
-        FatType::Fat16 => val & (1 << 14) == 0,
-        FatType::Fat32 => val & (1 << 26) == 0,
+    (ops.set)(fat, cluster, value)
+}
+
+pub(crate) fn count_free_clusters<IO: ReadWriteSeek>(
+    fat: &mut DiskSlice<IO>,
+    ops: FatOps<IO>,
+    total_clusters: u32,
+    cancel: &CancellationToken,
+) -> io::Result<u32> {
+    let end_cluster = total_clusters + RESERVED_FAT_ENTRIES;
+    (ops.count_free)(fat, end_cluster, cancel)
+}
+
+pub(crate) fn is_free_cluster<IO: ReadWriteSeek>(
+    fat: &mut DiskSlice<IO>,
+    ops: FatOps<IO>,
+    cluster: Cluster,
+) -> io::Result<bool> {
+    match (ops.get)(fat, cluster)? {
+        FatValue::Free => Ok(true),
+        _ => Ok(false),
+    }
+}
+
+// Returns number of clusters in the chain or None if chain contains free, bad or invalid
+// clusters, does not end with end of chain marker or contains a loop
+pub(crate) fn valid_chain_length<IO: ReadWriteSeek>(
+    fat: &mut DiskSlice<IO>,
+    ops: FatOps<IO>,
+    first_cluster: Cluster,
+    total_clusters: u32,
+    cancel: &CancellationToken,
+) -> io::Result<Option<u32>> {
+    let end_cluster = total_clusters + RESERVED_FAT_ENTRIES;
+    let mut cluster = first_cluster;
+    let mut num_clusters = 0;
+    loop {
+        let valid_cluster = cluster.0 >= RESERVED_FAT_ENTRIES && cluster.0 < end_cluster;
+        // chain longer than number of clusters must contain a loop
+        if !valid_cluster || num_clusters == total_clusters {
+            return Ok(None);
+        }
+        num_clusters += 1;
+        check_cancelled(cancel)?;
+        match (ops.get)(fat, cluster)? {
+            FatValue::Data(n) => cluster = Cluster(n),
+            FatValue::EndOfChain => return Ok(Some(num_clusters)),
+            FatValue::Free | FatValue::Bad => return Ok(None),
+        }
+    }
+}
+
+// Returns mask of FAT[1] bits used as volume status flags
+fn fat_flags_mask(fat_type: FatType) -> u32 {
+    match fat_type {
+        FatType::Fat12 => 0,
+        _ => {
+            let clean_shutdown_bit = (fat_type.entry_mask() + 1) >> 1;
+            clean_shutdown_bit | (clean_shutdown_bit >> 1)
+        }
+    }
+}
+
+// Returns expected values of FAT[0] (media descriptor with other bits set) and FAT[1] (end of
+// chain marker ignoring status flags)
+fn expected_reserved_fat_entries(fat_type: FatType, media: u8) -> (u32, u32) {
+    let mask = fat_type.entry_mask();
+    ((mask & !0xFF) | media as u32, mask & !fat_flags_mask(fat_type))
+}
+
+pub(crate) fn reserved_fat_entries_valid<IO: ReadWriteSeek>(
+    fat: &mut DiskSlice<IO>,
+    ops: FatOps<IO>,
+    media: u8,
+) -> io::Result<bool> {
+    let (fat_0, fat_1) = expected_reserved_fat_entries(ops.fat_type, media);
+    let flags_mask = fat_flags_mask(ops.fat_type);
+    Ok((ops.get_raw)(fat, Cluster(0))? == fat_0
+        && (ops.get_raw)(fat, Cluster(1))? & !flags_mask == fat_1)
+}
+
+pub(crate) fn fix_reserved_fat_entries<IO: ReadWriteSeek>(
+    fat: &mut DiskSlice<IO>,
+    ops: FatOps<IO>,
+    media: u8,
+) -> io::Result<()> {
+    let (fat_0, fat_1) = expected_reserved_fat_entries(ops.fat_type, media);
+    // keep volume status flags
+    let flags = (ops.get_raw)(fat, Cluster(1))? & fat_flags_mask(ops.fat_type);
+    (ops.set)(fat, Cluster(0), FatValue::Data(fat_0))?;
+    (ops.set)(fat, Cluster(1), FatValue::Data(fat_1 | flags))
+}
+
+pub(crate) fn read_fat_flags<IO: ReadWriteSeek>(
+    fat: &mut DiskSlice<IO>,
+    ops: FatOps<IO>,
+) -> io::Result<FsStatusFlags> {
+    // check MSB (except in FAT12)
+    let fat_type = ops.fat_type;
+    if fat_type == FatType::Fat12 {
+        return Ok(FsStatusFlags::default());
+    }
+    let val = (ops.get_raw)(fat, Cluster(1))?;
+    // flags are stored in two most significant bits of cluster number
+    let clean_shutdown_bit = (fat_type.entry_mask() + 1) >> 1;
+    let no_io_error_bit = clean_shutdown_bit >> 1;
+    Ok(FsStatusFlags {
+        dirty: val & clean_shutdown_bit == 0,
+        io_error: val & no_io_error_bit == 0,
+    })
+}
+
+// Sets or clears the I/O error flag stored in FAT[1] (FAT12 has no flags)
+pub(crate) fn write_fat_io_error_flag<IO: ReadWriteSeek>(
+    fat: &mut DiskSlice<IO>,
+    ops: FatOps<IO>,
+    io_error: bool,
+) -> io::Result<()> {
+    let fat_type = ops.fat_type;
+    if fat_type == FatType::Fat12 {
+        return Ok(());
+    }
+    // flag bit is cleared when an error was encountered
+    let no_io_error_bit = (fat_type.entry_mask() + 1) >> 2;
+    let val = (ops.get_raw)(fat, Cluster(1))?;
+    let new_val = if io_error {
+        val & !no_io_error_bit
+    } else {
+        val | no_io_error_bit
     };
-    Ok(FsStatusFlags { dirty, io_error })
+    if new_val != val {
+        (ops.set)(fat, Cluster(1), FatValue::Data(new_val))?;
+    }
+    Ok(())
 }
 
 impl FatTrait for Fat12 {
-    fn get_raw(fat: &mut ReadSeek, cluster: u32) -> io::Result<u32> {
+    fn get_raw<IO: ReadWriteSeek>(fat: &mut DiskSlice<IO>, cluster: Cluster) -> io::Result<u32> {
+        let Cluster(cluster) = cluster;
         let fat_offset = cluster + (cluster / 2);
         fat.seek(io::SeekFrom::Start(fat_offset as u64))?;
         let packed_val = fat.read_u16::<LittleEndian>()?;
@@ -113,23 +309,18 @@ impl FatTrait for Fat12 {
         } as u32)
     }
 
-    fn get(fat: &mut ReadSeek, cluster: u32) -> io::Result<FatValue> {
+    fn get<IO: ReadWriteSeek>(fat: &mut DiskSlice<IO>, cluster: Cluster) -> io::Result<FatValue> {
         let val = Self::get_raw(fat, cluster)?;
-        Ok(match val {
-            0 => FatValue::Free,
-            0xFF7 => FatValue::Bad,
-            0xFF8...0xFFF => FatValue::EndOfChain,
-            n => FatValue::Data(n as u32),
-        })
+        Ok(FatValue::from_raw(val, FatType::Fat12))
     }
 
-    fn set(fat: &mut DiskSlice, cluster: u32, value: FatValue) -> io::Result<()> {
-        let raw_val = match value {
-            FatValue::Free => 0,
-            FatValue::Bad => 0xFF7,
-            FatValue::EndOfChain => 0xFFF,
-            FatValue::Data(n) => n as u16,
-        };
+    fn set<IO: ReadWriteSeek>(
+        fat: &mut DiskSlice<IO>,
+        cluster: Cluster,
+        value: FatValue,
+    ) -> io::Result<()> {
+        let raw_val = value.to_raw(fat, FatType::Fat12) as u16;
+        let Cluster(cluster) = cluster;
         let fat_offset = cluster + (cluster / 2);
         fat.seek(io::SeekFrom::Start(fat_offset as u64))?;
         let old_packed = fat.read_u16::<LittleEndian>()?;
@@ -142,20 +333,33 @@ impl FatTrait for Fat12 {
         Ok(())
     }
 
-    fn find_free(fat: &mut ReadSeek, hint_cluster: u32) -> io::Result<u32> {
-        let mut cluster = hint_cluster;
+    fn find_free<IO: ReadWriteSeek>(
+        fat: &mut DiskSlice<IO>,
+        start_cluster: Cluster,
+        end_cluster: Cluster,
+        cancel: &CancellationToken,
+    ) -> io::Result<Cluster> {
+        let Cluster(end_cluster) = end_cluster;
+        let Cluster(mut cluster) = start_cluster;
+        if cluster >= end_cluster {
+            return Err(no_free_cluster_error());
+        }
         let fat_offset = cluster + (cluster / 2);
         fat.seek(io::SeekFrom::Start(fat_offset as u64))?;
         let mut packed_val = fat.read_u16::<LittleEndian>()?;
         loop {
+            check_cancelled(cancel)?;
             let val = match cluster & 1 {
                 0 => packed_val & 0x0FFF,
                 _ => packed_val >> 4,
             };
             if val == 0 {
-                return Ok(cluster);
+                return Ok(Cluster(cluster));
             }
             cluster += 1;
+            if cluster == end_cluster {
+                return Err(no_free_cluster_error());
+            }
             packed_val = match cluster & 1 {
                 0 => fat.read_u16::<LittleEndian>()?,
                 _ => {
@@ -165,137 +369,216 @@ impl FatTrait for Fat12 {
             };
         }
     }
+
+    fn count_free<IO: ReadWriteSeek>(
+        fat: &mut DiskSlice<IO>,
+        end_cluster: u32,
+        cancel: &CancellationToken,
+    ) -> io::Result<u32> {
+        let mut count = 0;
+        for cluster in RESERVED_FAT_ENTRIES..end_cluster {
+            check_cancelled(cancel)?;
+            if Self::get_raw(fat, Cluster(cluster))? == 0 {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
 }
 
 impl FatTrait for Fat16 {
-    fn get_raw(fat: &mut ReadSeek, cluster: u32) -> io::Result<u32> {
-        fat.seek(io::SeekFrom::Start((cluster * 2) as u64))?;
+    fn get_raw<IO: ReadWriteSeek>(fat: &mut DiskSlice<IO>, cluster: Cluster) -> io::Result<u32> {
+        fat.seek(io::SeekFrom::Start((cluster.0 * 2) as u64))?;
         Ok(fat.read_u16::<LittleEndian>()? as u32)
     }
 
-    fn get(fat: &mut ReadSeek, cluster: u32) -> io::Result<FatValue> {
+    fn get<IO: ReadWriteSeek>(fat: &mut DiskSlice<IO>, cluster: Cluster) -> io::Result<FatValue> {
         let val = Self::get_raw(fat, cluster)?;
-        Ok(match val {
-            0 => FatValue::Free,
-            0xFFF7 => FatValue::Bad,
-            0xFFF8...0xFFFF => FatValue::EndOfChain,
-            n => FatValue::Data(n as u32),
-        })
+        Ok(FatValue::from_raw(val, FatType::Fat16))
     }
 
-    fn set(fat: &mut DiskSlice, cluster: u32, value: FatValue) -> io::Result<()> {
-        fat.seek(io::SeekFrom::Start((cluster * 2) as u64))?;
-        let raw_val = match value {
-            FatValue::Free => 0,
-            FatValue::Bad => 0xFFF7,
-            FatValue::EndOfChain => 0xFFFF,
-            FatValue::Data(n) => n as u16,
-        };
+    fn set<IO: ReadWriteSeek>(
+        fat: &mut DiskSlice<IO>,
+        cluster: Cluster,
+        value: FatValue,
+    ) -> io::Result<()> {
+        fat.seek(io::SeekFrom::Start((cluster.0 * 2) as u64))?;
+        let raw_val = value.to_raw(fat, FatType::Fat16) as u16;
         fat.write_u16::<LittleEndian>(raw_val)?;
         Ok(())
     }
 
-    fn find_free(fat: &mut ReadSeek, hint_cluster: u32) -> io::Result<u32> {
-        let mut cluster = hint_cluster;
+    fn find_free<IO: ReadWriteSeek>(
+        fat: &mut DiskSlice<IO>,
+        start_cluster: Cluster,
+        end_cluster: Cluster,
+        cancel: &CancellationToken,
+    ) -> io::Result<Cluster> {
+        let Cluster(end_cluster) = end_cluster;
+        let Cluster(mut cluster) = start_cluster;
         fat.seek(io::SeekFrom::Start((cluster * 2) as u64))?;
-        loop {
+        while cluster < end_cluster {
+            check_cancelled(cancel)?;
             let val = fat.read_u16::<LittleEndian>()?;
             if val == 0 {
-                return Ok(cluster);
+                return Ok(Cluster(cluster));
             }
             cluster += 1;
         }
+        Err(no_free_cluster_error())
+    }
+
+    fn count_free<IO: ReadWriteSeek>(
+        fat: &mut DiskSlice<IO>,
+        end_cluster: u32,
+        cancel: &CancellationToken,
+    ) -> io::Result<u32> {
+        let mut count = 0;
+        fat.seek(io::SeekFrom::Start((RESERVED_FAT_ENTRIES * 2) as u64))?;
+        for _ in RESERVED_FAT_ENTRIES..end_cluster {
+            check_cancelled(cancel)?;
+            if fat.read_u16::<LittleEndian>()? == 0 {
+                count += 1;
+            }
+        }
+        Ok(count)
     }
 }
 
 impl FatTrait for Fat32 {
-    fn get_raw(fat: &mut ReadSeek, cluster: u32) -> io::Result<u32> {
-        fat.seek(io::SeekFrom::Start((cluster * 4) as u64))?;
-        Ok(fat.read_u32::<LittleEndian>()? & 0x0FFFFFFF)
+    fn get_raw<IO: ReadWriteSeek>(fat: &mut DiskSlice<IO>, cluster: Cluster) -> io::Result<u32> {
+        fat.seek(io::SeekFrom::Start((cluster.0 * 4) as u64))?;
+        Ok(fat.read_u32::<LittleEndian>()? & FatType::Fat32.entry_mask())
     }
 
-    fn get(fat: &mut ReadSeek, cluster: u32) -> io::Result<FatValue> {
+    fn get<IO: ReadWriteSeek>(fat: &mut DiskSlice<IO>, cluster: Cluster) -> io::Result<FatValue> {
         let val = Self::get_raw(fat, cluster)?;
-        Ok(match val {
-            0 => FatValue::Free,
-            0x0FFFFFF7 => FatValue::Bad,
-            0x0FFFFFF8...0x0FFFFFFF => FatValue::EndOfChain,
-            n => FatValue::Data(n as u32),
-        })
+        Ok(FatValue::from_raw(val, FatType::Fat32))
     }
 
-    fn set(fat: &mut DiskSlice, cluster: u32, value: FatValue) -> io::Result<()> {
-        fat.seek(io::SeekFrom::Start((cluster * 4) as u64))?;
-        let raw_val = match value {
-            FatValue::Free => 0,
-            FatValue::Bad => 0x0FFFFFF7,
-            FatValue::EndOfChain => 0x0FFFFFFF,
-            FatValue::Data(n) => n,
-        };
+    fn set<IO: ReadWriteSeek>(
+        fat: &mut DiskSlice<IO>,
+        cluster: Cluster,
+        value: FatValue,
+    ) -> io::Result<()> {
+        fat.seek(io::SeekFrom::Start((cluster.0 * 4) as u64))?;
+        let raw_val = value.to_raw(fat, FatType::Fat32);
         fat.write_u32::<LittleEndian>(raw_val)?;
         Ok(())
     }
 
-    fn find_free(fat: &mut ReadSeek, hint_cluster: u32) -> io::Result<u32> {
-        let mut cluster = hint_cluster;
+    fn find_free<IO: ReadWriteSeek>(
+        fat: &mut DiskSlice<IO>,
+        start_cluster: Cluster,
+        end_cluster: Cluster,
+        cancel: &CancellationToken,
+    ) -> io::Result<Cluster> {
+        let Cluster(end_cluster) = end_cluster;
+        let Cluster(mut cluster) = start_cluster;
         fat.seek(io::SeekFrom::Start((cluster * 4) as u64))?;
-        loop {
-            let val = fat.read_u32::<LittleEndian>()? & 0x0FFFFFFF;
+        while cluster < end_cluster {
+            check_cancelled(cancel)?;
+            let val = fat.read_u32::<LittleEndian>()? & FatType::Fat32.entry_mask();
             if val == 0 {
-                return Ok(cluster);
+                return Ok(Cluster(cluster));
             }
             cluster += 1;
         }
+        Err(no_free_cluster_error())
+    }
+
+    fn count_free<IO: ReadWriteSeek>(
+        fat: &mut DiskSlice<IO>,
+        end_cluster: u32,
+        cancel: &CancellationToken,
+    ) -> io::Result<u32> {
+        let mut count = 0;
+        fat.seek(io::SeekFrom::Start((RESERVED_FAT_ENTRIES * 4) as u64))?;
+        for _ in RESERVED_FAT_ENTRIES..end_cluster {
+            check_cancelled(cancel)?;
+            if fat.read_u32::<LittleEndian>()? & FatType::Fat32.entry_mask() == 0 {
+                count += 1;
+            }
+        }
+        Ok(count)
     }
 }
 
-pub(crate) struct ClusterIterator<'a, 'b: 'a> {
-    fat: DiskSlice<'a, 'b>,
-    fat_type: FatType,
-    cluster: Option<u32>,
+/// Iterator over clusters in a cluster chain.
+///
+/// Returned by `FileSystem::cluster_iter`.
+pub struct ClusterIterator<'a, IO: ReadWriteSeek + 'a> {
+    fat: DiskSlice<'a, IO>,
+    ops: FatOps<IO>,
+    cluster: Option<Cluster>,
     err: bool,
+    // strict mount profile - chains ending on a free or bad cluster are errors
+    strict: bool,
+    end_cluster: Cluster,
+    cancel: &'static CancellationToken,
 }
 
-impl<'a, 'b> ClusterIterator<'a, 'b> {
+impl<'a, IO: ReadWriteSeek> ClusterIterator<'a, IO> {
     pub(crate) fn new(
-        fat: DiskSlice<'a, 'b>,
-        fat_type: FatType,
-        cluster: u32,
-    ) -> ClusterIterator<'a, 'b> {
+        fat: DiskSlice<'a, IO>,
+        ops: FatOps<IO>,
+        cluster: Cluster,
+        strict: bool,
+        end_cluster: Cluster,
+        cancel: &'static CancellationToken,
+    ) -> ClusterIterator<'a, IO> {
         ClusterIterator {
             fat: fat,
-            fat_type: fat_type,
+            ops: ops,
             cluster: Some(cluster),
             err: false,
+            strict,
+            end_cluster,
+            cancel,
         }
     }
 
-    pub(crate) fn truncate(&mut self) -> io::Result<()> {
+    /// Marks current cluster as end of chain and frees all following clusters.
+    ///
+    /// Returns number of freed clusters.
+    pub(crate) fn truncate(&mut self) -> io::Result<u32> {
         match self.cluster {
             Some(n) => {
-                write_fat(&mut self.fat, self.fat_type, n, FatValue::EndOfChain)?;
-                self.next();
+                // read the rest of the chain before it gets cut off
+                match self.next() {
+                    Some(Err(err)) => return Err(err),
+                    _ => {}
+                }
+                (self.ops.set)(&mut self.fat, n, FatValue::EndOfChain)?;
                 self.free()
             }
-            None => Ok(()),
+            None => Ok(0),
         }
     }
 
-    pub(crate) fn free(&mut self) -> io::Result<()> {
+    /// Frees current cluster and all following clusters.
+    ///
+    /// Returns number of freed clusters.
+    pub(crate) fn free(&mut self) -> io::Result<u32> {
+        let mut num_free = 0;
         loop {
             let prev = self.cluster;
-            self.next();
+            match self.next() {
+                Some(Err(err)) => return Err(err),
+                _ => {}
+            }
             match prev {
-                Some(n) => write_fat(&mut self.fat, self.fat_type, n, FatValue::Free)?,
+                Some(n) => (self.ops.set)(&mut self.fat, n, FatValue::Free)?,
                 None => break,
             };
+            num_free += 1;
         }
-        Ok(())
+        Ok(num_free)
     }
 }
 
-impl<'a, 'b> Iterator for ClusterIterator<'a, 'b> {
-    type Item = io::Result<u32>;
+impl<'a, IO: ReadWriteSeek> Iterator for ClusterIterator<'a, IO> {
+    type Item = io::Result<Cluster>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.err {
@@ -303,9 +586,28 @@ impl<'a, 'b> Iterator for ClusterIterator<'a, 'b> {
         }
         match self.cluster {
             Some(current_cluster) => {
-                self.cluster = match get_next_cluster(&mut self.fat, self.fat_type, current_cluster)
-                {
-                    Ok(next_cluster) => next_cluster,
+                match check_cancelled(self.cancel) {
+                    Err(err) => {
+                        self.err = true;
+                        return Some(Err(err));
+                    }
+                    Ok(()) => {}
+                }
+                self.cluster = match (self.ops.get)(&mut self.fat, current_cluster) {
+                    Ok(FatValue::Data(n))
+                        if self.strict
+                            && (n < RESERVED_FAT_ENTRIES || n >= self.end_cluster.0) =>
+                    {
+                        self.err = true;
+                        return Some(Err(invalid_chain_error()));
+                    }
+                    Ok(FatValue::Data(n)) => Some(Cluster(n)),
+                    Ok(FatValue::EndOfChain) => None,
+                    Ok(_) if self.strict => {
+                        self.err = true;
+                        return Some(Err(invalid_chain_error()));
+                    }
+                    Ok(_) => None,
                     Err(err) => {
                         self.err = true;
                         return Some(Err(err));
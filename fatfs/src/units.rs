@@ -0,0 +1,32 @@
+use core::fmt;
+
+/// Number of a cluster.
+///
+/// Data clusters are numbered from 2 (numbers 0 and 1 belong to reserved FAT entries). Distinct
+/// type makes it impossible to pass a cluster number where a sector number or a byte offset is
+/// expected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Cluster(pub u32);
+
+impl Cluster {
+    /// Returns the next cluster number (not necessarily the next cluster in a chain).
+    pub fn next(self) -> Cluster {
+        Cluster(self.0 + 1)
+    }
+}
+
+impl fmt::Display for Cluster {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Number of a sector counted from the beginning of the volume.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Sector(pub u64);
+
+impl fmt::Display for Sector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
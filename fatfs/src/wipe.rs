@@ -0,0 +1,70 @@
+use io::{self, Seek, SeekFrom, Write};
+
+use cancel::check_cancelled;
+use dir::Dir;
+use dir_entry::DIR_ENTRY_SIZE;
+use fs::{FileSystem, ReadWriteSeek};
+use table::is_free_cluster;
+use units::Cluster;
+
+impl<IO: ReadWriteSeek> FileSystem<IO> {
+    /// Overwrites every free data cluster with `pattern` byte.
+    ///
+    /// Meant for sanitizing the device before it is decommissioned: contents of removed and
+    /// truncated files stay in free clusters until they are reused. Used clusters and filesystem
+    /// metadata are not touched. Names of removed files are left in directories - use
+    /// `wipe_deleted_entries` to clear them too.
+    ///
+    /// Returns number of overwritten clusters.
+    pub fn wipe_free_space(&self, pattern: u8) -> io::Result<u32> {
+        let cancel = self.options.cancellation_token;
+        let mut fat = self.fat_slice();
+        let mut wiped = 0;
+        for cluster in 2..self.total_clusters + 2 {
+            check_cancelled(cancel)?;
+            if is_free_cluster(&mut fat, self.fat_ops, Cluster(cluster))? {
+                self.fill_cluster(Cluster(cluster), pattern)?;
+                wiped += 1;
+            }
+        }
+        Ok(wiped)
+    }
+
+    /// Overwrites deleted directory entries in all directories with `pattern` byte.
+    ///
+    /// The first byte of every deleted entry keeps the deletion marker, so the entry stays
+    /// deleted, and the remaining 31 bytes (name, attributes, timestamps, first cluster and size)
+    /// are overwritten. Directories are scanned up to their end marker.
+    ///
+    /// Returns number of overwritten entries.
+    pub fn wipe_deleted_entries(&self, pattern: u8) -> io::Result<u32> {
+        self.wipe_deleted_entries_in(&self.root_dir(), pattern)
+    }
+
+    fn wipe_deleted_entries_in(&self, dir: &Dir<IO>, pattern: u8) -> io::Result<u32> {
+        let cancel = self.options.cancellation_token;
+        let buf = [pattern; DIR_ENTRY_SIZE as usize];
+        let mut wiped = 0;
+        for r in dir.iter_raw() {
+            let entry = r?;
+            check_cancelled(cancel)?;
+            match (entry.is_free(), entry.abs_pos()) {
+                (true, Some(pos)) => {
+                    let mut disk = self.disk.borrow_mut();
+                    disk.seek(SeekFrom::Start(pos + 1))?;
+                    disk.write_all(&buf[..entry.bytes().len() - 1])?;
+                    wiped += 1;
+                }
+                _ => {}
+            }
+        }
+        for r in dir.iter() {
+            let entry = r?;
+            let name = entry.short_file_name();
+            if entry.is_dir() && name != "." && name != ".." {
+                wiped += self.wipe_deleted_entries_in(&entry.to_dir(), pattern)?;
+            }
+        }
+        Ok(wiped)
+    }
+}
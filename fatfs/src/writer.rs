@@ -0,0 +1,113 @@
+use alloc::vec::Vec;
+use core::cmp;
+use io::{self, Seek, SeekFrom, Write};
+
+use file::File;
+use fs::ReadWriteSeek;
+
+/// A file writer buffering data until a whole cluster can be written.
+///
+/// Writing a part of a cluster requires the device to read the rest of the sector (or flash page)
+/// first. The writer collects small writes and passes data to the file in chunks ending on cluster
+/// boundaries, so byte-at-a-time writers do not cause a read-modify-write cycle for every write.
+///
+/// Buffered data is written when a cluster is filled, on `flush`, on `seek` and when the writer is
+/// dropped (errors are only logged then).
+pub struct ClusterAlignedWriter<'a, IO: ReadWriteSeek + 'a> {
+    // None only after into_inner
+    file: Option<File<'a, IO>>,
+    buf: Vec<u8>,
+    cluster_size: usize,
+    // position in file of the first buffered byte
+    pos: u64,
+}
+
+impl<'a, IO: ReadWriteSeek> ClusterAlignedWriter<'a, IO> {
+    /// Creates new writer with a buffer of the filesystem cluster size.
+    pub fn new(mut file: File<'a, IO>) -> io::Result<Self> {
+        let pos = file.seek(SeekFrom::Current(0))?;
+        let cluster_size = file.fs().cluster_size() as usize;
+        Ok(ClusterAlignedWriter {
+            file: Some(file),
+            buf: Vec::with_capacity(cluster_size),
+            cluster_size,
+            pos,
+        })
+    }
+
+    /// Returns reference to the underlying file.
+    pub fn get_ref(&self) -> &File<'a, IO> {
+        self.file.as_ref().unwrap() // SAFE: file is taken only by into_inner
+    }
+
+    /// Flushes buffered data and returns the underlying file.
+    pub fn into_inner(mut self) -> io::Result<File<'a, IO>> {
+        self.flush_buf()?;
+        Ok(self.file.take().unwrap()) // SAFE: file is taken only by into_inner
+    }
+
+    fn file_mut(&mut self) -> &mut File<'a, IO> {
+        self.file.as_mut().unwrap() // SAFE: file is taken only by into_inner
+    }
+
+    // Number of bytes from buffer start to the next cluster boundary
+    fn chunk_size(&self) -> usize {
+        let cluster_size = self.cluster_size as u64;
+        (cluster_size - self.pos % cluster_size) as usize
+    }
+
+    fn flush_buf(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            let file = self.file.as_mut().unwrap(); // SAFE: file is taken only by into_inner
+            file.write_all(&self.buf)?;
+            self.pos += self.buf.len() as u64;
+            self.buf.clear();
+        }
+        Ok(())
+    }
+}
+
+impl<'a, IO: ReadWriteSeek> Write for ClusterAlignedWriter<'a, IO> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let chunk_size = self.chunk_size();
+        if self.buf.is_empty() && buf.len() >= chunk_size {
+            // write directly to file up to the last cluster boundary
+            let cluster_size = self.cluster_size;
+            let len = chunk_size + (buf.len() - chunk_size) / cluster_size * cluster_size;
+            let written = self.file_mut().write(&buf[..len])?;
+            self.pos += written as u64;
+            return Ok(written);
+        }
+        let n = cmp::min(buf.len(), chunk_size - self.buf.len());
+        self.buf.extend_from_slice(&buf[..n]);
+        if self.buf.len() == chunk_size {
+            self.flush_buf()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_buf()?;
+        self.file_mut().flush()
+    }
+}
+
+impl<'a, IO: ReadWriteSeek> Seek for ClusterAlignedWriter<'a, IO> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.flush_buf()?;
+        self.pos = self.file_mut().seek(pos)?;
+        Ok(self.pos)
+    }
+}
+
+impl<'a, IO: ReadWriteSeek> Drop for ClusterAlignedWriter<'a, IO> {
+    fn drop(&mut self) {
+        if self.file.is_none() {
+            return;
+        }
+        match self.flush_buf() {
+            Err(err) => error!("flush failed {}", err),
+            _ => {}
+        }
+    }
+}
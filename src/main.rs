@@ -3,47 +3,716 @@ extern crate fatfs;
 
 use std::io::prelude::*;
 use std::path::Path;
-use std::{fs, io};
+use std::{cmp, env, fs, io, process};
 
-fn main() -> Result<(), io::Error> {
-    let mut data = read_file("fat32.img")?;
+const USAGE: &str = "usage:
+    fatfs-tool create <image> <size>[K|M|G]
+    fatfs-tool tree <image>
+    fatfs-tool du <image> [<path>]
+    fatfs-tool mv <image> <source> <destination>
+    fatfs-tool label <image> [<label>]
+    fatfs-tool cat <image> <path> [--offset <n>] [--len <n>]
+    fatfs-tool sync <directory> <image>[:<path>] [--delete]
+    fatfs-tool fsck <image> [--repair] [--rebuild-fsinfo]
+    fatfs-tool debug <image> (--dir <path> | --fat-chain <cluster>)";
+
+// Exit codes of fsck command (same as used by dosfsck)
+const FSCK_ERRORS_CORRECTED: i32 = 1;
+const FSCK_ERRORS_LEFT: i32 = 4;
+const USAGE_ERROR: i32 = 16;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(|s| s.as_str()) {
+        Some("create") => create(&args[1..]),
+        Some("tree") => tree(&args[1..]),
+        Some("du") => du(&args[1..]),
+        Some("mv") => mv(&args[1..]),
+        Some("label") => label(&args[1..]),
+        Some("cat") => cat(&args[1..]),
+        Some("sync") => sync(&args[1..]),
+        Some("fsck") => fsck(&args[1..]),
+        Some("debug") => debug(&args[1..]),
+        _ => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(code) => process::exit(code),
+        Err(msg) => {
+            eprintln!("{}", msg);
+            process::exit(USAGE_ERROR);
+        }
+    }
+}
+
+fn image_arg(args: &[String]) -> Result<&str, String> {
+    match args.first() {
+        Some(path) if !path.starts_with("--") => Ok(path),
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn parse_size(arg: &str) -> Option<u64> {
+    let (digits, multiplier) = match arg.chars().last() {
+        Some('K') => (&arg[..arg.len() - 1], 1024),
+        Some('M') => (&arg[..arg.len() - 1], 1024 * 1024),
+        Some('G') => (&arg[..arg.len() - 1], 1024 * 1024 * 1024),
+        _ => (arg, 1),
+    };
+    digits.parse::<u64>().ok().and_then(|n| n.checked_mul(multiplier))
+}
+
+fn create(args: &[String]) -> Result<i32, String> {
+    let path = image_arg(args)?;
+    let size = match (args.get(1).and_then(|s| parse_size(s)), args.len()) {
+        (Some(size), 2) => size,
+        _ => return Err(USAGE.to_string()),
+    };
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map_err(|err| format!("{}: {}", path, err))?;
+    // only metadata is written - the rest of the image is left for the host to allocate lazily
+    let mut disk = fatfs::AutoExtend::new(file, size).map_err(|err| err.to_string())?;
+    fatfs::format_volume(&mut disk, fatfs::FormatOptions::new()).map_err(|err| err.to_string())?;
+    // give the image its full size so it can be mounted - unwritten parts stay sparse
+    let file = disk.into_inner();
+    file.set_len(size).map_err(|err| format!("{}: {}", path, err))?;
+    println!("{}: created {} byte volume", path, size);
+    Ok(0)
+}
+
+fn tree(args: &[String]) -> Result<i32, String> {
+    let path = image_arg(args)?;
+    if args.len() != 1 {
+        return Err(USAGE.to_string());
+    }
+    let mut data = read_file(path).map_err(|err| format!("{}: {}", path, err))?;
     let mut file = basic_io::Cursor::new(&mut data[..]);
-    let fs = fatfs::FileSystem::new(&mut file).expect("failed to create fs");
-    print_fs(&fs);
-    fs.root_dir().create_file("foobar.txt").expect("failed to create");
-    print_fs(&fs);
-    Ok(())
+    let options = fatfs::FsOptions::new();
+    let fs = fatfs::FileSystem::new(&mut file, options).map_err(|err| err.to_string())?;
+    let mut lines = Vec::new();
+    let usage = walk_dir(&fs.root_dir(), 1, u64::from(fs.cluster_size()), &mut lines)?;
+    println!(
+        "/  ({} files, {} bytes, {} bytes allocated)",
+        usage.files, usage.bytes, usage.allocated
+    );
+    for line in &lines {
+        println!("{}", line);
+    }
+    println!("{} directories, {} files", usage.dirs, usage.files);
+    Ok(0)
 }
 
-fn print_fs(fs: &fatfs::FileSystem) {
-    let root = fs.root_dir();
-    println!("/");
-    for entry in root.iter() {
-        let entry = entry.expect("failed to read entry");
-        walk_file(1, entry);
+fn du(args: &[String]) -> Result<i32, String> {
+    let path = image_arg(args)?;
+    let dir_path = match (args.get(1), args.len()) {
+        (_, 1) => "",
+        (Some(dir_path), 2) => dir_path.trim_matches('/'),
+        _ => return Err(USAGE.to_string()),
+    };
+    let mut data = read_file(path).map_err(|err| format!("{}: {}", path, err))?;
+    let mut file = basic_io::Cursor::new(&mut data[..]);
+    let options = fatfs::FsOptions::new();
+    let fs = fatfs::FileSystem::new(&mut file, options).map_err(|err| err.to_string())?;
+    let dir = if dir_path.is_empty() {
+        fs.root_dir()
+    } else {
+        fs.root_dir().open_dir(dir_path).map_err(|err| format!("{}: {}", dir_path, err))?
+    };
+    println!("{:>12} {:>12} {:>13} path", "allocated", "slack", "free/slots");
+    let total = du_dir(&dir, &format!("/{}", dir_path))?;
+    let percent = match total.allocated {
+        0 => 0,
+        n => total.slack * 100 / n,
+    };
+    println!(
+        "total: {} files, {} bytes allocated, {} bytes slack ({}%), {} of {} directory slots free",
+        total.files,
+        total.allocated,
+        total.slack,
+        percent,
+        total.free_slots,
+        total.used_slots + total.free_slots
+    );
+    Ok(0)
+}
+
+// Prints slack space and directory slot usage of subdirectories and then of `dir` itself
+fn du_dir<IO: fatfs::ReadWriteSeek>(
+    dir: &fatfs::Dir<IO>,
+    path: &str,
+) -> Result<SlackUsage, String> {
+    let slots = dir.slot_usage().map_err(|err| format!("{}: {}", path, err))?;
+    let mut usage = SlackUsage {
+        used_slots: u64::from(slots.used),
+        free_slots: u64::from(slots.free),
+        ..SlackUsage::default()
+    };
+    for r in dir.iter() {
+        let entry = r.map_err(|err| format!("{}: {}", path, err))?;
+        let name = entry.file_name();
+        if name == "." || name == ".." {
+            continue;
+        }
+        if entry.is_dir() {
+            let sub_path = format!("{}/{}", path.trim_end_matches('/'), name);
+            let sub = du_dir(&entry.to_dir(), &sub_path)?;
+            usage.files += sub.files;
+            usage.allocated += sub.allocated;
+            usage.slack += sub.slack;
+            usage.used_slots += sub.used_slots;
+            usage.free_slots += sub.free_slots;
+        } else {
+            usage.files += 1;
+            usage.allocated += entry.allocated_len();
+            usage.slack += entry.slack();
+        }
     }
+    println!(
+        "{:>12} {:>12} {:>6}/{:<6} {}",
+        usage.allocated,
+        usage.slack,
+        slots.free,
+        slots.used + slots.free,
+        path
+    );
+    Ok(usage)
 }
 
-fn walk_file(indent: u32, file: fatfs::DirEntry) {
-    print_indent(indent);
-    if file.is_file() {
-        println!("{}", file.file_name());
-    } else if file.is_dir() {
-        let dir = file.to_dir();
-        println!("{}/ ({} entries)", file.file_name(), dir.iter().count());
-        if file.file_name() != "." && file.file_name() != ".." && file.file_name() != "" {
-            for entry in dir.iter() {
-                let entry = entry.expect("failed to read entry");
-                walk_file(indent + 1, entry);
+fn mv(args: &[String]) -> Result<i32, String> {
+    let path = image_arg(args)?;
+    let (src, dst) = match (args.get(1), args.get(2), args.len()) {
+        (Some(src), Some(dst), 3) => (src.trim_matches('/'), dst.trim_matches('/')),
+        _ => return Err(USAGE.to_string()),
+    };
+    let mut data = read_file(path).map_err(|err| format!("{}: {}", path, err))?;
+    {
+        let mut file = basic_io::Cursor::new(&mut data[..]);
+        let options = fatfs::FsOptions::new();
+        let fs = fatfs::FileSystem::new(&mut file, options).map_err(|err| err.to_string())?;
+        {
+            let root = fs.root_dir();
+            root.rename(src, &root, dst)
+                .map_err(|err| format!("{} -> {}: {}", src, dst, err))?;
+        }
+        fs.unmount().map_err(|err| err.to_string())?;
+    }
+    fs::write(path, &data).map_err(|err| format!("{}: {}", path, err))?;
+    Ok(0)
+}
+
+fn label(args: &[String]) -> Result<i32, String> {
+    let path = image_arg(args)?;
+    let new_label = match (args.get(1), args.len()) {
+        (label, 1) | (label, 2) => label,
+        _ => return Err(USAGE.to_string()),
+    };
+    let mut data = read_file(path).map_err(|err| format!("{}: {}", path, err))?;
+    {
+        let mut file = basic_io::Cursor::new(&mut data[..]);
+        let options = fatfs::FsOptions::new();
+        let mut fs = fatfs::FileSystem::new(&mut file, options).map_err(|err| err.to_string())?;
+        match new_label {
+            Some(label) => fs.set_volume_label(label).map_err(|err| err.to_string())?,
+            None => {
+                println!("{}", fs.volume_label());
+                return Ok(0);
             }
         }
+        fs.unmount().map_err(|err| err.to_string())?;
+    }
+    fs::write(path, &data).map_err(|err| format!("{}: {}", path, err))?;
+    Ok(0)
+}
+
+fn cat(args: &[String]) -> Result<i32, String> {
+    let path = image_arg(args)?;
+    let file_path = match args.get(1) {
+        Some(file_path) if !file_path.starts_with("--") => file_path.trim_matches('/'),
+        _ => return Err(USAGE.to_string()),
+    };
+    let mut offset = 0;
+    let mut len = u64::MAX;
+    let mut rest = args[2..].iter();
+    while let Some(arg) = rest.next() {
+        let value = rest.next().and_then(|s| parse_size(s)).ok_or(USAGE.to_string())?;
+        match arg.as_str() {
+            "--offset" => offset = value,
+            "--len" => len = value,
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    // image is accessed directly so only the requested part of the file is read
+    let image = fs::File::open(path).map_err(|err| format!("{}: {}", path, err))?;
+    let mut disk = fatfs::AutoExtend::new(image, 0).map_err(|err| format!("{}: {}", path, err))?;
+    let options = fatfs::FsOptions::new();
+    let fs = fatfs::FileSystem::new(&mut disk, options).map_err(|err| err.to_string())?;
+    let mut file = fs
+        .root_dir()
+        .open_file(file_path)
+        .map_err(|err| format!("{}: {}", file_path, err))?;
+    basic_io::Seek::seek(&mut file, basic_io::SeekFrom::Start(offset))
+        .map_err(|err| err.to_string())?;
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut buf = vec![0u8; 64 * 1024];
+    while len > 0 {
+        let max = cmp::min(len, buf.len() as u64) as usize;
+        let n = basic_io::Read::read(&mut file, &mut buf[..max]).map_err(|err| err.to_string())?;
+        if n == 0 {
+            break;
+        }
+        out.write_all(&buf[..n]).map_err(|err| err.to_string())?;
+        len -= n as u64;
+    }
+    out.flush().map_err(|err| err.to_string())?;
+    Ok(0)
+}
+
+// Changes made by sync command
+#[derive(Default)]
+struct SyncSummary {
+    added: u32,
+    updated: u32,
+    deleted: u32,
+    unchanged: u32,
+    skipped: u32,
+}
+
+fn sync(args: &[String]) -> Result<i32, String> {
+    let (host_dir, target, delete) = match (args.first(), args.get(1), args.get(2)) {
+        (Some(host_dir), Some(target), None) => (host_dir, target, false),
+        (Some(host_dir), Some(target), Some(flag)) if flag == "--delete" && args.len() == 3 => {
+            (host_dir, target, true)
+        }
+        _ => return Err(USAGE.to_string()),
+    };
+    let (path, dir_path) = match target.rfind(':') {
+        Some(i) => (&target[..i], target[i + 1..].trim_matches('/')),
+        None => (target.as_str(), ""),
+    };
+    // image is accessed directly so unchanged files are not read or written
+    let image = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .map_err(|err| format!("{}: {}", path, err))?;
+    let mut disk = fatfs::AutoExtend::new(image, 0).map_err(|err| format!("{}: {}", path, err))?;
+    let mut summary = SyncSummary::default();
+    {
+        let options = fatfs::FsOptions::new();
+        let fs = fatfs::FileSystem::new(&mut disk, options).map_err(|err| err.to_string())?;
+        {
+            let mut dir = if dir_path.is_empty() {
+                fs.root_dir()
+            } else {
+                fs.root_dir()
+                    .create_dir(dir_path)
+                    .map_err(|err| format!("{}: {}", dir_path, err))?
+            };
+            let prefix = format!("/{}", dir_path);
+            sync_dir(Path::new(host_dir), &mut dir, &prefix, delete, &mut summary)?;
+        }
+        fs.unmount().map_err(|err| err.to_string())?;
     }
+    println!(
+        "{} added, {} updated, {} deleted, {} unchanged, {} skipped",
+        summary.added, summary.updated, summary.deleted, summary.unchanged, summary.skipped
+    );
+    Ok(0)
 }
 
-fn print_indent(indent: u32) {
-    for _ in 0..indent {
-        print!("  ");
+fn sync_dir<IO: fatfs::ReadWriteSeek>(
+    host_dir: &Path,
+    dir: &mut fatfs::Dir<IO>,
+    prefix: &str,
+    delete: bool,
+    summary: &mut SyncSummary,
+) -> Result<(), String> {
+    let host_err = |err: io::Error| format!("{}: {}", host_dir.display(), err);
+    let mut host_entries = Vec::new();
+    for r in fs::read_dir(host_dir).map_err(host_err)? {
+        let entry = r.map_err(host_err)?;
+        host_entries.push((entry.file_name(), entry.path()));
     }
+    host_entries.sort();
+    // (name, is_dir, len, modified) of entries already in the image
+    let mut image_entries = Vec::new();
+    for r in dir.iter() {
+        let e = r.map_err(|err| format!("{}: {}", prefix, err))?;
+        if e.file_name() != "." && e.file_name() != ".." {
+            image_entries.push((e.file_name().to_string(), e.is_dir(), e.len(), e.modified()));
+        }
+    }
+    let mut synced_names = Vec::new();
+    for (file_name, host_path) in host_entries {
+        let name = match file_name.to_str() {
+            // long names are not stored yet so they would not be found by the next sync
+            Some(name) if is_short_name(name) => name,
+            _ => {
+                println!("skipped {}: not a short name", host_path.display());
+                summary.skipped += 1;
+                continue;
+            }
+        };
+        let image_path = format!("{}/{}", prefix.trim_end_matches('/'), name);
+        let err_msg = |err: basic_io::Error| format!("{}: {}", image_path, err);
+        synced_names.push(name.to_string());
+        let metadata = fs::metadata(&host_path).map_err(|err| err.to_string())?;
+        let existing = image_entries.iter().find(|e| e.0.eq_ignore_ascii_case(name));
+        if metadata.is_dir() {
+            match existing {
+                Some(&(_, true, ..)) => {}
+                Some(_) => {
+                    dir.remove_file(name).map_err(err_msg)?;
+                    println!("deleted {}", image_path);
+                    println!("added {}/", image_path);
+                    summary.deleted += 1;
+                    summary.added += 1;
+                }
+                None => {
+                    println!("added {}/", image_path);
+                    summary.added += 1;
+                }
+            }
+            let mut sub_dir = dir.create_dir(name).map_err(err_msg)?;
+            sync_dir(&host_path, &mut sub_dir, &image_path, delete, summary)?;
+            continue;
+        }
+        let modified = to_fat_date_time(metadata.modified().map_err(|err| err.to_string())?);
+        match existing {
+            Some(&(_, false, len, image_modified))
+                if len == metadata.len() && image_modified == modified =>
+            {
+                summary.unchanged += 1;
+                continue;
+            }
+            Some(&(_, false, ..)) => {
+                println!("updated {}", image_path);
+                summary.updated += 1;
+            }
+            Some(&(_, true, ..)) => {
+                remove_tree(dir, name).map_err(err_msg)?;
+                println!("deleted {}/", image_path);
+                println!("added {}", image_path);
+                summary.deleted += 1;
+                summary.added += 1;
+            }
+            None => {
+                println!("added {}", image_path);
+                summary.added += 1;
+            }
+        }
+        let data =
+            read_file(&host_path).map_err(|err| format!("{}: {}", host_path.display(), err))?;
+        let mut file = dir.create_file(name).map_err(err_msg)?;
+        file.truncate().map_err(err_msg)?;
+        basic_io::Write::write_all(&mut file, &data).map_err(err_msg)?;
+        file.set_modified(modified).map_err(err_msg)?;
+        basic_io::Write::flush(&mut file).map_err(err_msg)?;
+    }
+    if delete {
+        for &(ref name, is_dir, ..) in &image_entries {
+            if synced_names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+                continue;
+            }
+            let image_path = format!("{}/{}", prefix.trim_end_matches('/'), name);
+            let err_msg = |err: basic_io::Error| format!("{}: {}", image_path, err);
+            if is_dir {
+                remove_tree(dir, name).map_err(err_msg)?;
+                println!("deleted {}/", image_path);
+            } else {
+                dir.remove_file(name).map_err(err_msg)?;
+                println!("deleted {}", image_path);
+            }
+            summary.deleted += 1;
+        }
+    }
+    Ok(())
+}
+
+fn remove_tree<IO: fatfs::ReadWriteSeek>(
+    dir: &mut fatfs::Dir<IO>,
+    name: &str,
+) -> basic_io::Result<()> {
+    {
+        let mut sub_dir = dir.open_dir(name)?;
+        let mut children = Vec::new();
+        for r in sub_dir.iter() {
+            let e = r?;
+            if e.file_name() != "." && e.file_name() != ".." {
+                children.push((e.file_name().to_string(), e.is_dir()));
+            }
+        }
+        for (child, is_dir) in children {
+            if is_dir {
+                remove_tree(&mut sub_dir, &child)?;
+            } else {
+                sub_dir.remove_file(&child)?;
+            }
+        }
+    }
+    dir.remove_dir(name)
+}
+
+// Checks if name can be stored as 8.3 short name without changes other than case
+fn is_short_name(name: &str) -> bool {
+    let valid_chars = |s: &str| {
+        s.chars().all(|c| c.is_ascii_alphanumeric() || "!#$%&'()-@^_`{}~".contains(c))
+    };
+    let (base, ext) = match name.find('.') {
+        Some(i) => (&name[..i], &name[i + 1..]),
+        None => (name, ""),
+    };
+    !base.is_empty() && base.len() <= 8 && ext.len() <= 3 && valid_chars(base) && valid_chars(ext)
+}
+
+// Converts host timestamp to FAT timestamp in UTC
+fn to_fat_date_time(time: std::time::SystemTime) -> fatfs::DateTime {
+    let secs = match time.duration_since(std::time::UNIX_EPOCH) {
+        Ok(duration) => cmp::min(duration.as_secs(), i64::MAX as u64) as i64,
+        Err(_) => 0,
+    };
+    let date_time = fatfs::DateTime::from_unix_epoch(secs);
+    // FAT stores seconds with 2 second resolution
+    fatfs::DateTime {
+        date: date_time.date,
+        time: fatfs::Time {
+            sec: date_time.time.sec / 2 * 2,
+            ..date_time.time
+        },
+    }
+}
+
+fn fsck(args: &[String]) -> Result<i32, String> {
+    let path = image_arg(args)?;
+    let mut repair = false;
+    let mut rebuild_fs_info = false;
+    for arg in &args[1..] {
+        match arg.as_str() {
+            "--repair" => repair = true,
+            "--rebuild-fsinfo" => rebuild_fs_info = true,
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let check_options = fatfs::CheckOptions::new()
+        .repair(repair)
+        .rebuild_fs_info(rebuild_fs_info);
+    let mut data = read_file(path).map_err(|err| format!("{}: {}", path, err))?;
+    let summary = {
+        let mut file = basic_io::Cursor::new(&mut data[..]);
+        let options = fatfs::FsOptions::new();
+        let fs = fatfs::FileSystem::new(&mut file, options).map_err(|err| err.to_string())?;
+        let summary = fs
+            .check(check_options, &mut print_issue)
+            .map_err(|err| err.to_string())?;
+        fs.unmount().map_err(|err| err.to_string())?;
+        summary
+    };
+    if summary.repaired > 0 || rebuild_fs_info {
+        fs::write(path, &data).map_err(|err| format!("{}: {}", path, err))?;
+    }
+    if summary.is_clean() {
+        println!("{}: no problems found", path);
+        Ok(0)
+    } else {
+        println!(
+            "{}: {} problems found, {} repaired",
+            path, summary.issues, summary.repaired
+        );
+        if summary.repaired == summary.issues {
+            Ok(FSCK_ERRORS_CORRECTED)
+        } else {
+            Ok(FSCK_ERRORS_LEFT)
+        }
+    }
+}
+
+fn debug(args: &[String]) -> Result<i32, String> {
+    let path = image_arg(args)?;
+    let mut data = read_file(path).map_err(|err| format!("{}: {}", path, err))?;
+    let mut file = basic_io::Cursor::new(&mut data[..]);
+    let options = fatfs::FsOptions::new();
+    let fs = fatfs::FileSystem::new(&mut file, options).map_err(|err| err.to_string())?;
+    match (args.get(1).map(|s| s.as_str()), args.get(2), args.len()) {
+        (Some("--dir"), Some(dir_path), 3) => dump_dir(&fs, dir_path),
+        (Some("--fat-chain"), Some(cluster), 3) => {
+            let cluster = cluster.parse().map_err(|_| USAGE.to_string())?;
+            dump_fat_chain(&fs, cluster)
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn dump_dir<IO: fatfs::ReadWriteSeek>(
+    fs: &fatfs::FileSystem<IO>,
+    path: &str,
+) -> Result<i32, String> {
+    let path = path.trim_matches('/');
+    let dir = if path.is_empty() {
+        fs.root_dir()
+    } else {
+        fs.root_dir().open_dir(path).map_err(|err| format!("{}: {}", path, err))?
+    };
+    for r in dir.iter_raw() {
+        let entry = r.map_err(|err| err.to_string())?;
+        let bytes = entry.bytes();
+        match entry.abs_pos() {
+            Some(pos) => println!("entry at {:#x} (volume offset {:#x})", entry.offset(), pos),
+            None => println!("entry at {:#x}", entry.offset()),
+        }
+        for line in bytes.chunks(16) {
+            let hex: Vec<String> = line.iter().map(|b| format!("{:02x}", b)).collect();
+            let text: String = line
+                .iter()
+                .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+                .collect();
+            println!("    {}  {}", hex.join(" "), text);
+        }
+        println!("    {}", describe_raw_entry(&entry));
+    }
+    Ok(0)
+}
+
+fn describe_raw_entry(entry: &fatfs::RawDirEntry) -> String {
+    let bytes = entry.bytes();
+    let u16_at = |i: usize| u32::from(bytes[i]) | u32::from(bytes[i + 1]) << 8;
+    if entry.is_end() {
+        "end of directory".to_string()
+    } else if entry.is_lfn() {
+        format!(
+            "long name part: order={:#04x} checksum={:#04x}{}",
+            bytes[0],
+            bytes[13],
+            if entry.is_free() { " (deleted)" } else { "" }
+        )
+    } else {
+        format!(
+            "short name: {:?} attrs={:#04x} cluster={} size={}{}",
+            String::from_utf8_lossy(&bytes[..11]),
+            bytes[11],
+            u16_at(20) << 16 | u16_at(26),
+            u16_at(28) | u16_at(30) << 16,
+            if entry.is_free() { " (deleted)" } else { "" }
+        )
+    }
+}
+
+fn dump_fat_chain<IO: fatfs::ReadWriteSeek>(
+    fs: &fatfs::FileSystem<IO>,
+    first_cluster: u32,
+) -> Result<i32, String> {
+    let total_clusters = fs.stats().map_err(|err| err.to_string())?.total_clusters;
+    if first_cluster < 2 || first_cluster >= total_clusters + 2 {
+        return Err(format!("cluster {} is outside of data area", first_cluster));
+    }
+    let mut chain = vec![first_cluster.to_string()];
+    for r in fs.cluster_iter(fatfs::Cluster(first_cluster)) {
+        if chain.len() as u32 == total_clusters {
+            chain.push("... (loop)".to_string());
+            break;
+        }
+        chain.push(r.map_err(|err| err.to_string())?.to_string());
+    }
+    println!("{}", chain.join(" -> "));
+    Ok(0)
+}
+
+fn print_issue(issue: &fatfs::CheckIssue) {
+    let repaired = if issue.repaired { " (repaired)" } else { "" };
+    if issue.name.is_empty() {
+        println!("{}{}", issue.kind.description(), repaired);
+    } else {
+        println!("{}: {}{}", issue.name, issue.kind.description(), repaired);
+    }
+}
+
+// Totals of a directory subtree
+#[derive(Default)]
+struct Usage {
+    files: u64,
+    dirs: u64,
+    bytes: u64,
+    allocated: u64,
+}
+
+// Totals of a directory subtree reported by du command
+#[derive(Default)]
+struct SlackUsage {
+    files: u64,
+    allocated: u64,
+    slack: u64,
+    used_slots: u64,
+    free_slots: u64,
+}
+
+fn walk_dir<IO: fatfs::ReadWriteSeek>(
+    dir: &fatfs::Dir<IO>,
+    indent: usize,
+    cluster_size: u64,
+    lines: &mut Vec<String>,
+) -> Result<Usage, String> {
+    let mut usage = Usage::default();
+    for r in dir.iter() {
+        let entry = r.map_err(|err| err.to_string())?;
+        let name = entry.file_name();
+        if name == "." || name == ".." {
+            continue;
+        }
+        let info = format!(
+            "{}  {}",
+            format_attributes(entry.attributes()),
+            format_date_time(entry.modified())
+        );
+        if entry.is_dir() {
+            let line_index = lines.len();
+            lines.push(String::new());
+            let sub = walk_dir(&entry.to_dir(), indent + 1, cluster_size, lines)?;
+            lines[line_index] = format!(
+                "{:indent$}{}/  {}  ({} files, {} bytes, {} bytes allocated)",
+                "",
+                name,
+                info,
+                sub.files,
+                sub.bytes,
+                sub.allocated,
+                indent = indent * 2
+            );
+            usage.dirs += sub.dirs + 1;
+            usage.files += sub.files;
+            usage.bytes += sub.bytes;
+            usage.allocated += sub.allocated;
+        } else {
+            let len = entry.len();
+            lines.push(format!("{:indent$}{}  {}  {}", "", name, len, info, indent = indent * 2));
+            usage.files += 1;
+            usage.bytes += len;
+            usage.allocated += len.div_ceil(cluster_size) * cluster_size;
+        }
+    }
+    Ok(usage)
+}
+
+fn format_attributes(attrs: fatfs::FileAttributes) -> String {
+    let flags = [
+        (fatfs::FileAttributes::READ_ONLY, 'R'),
+        (fatfs::FileAttributes::HIDDEN, 'H'),
+        (fatfs::FileAttributes::SYSTEM, 'S'),
+        (fatfs::FileAttributes::ARCHIVE, 'A'),
+    ];
+    flags
+        .iter()
+        .map(|&(flag, c)| if attrs.contains(flag) { c } else { '-' })
+        .collect()
+}
+
+fn format_date_time(dt: fatfs::DateTime) -> String {
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        dt.date.year, dt.date.month, dt.date.day, dt.time.hour, dt.time.min, dt.time.sec
+    )
 }
 
 fn read_file<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
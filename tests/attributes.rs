@@ -0,0 +1,66 @@
+// Tests of changing attributes of directory entries.
+
+extern crate basic_io;
+extern crate fatfs;
+
+use basic_io::{Cursor, ErrorKind, Write};
+use fatfs::{Dir, DirEntry, FileAttributes, FileSystem, FormatOptions, FsOptions, ReadWriteSeek};
+
+const VOLUME_SIZE: usize = 4 * 1024 * 1024;
+
+fn entry<'a, IO: ReadWriteSeek>(dir: &Dir<'a, IO>, name: &str) -> DirEntry<'a, IO> {
+    dir.iter().map(|r| r.unwrap()).find(|e| e.eq_name(name)).unwrap()
+}
+
+#[test]
+fn set_attributes_is_persisted() {
+    let mut data = vec![0u8; VOLUME_SIZE];
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        fatfs::format_volume(&mut cursor, FormatOptions::new()).unwrap();
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        let mut root = fs.root_dir();
+        root.create_file("FW.BIN").unwrap().write_all(b"firmware").unwrap();
+        root.create_dir("SYS").unwrap();
+        let mut fw = entry(&root, "FW.BIN");
+        let attrs = FileAttributes::HIDDEN | FileAttributes::SYSTEM;
+        fw.set_attributes(attrs).unwrap();
+        assert_eq!(fw.attributes(), attrs);
+        // directory attribute is kept
+        let mut sys = entry(&root, "SYS");
+        sys.set_attributes(FileAttributes::HIDDEN).unwrap();
+        assert_eq!(sys.attributes(), FileAttributes::HIDDEN | FileAttributes::DIRECTORY);
+        assert!(sys.is_dir());
+    }
+    let entry_data = data.chunks(32).find(|e| &e[..11] == b"FW      BIN").unwrap();
+    assert_eq!(entry_data[11], 0x06);
+
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let root = fs.root_dir();
+    let fw = entry(&root, "FW.BIN");
+    assert_eq!(fw.attributes(), FileAttributes::HIDDEN | FileAttributes::SYSTEM);
+    assert_eq!(fw.len(), 8);
+    let mut sys = entry(&root, "SYS");
+    assert_eq!(sys.attributes(), FileAttributes::HIDDEN | FileAttributes::DIRECTORY);
+    sys.set_attributes(FileAttributes::empty()).unwrap();
+    assert_eq!(entry(&root, "SYS").attributes(), FileAttributes::DIRECTORY);
+}
+
+#[test]
+fn set_attributes_rejects_type_attributes() {
+    let mut data = vec![0u8; VOLUME_SIZE];
+    let mut cursor = Cursor::new(&mut data[..]);
+    fatfs::format_volume(&mut cursor, FormatOptions::new()).unwrap();
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    root.create_file("A.TXT").unwrap();
+    let mut e = entry(&root, "A.TXT");
+    let original = e.attributes();
+    let invalid = [FileAttributes::DIRECTORY, FileAttributes::VOLUME_ID, FileAttributes::LFN];
+    for &attrs in invalid.iter() {
+        let err = e.set_attributes(attrs).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+    assert_eq!(entry(&root, "A.TXT").attributes(), original);
+}
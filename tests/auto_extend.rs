@@ -0,0 +1,62 @@
+// Tests of volumes stored in lazily growing backing streams.
+
+extern crate basic_io;
+extern crate fatfs;
+
+use basic_io::{Read, Seek, SeekFrom, Write};
+use fatfs::{AutoExtend, FileSystem, FormatOptions, FsOptions};
+
+const MB: u64 = 1024 * 1024;
+
+#[test]
+fn format_and_use_volume_in_empty_stream() {
+    let stream = std::io::Cursor::new(Vec::new());
+    let mut disk = AutoExtend::new(stream, 8 * MB).unwrap();
+    fatfs::format_volume(&mut disk, FormatOptions::new()).unwrap();
+    // only metadata was written
+    let formatted_len = disk.backing_len();
+    assert!(formatted_len > 0 && formatted_len < MB);
+    {
+        let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+        assert!(!fs.is_read_only());
+        assert!(fs.stats().unwrap().total_clusters as u64 * fs.cluster_size() as u64 > 7 * MB);
+        let mut file = fs.root_dir().create_file("data.bin").unwrap();
+        file.write_all(&[0x5A; 4096]).unwrap();
+    }
+    assert!(disk.backing_len() > formatted_len);
+    let data = disk.into_inner().into_inner();
+    assert!((data.len() as u64) < 8 * MB);
+
+    // volume can be mounted again from the short stream
+    let mut disk = AutoExtend::new(std::io::Cursor::new(data), 8 * MB).unwrap();
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    let mut file = fs.root_dir().open_file("data.bin").unwrap();
+    let mut buf = vec![0; 4096];
+    file.read_exact(&mut buf).unwrap();
+    assert!(buf.iter().all(|&b| b == 0x5A));
+    fs.verify_invariants().unwrap();
+}
+
+#[test]
+fn unwritten_parts_read_as_zeros() {
+    let stream = std::io::Cursor::new(vec![1u8; 100]);
+    let mut disk = AutoExtend::new(stream, 1000).unwrap();
+    assert_eq!(disk.seek(SeekFrom::End(0)).unwrap(), 1000);
+    disk.seek(SeekFrom::Start(90)).unwrap();
+    let mut buf = [0xFF; 20];
+    disk.read_exact(&mut buf).unwrap();
+    assert_eq!(buf[..10], [1; 10]);
+    assert_eq!(buf[10..], [0; 10]);
+    disk.seek(SeekFrom::Start(995)).unwrap();
+    assert_eq!(disk.read(&mut buf).unwrap(), 5);
+    assert_eq!(disk.read(&mut buf).unwrap(), 0);
+
+    // writes beyond the end extend the backing stream and the device
+    disk.seek(SeekFrom::Start(1010)).unwrap();
+    disk.write_all(&[2; 10]).unwrap();
+    assert_eq!(disk.backing_len(), 1020);
+    assert_eq!(disk.seek(SeekFrom::End(0)).unwrap(), 1020);
+    let data = disk.into_inner().into_inner();
+    assert_eq!(data[..100], [1; 100][..]);
+    assert!(data[100..1010].iter().all(|&b| b == 0));
+}
@@ -0,0 +1,139 @@
+// Tests of FAT cache buffers taken from a buffer provider with a budget.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use basic_io::{Cursor, Read, Write};
+use fatfs::{BufferProvider, FatType, File, FileSystem, FsOptions, HeapBufferProvider,
+            ReadWriteSeek};
+
+const FILE_SIZE: usize = 256 * 1024;
+
+fn write_files(data: &mut [u8], provider: &'static HeapBufferProvider, budget: usize) {
+    let mut cursor = Cursor::new(data);
+    let options = FsOptions::new().lazy_fat_writes(true).buffer_provider(provider);
+    let fs = FileSystem::new(&mut cursor, options).unwrap();
+    for i in 0..4u8 {
+        let name = format!("file{}.bin", i);
+        let mut file = fs.root_dir().create_file(&name).unwrap();
+        file.write_all(&vec![i; FILE_SIZE]).unwrap();
+        assert!(provider.used() <= budget);
+    }
+    fs.unmount().unwrap();
+    assert_eq!(provider.used(), 0);
+}
+
+fn check_files(data: &mut [u8]) {
+    let mut cursor = Cursor::new(data);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    for i in 0..4u8 {
+        let name = format!("file{}.bin", i);
+        let mut file = fs.root_dir().open_file(&name).unwrap();
+        let mut buf = vec![0; FILE_SIZE];
+        file.read_exact(&mut buf).unwrap();
+        assert!(buf.iter().all(|&b| b == i));
+    }
+    fs.verify_invariants().unwrap();
+}
+
+#[test]
+fn cache_stays_within_budget() {
+    static PROVIDER: HeapBufferProvider = HeapBufferProvider::new(1024);
+    let mut data = common::small_volume(FatType::Fat16);
+    write_files(&mut data, &PROVIDER, 1024);
+    check_files(&mut data);
+}
+
+#[test]
+fn exhausted_budget_falls_back_to_write_through() {
+    static PROVIDER: HeapBufferProvider = HeapBufferProvider::new(0);
+    let mut data = common::small_volume(FatType::Fat16);
+    write_files(&mut data, &PROVIDER, 0);
+    check_files(&mut data);
+}
+
+const POOL_SECTORS: usize = 8;
+static mut POOL: [[u8; 512]; POOL_SECTORS] = [[0; 512]; POOL_SECTORS];
+
+// Provider handing out sector buffers from a static pool - bit N of `taken` is set when buffer N
+// is in use
+struct PoolProvider {
+    taken: AtomicUsize,
+}
+
+impl BufferProvider for PoolProvider {
+    fn alloc(&self, size: usize) -> Option<&'static mut [u8]> {
+        if size != 512 {
+            return None;
+        }
+        for i in 0..POOL_SECTORS {
+            if self.taken.fetch_or(1 << i, Ordering::SeqCst) & 1 << i == 0 {
+                return Some(unsafe { &mut (&mut *ptr::addr_of_mut!(POOL))[i][..] });
+            }
+        }
+        None
+    }
+
+    unsafe fn free(&self, buf: &'static mut [u8]) {
+        let i = (buf.as_ptr() as usize - ptr::addr_of!(POOL) as usize) / 512;
+        self.taken.fetch_and(!(1 << i), Ordering::SeqCst);
+    }
+}
+
+fn big_file_contents() -> Vec<u8> {
+    (0..5 * 1024 * 1024).map(|i| (i / 512) as u8).collect()
+}
+
+// Writes a file big enough to modify more FAT sectors than the cache can hold
+fn write_big_file<'a, IO: ReadWriteSeek>(fs: &'a FileSystem<IO>, contents: &[u8]) -> File<'a, IO> {
+    let mut file = fs.root_dir().create_file("big.bin").unwrap();
+    file.write_all(contents).unwrap();
+    file
+}
+
+fn check_big_file(data: &mut [u8], contents: &[u8]) {
+    let mut cursor = Cursor::new(data);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut buf = vec![0; contents.len()];
+    fs.root_dir().open_file("big.bin").unwrap().read_exact(&mut buf).unwrap();
+    assert!(buf == contents);
+    fs.verify_invariants().unwrap();
+}
+
+#[test]
+fn buffers_can_come_from_static_pool() {
+    static PROVIDER: PoolProvider = PoolProvider { taken: AtomicUsize::new(0) };
+    let mut data = common::small_volume(FatType::Fat32);
+    let contents = big_file_contents();
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let options = FsOptions::new().lazy_fat_writes(true).buffer_provider(&PROVIDER);
+        let fs = FileSystem::new(&mut cursor, options).unwrap();
+        write_big_file(&fs, &contents);
+    }
+    assert_eq!(PROVIDER.taken.load(Ordering::SeqCst), 0);
+    check_big_file(&mut data, &contents);
+}
+
+#[test]
+fn number_of_cached_sectors_is_limited() {
+    static PROVIDER: HeapBufferProvider = HeapBufferProvider::unlimited();
+    let mut data = common::small_volume(FatType::Fat32);
+    let contents = big_file_contents();
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let options = FsOptions::new().lazy_fat_writes(true).buffer_provider(&PROVIDER);
+        let fs = FileSystem::new(&mut cursor, options).unwrap();
+        let file = write_big_file(&fs, &contents);
+        // FAT sectors beyond the cache capacity were written through
+        assert_eq!(PROVIDER.used(), 32 * 512);
+        drop(file);
+    }
+    assert_eq!(PROVIDER.used(), 0);
+    check_big_file(&mut data, &contents);
+}
@@ -0,0 +1,65 @@
+// Tests of long running operations aborted through a cancellation token.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, ErrorKind, Read, Write};
+use fatfs::{Cluster, FatType, FileSystem, FlagCancellationToken, FsOptions};
+
+#[test]
+fn free_space_scan_is_interrupted() {
+    static TOKEN: FlagCancellationToken = FlagCancellationToken::new();
+    let mut data = common::small_volume(FatType::Fat16);
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new().cancellation_token(&TOKEN)).unwrap();
+    TOKEN.cancel();
+    assert_eq!(fs.stats().err().unwrap().kind(), ErrorKind::Interrupted);
+    TOKEN.reset();
+    assert!(fs.stats().unwrap().free_clusters > 0);
+}
+
+#[test]
+fn directory_scan_and_allocation_are_interrupted() {
+    static TOKEN: FlagCancellationToken = FlagCancellationToken::new();
+    let mut data = common::small_volume(FatType::Fat16);
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new().cancellation_token(&TOKEN)).unwrap();
+    let mut root = fs.root_dir();
+    root.create_file("a.txt").unwrap();
+    TOKEN.cancel();
+    assert_eq!(root.open_file("a.txt").err().unwrap().kind(), ErrorKind::Interrupted);
+    assert_eq!(root.create_dir("sub").err().unwrap().kind(), ErrorKind::Interrupted);
+    match root.iter().next() {
+        Some(Err(err)) => assert_eq!(err.kind(), ErrorKind::Interrupted),
+        _ => panic!("iteration was not interrupted"),
+    }
+    TOKEN.reset();
+    root.create_dir("sub").unwrap();
+    root.open_file("a.txt").unwrap();
+    fs.verify_invariants().unwrap();
+}
+
+#[test]
+fn chain_walk_is_interrupted() {
+    static TOKEN: FlagCancellationToken = FlagCancellationToken::new();
+    let mut data = common::small_volume(FatType::Fat16);
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new().cancellation_token(&TOKEN)).unwrap();
+    let mut root = fs.root_dir();
+    let mut file = root.create_file("big.bin").unwrap();
+    file.write_all(&vec![7u8; 4096]).unwrap();
+    drop(file);
+    let mut file = root.open_file("big.bin").unwrap();
+    let mut buf = vec![0u8; 4096];
+    TOKEN.cancel();
+    assert_eq!(file.read_exact(&mut buf).err().unwrap().kind(), ErrorKind::Interrupted);
+    let r = fs.cluster_iter(Cluster(2)).next().unwrap();
+    assert_eq!(r.err().unwrap().kind(), ErrorKind::Interrupted);
+    TOKEN.reset();
+    drop(file);
+    let mut file = root.open_file("big.bin").unwrap();
+    file.read_exact(&mut buf).unwrap();
+    assert!(buf.iter().all(|&b| b == 7));
+}
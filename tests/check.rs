@@ -0,0 +1,148 @@
+// Tests of filesystem check and repair.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, ErrorKind, Read, Write};
+use fatfs::{CheckIssueKind, CheckOptions, FatType, FileSystem, FormatOptions, FsOptions};
+
+const DOT_NAME: &[u8; 11] = b".          ";
+
+fn create_volume(fat_type: FatType, size: usize) -> Vec<u8> {
+    let mut data = common::format_image(size, FormatOptions::new().fat_type(fat_type));
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        let mut a = fs.root_dir().create_dir("a").unwrap();
+        let mut b = a.create_dir("b").unwrap();
+        b.create_file("x").unwrap();
+    }
+    data
+}
+
+// Offsets of "." entries in order of directory creation
+fn dot_entry_offsets(data: &[u8]) -> Vec<usize> {
+    (0..data.len() / 32).map(|i| i * 32).filter(|&off| &data[off..off + 11] == DOT_NAME).collect()
+}
+
+fn check(data: &mut [u8], repair: bool) -> Vec<(CheckIssueKind, String, bool)> {
+    let mut cursor = Cursor::new(data);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut issues = Vec::new();
+    fs.check(CheckOptions::new().repair(repair), &mut |issue| {
+        issues.push((issue.kind, issue.name.to_string(), issue.repaired));
+    })
+    .unwrap();
+    issues
+}
+
+fn test_dot_entries_repair(fat_type: FatType, size: usize) {
+    let mut data = create_volume(fat_type, size);
+    let dots = dot_entry_offsets(&data);
+    assert_eq!(dots.len(), 2);
+    let (a, b) = (dots[0], dots[1]);
+    // "." of A points to a wrong cluster
+    data[a + 26] ^= 0x07;
+    // "." of B is not a directory
+    data[b + 11] = 0x20;
+    // ".." of B is deleted
+    data[b + 32] = 0xE5;
+
+    let expected = |repaired| {
+        vec![
+            (CheckIssueKind::InvalidDotEntry, "A".to_string(), repaired),
+            (CheckIssueKind::InvalidDotEntry, "B".to_string(), repaired),
+            (CheckIssueKind::MissingDotEntries, "B".to_string(), repaired),
+        ]
+    };
+    assert_eq!(check(&mut data, false), expected(false));
+    assert_eq!(check(&mut data, true), expected(true));
+    assert_eq!(check(&mut data, false), Vec::new());
+
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut b = fs.root_dir().open_dir("a/b/../b/.").unwrap();
+    b.open_file("x").unwrap();
+}
+
+#[test]
+fn dot_entries_repair_fat12() {
+    test_dot_entries_repair(FatType::Fat12, 1024 * 1024);
+}
+
+#[test]
+fn dot_entries_repair_fat32() {
+    test_dot_entries_repair(FatType::Fat32, 40 * 1024 * 1024);
+}
+
+fn read_file(data: &mut [u8], path: &str, buf: &mut [u8]) -> basic_io::Result<usize> {
+    let mut cursor = Cursor::new(data);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut file = fs.root_dir().open_file(path).unwrap();
+    let mut total = 0;
+    loop {
+        match file.read(&mut buf[total..])? {
+            0 => return Ok(total),
+            n => total += n,
+        }
+    }
+}
+
+#[test]
+fn size_larger_than_chain() {
+    let mut data = create_volume(FatType::Fat12, 1024 * 1024);
+    let cluster_size = {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        let cluster_size = fs.stats().unwrap().cluster_size as usize;
+        let mut file = fs.root_dir().create_file("big.bin").unwrap();
+        file.write_all(&vec![0xAB; cluster_size * 3]).unwrap();
+        cluster_size
+    };
+    // make size in the entry two clusters larger than the cluster chain
+    let entry = data.chunks(32).position(|e| &e[..11] == b"BIG     BIN").unwrap();
+    let size_offset = entry * 32 + 28;
+    let size = (cluster_size * 5) as u32;
+    data[size_offset..size_offset + 4].copy_from_slice(&size.to_le_bytes());
+
+    let mut buf = vec![0u8; cluster_size * 5];
+    let err = read_file(&mut data, "big.bin", &mut buf).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::CorruptedFile);
+
+    let expected =
+        |repaired| vec![(CheckIssueKind::SizeMismatch, "BIG.BIN".to_string(), repaired)];
+    assert_eq!(check(&mut data, false), expected(false));
+    assert_eq!(check(&mut data, true), expected(true));
+    assert_eq!(check(&mut data, false), Vec::new());
+
+    assert_eq!(read_file(&mut data, "big.bin", &mut buf).unwrap(), cluster_size * 3);
+    assert!(buf[..cluster_size * 3].iter().all(|&b| b == 0xAB));
+}
+
+#[test]
+fn reserved_fat_entries_repair() {
+    let volumes = [
+        (FatType::Fat12, 1024 * 1024),
+        (FatType::Fat16, 8 * 1024 * 1024),
+        (FatType::Fat32, 40 * 1024 * 1024),
+    ];
+    for &(fat_type, size) in volumes.iter() {
+        let original = common::format_image(size, FormatOptions::new().fat_type(fat_type));
+        let fat = u16::from_le_bytes([original[14], original[15]]) as usize * 512;
+        assert_eq!(original[fat], original[21], "{:?}", fat_type);
+        assert_eq!(check(&mut original.clone(), false), Vec::new());
+
+        let mut data = original.clone();
+        // FAT[0] without media descriptor and FAT[1] not end of chain
+        data[fat] = 0xF0;
+        data[fat + 2] = 0;
+        let issue = CheckIssueKind::InvalidReservedFatEntries;
+        let expected = |repaired| vec![(issue, String::new(), repaired)];
+        assert_eq!(check(&mut data, false), expected(false));
+        assert_eq!(check(&mut data, true), expected(true));
+        assert_eq!(check(&mut data, false), Vec::new());
+        assert_eq!(data[fat..fat + 8], original[fat..fat + 8], "{:?}", fat_type);
+    }
+}
@@ -0,0 +1,79 @@
+// Tests of buffering writes until whole clusters can be written.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Read, Seek, SeekFrom, Write};
+use fatfs::{ClusterAlignedWriter, FatType, FileSystem, FsOptions};
+use common::FaultyDisk;
+
+fn content(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251 + 1) as u8).collect()
+}
+
+#[test]
+fn small_writes_are_passed_in_whole_clusters() {
+    let mut disk = FaultyDisk::new(common::small_volume(FatType::Fat16));
+    let write_log = disk.write_log.clone();
+    let data = content(1300);
+    {
+        let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+        assert_eq!(fs.cluster_size(), 512);
+        let mut root = fs.root_dir();
+        let mut writer = ClusterAlignedWriter::new(root.create_file("a.bin").unwrap()).unwrap();
+        for b in data.iter() {
+            writer.write_all(&[*b]).unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+        drop(root);
+        fs.unmount().unwrap();
+    }
+
+    // file is contiguous on a new volume
+    let start = (0..disk.data.len()).find(|&i| disk.data[i..i + 16] == data[..16]).unwrap();
+    assert_eq!(disk.data[start..start + data.len()], data[..]);
+    let end = (start + data.len()) as u64;
+    let writes: Vec<_> = write_log
+        .borrow()
+        .iter()
+        .cloned()
+        .filter(|&(pos, _)| pos >= start as u64 && pos < end)
+        .collect();
+    let start = start as u64;
+    assert_eq!(writes, vec![(start, 512), (start + 512, 512), (start + 1024, 276)]);
+}
+
+#[test]
+fn seek_writes_buffered_data() {
+    let mut disk = FaultyDisk::new(common::small_volume(FatType::Fat16));
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let mut writer = ClusterAlignedWriter::new(root.create_file("a.bin").unwrap()).unwrap();
+    writer.write_all(&content(700)).unwrap();
+    // only the first cluster was written so far
+    assert_eq!(writer.get_ref().clusters().count(), 1);
+    // write crossing cluster boundary after seek to the middle of a cluster
+    assert_eq!(writer.seek(SeekFrom::Start(100)).unwrap(), 100);
+    assert_eq!(writer.get_ref().clusters().count(), 2);
+    writer.write_all(&[0; 600]).unwrap();
+    let mut file = writer.into_inner().unwrap();
+    assert_eq!(file.seek(SeekFrom::End(0)).unwrap(), 700);
+
+    let mut expected = content(700);
+    for b in expected[100..].iter_mut() {
+        *b = 0;
+    }
+    let mut buf = Vec::new();
+    file.seek(SeekFrom::Start(0)).unwrap();
+    let mut chunk = [0u8; 256];
+    loop {
+        match file.read(&mut chunk).unwrap() {
+            0 => break,
+            n => buf.extend_from_slice(&chunk[..n]),
+        }
+    }
+    assert_eq!(buf, expected);
+}
@@ -0,0 +1,73 @@
+// Tests of iterating over clusters of a file.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, ErrorKind, Seek, SeekFrom, Write};
+use fatfs::{Cluster, FatType, FileSystem, FormatOptions, FsOptions, ReadWriteSeek};
+
+const VOLUME_SIZE: usize = 4 * 1024 * 1024;
+const CLUSTER_SIZE: usize = 512;
+
+fn create_volume() -> Vec<u8> {
+    let options =
+        FormatOptions::new().fat_type(FatType::Fat16).bytes_per_cluster(CLUSTER_SIZE as u32);
+    common::format_image(VOLUME_SIZE, options)
+}
+
+fn file_clusters<IO: ReadWriteSeek>(
+    fs: &FileSystem<IO>,
+    path: &str,
+) -> basic_io::Result<Vec<Cluster>> {
+    fs.root_dir().open_file(path).unwrap().clusters().collect()
+}
+
+#[test]
+fn clusters_of_fragmented_file() {
+    let mut data = create_volume();
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    root.create_file("empty.txt").unwrap();
+    root.create_file("a.bin").unwrap().write_all(&[1; CLUSTER_SIZE * 3]).unwrap();
+    root.create_file("b.bin").unwrap().write_all(&[2; CLUSTER_SIZE]).unwrap();
+    let mut file = root.open_file("a.bin").unwrap();
+    file.seek(SeekFrom::End(0)).unwrap();
+    file.write_all(&[3; CLUSTER_SIZE * 2]).unwrap();
+    drop(file);
+
+    assert_eq!(file_clusters(&fs, "empty.txt").unwrap(), Vec::new());
+    let a = file_clusters(&fs, "a.bin").unwrap();
+    let b = file_clusters(&fs, "b.bin").unwrap();
+    assert_eq!(b.len(), 1);
+    assert_eq!(a.len(), 5);
+    assert_eq!(a[1..3], [a[0].next(), a[0].next().next()]);
+    assert_eq!(a[3], b[0].next());
+    assert_eq!(a[4], a[3].next());
+}
+
+#[test]
+fn clusters_with_loop() {
+    let mut data = create_volume();
+    let a = {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        fs.root_dir().create_file("a.bin").unwrap().write_all(&[1; CLUSTER_SIZE * 2]).unwrap();
+        file_clusters(&fs, "a.bin").unwrap()
+    };
+    // make the last cluster point back to the first one
+    let reserved_sectors = u16::from_le_bytes([data[14], data[15]]) as usize;
+    let entry_offset = reserved_sectors * 512 + a[1].0 as usize * 2;
+    data[entry_offset..entry_offset + 2].copy_from_slice(&(a[0].0 as u16).to_le_bytes());
+
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let file = fs.root_dir().open_file("a.bin").unwrap();
+    let mut clusters = file.clusters();
+    let total_clusters = fs.stats().unwrap().total_clusters as usize;
+    assert!(clusters.by_ref().take(total_clusters).all(|r| r.is_ok()));
+    assert_eq!(clusters.next().unwrap().unwrap_err().kind(), ErrorKind::CorruptedFile);
+    assert!(clusters.next().is_none());
+}
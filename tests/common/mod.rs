@@ -0,0 +1,102 @@
+// Volume images and devices shared by integration tests.
+#![allow(dead_code)]
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use basic_io::{Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
+use fatfs::{FatType, FormatOptions};
+
+pub const MB: usize = 1024 * 1024;
+
+// Returns image of a freshly formatted volume of `size` bytes
+pub fn format_image(size: usize, options: FormatOptions) -> Vec<u8> {
+    let mut data = vec![0u8; size];
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        fatfs::format_volume(&mut cursor, options).unwrap();
+    }
+    data
+}
+
+// Returns image of a volume with 512-byte clusters - 4 MB or 40 MB for FAT32 (smallest FAT32 size)
+pub fn small_volume(fat_type: FatType) -> Vec<u8> {
+    let size = if fat_type == FatType::Fat32 { 40 * MB } else { 4 * MB };
+    format_image(size, FormatOptions::new().fat_type(fat_type).bytes_per_cluster(512))
+}
+
+// Device kept in memory failing accesses on demand and recording writes
+pub struct FaultyDisk {
+    pub data: Vec<u8>,
+    pos: u64,
+    // all writes fail while set
+    pub writes_fail: Rc<Cell<bool>>,
+    // reads touching this byte range fail while set
+    pub read_fault: Rc<Cell<Option<(u64, u64)>>>,
+    // offsets and lengths of successful writes
+    pub write_log: Rc<RefCell<Vec<(u64, usize)>>>,
+    // flush fails and makes all writes fail while set (power is lost at a write barrier)
+    pub flush_fails: Rc<Cell<bool>>,
+}
+
+impl FaultyDisk {
+    pub fn new(data: Vec<u8>) -> Self {
+        FaultyDisk {
+            data,
+            pos: 0,
+            writes_fail: Rc::new(Cell::new(false)),
+            read_fault: Rc::new(Cell::new(None)),
+            write_log: Rc::new(RefCell::new(Vec::new())),
+            flush_fails: Rc::new(Cell::new(false)),
+        }
+    }
+}
+
+impl Read for FaultyDisk {
+    fn read(&mut self, buf: &mut [u8]) -> basic_io::Result<usize> {
+        let start = self.pos as usize;
+        let n = buf.len().min(self.data.len() - start);
+        match self.read_fault.get() {
+            Some((begin, end)) if self.pos < end && self.pos + n as u64 > begin => {
+                return Err(basic_io::Error::new(ErrorKind::Other, "read error"));
+            }
+            _ => {}
+        }
+        buf[..n].copy_from_slice(&self.data[start..start + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Write for FaultyDisk {
+    fn write(&mut self, buf: &[u8]) -> basic_io::Result<usize> {
+        if self.writes_fail.get() {
+            return Err(basic_io::Error::new(ErrorKind::Other, "write error"));
+        }
+        let start = self.pos as usize;
+        let n = buf.len().min(self.data.len() - start);
+        self.data[start..start + n].copy_from_slice(&buf[..n]);
+        self.write_log.borrow_mut().push((self.pos, n));
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> basic_io::Result<()> {
+        if self.flush_fails.get() {
+            self.writes_fail.set(true);
+            return Err(basic_io::Error::new(ErrorKind::Other, "flush error"));
+        }
+        Ok(())
+    }
+}
+
+impl Seek for FaultyDisk {
+    fn seek(&mut self, pos: SeekFrom) -> basic_io::Result<u64> {
+        self.pos = match pos {
+            SeekFrom::Start(n) => n,
+            SeekFrom::Current(n) => (self.pos as i64 + n) as u64,
+            SeekFrom::End(n) => (self.data.len() as i64 + n) as u64,
+        };
+        Ok(self.pos)
+    }
+}
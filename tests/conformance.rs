@@ -0,0 +1,281 @@
+// Conformance tests against reference images created by other FAT implementations.
+//
+// Every fixture describes an image stored in the repository and the tree it is expected to
+// contain. FAT12 and FAT16 images are written by tests/fixtures/make_images.py, which does not use
+// this crate, and cover Unicode long names, deep trees, fragmented files and multi-cluster
+// directories. No image made by Windows is included yet - new images are added by extending
+// FIXTURES.
+
+extern crate basic_io;
+extern crate fatfs;
+
+use std::fs;
+use std::path::Path;
+
+use basic_io::Read;
+use fatfs::{Dir, FatType, FileSystem, FsOptions, ReadWriteSeek};
+
+struct ExpectedEntry {
+    path: &'static str,
+    // None for directories
+    size: Option<u64>,
+    // FNV-1a 64-bit hash of file contents
+    hash: u64,
+}
+
+struct Fixture {
+    image: &'static str,
+    fat_type: FatType,
+    volume_label: &'static str,
+    entries: &'static [ExpectedEntry],
+    // long paths and short paths of the same entries
+    long_names: &'static [(&'static str, &'static str)],
+}
+
+const SHORT_HASH: u64 = 0x715885790404b4c9;
+const LONG_HASH: u64 = 0x9c91d53679f5b1a5;
+
+const FIXTURES: &[Fixture] = &[
+    // mkfs.fat -F 32, 512 bytes per cluster, populated on Linux
+    Fixture {
+        image: "fat32.img",
+        fat_type: FatType::Fat32,
+        volume_label: "Test!",
+        entries: &[
+            ExpectedEntry { path: "/LONG.TXT", size: Some(14000), hash: LONG_HASH },
+            ExpectedEntry { path: "/SHORT.TXT", size: Some(14), hash: SHORT_HASH },
+            ExpectedEntry { path: "/VERY", size: None, hash: 0 },
+            ExpectedEntry { path: "/VERY/LONG", size: None, hash: 0 },
+            ExpectedEntry { path: "/VERY/LONG/PATH", size: None, hash: 0 },
+            ExpectedEntry { path: "/VERY/LONG/PATH/TEST.TXT", size: Some(14), hash: SHORT_HASH },
+            ExpectedEntry { path: "/VERY-L~1", size: None, hash: 0 },
+            ExpectedEntry { path: "/VERY-L~1/VERY-L~1.TXT", size: Some(14), hash: SHORT_HASH },
+        ],
+        long_names: &[
+            ("/very-long-dir-name", "/VERY-L~1"),
+            ("/very-long-dir-name/very-long-file-name.txt", "/VERY-L~1/VERY-L~1.TXT"),
+        ],
+    },
+    // 1.44 MB floppy layout
+    Fixture {
+        image: "tests/fixtures/fat12.img",
+        fat_type: FatType::Fat12,
+        volume_label: "FLOPPY",
+        entries: &[
+            ExpectedEntry { path: "/README.TXT", size: Some(39), hash: 0xf6f67939aca6ba0c },
+            ExpectedEntry { path: "/_NIC_D~1.TXT", size: Some(3000), hash: 0x122d52aa787d33f8 },
+            ExpectedEntry { path: "/EXACTL~1.TXT", size: Some(700), hash: 0xddce3a629a0ee646 },
+            ExpectedEntry { path: "/FRAG1.BIN", size: Some(5020), hash: 0x0cac41be12e19d2d },
+            ExpectedEntry { path: "/FRAG2.BIN", size: Some(5120), hash: 0x86d7319ded375b25 },
+            ExpectedEntry { path: "/EMPTY.TXT", size: Some(0), hash: 0xcbf29ce484222325 },
+            ExpectedEntry { path: "/DEEP", size: None, hash: 0 },
+            ExpectedEntry { path: "/DEEP/L1", size: None, hash: 0 },
+            ExpectedEntry { path: "/DEEP/L1/L2", size: None, hash: 0 },
+            ExpectedEntry { path: "/DEEP/L1/L2/L3", size: None, hash: 0 },
+            ExpectedEntry { path: "/DEEP/L1/L2/L3/L4", size: None, hash: 0 },
+            ExpectedEntry { path: "/DEEP/L1/L2/L3/L4/L5", size: None, hash: 0 },
+            ExpectedEntry { path: "/DEEP/L1/L2/L3/L4/L5/L6", size: None, hash: 0 },
+            ExpectedEntry {
+                path: "/DEEP/L1/L2/L3/L4/L5/L6/L7",
+                size: None,
+                hash: 0,
+            },
+            ExpectedEntry {
+                path: "/DEEP/L1/L2/L3/L4/L5/L6/L7/LEAF.TXT",
+                size: Some(1234),
+                hash: 0x4246a7efb1678b40,
+            },
+        ],
+        long_names: &[
+            ("/Ünïcödé файл.txt", "/_NIC_D~1.TXT"),
+            ("/exactly13.txt", "/EXACTL~1.TXT"),
+        ],
+    },
+    // 512 bytes per cluster, directory and file with interleaved clusters
+    Fixture {
+        image: "tests/fixtures/fat16.img",
+        fat_type: FatType::Fat16,
+        volume_label: "FAT16VOL",
+        entries: &[
+            ExpectedEntry { path: "/_____~1.TXT", size: Some(5000), hash: 0x27d98c101807cb02 },
+            ExpectedEntry { path: "/BIG.BIN", size: Some(28143), hash: 0x50ed7e1ea5ef1bbe },
+            ExpectedEntry { path: "/MANY", size: None, hash: 0 },
+            ExpectedEntry { path: "/MANY/F00.DAT", size: Some(0), hash: 0xcbf29ce484222325 },
+            ExpectedEntry { path: "/MANY/F01.DAT", size: Some(37), hash: 0x09a75cb388c8b90f },
+            ExpectedEntry { path: "/MANY/F02.DAT", size: Some(74), hash: 0x2be71d1c152cb2d6 },
+            ExpectedEntry { path: "/MANY/F03.DAT", size: Some(111), hash: 0xe795c72d1fbac8c9 },
+            ExpectedEntry { path: "/MANY/F04.DAT", size: Some(148), hash: 0xc6a214aa8d4e183e },
+            ExpectedEntry { path: "/MANY/F05.DAT", size: Some(185), hash: 0x860fe119e35b1274 },
+            ExpectedEntry { path: "/MANY/F06.DAT", size: Some(222), hash: 0xb772626dcdd4c4e8 },
+            ExpectedEntry { path: "/MANY/F07.DAT", size: Some(259), hash: 0xeba6bca6e2c456cc },
+            ExpectedEntry { path: "/MANY/F08.DAT", size: Some(296), hash: 0x0aa6cff04fe776e2 },
+            ExpectedEntry { path: "/MANY/F09.DAT", size: Some(333), hash: 0xce0b0ba3aa610275 },
+            ExpectedEntry { path: "/MANY/F10.DAT", size: Some(370), hash: 0x94ce77c760d08485 },
+            ExpectedEntry { path: "/MANY/F11.DAT", size: Some(407), hash: 0xf184b59015a8e89f },
+            ExpectedEntry { path: "/MANY/F12.DAT", size: Some(444), hash: 0x67452e522ea462e5 },
+            ExpectedEntry { path: "/MANY/F13.DAT", size: Some(481), hash: 0x304fa4b306fea5ec },
+            ExpectedEntry { path: "/MANY/F14.DAT", size: Some(518), hash: 0x5e32ea7118c763d5 },
+            ExpectedEntry { path: "/MANY/F15.DAT", size: Some(555), hash: 0xabf1c382dc0c8c79 },
+            ExpectedEntry { path: "/MANY/F16.DAT", size: Some(592), hash: 0xed198fba7231e06d },
+            ExpectedEntry { path: "/MANY/F17.DAT", size: Some(629), hash: 0x3c655d1045f9fb62 },
+            ExpectedEntry { path: "/MANY/F18.DAT", size: Some(666), hash: 0x601e4e3d76855559 },
+            ExpectedEntry { path: "/MANY/F19.DAT", size: Some(703), hash: 0xf635b94fe8844951 },
+            ExpectedEntry { path: "/MANY/F20.DAT", size: Some(740), hash: 0x485210134ba64d6b },
+            ExpectedEntry { path: "/MANY/F21.DAT", size: Some(777), hash: 0x08f33f5fa2127266 },
+            ExpectedEntry { path: "/MANY/F22.DAT", size: Some(814), hash: 0x855724a8151cf29a },
+            ExpectedEntry { path: "/MANY/F23.DAT", size: Some(851), hash: 0xe3d228e97409afab },
+            ExpectedEntry { path: "/MANY/F24.DAT", size: Some(888), hash: 0xdfbf8a9d1aef2d79 },
+            ExpectedEntry { path: "/MANY/F25.DAT", size: Some(925), hash: 0x32b9746b24d23950 },
+            ExpectedEntry { path: "/MANY/F26.DAT", size: Some(962), hash: 0x256f67cbcd92e3da },
+            ExpectedEntry { path: "/MANY/F27.DAT", size: Some(999), hash: 0xca3a62886e0b011e },
+            ExpectedEntry { path: "/MANY/F28.DAT", size: Some(1036), hash: 0x0eb6d18733a56121 },
+            ExpectedEntry { path: "/MANY/F29.DAT", size: Some(1073), hash: 0xafeb56d7655d1241 },
+            ExpectedEntry { path: "/MANY/F30.DAT", size: Some(1110), hash: 0x35924272a3c61d91 },
+            ExpectedEntry { path: "/MANY/F31.DAT", size: Some(1147), hash: 0xcf05981e055eef99 },
+            ExpectedEntry { path: "/MANY/F32.DAT", size: Some(1184), hash: 0xe422df2b007a606d },
+            ExpectedEntry { path: "/MANY/F33.DAT", size: Some(1221), hash: 0x62c162c12a019799 },
+            ExpectedEntry { path: "/MANY/F34.DAT", size: Some(1258), hash: 0x14be536b20aa0c5f },
+            ExpectedEntry { path: "/MANY/F35.DAT", size: Some(1295), hash: 0x15f8908c0a5c0daf },
+            ExpectedEntry { path: "/MANY/F36.DAT", size: Some(1332), hash: 0x66cb0de7bcd3de9f },
+            ExpectedEntry { path: "/MANY/F37.DAT", size: Some(1369), hash: 0x66326a3c502f109a },
+            ExpectedEntry { path: "/MANY/F38.DAT", size: Some(1406), hash: 0xf7901e1815a6ae0a },
+            ExpectedEntry { path: "/MANY/F39.DAT", size: Some(1443), hash: 0x4fc7ecaf1292601f },
+        ],
+        long_names: &[
+            ("/日本語のファイル名、長い名前のテストです。三つのエントリ.txt", "/_____~1.TXT"),
+            ("/Directory with many files", "/MANY"),
+            ("/MANY/file number 0.dat", "/MANY/F00.DAT"),
+            ("/MANY/file number 3.dat", "/MANY/F03.DAT"),
+            ("/MANY/file number 6.dat", "/MANY/F06.DAT"),
+            ("/MANY/file number 9.dat", "/MANY/F09.DAT"),
+            ("/MANY/file number 12.dat", "/MANY/F12.DAT"),
+            ("/MANY/file number 15.dat", "/MANY/F15.DAT"),
+            ("/MANY/file number 18.dat", "/MANY/F18.DAT"),
+            ("/MANY/file number 21.dat", "/MANY/F21.DAT"),
+            ("/MANY/file number 24.dat", "/MANY/F24.DAT"),
+            ("/MANY/file number 27.dat", "/MANY/F27.DAT"),
+            ("/MANY/file number 30.dat", "/MANY/F30.DAT"),
+            ("/MANY/file number 33.dat", "/MANY/F33.DAT"),
+            ("/MANY/file number 36.dat", "/MANY/F36.DAT"),
+            ("/MANY/file number 39.dat", "/MANY/F39.DAT"),
+        ],
+    },
+];
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |h, b| (h ^ u64::from(*b)).wrapping_mul(0x100_0000_01b3))
+}
+
+fn read_image(name: &str) -> Vec<u8> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(name);
+    fs::read(&path).unwrap_or_else(|err| panic!("cannot read fixture {}: {}", name, err))
+}
+
+fn hash_file<R: Read>(file: &mut R) -> (u64, u64) {
+    let mut hash = 0xcbf2_9ce4_8422_2325;
+    let mut len = 0;
+    let mut buf = [0u8; 1000];
+    loop {
+        let n = file.read(&mut buf).expect("read failed");
+        if n == 0 {
+            break;
+        }
+        hash = fnv1a(hash, &buf[..n]);
+        len += n as u64;
+    }
+    (hash, len)
+}
+
+fn collect_tree<IO: ReadWriteSeek>(
+    dir: &Dir<IO>,
+    path: &str,
+    out: &mut Vec<(String, Option<u64>, u64)>,
+) {
+    for entry in dir.iter() {
+        let entry = entry.expect("failed to read entry");
+        let name = entry.short_file_name();
+        if name == "." || name == ".." {
+            continue;
+        }
+        let entry_path = format!("{}/{}", path, name);
+        if entry.is_dir() {
+            out.push((entry_path.clone(), None, 0));
+            collect_tree(&entry.to_dir(), &entry_path, out);
+        } else {
+            let (hash, len) = hash_file(&mut entry.to_file());
+            assert_eq!(len, entry.len(), "{}: read length differs from entry size", entry_path);
+            out.push((entry_path, Some(entry.len()), hash));
+        }
+    }
+}
+
+#[test]
+fn fixtures_mount_with_expected_parameters() {
+    for fixture in FIXTURES {
+        let mut data = read_image(fixture.image);
+        let mut disk = basic_io::Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut disk, FsOptions::new()).expect("mount failed");
+        assert_eq!(fs.fat_type(), fixture.fat_type, "{}", fixture.image);
+        assert_eq!(fs.volume_label(), fixture.volume_label, "{}", fixture.image);
+    }
+}
+
+#[test]
+fn fixtures_list_and_read_expected_tree() {
+    for fixture in FIXTURES {
+        let mut data = read_image(fixture.image);
+        let mut disk = basic_io::Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut disk, FsOptions::new()).expect("mount failed");
+        let mut tree = Vec::new();
+        collect_tree(&fs.root_dir(), "", &mut tree);
+        let expected: Vec<_> = fixture
+            .entries
+            .iter()
+            .map(|e| (e.path.to_string(), e.size, e.hash))
+            .collect();
+        assert_eq!(tree, expected, "{}", fixture.image);
+    }
+}
+
+#[test]
+fn fixtures_open_files_by_path() {
+    for fixture in FIXTURES {
+        let mut data = read_image(fixture.image);
+        let mut disk = basic_io::Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut disk, FsOptions::new()).expect("mount failed");
+        for entry in fixture.entries.iter().filter(|e| e.size.is_some()) {
+            // lookups are case insensitive
+            let path = entry.path.to_lowercase();
+            let mut file = fs.root_dir().open_file(&path).expect("open failed");
+            let (hash, len) = hash_file(&mut file);
+            assert_eq!((Some(len), hash), (entry.size, entry.hash), "{}", entry.path);
+        }
+    }
+}
+
+#[test]
+fn fixtures_resolve_long_names() {
+    for fixture in FIXTURES {
+        let mut data = read_image(fixture.image);
+        let mut disk = basic_io::Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut disk, FsOptions::new()).expect("mount failed");
+        for &(long_path, short_path) in fixture.long_names {
+            let (parent, short_name) = short_path.split_at(short_path.rfind('/').unwrap() + 1);
+            let dir = match parent.trim_matches('/') {
+                "" => fs.root_dir(),
+                parent => fs.root_dir().open_dir(parent).expect("open parent failed"),
+            };
+            let entry = dir
+                .iter()
+                .map(|e| e.expect("failed to read entry"))
+                .find(|e| e.short_file_name() == short_name)
+                .expect("entry not found");
+            assert_eq!(entry.file_name(), &long_path[long_path.rfind('/').unwrap() + 1..]);
+            if entry.is_file() {
+                let mut file = fs.root_dir().open_file(&long_path[1..]).expect("open failed");
+                let expected = fixture.entries.iter().find(|e| e.path == short_path).unwrap();
+                assert_eq!(hash_file(&mut file), (expected.hash, expected.size.unwrap()));
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+// Tests of reading physically consecutive clusters in one device operation.
+
+extern crate basic_io;
+extern crate fatfs;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use basic_io::{Cursor, Read, Seek, SeekFrom, Write};
+use fatfs::{FatType, FileSystem, FormatOptions, FsOptions};
+
+const CLUSTER_SIZE: usize = 512;
+
+// Device recording lengths of all reads
+struct CountingDisk {
+    data: Vec<u8>,
+    pos: usize,
+    reads: Rc<RefCell<Vec<usize>>>,
+}
+
+impl Read for CountingDisk {
+    fn read(&mut self, buf: &mut [u8]) -> basic_io::Result<usize> {
+        self.reads.borrow_mut().push(buf.len());
+        let n = buf.len().min(self.data.len() - self.pos);
+        buf[..n].copy_from_slice(&self.data[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl Write for CountingDisk {
+    fn write(&mut self, buf: &[u8]) -> basic_io::Result<usize> {
+        let n = buf.len().min(self.data.len() - self.pos);
+        self.data[self.pos..self.pos + n].copy_from_slice(&buf[..n]);
+        self.pos += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> basic_io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for CountingDisk {
+    fn seek(&mut self, pos: SeekFrom) -> basic_io::Result<u64> {
+        self.pos = match pos {
+            SeekFrom::Start(n) => n as usize,
+            SeekFrom::Current(n) => (self.pos as i64 + n) as usize,
+            SeekFrom::End(n) => (self.data.len() as i64 + n) as usize,
+        };
+        Ok(self.pos as u64)
+    }
+}
+
+fn content(len: usize, seed: u8) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8 ^ seed).collect()
+}
+
+fn create_disk() -> CountingDisk {
+    let mut data = vec![0u8; 4 * 1024 * 1024];
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let options =
+            FormatOptions::new().fat_type(FatType::Fat16).bytes_per_cluster(CLUSTER_SIZE as u32);
+        fatfs::format_volume(&mut cursor, options).unwrap();
+    }
+    let mut disk = CountingDisk { data, pos: 0, reads: Rc::new(RefCell::new(Vec::new())) };
+    {
+        let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+        let mut root = fs.root_dir();
+        root.create_file("a.bin").unwrap().write_all(&content(CLUSTER_SIZE * 16, 1)).unwrap();
+        // fragment b.bin: 4 clusters, then a cluster of c.bin, then 4 more clusters
+        root.create_file("b.bin").unwrap().write_all(&content(CLUSTER_SIZE * 4, 2)).unwrap();
+        root.create_file("c.bin").unwrap().write_all(&content(CLUSTER_SIZE, 3)).unwrap();
+        let mut b = root.open_file("b.bin").unwrap();
+        b.seek(SeekFrom::End(0)).unwrap();
+        b.write_all(&content(CLUSTER_SIZE * 4, 4)).unwrap();
+    }
+    disk
+}
+
+// Reads whole file with reads of `chunk_size` bytes and returns lengths of device reads at least
+// cluster long (FAT and directory entries are read in smaller pieces)
+fn read_file(disk: &mut CountingDisk, path: &str, chunk_size: usize) -> (Vec<u8>, Vec<usize>) {
+    disk.pos = 0;
+    let reads = disk.reads.clone();
+    let fs = FileSystem::new(disk, FsOptions::new()).unwrap();
+    let mut file = fs.root_dir().open_file(path).unwrap();
+    // start in the middle of a cluster
+    let mut data = vec![0u8; 100];
+    file.read_exact(&mut data).unwrap();
+    reads.borrow_mut().clear();
+    let mut chunk = vec![0u8; chunk_size];
+    loop {
+        match file.read(&mut chunk).unwrap() {
+            0 => break,
+            n => data.extend_from_slice(&chunk[..n]),
+        }
+    }
+    let data_reads = reads.borrow().iter().cloned().filter(|&n| n >= CLUSTER_SIZE).collect();
+    (data, data_reads)
+}
+
+#[test]
+fn contiguous_file_is_read_in_one_operation() {
+    let mut disk = create_disk();
+    let (data, reads) = read_file(&mut disk, "a.bin", CLUSTER_SIZE * 64);
+    assert!(data == content(CLUSTER_SIZE * 16, 1));
+    assert_eq!(reads, vec![CLUSTER_SIZE * 16 - 100]);
+}
+
+#[test]
+fn fragmented_file_is_read_per_fragment() {
+    let mut disk = create_disk();
+    let mut expected = content(CLUSTER_SIZE * 4, 2);
+    expected.extend_from_slice(&content(CLUSTER_SIZE * 4, 4));
+    let (data, reads) = read_file(&mut disk, "b.bin", CLUSTER_SIZE * 64);
+    assert!(data == expected);
+    assert_eq!(reads, vec![CLUSTER_SIZE * 4 - 100, CLUSTER_SIZE * 4]);
+    // reads are also bounded by buffer size
+    let (data, reads) = read_file(&mut disk, "b.bin", CLUSTER_SIZE * 3);
+    assert!(data == expected);
+    assert!(reads.iter().all(|&n| n <= CLUSTER_SIZE * 3));
+}
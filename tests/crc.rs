@@ -0,0 +1,130 @@
+// Tests of metadata CRC protection (enabled by `crc` feature).
+#![cfg(feature = "crc")]
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use std::mem;
+use std::rc::Rc;
+
+use basic_io::{ErrorKind, Seek, SeekFrom, Write};
+use fatfs::{FatType, FileSystem, FsOptions, MountProfile};
+use common::FaultyDisk;
+
+fn options() -> FsOptions {
+    FsOptions::new().metadata_crc(true)
+}
+
+// Returns image of a volume with a CRC file and a single file
+fn protected_volume() -> Vec<u8> {
+    let mut disk = FaultyDisk::new(common::small_volume(FatType::Fat16));
+    {
+        let fs = FileSystem::new(&mut disk, options()).unwrap();
+        fs.root_dir().create_file("a.txt").unwrap().write_all(b"data").unwrap();
+    }
+    disk.data
+}
+
+// Offset of short name entry with given raw name
+fn entry_offset(data: &[u8], raw_name: &[u8; 11]) -> usize {
+    data.chunks(32).position(|e| &e[..11] == raw_name).unwrap() * 32
+}
+
+#[test]
+fn modified_metadata_is_detected() {
+    let mut data = protected_volume();
+    // change modification time of the file
+    let entry = entry_offset(&data, b"A       TXT");
+    data[entry + 22] ^= 1;
+    let disk = FaultyDisk::new(data.clone());
+    let writes_fail = disk.writes_fail.clone();
+    let err = FileSystem::new(disk, options()).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::Other);
+    // rejected device is dropped, not leaked
+    assert_eq!(Rc::strong_count(&writes_fail), 1);
+    let fs = FileSystem::new(FaultyDisk::new(data), options().verify_metadata_crc(false));
+    assert!(fs.unwrap().root_dir().open_file("a.txt").is_ok());
+}
+
+#[test]
+fn rejected_volume_is_not_modified() {
+    let mut data = protected_volume();
+    let entry = entry_offset(&data, b"A       TXT");
+    data[entry + 22] ^= 1;
+    let mut disk = FaultyDisk::new(data.clone());
+    assert!(FileSystem::new(&mut disk, options()).is_err());
+    assert!(disk.data == data);
+
+    // volume without CRC file rejected after the boot sector was read
+    let mut data = common::small_volume(FatType::Fat16);
+    let fat_offset = 512 * (data[14] as usize | (data[15] as usize) << 8);
+    data[fat_offset] = 0;
+    let mut disk = FaultyDisk::new(data.clone());
+    let options = options().profile(MountProfile::Strict);
+    assert!(FileSystem::new(&mut disk, options).is_err());
+    assert!(disk.data == data);
+}
+
+#[test]
+fn flush_updates_checksums() {
+    let mut disk = FaultyDisk::new(protected_volume());
+    {
+        let fs = FileSystem::new(&mut disk, options()).unwrap();
+        fs.root_dir().create_dir("dir").unwrap().create_file("b.txt").unwrap();
+        fs.flush().unwrap();
+        // power loss - filesystem is never unmounted
+        mem::forget(fs);
+    }
+    disk.seek(SeekFrom::Start(0)).unwrap();
+    let fs = FileSystem::new(&mut disk, options()).unwrap();
+    assert!(fs.root_dir().open_file("dir/b.txt").is_ok());
+}
+
+#[test]
+fn volume_with_io_error_flag_can_be_mounted_again() {
+    let mut disk = FaultyDisk::new(common::small_volume(FatType::Fat16));
+    let writes_fail = disk.writes_fail.clone();
+    {
+        let fs = FileSystem::new(&mut disk, options()).unwrap();
+        let mut file = fs.root_dir().create_file("a.txt").unwrap();
+        file.write_all(b"data").unwrap();
+        file.flush().unwrap();
+        // data write fails without poisoning the filesystem
+        writes_fail.set(true);
+        assert!(file.write_all(b"more").is_err());
+        writes_fail.set(false);
+        drop(file);
+        assert!(!fs.is_poisoned());
+        // unmounted on drop
+    }
+    disk.seek(SeekFrom::Start(0)).unwrap();
+    let fs = FileSystem::new(&mut disk, options()).unwrap();
+    assert!(fs.read_status_flags().unwrap().io_error);
+}
+
+#[test]
+fn protection_is_disabled_by_default() {
+    let mut data = protected_volume();
+    let entry = entry_offset(&data, b"A       TXT");
+    data[entry + 22] ^= 1;
+    let mut disk = FaultyDisk::new(data.clone());
+    {
+        let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+        assert!(fs.root_dir().open_file("a.txt").is_ok());
+    }
+    // CRC file is not updated
+    assert!(disk.data == data);
+    disk.seek(SeekFrom::Start(0)).unwrap();
+    assert!(FileSystem::new(&mut disk, options()).is_err());
+
+    // CRC file is not created
+    let mut disk = FaultyDisk::new(common::small_volume(FatType::Fat16));
+    {
+        let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+        fs.root_dir().create_file("a.txt").unwrap();
+    }
+    let data = disk.data;
+    assert!(!data.chunks(32).any(|e| &e[..11] == b"FATCRC  SYS"));
+}
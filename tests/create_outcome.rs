@@ -0,0 +1,44 @@
+// Tests of creation APIs reporting whether an entry was created or opened.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, Read, Write};
+use fatfs::{FileSystem, FormatOptions, FsOptions};
+use common::MB;
+
+#[test]
+fn file_creation_is_reported_once() {
+    let mut data = common::format_image(MB, FormatOptions::new());
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let outcome = root.open_or_create_file("boot.cfg").unwrap();
+    assert!(outcome.is_created());
+    outcome.into_inner().write_all(b"first boot").unwrap();
+    let outcome = root.open_or_create_file("boot.cfg").unwrap();
+    assert!(!outcome.is_created());
+    // existing file is opened without truncating
+    let mut buf = [0u8; 10];
+    outcome.into_inner().read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"first boot");
+    assert_eq!(root.iter().count(), 1);
+}
+
+#[test]
+fn dir_creation_is_reported_once() {
+    let mut data = common::format_image(MB, FormatOptions::new());
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    assert!(root.open_or_create_dir("data").unwrap().is_created());
+    assert!(!root.open_or_create_dir("data").unwrap().is_created());
+    // nested paths report the outcome for the last component
+    assert!(root.open_or_create_file("data/log.txt").unwrap().is_created());
+    assert!(!root.open_or_create_file("data/log.txt").unwrap().is_created());
+    let sub = root.open_or_create_dir("data").unwrap().into_inner();
+    assert_eq!(sub.iter().count(), 3);
+    fs.verify_invariants().unwrap();
+}
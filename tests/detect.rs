@@ -0,0 +1,54 @@
+// Tests of probing devices for FAT filesystem.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, Seek, SeekFrom};
+use fatfs::{FatType, FormatOptions};
+
+fn create_volume(fat_type: FatType, size: usize) -> Vec<u8> {
+    common::format_image(size, FormatOptions::new().fat_type(fat_type))
+}
+
+fn detect(data: &mut [u8]) -> Option<FatType> {
+    let mut cursor = Cursor::new(data);
+    cursor.seek(SeekFrom::Start(1234)).unwrap();
+    let r = fatfs::detect(&mut cursor);
+    // position is restored
+    assert_eq!(cursor.seek(SeekFrom::Current(0)).unwrap(), 1234);
+    r
+}
+
+#[test]
+fn detect_formatted_volumes() {
+    use common::MB;
+    let volumes = [(FatType::Fat12, MB), (FatType::Fat16, 8 * MB), (FatType::Fat32, 40 * MB)];
+    for &(fat_type, size) in volumes.iter() {
+        let mut data = create_volume(fat_type, size);
+        assert_eq!(detect(&mut data), Some(fat_type));
+    }
+}
+
+#[test]
+fn detect_rejects_non_fat_data() {
+    let mut zeros = vec![0u8; 64 * 1024];
+    assert_eq!(detect(&mut zeros), None);
+    let mut short = vec![0xEBu8; 100];
+    assert_eq!(detect(&mut short), None);
+
+    let corruptions: [(usize, u8); 6] = [
+        (0, 0x00),   // jump instruction
+        (12, 0x03),  // bytes per sector not power of two
+        (13, 3),     // sectors per cluster not power of two
+        (16, 0),     // number of FATs
+        (21, 0x12),  // media descriptor
+        (510, 0x00), // signature
+    ];
+    for &(offset, value) in corruptions.iter() {
+        let mut data = create_volume(FatType::Fat16, 8 * 1024 * 1024);
+        data[offset] = value;
+        assert_eq!(detect(&mut data), None, "corrupted byte {}", offset);
+    }
+}
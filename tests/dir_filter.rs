@@ -0,0 +1,93 @@
+// Tests of directory iterators filtering entries by attributes and names.
+
+extern crate basic_io;
+extern crate fatfs;
+
+use basic_io::Cursor;
+use fatfs::{DirEntry, FatType, FileAttributes, FileSystem, FormatOptions, FsOptions, ReadWriteSeek};
+
+const MB: usize = 1024 * 1024;
+
+fn names<'a, IO: ReadWriteSeek + 'a, I>(iter: I) -> Vec<String>
+where
+    I: Iterator<Item = basic_io::Result<DirEntry<'a, IO>>>,
+{
+    iter.map(|e| e.unwrap().file_name().to_string().to_lowercase()).collect()
+}
+
+#[test]
+fn filtered_iterators() {
+    let mut data = vec![0u8; MB];
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        fatfs::format_volume(&mut cursor, FormatOptions::new().fat_type(FatType::Fat12)).unwrap();
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        let mut root = fs.root_dir();
+        root.create_file("a.txt").unwrap();
+        let mut sub = root.create_dir("sub").unwrap();
+        root.create_file("b.txt").unwrap();
+        root.create_dir("other").unwrap();
+        sub.create_file("c.txt").unwrap();
+    }
+    // mark b.txt as hidden
+    let entry = data.chunks_mut(32).find(|e| &e[..11] == b"B       TXT").unwrap();
+    entry[11] |= FileAttributes::HIDDEN.bits();
+
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let sub = root.open_dir("sub").unwrap();
+    assert_eq!(names(root.files()), ["a.txt", "b.txt"]);
+    assert_eq!(names(root.dirs()), ["sub", "other"]);
+    assert_eq!(names(sub.files()), ["c.txt"]);
+    assert_eq!(names(sub.dirs()), [".", ".."]);
+    let hidden = root.iter_filtered(FileAttributes::HIDDEN, FileAttributes::empty());
+    assert_eq!(names(hidden), ["b.txt"]);
+    let visible = root.iter_filtered(FileAttributes::empty(), FileAttributes::HIDDEN);
+    assert_eq!(names(visible), ["a.txt", "sub", "other"]);
+    let all = root.iter_filtered(FileAttributes::empty(), FileAttributes::empty());
+    assert_eq!(names(all), names(root.iter()));
+}
+
+#[test]
+fn entries_found_by_long_or_short_name_prefix() {
+    let mut data = vec![0u8; MB];
+    let mut cursor = Cursor::new(&mut data[..]);
+    fatfs::format_volume(&mut cursor, FormatOptions::new().fat_type(FatType::Fat12)).unwrap();
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    root.create_file("Report 2020.txt").unwrap();
+    root.create_file("old.doc").unwrap();
+    root.create_file("report.doc").unwrap();
+    root.create_file("readme.md").unwrap();
+    root.create_dir("Reports").unwrap();
+    root.remove("old.doc").unwrap();
+
+    assert_eq!(names(root.find_prefix("rep")), ["report 2020.txt", "report.doc", "reports"]);
+    assert_eq!(names(root.find_prefix("REPORT.")), ["report.doc"]);
+    // short name of the first file has no space
+    assert_eq!(names(root.find_prefix("report2")), ["report 2020.txt"]);
+    assert_eq!(names(root.find_prefix("")).len(), 4);
+    assert!(names(root.find_prefix("old")).is_empty());
+    assert!(names(root.find_prefix("readme.md.")).is_empty());
+    // scan can be restarted from a copy of the iterator
+    let mut iter = root.find_prefix("re");
+    iter.next();
+    assert_eq!(names(iter.clone()), names(iter));
+}
+
+#[test]
+fn prefix_ignores_case_of_non_ascii_characters() {
+    let mut data = vec![0u8; MB];
+    let mut cursor = Cursor::new(&mut data[..]);
+    fatfs::format_volume(&mut cursor, FormatOptions::new().fat_type(FatType::Fat12)).unwrap();
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    root.create_file("Übersicht.txt").unwrap();
+    root.create_file("Straße.txt").unwrap();
+    assert_eq!(names(root.find_prefix("üBER")), ["übersicht.txt"]);
+    assert!(root.find_prefix("ÜBERSICHT.TXT").next().is_some());
+    // characters without a single character upper case form are compared exactly
+    assert_eq!(names(root.find_prefix("STRAß")), ["straße.txt"]);
+    assert!(names(root.find_prefix("STRASS")).is_empty());
+}
@@ -0,0 +1,44 @@
+// Tests of directory entry layout information.
+
+extern crate basic_io;
+extern crate fatfs;
+
+use basic_io::Cursor;
+use fatfs::{FileSystem, FormatOptions, FsOptions};
+
+#[test]
+fn offset_range_includes_lfn_entries() {
+    let mut data = vec![0u8; 1024 * 1024];
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        fatfs::format_volume(&mut cursor, FormatOptions::new()).unwrap();
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        let mut root = fs.root_dir();
+        for name in ["a.txt", "lfn", "b.txt"].iter() {
+            root.create_file(name).unwrap();
+        }
+    }
+    // turn "LFN" entry into a long file name entry belonging to "B.TXT"
+    let slot = data.chunks(32).position(|e| &e[..11] == b"LFN        ").unwrap() * 32;
+    data[slot..slot + 32].copy_from_slice(&[0; 32]);
+    data[slot] = 0x41;
+    data[slot + 11] = 0x0F;
+
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let entries: Vec<_> = root.iter().map(|e| e.unwrap()).collect();
+    assert_eq!(entries.len(), 2);
+    let (a, b) = (&entries[0], &entries[1]);
+    assert_eq!(a.short_file_name(), "A.TXT");
+    assert_eq!(a.slot_count(), 1);
+    assert_eq!(a.offset_range().1 - a.offset_range().0, 32);
+    assert_eq!(b.short_file_name(), "B.TXT");
+    assert_eq!(b.slot_count(), 2);
+    assert_eq!(b.offset_range(), (a.offset_range().1, a.offset_range().1 + 64));
+
+    root.remove("a.txt").unwrap();
+    let b = root.iter().next().unwrap().unwrap();
+    assert_eq!(b.short_file_name(), "B.TXT");
+    assert_eq!(b.offset_range(), entries[1].offset_range());
+}
@@ -0,0 +1,54 @@
+// Tests of reporting changes of open files that have not been flushed yet.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, Write};
+use fatfs::{FileSystem, FormatOptions, FsOptions};
+use common::MB;
+
+#[test]
+fn file_is_dirty_until_flushed() {
+    let mut data = common::format_image(4 * MB, FormatOptions::new());
+    let mut disk = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let mut file = root.create_file("a.txt").unwrap();
+    file.flush().unwrap();
+    assert!(!file.is_dirty());
+    // data is written immediately but size in the directory entry is not
+    file.write_all(b"data").unwrap();
+    assert!(file.is_dirty());
+    file.flush().unwrap();
+    assert!(!file.is_dirty());
+
+    let mut file = root.open_file("a.txt").unwrap();
+    assert!(!file.is_dirty());
+    file.truncate().unwrap();
+    assert!(file.is_dirty());
+    file.flush().unwrap();
+    assert!(!file.is_dirty());
+}
+
+#[test]
+fn pending_lazy_fat_writes_make_files_dirty() {
+    let mut data = common::format_image(4 * MB, FormatOptions::new());
+    let mut disk = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut disk, FsOptions::new().lazy_fat_writes(true)).unwrap();
+    let mut root = fs.root_dir();
+    let mut file = root.create_file("a.txt").unwrap();
+    let mut other = root.create_file("b.txt").unwrap();
+    file.flush().unwrap();
+    other.flush().unwrap();
+    assert!(!other.is_dirty());
+    // allocated cluster is kept in FAT cache shared by all files
+    file.write_all(&[1; 5000]).unwrap();
+    assert!(other.is_dirty());
+    other.flush().unwrap();
+    assert!(!other.is_dirty());
+    assert!(file.is_dirty());
+    file.flush().unwrap();
+    assert!(!file.is_dirty());
+}
@@ -0,0 +1,106 @@
+// Interoperability test: images modified by this crate must be accepted by dosfsck.
+//
+// The library invariant checks always run. The external check is opt-in: it runs only when
+// `fsck.fat` or `dosfsck` is available (path can be overridden with FATFS_DOSFSCK variable).
+
+extern crate basic_io;
+extern crate fatfs;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use basic_io::{Seek, SeekFrom, Write};
+use fatfs::{FileSystem, FsOptions, ReadWriteSeek};
+
+fn find_dosfsck() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("FATFS_DOSFSCK") {
+        return Some(PathBuf::from(path));
+    }
+    let paths = env::var_os("PATH")?;
+    for dir in env::split_paths(&paths) {
+        for name in &["fsck.fat", "dosfsck"] {
+            let candidate = dir.join(name);
+            if candidate.is_file() {
+                return Some(candidate);
+            }
+        }
+    }
+    None
+}
+
+fn run_workload<IO: ReadWriteSeek>(fs: &FileSystem<IO>) {
+    let mut root = fs.root_dir();
+    let mut dir = root.create_dir("work").expect("create_dir failed");
+    dir.create_dir("nested/").expect("create_dir failed");
+    fs.verify_invariants().expect("invariants broken after create_dir");
+
+    let data: Vec<u8> = (0..20000u32).map(|i| i as u8).collect();
+    for (i, size) in [0usize, 1, 511, 512, 513, 5000, 20000].iter().enumerate() {
+        let name = format!("nested/file{}.bin", i);
+        let mut file = dir.create_file(&name).expect("create_file failed");
+        file.write_all(&data[..*size]).expect("write failed");
+    }
+    fs.verify_invariants().expect("invariants broken after writes");
+
+    {
+        let mut file = dir.open_file("nested/file6.bin").expect("open_file failed");
+        file.seek(SeekFrom::Start(1500)).expect("seek failed");
+        file.truncate().expect("truncate failed");
+        let mut file = dir.open_file("nested/file5.bin").expect("open_file failed");
+        file.truncate().expect("truncate failed");
+    }
+    fs.verify_invariants().expect("invariants broken after truncate");
+
+    dir.remove("nested/file2.bin").expect("remove failed");
+    dir.remove("nested/file4.bin").expect("remove failed");
+    root.create_dir("empty").expect("create_dir failed");
+    root.remove("empty").expect("remove failed");
+    for i in 0..40 {
+        let name = format!("many{}.txt", i);
+        let mut file = dir.create_file(&name).expect("create_file failed");
+        file.write_all(&data[..i * 100]).expect("write failed");
+    }
+    fs.verify_invariants().expect("invariants broken after remove");
+
+    dir.rename("many0.txt", &dir, "renamed.txt").expect("rename failed");
+    dir.rename("many1.txt", &root, "moved.txt").expect("rename failed");
+    root.create_dir("target").expect("create_dir failed");
+    dir.rename("nested", &root, "target/nested").expect("rename failed");
+    fs.verify_invariants().expect("invariants broken after rename");
+}
+
+#[test]
+fn modified_image_passes_dosfsck() {
+    let mut data = fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("fat32.img"))
+        .expect("cannot read fat32.img");
+    {
+        let mut disk = basic_io::Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut disk, FsOptions::new()).expect("mount failed");
+        fs.verify_invariants().expect("invariants broken in fixture");
+        run_workload(&fs);
+        fs.unmount().expect("unmount failed");
+    }
+
+    let dosfsck = match find_dosfsck() {
+        Some(path) => path,
+        None => {
+            eprintln!("dosfsck not found - skipping external check");
+            return;
+        }
+    };
+    let image_path = env::temp_dir().join(format!("fatfs-dosfsck-{}.img", std::process::id()));
+    fs::write(&image_path, &data).expect("cannot write image");
+    let output = Command::new(&dosfsck)
+        .arg("-n")
+        .arg(&image_path)
+        .output()
+        .expect("cannot run dosfsck");
+    let _ = fs::remove_file(&image_path);
+    assert!(
+        output.status.success(),
+        "dosfsck reported errors:\n{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
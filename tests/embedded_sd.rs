@@ -0,0 +1,91 @@
+// Tests of the SD card over SPI example (fatfs/examples/embedded-sd) using its simulated card.
+
+extern crate basic_io;
+extern crate fatfs;
+
+#[path = "../fatfs/examples/embedded-sd/hal.rs"]
+mod hal;
+#[path = "../fatfs/examples/embedded-sd/sd.rs"]
+mod sd;
+#[path = "../fatfs/examples/embedded-sd/sim.rs"]
+mod sim;
+
+use basic_io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use fatfs::{FileSystem, FormatOptions, FsOptions};
+
+use sd::{Error, SdCard, SdDevice, BLOCK_SIZE};
+
+const NUM_BLOCKS: usize = 32 * 1024;
+
+fn device() -> SdDevice<sim::SimSpi, sim::SimCs> {
+    let (spi, cs) = sim::card(NUM_BLOCKS);
+    let mut card = SdCard::new(spi, cs);
+    card.init().unwrap();
+    SdDevice::new(card).unwrap()
+}
+
+#[test]
+fn card_blocks_round_trip() {
+    let (spi, cs) = sim::card(NUM_BLOCKS);
+    let mut card = SdCard::new(spi, cs);
+    card.init().unwrap();
+    assert_eq!(card.num_blocks().unwrap(), NUM_BLOCKS as u64);
+    let mut block = [0u8; BLOCK_SIZE];
+    for (i, b) in block.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    card.write_block(7, &block).unwrap();
+    let mut buf = [0u8; BLOCK_SIZE];
+    card.read_block(7, &mut buf).unwrap();
+    assert_eq!(buf[..], block[..]);
+    card.read_block(8, &mut buf).unwrap();
+    assert_eq!(buf[..], [0; BLOCK_SIZE][..]);
+    // card reports address error for blocks past its end
+    match card.read_block(NUM_BLOCKS as u32, &mut buf) {
+        Err(Error::Card(r)) => assert_ne!(r & 0x20, 0),
+        r => panic!("unexpected result {:?}", r),
+    }
+}
+
+#[test]
+fn device_handles_unaligned_accesses() {
+    let mut device = device();
+    let len = device.seek(SeekFrom::End(0)).unwrap();
+    assert_eq!(len, (NUM_BLOCKS * BLOCK_SIZE) as u64);
+    let data: Vec<u8> = (0..1500).map(|i| (i % 251) as u8).collect();
+    device.seek(SeekFrom::Start(300)).unwrap();
+    device.write_all(&data).unwrap();
+    let mut buf = vec![0u8; 1600];
+    device.seek(SeekFrom::Start(250)).unwrap();
+    device.read_exact(&mut buf).unwrap();
+    assert_eq!(buf[..50], [0; 50][..]);
+    assert_eq!(buf[50..1550], data[..]);
+    assert_eq!(buf[1550..], [0; 50][..]);
+
+    // accesses at the end of the card
+    assert_eq!(device.seek(SeekFrom::End(1)).err().unwrap().kind(), ErrorKind::InvalidInput);
+    device.seek(SeekFrom::End(-2)).unwrap();
+    assert_eq!(device.write(&[1; 10]).unwrap(), 2);
+    assert_eq!(device.write(&[1; 10]).err().unwrap().kind(), ErrorKind::NotEnoughSpace);
+    assert_eq!(device.read(&mut buf).unwrap(), 0);
+}
+
+#[test]
+fn filesystem_on_simulated_card() {
+    let mut device = device();
+    fatfs::format_volume(&mut device, FormatOptions::new()).unwrap();
+    {
+        let fs = FileSystem::new(&mut device, FsOptions::new()).unwrap();
+        let mut dir = fs.root_dir().create_dir("logs").unwrap();
+        dir.create_file("boot.txt").unwrap().write_all(&[5; 3000]).unwrap();
+        drop(dir);
+        fs.unmount().unwrap();
+    }
+    device.seek(SeekFrom::Start(0)).unwrap();
+    let fs = FileSystem::new(&mut device, FsOptions::new()).unwrap();
+    let mut file = fs.root_dir().open_file("logs/boot.txt").unwrap();
+    let mut buf = [0u8; 3000];
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(buf[..], [5; 3000][..]);
+    assert_eq!(file.read(&mut buf).unwrap(), 0);
+}
@@ -0,0 +1,161 @@
+// Tests checking that empty files never have clusters allocated.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use basic_io::{Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
+use fatfs::{FatType, FileSystem, FormatOptions, FsOptions, ReadWriteSeek};
+use common::MB;
+
+const CLUSTER_SIZE: usize = 512;
+
+fn create_volume(fat_type: FatType, size: usize) -> Vec<u8> {
+    let options = FormatOptions::new().fat_type(fat_type).bytes_per_cluster(CLUSTER_SIZE as u32);
+    common::format_image(size, options)
+}
+
+fn free_clusters<IO: ReadWriteSeek>(fs: &FileSystem<IO>) -> u32 {
+    fs.stats().unwrap().free_clusters
+}
+
+// Returns high and low half of first cluster stored in the entry with given short name
+fn raw_first_cluster(data: &[u8], short_name: &[u8; 11]) -> (u16, u16) {
+    let entry = data.chunks(32).find(|e| &e[..11] == short_name).unwrap();
+    (u16::from_le_bytes([entry[20], entry[21]]), u16::from_le_bytes([entry[26], entry[27]]))
+}
+
+#[test]
+fn creating_empty_file_does_not_allocate() {
+    let volumes = [(FatType::Fat12, MB), (FatType::Fat16, 4 * MB), (FatType::Fat32, 40 * MB)];
+    for &(fat_type, size) in volumes.iter() {
+        let mut data = create_volume(fat_type, size);
+        let mut cursor = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        let free = free_clusters(&fs);
+        let mut root = fs.root_dir();
+        root.create_file("a.txt").unwrap();
+        let mut file = root.create_file("b.txt").unwrap();
+        file.write_all(&[]).unwrap();
+        file.flush().unwrap();
+        drop(file);
+        assert_eq!(free_clusters(&fs), free);
+        assert_eq!(root.open_file("b.txt").unwrap().clusters().count(), 0);
+        fs.verify_invariants().unwrap();
+    }
+}
+
+#[test]
+fn truncate_to_zero_frees_clusters() {
+    let mut data = create_volume(FatType::Fat32, 40 * MB);
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        // fill clusters below 0x10000 so the file gets clusters with non-zero high half
+        let mut filler = fs.root_dir().create_file("filler.bin").unwrap();
+        let chunk = [0u8; 64 * 1024];
+        for _ in 0..(0x10000 * CLUSTER_SIZE / chunk.len()) {
+            filler.write_all(&chunk).unwrap();
+        }
+        drop(filler);
+        let free = free_clusters(&fs);
+        fs.root_dir().create_file("a.bin").unwrap().write_all(&[1; CLUSTER_SIZE * 3]).unwrap();
+        assert_eq!(free_clusters(&fs), free - 3);
+    }
+    assert_ne!(raw_first_cluster(&data, b"A       BIN").0, 0);
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        let free = free_clusters(&fs);
+        let mut file = fs.root_dir().open_file("a.bin").unwrap();
+        file.seek(SeekFrom::End(0)).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.truncate().unwrap();
+        assert_eq!(file.clusters().count(), 0);
+        drop(file);
+        assert_eq!(free_clusters(&fs), free + 3);
+        fs.verify_invariants().unwrap();
+    }
+    assert_eq!(raw_first_cluster(&data, b"A       BIN"), (0, 0));
+}
+
+// Device failing all writes starting at `fail_from` offset
+struct FailingDisk {
+    data: Vec<u8>,
+    pos: u64,
+    fail_from: Rc<Cell<u64>>,
+}
+
+impl Read for FailingDisk {
+    fn read(&mut self, buf: &mut [u8]) -> basic_io::Result<usize> {
+        let start = self.pos as usize;
+        let n = buf.len().min(self.data.len() - start);
+        buf[..n].copy_from_slice(&self.data[start..start + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Write for FailingDisk {
+    fn write(&mut self, buf: &[u8]) -> basic_io::Result<usize> {
+        if self.pos >= self.fail_from.get() {
+            return Err(basic_io::Error::new(ErrorKind::Other, "write error"));
+        }
+        let start = self.pos as usize;
+        let n = buf.len().min(self.data.len() - start);
+        self.data[start..start + n].copy_from_slice(&buf[..n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> basic_io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for FailingDisk {
+    fn seek(&mut self, pos: SeekFrom) -> basic_io::Result<u64> {
+        self.pos = match pos {
+            SeekFrom::Start(n) => n,
+            SeekFrom::Current(n) => (self.pos as i64 + n) as u64,
+            SeekFrom::End(n) => (self.data.len() as i64 + n) as u64,
+        };
+        Ok(self.pos)
+    }
+}
+
+#[test]
+fn failed_write_does_not_leak_clusters() {
+    // FAT16 keeps root directory outside of the data region so entries can still be written
+    let data = create_volume(FatType::Fat16, 4 * MB);
+    let read_u16 = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]) as u64;
+    let root_dir_sectors = (read_u16(17) * 32).div_ceil(read_u16(11));
+    let data_region_offset =
+        (read_u16(14) + data[16] as u64 * read_u16(22) + root_dir_sectors) * read_u16(11);
+    let fail_from = Rc::new(Cell::new(u64::MAX));
+    let mut disk = FailingDisk { data, pos: 0, fail_from: fail_from.clone() };
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    let free = free_clusters(&fs);
+    let mut root = fs.root_dir();
+
+    let mut file = root.create_file("empty.bin").unwrap();
+    fail_from.set(data_region_offset);
+    assert!(file.write_all(&[2; CLUSTER_SIZE]).is_err());
+    assert_eq!(file.clusters().count(), 0);
+    drop(file);
+    assert_eq!(free_clusters(&fs), free);
+
+    fail_from.set(u64::MAX);
+    let mut file = root.create_file("short.bin").unwrap();
+    file.write_all(&[3; CLUSTER_SIZE]).unwrap();
+    fail_from.set(data_region_offset);
+    assert!(file.write_all(&[4; CLUSTER_SIZE]).is_err());
+    assert_eq!(file.clusters().count(), 1);
+    drop(file);
+    assert_eq!(free_clusters(&fs), free - 1);
+    fs.verify_invariants().unwrap();
+}
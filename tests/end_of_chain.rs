@@ -0,0 +1,69 @@
+// Tests of configurable end of chain marker written to the FAT.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, Read, Write};
+use fatfs::{FatType, FileSystem, FsOptions};
+
+// Reads FAT16 or FAT32 entry from the first FAT
+fn read_fat_entry(data: &[u8], fat_type: FatType, cluster: usize) -> u32 {
+    let reserved_sectors = data[14] as usize | (data[15] as usize) << 8;
+    let fat_offset = reserved_sectors * 512;
+    match fat_type {
+        FatType::Fat16 => {
+            let offset = fat_offset + cluster * 2;
+            data[offset] as u32 | (data[offset + 1] as u32) << 8
+        }
+        _ => {
+            let offset = fat_offset + cluster * 4;
+            let bytes = [data[offset], data[offset + 1], data[offset + 2], data[offset + 3]];
+            u32::from_le_bytes(bytes) & 0x0FFFFFFF
+        }
+    }
+}
+
+// Writes a two cluster file and returns FAT entry of its last cluster
+fn write_file(fat_type: FatType, options: FsOptions) -> (Vec<u8>, u32) {
+    let mut data = common::small_volume(fat_type);
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut cursor, options).unwrap();
+        fs.root_dir().create_file("a.bin").unwrap().write_all(&[1; 1024]).unwrap();
+        fs.unmount().unwrap();
+    }
+    // FAT32 root directory takes the first data cluster
+    let last_cluster = if fat_type == FatType::Fat32 { 4 } else { 3 };
+    let eoc = read_fat_entry(&data, fat_type, last_cluster);
+    (data, eoc)
+}
+
+#[test]
+fn default_marker_is_the_highest() {
+    assert_eq!(write_file(FatType::Fat16, FsOptions::new()).1, 0xFFFF);
+    assert_eq!(write_file(FatType::Fat32, FsOptions::new()).1, 0x0FFFFFFF);
+}
+
+#[test]
+fn configured_marker_is_written() {
+    let options = FsOptions::new().end_of_chain_marker(0);
+    assert_eq!(write_file(FatType::Fat16, options).1, 0xFFF8);
+    let options = FsOptions::new().end_of_chain_marker(0);
+    assert_eq!(write_file(FatType::Fat32, options).1, 0x0FFFFFF8);
+    // out of range marker is clamped
+    let options = FsOptions::new().end_of_chain_marker(100);
+    assert_eq!(write_file(FatType::Fat16, options).1, 0xFFFF);
+}
+
+#[test]
+fn any_marker_is_accepted() {
+    let (mut data, _) = write_file(FatType::Fat16, FsOptions::new().end_of_chain_marker(3));
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut buf = [0u8; 1024];
+    fs.root_dir().open_file("a.bin").unwrap().read_exact(&mut buf).unwrap();
+    assert!(buf.iter().all(|&b| b == 1));
+    fs.verify_invariants().unwrap();
+}
@@ -0,0 +1,75 @@
+// Tests that directory entries are written to the device as a whole so they cannot be torn and
+// that they are written after FAT changes they refer to.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Seek, SeekFrom, Write};
+use fatfs::{CheckOptions, FatType, FileSystem, FsOptions};
+use common::FaultyDisk;
+
+// Offsets of short name entries with given raw names
+fn entry_offsets(data: &[u8], names: &[&[u8; 11]]) -> Vec<u64> {
+    let offsets: Vec<u64> = (0..data.len() / 32)
+        .filter(|i| names.iter().any(|name| &data[i * 32..i * 32 + 11] == *name))
+        .map(|i| i as u64 * 32)
+        .collect();
+    assert_eq!(offsets.len(), names.len());
+    offsets
+}
+
+#[test]
+fn entries_are_written_in_single_operation() {
+    let mut disk = FaultyDisk::new(common::small_volume(FatType::Fat16));
+    let write_log = disk.write_log.clone();
+    {
+        let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+        let mut root = fs.root_dir();
+        let mut file = root.create_file("a.txt").unwrap();
+        file.write_all(&[1; 5000]).unwrap();
+        file.seek(SeekFrom::Start(700)).unwrap();
+        file.truncate().unwrap();
+        drop(file);
+        let mut dir = root.create_dir("dir").unwrap();
+        dir.create_file("b.txt").unwrap().write_all(&[2; 100]).unwrap();
+        root.rename("a.txt", &dir, "c.txt").unwrap();
+        drop(dir);
+        drop(root);
+        fs.unmount().unwrap();
+    }
+
+    let names = [b"DIR        ", b"B       TXT", b"C       TXT"];
+    for offset in entry_offsets(&disk.data, &names) {
+        let overlaps = |&(pos, len): &(u64, usize)| pos < offset + 32 && pos + len as u64 > offset;
+        let writes: Vec<_> = write_log.borrow().iter().cloned().filter(overlaps).collect();
+        assert!(!writes.is_empty());
+        for (pos, len) in writes {
+            assert!(pos <= offset && pos + len as u64 >= offset + 32, "{} {}", pos, len);
+        }
+    }
+}
+
+#[test]
+fn fat_is_written_before_entry_referring_to_it() {
+    let mut disk = FaultyDisk::new(common::small_volume(FatType::Fat16));
+    let flush_fails = disk.flush_fails.clone();
+    {
+        let fs = FileSystem::new(&mut disk, FsOptions::new().lazy_fat_writes(true)).unwrap();
+        let mut file = fs.root_dir().create_file("a.txt").unwrap();
+        file.write_all(&[1; 3000]).unwrap();
+        // power is lost after cached FAT sectors are written
+        flush_fails.set(true);
+        assert!(file.flush().is_err());
+    }
+    flush_fails.set(false);
+    disk.writes_fail.set(false);
+    disk.seek(SeekFrom::Start(0)).unwrap();
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    // the entry does not refer to the allocated clusters yet - they are lost, but no file uses
+    // free clusters
+    let entry = fs.root_dir().iter().next().unwrap().unwrap();
+    assert_eq!(entry.len(), 0);
+    fs.check(CheckOptions::new(), &mut |issue| panic!("{:?}", issue)).unwrap();
+}
@@ -0,0 +1,69 @@
+// Tests of FAT copy comparison and repair.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, ErrorKind, Write};
+use fatfs::{FatDivergence, FatType, FileSystem, FormatOptions, FsOptions};
+use common::MB;
+
+fn read_u16(data: &[u8], offset: usize) -> usize {
+    data[offset] as usize | (data[offset + 1] as usize) << 8
+}
+
+// Returns offset of the first FAT and size of a single FAT copy
+fn fat_layout(data: &[u8]) -> (usize, usize) {
+    let bytes_per_sector = read_u16(data, 11);
+    let sectors_per_fat = read_u16(data, 22);
+    (read_u16(data, 14) * bytes_per_sector, sectors_per_fat * bytes_per_sector)
+}
+
+fn create_volume() -> Vec<u8> {
+    let options = FormatOptions::new().fat_type(FatType::Fat16).bytes_per_cluster(512);
+    let mut data = common::format_image(8 * MB, options);
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        let mut file = fs.root_dir().create_file("data.bin").unwrap();
+        file.write_all(&vec![0x5A; 600 * 512]).unwrap();
+    }
+    data
+}
+
+#[test]
+fn consistent_fats() {
+    let mut data = create_volume();
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new().lazy_fat_writes(true)).unwrap();
+    fs.root_dir().create_file("b.txt").unwrap().write_all(&[1; 5000]).unwrap();
+    // pending lazy writes are flushed before comparing
+    assert_eq!(fs.check_fat_consistency(None).unwrap(), None);
+}
+
+#[test]
+fn divergence_is_reported_and_repaired() {
+    let mut data = create_volume();
+    let (first_fat, fat_size) = fat_layout(&data);
+    // firmware updated only the first FAT: entries in its third sector were freed
+    let good_second_fat = data[first_fat + fat_size..first_fat + 2 * fat_size].to_vec();
+    for b in data[first_fat + 2 * 512 + 10..first_fat + 2 * 512 + 40].iter_mut() {
+        *b = 0;
+    }
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        let expected = FatDivergence { fat: 1, sector: 2 };
+        assert_eq!(fs.check_fat_consistency(None).unwrap(), Some(expected));
+        let err = fs.check_fat_consistency(Some(2)).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        // second FAT is the good one
+        assert_eq!(fs.check_fat_consistency(Some(1)).unwrap(), Some(expected));
+        assert_eq!(fs.check_fat_consistency(None).unwrap(), None);
+    }
+    assert!(data[first_fat..first_fat + fat_size] == good_second_fat[..]);
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    fs.verify_invariants().unwrap();
+}
@@ -0,0 +1,87 @@
+// Tests of FAT changes written to every FAT copy.
+
+extern crate basic_io;
+extern crate fatfs;
+
+use basic_io::{Cursor, Write};
+use fatfs::{FatType, FileSystem, FormatOptions, FsOptions};
+
+const MB: usize = 1024 * 1024;
+
+fn read_u16(data: &[u8], offset: usize) -> usize {
+    data[offset] as usize | (data[offset + 1] as usize) << 8
+}
+
+fn read_u32(data: &[u8], offset: usize) -> usize {
+    read_u16(data, offset) | read_u16(data, offset + 2) << 16
+}
+
+// Returns byte ranges of all FAT copies declared in BPB
+fn fat_copies(data: &[u8]) -> Vec<(usize, usize)> {
+    let bytes_per_sector = read_u16(data, 11);
+    let reserved_sectors = read_u16(data, 14);
+    let fats = data[16] as usize;
+    let sectors_per_fat = match read_u16(data, 22) {
+        0 => read_u32(data, 36),
+        n => n,
+    };
+    let fat_size = sectors_per_fat * bytes_per_sector;
+    let first = reserved_sectors * bytes_per_sector;
+    (0..fats).map(|i| (first + i * fat_size, first + (i + 1) * fat_size)).collect()
+}
+
+fn write_session(data: &mut [u8], options: FsOptions) {
+    let mut cursor = Cursor::new(data);
+    let fs = FileSystem::new(&mut cursor, options).unwrap();
+    {
+        let mut root = fs.root_dir();
+        for i in 0..8 {
+            let mut file = root.create_file(&format!("f{}.bin", i)).unwrap();
+            file.write_all(&vec![i as u8; 3000 + i * 700]).unwrap();
+        }
+        root.create_dir("dir").unwrap();
+        root.remove("f3.bin").unwrap();
+        root.open_file("f5.bin").unwrap().truncate().unwrap();
+    }
+    fs.unmount().unwrap();
+}
+
+fn check_mirrored(fat_type: FatType, size: usize, options: FsOptions) {
+    let format_options = FormatOptions::new().fat_type(fat_type).bytes_per_cluster(512);
+    let mut data = vec![0u8; size];
+    fatfs::format_volume(&mut Cursor::new(&mut data[..]), format_options).unwrap();
+    write_session(&mut data, options);
+    let copies = fat_copies(&data);
+    assert_eq!(copies.len(), 2);
+    let (first, second) = (copies[0], copies[1]);
+    assert!(data[first.0..first.1] == data[second.0..second.1], "{:?} FATs differ", fat_type);
+}
+
+#[test]
+fn all_fat_copies_are_updated() {
+    for &(fat_type, size) in [(FatType::Fat12, MB), (FatType::Fat16, 8 * MB)].iter() {
+        check_mirrored(fat_type, size, FsOptions::new());
+        check_mirrored(fat_type, size, FsOptions::new().lazy_fat_writes(true));
+    }
+}
+
+#[test]
+fn all_fat32_copies_are_updated() {
+    check_mirrored(FatType::Fat32, 40 * MB, FsOptions::new());
+    check_mirrored(FatType::Fat32, 40 * MB, FsOptions::new().lazy_fat_writes(true));
+}
+
+#[test]
+fn only_active_fat32_copy_is_updated_without_mirroring() {
+    let format_options = FormatOptions::new().fat_type(FatType::Fat32).bytes_per_cluster(512);
+    let mut data = vec![0u8; 40 * MB];
+    fatfs::format_volume(&mut Cursor::new(&mut data[..]), format_options).unwrap();
+    // disable mirroring and make the second FAT active
+    data[40] = 0x81;
+    let copies = fat_copies(&data);
+    let (first, second) = (copies[0], copies[1]);
+    let inactive_before = data[first.0..first.1].to_vec();
+    write_session(&mut data, FsOptions::new());
+    assert!(data[first.0..first.1] == inactive_before[..]);
+    assert!(data[first.0..first.1] != data[second.0..second.1]);
+}
@@ -0,0 +1,58 @@
+// Tests of FAT type parameters against formatted volumes.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, Write};
+use fatfs::{FatType, FileSystem, FormatOptions, FsOptions};
+use common::MB;
+
+const TYPES: [FatType; 3] = [FatType::Fat12, FatType::Fat16, FatType::Fat32];
+
+// Reads entry of the first FAT using entry width reported by the FAT type
+fn fat_entry(data: &[u8], fat_type: FatType, cluster: u32) -> u32 {
+    let reserved_sectors = u16::from_le_bytes([data[14], data[15]]) as usize;
+    let fat = &data[reserved_sectors * 512..];
+    let bit_offset = (cluster * fat_type.bits_per_entry()) as usize;
+    let bytes = &fat[bit_offset / 8..bit_offset / 8 + 4];
+    let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) >> (bit_offset % 8);
+    value & fat_type.entry_mask()
+}
+
+#[test]
+fn cluster_count_ranges_are_adjacent() {
+    assert_eq!(FatType::Fat12.min_clusters(), 1);
+    assert_eq!(FatType::Fat12.max_clusters() + 1, FatType::Fat16.min_clusters());
+    assert_eq!(FatType::Fat16.max_clusters() + 1, FatType::Fat32.min_clusters());
+    for fat_type in TYPES.iter() {
+        // cluster numbers of all data clusters fit below special values
+        assert!(fat_type.max_clusters() + 1 < fat_type.bad_cluster());
+        let (first, last) = fat_type.end_of_chain();
+        assert!(fat_type.bad_cluster() < first);
+        assert_eq!(last, fat_type.entry_mask());
+    }
+}
+
+#[test]
+fn formatted_volumes_match_fat_type_parameters() {
+    let volumes = [(FatType::Fat12, MB), (FatType::Fat16, 8 * MB), (FatType::Fat32, 40 * MB)];
+    for &(fat_type, size) in volumes.iter() {
+        let mut data = common::format_image(size, FormatOptions::new().fat_type(fat_type));
+        let cluster = {
+            let mut cursor = Cursor::new(&mut data[..]);
+            let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+            let total_clusters = fs.stats().unwrap().total_clusters;
+            assert!(total_clusters >= fat_type.min_clusters(), "{:?}", fat_type);
+            assert!(total_clusters <= fat_type.max_clusters(), "{:?}", fat_type);
+            let mut file = fs.root_dir().create_file("a.txt").unwrap();
+            file.write_all(b"data").unwrap();
+            file.clusters().next().unwrap().unwrap().0
+        };
+        // FAT[0] holds media descriptor with all other bits set
+        assert_eq!(fat_entry(&data, fat_type, 0), (fat_type.entry_mask() & !0xFF) | 0xF8);
+        // single cluster chain ends with the default end of chain marker
+        assert_eq!(fat_entry(&data, fat_type, cluster), fat_type.end_of_chain().1);
+    }
+}
@@ -0,0 +1,166 @@
+// Tests of the C ABI (enabled by `ffi` feature).
+#![cfg(feature = "ffi")]
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use std::ffi::CStr;
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+
+use fatfs::ffi::*;
+use fatfs::{FatType, FormatOptions};
+use common::MB;
+
+// Device state passed to callbacks as context
+struct Memory {
+    data: Vec<u8>,
+    pos: usize,
+    // value added to lengths returned by read callback
+    read_extra: isize,
+    writes_fail: bool,
+}
+
+extern "C" fn mem_read(ctx: *mut c_void, buf: *mut u8, len: usize) -> isize {
+    let mem = unsafe { &mut *(ctx as *mut Memory) };
+    let n = len.min(mem.data.len() - mem.pos);
+    unsafe { ptr::copy_nonoverlapping(mem.data[mem.pos..].as_ptr(), buf, n) };
+    mem.pos += n;
+    n as isize + mem.read_extra
+}
+
+extern "C" fn mem_write(ctx: *mut c_void, buf: *const u8, len: usize) -> isize {
+    let mem = unsafe { &mut *(ctx as *mut Memory) };
+    if mem.writes_fail {
+        return -1;
+    }
+    let n = len.min(mem.data.len() - mem.pos);
+    unsafe { ptr::copy_nonoverlapping(buf, mem.data[mem.pos..].as_mut_ptr(), n) };
+    mem.pos += n;
+    n as isize
+}
+
+extern "C" fn mem_seek(ctx: *mut c_void, offset: i64, whence: c_int) -> i64 {
+    let mem = unsafe { &mut *(ctx as *mut Memory) };
+    let base = match whence {
+        FATFS_SEEK_SET => 0,
+        FATFS_SEEK_CUR => mem.pos as i64,
+        _ => mem.data.len() as i64,
+    };
+    mem.pos = (base + offset) as usize;
+    mem.pos as i64
+}
+
+extern "C" fn mem_flush(_ctx: *mut c_void) -> c_int {
+    0
+}
+
+fn memory() -> Memory {
+    Memory {
+        data: common::format_image(4 * MB, FormatOptions::new().fat_type(FatType::Fat16)),
+        pos: 0,
+        read_extra: 0,
+        writes_fail: false,
+    }
+}
+
+fn device(mem: &mut Memory) -> fatfs_device {
+    fatfs_device {
+        ctx: mem as *mut Memory as *mut c_void,
+        read: mem_read,
+        write: mem_write,
+        seek: mem_seek,
+        flush: mem_flush,
+    }
+}
+
+fn mount(mem: &mut Memory) -> *mut fatfs_fs {
+    let mut fs = ptr::null_mut();
+    assert_eq!(unsafe { fatfs_mount(&device(mem), &mut fs) }, FATFS_OK);
+    fs
+}
+
+fn open(fs: *mut fatfs_fs, path: &CStr, create: bool) -> *mut fatfs_file {
+    let mut file = ptr::null_mut();
+    assert_eq!(unsafe { fatfs_open(fs, path.as_ptr(), create as c_int, &mut file) }, FATFS_OK);
+    file
+}
+
+fn c_str(s: &[u8]) -> &CStr {
+    CStr::from_bytes_with_nul(s).unwrap()
+}
+
+#[test]
+fn files_written_through_c_abi_can_be_read_back() {
+    let mut mem = memory();
+    unsafe {
+        let fs = mount(&mut mem);
+        let file = open(fs, c_str(b"hello.txt\0"), true);
+        assert_eq!(fatfs_write(file, b"Hello!".as_ptr(), 6), 6);
+        assert_eq!(fatfs_close(file), FATFS_OK);
+        assert_eq!(fatfs_unmount(fs), FATFS_OK);
+
+        let fs = mount(&mut mem);
+        let file = open(fs, c_str(b"HELLO.TXT\0"), false);
+        assert_eq!(fatfs_seek(file, 1, FATFS_SEEK_SET), 1);
+        let mut buf = [0u8; 8];
+        assert_eq!(fatfs_read(file, buf.as_mut_ptr(), buf.len()), 5);
+        assert_eq!(&buf[..5], b"ello!");
+        assert_eq!(fatfs_read(file, buf.as_mut_ptr(), buf.len()), 0);
+        assert_eq!(fatfs_close(file), FATFS_OK);
+
+        let mut dir = ptr::null_mut();
+        assert_eq!(fatfs_opendir(fs, c_str(b"/\0").as_ptr(), &mut dir), FATFS_OK);
+        let mut entry = fatfs_dirent { name: [0; 13], attributes: 0, size: 0 };
+        assert_eq!(fatfs_readdir(dir, &mut entry), 1);
+        assert_eq!(CStr::from_ptr(entry.name.as_ptr()).to_bytes(), b"HELLO.TXT");
+        assert_eq!(entry.size, 6);
+        assert_eq!(fatfs_readdir(dir, &mut entry), 0);
+        assert_eq!(fatfs_closedir(dir), FATFS_OK);
+        assert_eq!(fatfs_unmount(fs), FATFS_OK);
+    }
+}
+
+#[test]
+fn errors_are_returned_as_codes() {
+    let mut mem = memory();
+    unsafe {
+        let fs = mount(&mut mem);
+        let mut file = ptr::null_mut();
+        let path = c_str(b"missing.txt\0").as_ptr();
+        assert_eq!(fatfs_open(fs, path, 0, &mut file), FATFS_ERR_NOT_FOUND);
+        assert_eq!(fatfs_open(fs, ptr::null(), 0, &mut file), FATFS_ERR_INVALID_INPUT);
+        assert!(file.is_null());
+        assert_eq!(fatfs_close(ptr::null_mut()), FATFS_ERR_INVALID_INPUT);
+        assert_eq!(fatfs_unmount(fs), FATFS_OK);
+    }
+}
+
+#[test]
+fn device_returning_too_many_bytes_is_rejected() {
+    let mut mem = memory();
+    mem.read_extra = 1;
+    let mut fs = ptr::null_mut();
+    assert_eq!(unsafe { fatfs_mount(&device(&mut mem), &mut fs) }, FATFS_ERR_OTHER);
+    assert!(fs.is_null());
+}
+
+#[test]
+fn close_releases_file_when_flush_fails() {
+    let mut mem = memory();
+    unsafe {
+        let fs = mount(&mut mem);
+        let file = open(fs, c_str(b"a.txt\0"), true);
+        assert_eq!(fatfs_write(file, b"data".as_ptr(), 4), 4);
+        mem.writes_fail = true;
+        assert_eq!(fatfs_close(file), FATFS_ERR_OTHER);
+        mem.writes_fail = false;
+        // handle was released so the file can be opened again
+        let file = open(fs, c_str(b"a.txt\0"), false);
+        assert_eq!(fatfs_close(file), FATFS_OK);
+        // failed write poisoned the filesystem
+        assert_eq!(fatfs_unmount(fs), FATFS_ERR_FILESYSTEM_POISONED);
+    }
+}
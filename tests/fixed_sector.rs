@@ -0,0 +1,44 @@
+// Tests of filesystem with sector size fixed at compile time.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, ErrorKind, Write};
+use fatfs::{FixedSectorFileSystem, FormatOptions, FsOptions, Sector};
+
+const VOLUME_SIZE: usize = 4 * 1024 * 1024;
+
+fn create_volume(bytes_per_sector: u16) -> Vec<u8> {
+    common::format_image(VOLUME_SIZE, FormatOptions::new().bytes_per_sector(bytes_per_sector))
+}
+
+#[test]
+fn mount_with_matching_sector_size() {
+    let mut data = create_volume(512);
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FixedSectorFileSystem::<_, 512>::new(&mut cursor, FsOptions::new()).unwrap();
+    fs.root_dir().create_file("A.TXT").unwrap().write_all(b"fixed").unwrap();
+
+    let mut buf = FixedSectorFileSystem::<&mut Cursor<&mut [u8]>, 512>::sector_buffer();
+    fs.read_sector(Sector(0), &mut buf).unwrap();
+    assert_eq!(&buf[510..], &[0x55, 0xAA]);
+
+    let sector = Sector(7);
+    assert_eq!(fs.offset_from_sector(sector), (*fs).offset_from_sector(sector));
+    let cluster = fs.root_dir().open_file("A.TXT").unwrap().clusters().next().unwrap().unwrap();
+    let offset = fs.offset_from_cluster(cluster);
+    assert_eq!(offset, (*fs).offset_from_cluster(cluster));
+    fs.read_sector(Sector(offset / 512), &mut buf).unwrap();
+    assert_eq!(&buf[..5], b"fixed");
+    fs.into_inner().unwrap();
+}
+
+#[test]
+fn mount_with_different_sector_size_fails() {
+    let mut data = create_volume(4096);
+    let mut cursor = Cursor::new(&mut data[..]);
+    let r = FixedSectorFileSystem::<_, 512>::new(&mut cursor, FsOptions::new());
+    assert_eq!(r.err().unwrap().kind(), ErrorKind::InvalidInput);
+}
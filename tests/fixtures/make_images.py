@@ -0,0 +1,309 @@
+#!/usr/bin/env python3
+"""Generates FAT12 and FAT16 fixture images used by tests/conformance.rs.
+
+The images are written by this script without using fatfs, so they exercise layouts the crate
+never produces itself: fragmented and backwards cluster chains, long names with non-ASCII
+characters, names filling LFN entries exactly, deleted entries and multi-cluster directories.
+
+Run from the repository root: python3 tests/fixtures/make_images.py
+Expected entries for tests/conformance.rs are printed to standard output.
+"""
+
+import os
+import struct
+
+SECTOR = 512
+# 2019-03-14 15:09:26
+DATE = ((2019 - 1980) << 9) | (3 << 5) | 14
+TIME = (15 << 11) | (9 << 5) | (26 // 2)
+
+ATTR_DIR = 0x10
+ATTR_ARCHIVE = 0x20
+ATTR_VOLUME = 0x08
+ATTR_LFN = 0x0F
+
+
+def fnv1a(data):
+    h = 0xcbf29ce484222325
+    for b in data:
+        h = ((h ^ b) * 0x100000001b3) & 0xFFFFFFFFFFFFFFFF
+    return h
+
+
+def contents(seed, size):
+    # deterministic pseudo-random bytes (LCG)
+    out = bytearray(size)
+    x = seed
+    for i in range(size):
+        x = (x * 1103515245 + 12345) & 0x7FFFFFFF
+        out[i] = (x >> 16) & 0xFF
+    return bytes(out)
+
+
+def short_name(name):
+    base, _, ext = name.partition('.')
+    return (base.ljust(8) + ext.ljust(3)).encode('ascii')
+
+
+def lfn_checksum(raw):
+    s = 0
+    for c in raw:
+        s = (((s & 1) << 7) + (s >> 1) + c) & 0xFF
+    return s
+
+
+def short_entry(raw, attr, cluster, size, deleted=False):
+    if deleted:
+        raw = b'\xe5' + raw[1:]
+    return raw + struct.pack('<BBBHHHHHHHI', attr, 0, 0, TIME, DATE, DATE, cluster >> 16,
+                             TIME, DATE, cluster & 0xFFFF, size)
+
+
+def lfn_entries(long_name, raw, deleted=False):
+    encoded = long_name.encode('utf-16-le')
+    units = list(struct.unpack('<%dH' % (len(encoded) // 2), encoded))
+    if len(units) % 13 != 0:
+        units.append(0)
+    while len(units) % 13 != 0:
+        units.append(0xFFFF)
+    count = len(units) // 13
+    checksum = lfn_checksum(raw)
+    entries = []
+    for i in range(count):
+        part = units[i * 13:(i + 1) * 13]
+        order = i + 1
+        if i == count - 1:
+            order |= 0x40
+        if deleted:
+            order = 0xE5
+        e = struct.pack('<B5HBBB6HH2H', order, *(part[:5] + [ATTR_LFN, 0, checksum] + part[5:11]
+                                                 + [0] + part[11:]))
+        entries.append(e)
+    # entries are stored from the last one
+    return list(reversed(entries))
+
+
+class Image:
+    def __init__(self, fat_type, total_sectors, sectors_per_cluster, root_entries, fat_sectors,
+                 media, label, volume_id):
+        self.fat_type = fat_type
+        self.total_sectors = total_sectors
+        self.spc = sectors_per_cluster
+        self.root_entries = root_entries
+        self.fat_sectors = fat_sectors
+        self.media = media
+        self.label = label
+        self.volume_id = volume_id
+        self.reserved = 1
+        self.root_start = (self.reserved + 2 * fat_sectors) * SECTOR
+        self.data_start = self.root_start + root_entries * 32
+        self.cluster_size = sectors_per_cluster * SECTOR
+        self.cluster_count = (total_sectors * SECTOR - self.data_start) // self.cluster_size
+        self.fat = [0] * (self.cluster_count + 2)
+        self.fat[0] = (0xFF00 if fat_type == 16 else 0xF00) | media
+        self.fat[1] = 0xFFFF if fat_type == 16 else 0xFFF
+        self.data = bytearray(total_sectors * SECTOR)
+        self.next_cluster = 2
+        # listing of files in directory order: (path, size or None, hash)
+        self.expected = []
+        # (long path, short path) pairs
+        self.long_names = []
+
+    def alloc(self, n):
+        clusters = list(range(self.next_cluster, self.next_cluster + n))
+        self.next_cluster += n
+        return clusters
+
+    def write_chain(self, clusters, data):
+        eoc = 0xFFFF if self.fat_type == 16 else 0xFFF
+        for i, c in enumerate(clusters):
+            self.fat[c] = clusters[i + 1] if i + 1 < len(clusters) else eoc
+            chunk = data[i * self.cluster_size:(i + 1) * self.cluster_size]
+            off = self.data_start + (c - 2) * self.cluster_size
+            self.data[off:off + len(chunk)] = chunk
+
+    def write_dir(self, clusters, entries):
+        data = b''.join(entries)
+        assert len(data) <= len(clusters) * self.cluster_size
+        self.write_chain(clusters, data)
+
+    def write_root(self, entries):
+        data = b''.join(entries)
+        assert len(data) <= self.root_entries * 32
+        self.data[self.root_start:self.root_start + len(data)] = data
+
+    def serialize_fat(self):
+        if self.fat_type == 16:
+            fat = b''.join(struct.pack('<H', v) for v in self.fat)
+        else:
+            fat = bytearray((len(self.fat) * 3 + 1) // 2)
+            for n, v in enumerate(self.fat):
+                off = n * 3 // 2
+                if n % 2 == 0:
+                    fat[off] = v & 0xFF
+                    fat[off + 1] = (fat[off + 1] & 0xF0) | ((v >> 8) & 0x0F)
+                else:
+                    fat[off] = (fat[off] & 0x0F) | ((v << 4) & 0xF0)
+                    fat[off + 1] = (v >> 4) & 0xFF
+            fat = bytes(fat)
+        assert len(fat) <= self.fat_sectors * SECTOR
+        return fat
+
+    def finish(self, path):
+        bs = bytearray(SECTOR)
+        bs[0:3] = b'\xeb\x3c\x90'
+        bs[3:11] = b'FIXTURES'
+        struct.pack_into('<HBHBHHBHHHII', bs, 11, SECTOR, self.spc, self.reserved, 2,
+                         self.root_entries, self.total_sectors if self.total_sectors < 0x10000
+                         else 0, self.media, self.fat_sectors, 18, 2, 0,
+                         0 if self.total_sectors < 0x10000 else self.total_sectors)
+        struct.pack_into('<BBBI', bs, 36, 0, 0, 0x29, self.volume_id)
+        bs[43:54] = self.label.encode('ascii').ljust(11)
+        bs[54:62] = ('FAT%d' % self.fat_type).encode('ascii').ljust(8)
+        bs[510:512] = b'\x55\xaa'
+        self.data[0:SECTOR] = bs
+        fat = self.serialize_fat()
+        for i in range(2):
+            off = (self.reserved + i * self.fat_sectors) * SECTOR
+            self.data[off:off + len(fat)] = fat
+        with open(path, 'wb') as f:
+            f.write(self.data)
+
+
+def file_entries(img, path, name, data, clusters, long_name=None):
+    raw = short_name(name)
+    entries = lfn_entries(long_name, raw) if long_name else []
+    img.write_chain(clusters, data)
+    entries.append(short_entry(raw, ATTR_ARCHIVE, clusters[0] if clusters else 0, len(data)))
+    img.expected.append(('%s/%s' % (path, name), len(data), fnv1a(data)))
+    if long_name:
+        img.long_names.append(('%s/%s' % (path, long_name), '%s/%s' % (path, name)))
+    return entries
+
+
+def dir_entry(img, path, name, cluster, long_name=None):
+    raw = short_name(name)
+    entries = lfn_entries(long_name, raw) if long_name else []
+    entries.append(short_entry(raw, ATTR_DIR, cluster, 0))
+    img.expected.append(('%s/%s' % (path, name), None, 0))
+    if long_name:
+        img.long_names.append(('%s/%s' % (path, long_name), '%s/%s' % (path, name)))
+    return entries
+
+
+def dot_entries(cluster, parent):
+    return [short_entry(b'.          ', ATTR_DIR, cluster, 0),
+            short_entry(b'..         ', ATTR_DIR, parent, 0)]
+
+
+def clusters_for(img, size):
+    return (size + img.cluster_size - 1) // img.cluster_size
+
+
+def fat12_image():
+    # 1.44 MB floppy layout
+    img = Image(12, 2880, 1, 224, 9, 0xF0, 'FLOPPY', 0x12345678)
+    root = [short_entry(short_name('FLOPPY'), ATTR_VOLUME, 0, 0)]
+
+    data = b'Hello from an independent FAT writer!\r\n'
+    root += file_entries(img, '', 'README.TXT', data, img.alloc(1))
+
+    # deleted file followed by a live one - deleted entries must be skipped
+    deleted = short_name('OLD.TXT')
+    root += lfn_entries('old file.txt', deleted, deleted=True)
+    root.append(short_entry(deleted, ATTR_ARCHIVE, 0, 0, deleted=True))
+
+    # non-ASCII long name
+    data = contents(1, 3000)
+    root += file_entries(img, '', '_NIC_D~1.TXT', data, img.alloc(clusters_for(img, 3000)),
+                         long_name='Ünïcödé файл.txt')
+
+    # long name filling LFN entry exactly (no terminator)
+    data = contents(2, 700)
+    root += file_entries(img, '', 'EXACTL~1.TXT', data, img.alloc(2), long_name='exactly13.txt')
+
+    # two files with interleaved clusters, the second one stored backwards
+    a = contents(3, 10 * SECTOR - 100)
+    b = contents(4, 10 * SECTOR)
+    block = img.alloc(20)
+    root += file_entries(img, '', 'FRAG1.BIN', a, block[0::2])
+    root += file_entries(img, '', 'FRAG2.BIN', b, list(reversed(block[1::2])))
+
+    root += file_entries(img, '', 'EMPTY.TXT', b'', [])
+
+    # deep tree: /DEEP/L1/L2/.../L7/LEAF.TXT
+    parent_cluster = 0
+    path = ''
+    level_entries = root
+    names = ['DEEP'] + ['L%d' % i for i in range(1, 8)]
+    dirs = []
+    for name in names:
+        cluster = img.alloc(1)[0]
+        level_entries += dir_entry(img, path, name, cluster)
+        path = '%s/%s' % (path, name)
+        entries = dot_entries(cluster, parent_cluster)
+        dirs.append((cluster, entries))
+        level_entries = entries
+        parent_cluster = cluster
+    data = contents(5, 1234)
+    level_entries += file_entries(img, path, 'LEAF.TXT', data, img.alloc(3))
+    for cluster, entries in dirs:
+        img.write_dir([cluster], entries)
+
+    img.write_root(root)
+    return img
+
+
+def fat16_image():
+    # smallest FAT16 layout with 512-byte clusters
+    img = Image(16, 4400, 1, 512, 18, 0xF8, 'FAT16VOL', 0x0BADF00D)
+    assert 4085 <= img.cluster_count < 65525
+    root = [short_entry(short_name('FAT16VOL'), ATTR_VOLUME, 0, 0)]
+
+    # CJK long name using three LFN entries
+    data = contents(6, 5000)
+    root += file_entries(img, '', '_____~1.TXT', data, img.alloc(clusters_for(img, 5000)),
+                         long_name='日本語のファイル名、長い名前のテストです。'
+                                   '三つのエントリ.txt')
+
+    # directory with many entries spanning non-contiguous clusters which are interleaved
+    # with clusters of a fragmented file
+    block = img.alloc(60)
+    dir_clusters = [block[5], block[30], block[17], block[50], block[41]]
+    file_clusters = [c for c in block if c not in dir_clusters]
+    big = contents(7, len(file_clusters) * SECTOR - 17)
+    root += file_entries(img, '', 'BIG.BIN', big, file_clusters)
+
+    root += dir_entry(img, '', 'MANY', dir_clusters[0], long_name='Directory with many files')
+    entries = dot_entries(dir_clusters[0], 0)
+    for i in range(40):
+        name = 'F%02d.DAT' % i
+        data = contents(100 + i, 37 * i)
+        clusters = img.alloc(clusters_for(img, len(data)))
+        if i % 3 == 0:
+            entries += file_entries(img, '/MANY', name, data, clusters,
+                                    long_name='file number %d.dat' % i)
+        else:
+            entries += file_entries(img, '/MANY', name, data, clusters)
+    img.write_dir(dir_clusters, entries)
+
+    img.write_root(root)
+    return img
+
+
+def rust_entries(img):
+    lines = []
+    for path, size, h in img.expected:
+        size = 'Some(%d)' % size if size is not None else 'None'
+        lines.append('ExpectedEntry { path: "%s", size: %s, hash: 0x%016x },' % (path, size, h))
+    for long_path, short_path in img.long_names:
+        lines.append('("%s", "%s"),' % (long_path, short_path))
+    return '\n'.join(lines)
+
+
+if __name__ == '__main__':
+    out_dir = os.path.dirname(os.path.abspath(__file__))
+    for name, img in (('fat12.img', fat12_image()), ('fat16.img', fat16_image())):
+        img.finish(os.path.join(out_dir, name))
+        print('// %s' % name)
+        print(rust_entries(img))
@@ -0,0 +1,105 @@
+// Tests of volume formatting options.
+
+extern crate basic_io;
+extern crate fatfs;
+
+use basic_io::{Cursor, Write};
+use fatfs::{FatType, FileSystem, FormatOptions, FsOptions};
+
+const MB: usize = 1024 * 1024;
+
+fn format(size: usize, options: FormatOptions) -> Vec<u8> {
+    let mut data = vec![0u8; size];
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        fatfs::format_volume(&mut cursor, options).unwrap();
+    }
+    data
+}
+
+fn read_u16(data: &[u8], offset: usize) -> usize {
+    u16::from_le_bytes([data[offset], data[offset + 1]]) as usize
+}
+
+fn read_u32(data: &[u8], offset: usize) -> usize {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]) as usize
+}
+
+// Returns offset of the data region computed from BPB
+fn data_region_offset(data: &[u8]) -> usize {
+    let bytes_per_sector = read_u16(data, 11);
+    let reserved_sectors = read_u16(data, 14);
+    let fats = data[16] as usize;
+    let root_entries = read_u16(data, 17);
+    let sectors_per_fat = match read_u16(data, 22) {
+        0 => read_u32(data, 36),
+        n => n,
+    };
+    let root_dir_sectors = (root_entries * 32).div_ceil(bytes_per_sector);
+    (reserved_sectors + fats * sectors_per_fat + root_dir_sectors) * bytes_per_sector
+}
+
+fn check_volume(data: &mut [u8]) -> FatType {
+    let mut cursor = Cursor::new(data);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut dir = fs.root_dir().create_dir("EFI").unwrap();
+    dir.create_file("boot.efi").unwrap().write_all(b"MZ").unwrap();
+    fs.verify_invariants().unwrap();
+    fs.fat_type()
+}
+
+#[test]
+fn esp_preset() {
+    for &size in [64 * MB, 300 * MB].iter() {
+        let mut data = format(size, FormatOptions::esp());
+        assert_eq!(data_region_offset(&data) % MB, 0);
+        assert_eq!(read_u16(&data, 11), 512);
+        let cluster_size = 512 * data[13] as usize;
+        assert_eq!(cluster_size, if size < 256 * MB { 512 } else { 4096 });
+        assert_eq!(check_volume(&mut data), FatType::Fat32);
+    }
+}
+
+#[test]
+fn data_alignment() {
+    let volumes = [(FatType::Fat12, MB), (FatType::Fat16, 8 * MB), (FatType::Fat32, 40 * MB)];
+    for &(fat_type, size) in volumes.iter() {
+        let options = FormatOptions::new().fat_type(fat_type).bytes_per_cluster(512);
+        let mut data = format(size, options.data_alignment(64 * 1024));
+        assert_eq!(data_region_offset(&data) % (64 * 1024), 0);
+        assert_eq!(check_volume(&mut data), fat_type);
+    }
+}
+
+#[test]
+fn erase_block_alignment() {
+    let volumes = [(FatType::Fat12, MB), (FatType::Fat16, 8 * MB), (FatType::Fat32, 40 * MB)];
+    for &(fat_type, size) in volumes.iter() {
+        for &fats in [1, 2].iter() {
+            let erase_block_size = 128 * 1024;
+            let options = FormatOptions::new()
+                .fat_type(fat_type)
+                .bytes_per_cluster(512)
+                .fats(fats)
+                .root_entries(100)
+                .erase_block_size(erase_block_size as u32);
+            let mut data = format(size, options);
+            let fat_offset = read_u16(&data, 14) * read_u16(&data, 11);
+            assert_eq!(fat_offset % erase_block_size, 0);
+            assert_eq!(data_region_offset(&data) % erase_block_size, 0);
+            assert_eq!(check_volume(&mut data), fat_type);
+        }
+    }
+}
+
+#[test]
+fn invalid_data_alignment() {
+    let mut data = vec![0u8; 4 * MB];
+    let mut cursor = Cursor::new(&mut data[..]);
+    for &alignment in [256, 3000, 1 << 30].iter() {
+        let options = FormatOptions::new().data_alignment(alignment);
+        assert!(fatfs::format_volume(&mut cursor, options).is_err());
+        let options = FormatOptions::new().erase_block_size(alignment);
+        assert!(fatfs::format_volume(&mut cursor, options).is_err());
+    }
+}
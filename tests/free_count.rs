@@ -0,0 +1,116 @@
+// Tests of the free cluster count and of cluster allocation when the volume becomes full.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, ErrorKind, Seek, SeekFrom, Write};
+use fatfs::{CheckOptions, FatType, FileSystem, FormatOptions, FsOptions, ReadWriteSeek};
+use common::{FaultyDisk, MB};
+
+const CLUSTER_SIZE: usize = 512;
+
+fn free_clusters<IO: ReadWriteSeek>(fs: &FileSystem<IO>) -> u32 {
+    fs.stats().unwrap().free_clusters
+}
+
+#[test]
+fn free_count_is_updated_and_stored_in_fs_info() {
+    let mut data = common::small_volume(FatType::Fat32);
+    let expected = {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        let initial = free_clusters(&fs);
+        let mut root = fs.root_dir();
+        let mut file = root.create_file("DATA.BIN").unwrap();
+        file.write_all(&[1; CLUSTER_SIZE * 10]).unwrap();
+        assert_eq!(free_clusters(&fs), initial - 10);
+        file.seek(SeekFrom::Start(CLUSTER_SIZE as u64 * 4)).unwrap();
+        file.truncate().unwrap();
+        assert_eq!(free_clusters(&fs), initial - 4);
+        drop(file);
+        root.create_dir("DIR").unwrap();
+        assert_eq!(free_clusters(&fs), initial - 5);
+        root.remove("DATA.BIN").unwrap();
+        assert_eq!(free_clusters(&fs), initial - 1);
+        initial - 1
+    };
+    // value stored in FSInfo sector matches the FAT
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    assert_eq!(free_clusters(&fs), expected);
+    assert!(fs.check(CheckOptions::new(), &mut |_| {}).unwrap().is_clean());
+}
+
+#[test]
+fn free_count_is_recalculated_after_partially_freed_chain() {
+    let mut disk = FaultyDisk::new(common::small_volume(FatType::Fat16));
+    let reserved_sectors = disk.data[14] as u64 | (disk.data[15] as u64) << 8;
+    let fat_offset = reserved_sectors * 512;
+    let read_fault = disk.read_fault.clone();
+    let after = {
+        let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+        let mut root = fs.root_dir();
+        // chain crosses the boundary between the first and the second FAT sector
+        let mut file = root.create_file("DATA.BIN").unwrap();
+        file.write_all(&[1; CLUSTER_SIZE * 300]).unwrap();
+        drop(file);
+        let before = free_clusters(&fs);
+        read_fault.set(Some((fat_offset + 512, fat_offset + 1024)));
+        assert!(root.remove("DATA.BIN").is_err());
+        read_fault.set(None);
+        // some clusters were freed before the error - count is not off by them
+        let after = free_clusters(&fs);
+        assert!(after > before);
+        after
+    };
+    // compare with the count from a full FAT scan
+    let mut cursor = Cursor::new(&mut disk.data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    assert_eq!(after, free_clusters(&fs));
+}
+
+fn test_full_volume(mut data: Vec<u8>) {
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let mut first = root.create_file("FIRST.BIN").unwrap();
+    first.write_all(&[1; CLUSTER_SIZE * 10]).unwrap();
+    drop(first);
+    let mut file = root.create_file("FILL.BIN").unwrap();
+    // each write takes 64 clusters so the volume is full after a bounded number of writes
+    let chunk = [2; CLUSTER_SIZE * 64];
+    let writes = fs.stats().unwrap().total_clusters / 64 + 1;
+    let err = (0..writes).map(|_| file.write_all(&chunk)).find(|r| r.is_err());
+    assert_eq!(err.unwrap().unwrap_err().kind(), ErrorKind::NotEnoughSpace);
+    assert_eq!(free_clusters(&fs), 0);
+    assert_eq!(file.write(&[3]).unwrap_err().kind(), ErrorKind::NotEnoughSpace);
+
+    // clusters freed before the allocation hint are found by wrapping around
+    root.remove("FIRST.BIN").unwrap();
+    file.seek(SeekFrom::End(0)).unwrap();
+    let pos = file.seek(SeekFrom::Current(0)).unwrap();
+    let to_boundary = (CLUSTER_SIZE - pos as usize % CLUSTER_SIZE) % CLUSTER_SIZE;
+    file.write_all(&vec![4; to_boundary + CLUSTER_SIZE * 10]).unwrap();
+    assert_eq!(free_clusters(&fs), 0);
+    assert_eq!(file.write(&[5]).unwrap_err().kind(), ErrorKind::NotEnoughSpace);
+    drop(file);
+    assert!(fs.check(CheckOptions::new(), &mut |_| {}).unwrap().is_clean());
+}
+
+#[test]
+fn full_volume_reports_not_enough_space_fat12() {
+    let options = FormatOptions::new().fat_type(FatType::Fat12).bytes_per_cluster(512);
+    test_full_volume(common::format_image(MB, options));
+}
+
+#[test]
+fn full_volume_reports_not_enough_space_fat16() {
+    test_full_volume(common::small_volume(FatType::Fat16));
+}
+
+#[test]
+fn full_volume_reports_not_enough_space_fat32() {
+    test_full_volume(common::small_volume(FatType::Fat32));
+}
@@ -0,0 +1,63 @@
+// Tests of counting free clusters in small steps.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, Seek, SeekFrom, Write};
+use fatfs::{FatType, FileSystem, FsOptions};
+
+fn full_scan(data: &mut [u8]) -> u32 {
+    let mut cursor = Cursor::new(data);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    fs.stats().unwrap().free_clusters
+}
+
+#[test]
+fn scan_completes_in_steps() {
+    let mut data = common::small_volume(FatType::Fat16);
+    let expected = full_scan(&mut data);
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let total_clusters = fs.stats().unwrap().total_clusters;
+    let mut calls = 1;
+    while fs.scan_free_space_incremental(1000).unwrap().is_none() {
+        calls += 1;
+    }
+    assert_eq!(calls, total_clusters.div_ceil(1000));
+    // next call starts a new scan
+    assert_eq!(fs.scan_free_space_incremental(0).unwrap(), None);
+    assert_eq!(fs.scan_free_space_incremental(total_clusters).unwrap(), Some(expected));
+}
+
+#[test]
+fn modifications_during_scan_are_accounted_for() {
+    let mut data = common::small_volume(FatType::Fat16);
+    let result = {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        let mut root = fs.root_dir();
+        root.create_file("a.bin").unwrap().write_all(&[1; 5000]).unwrap();
+        root.create_file("b.bin").unwrap().write_all(&[2; 5000]).unwrap();
+        // scan past both files
+        assert_eq!(fs.scan_free_space_incremental(100).unwrap(), None);
+        // frees clusters in the scanned part
+        root.remove("a.bin").unwrap();
+        let mut file = root.open_file("b.bin").unwrap();
+        file.seek(SeekFrom::Start(1000)).unwrap();
+        file.truncate().unwrap();
+        // allocates clusters on both sides of the scan cursor
+        root.create_file("c.bin").unwrap().write_all(&[3; 100_000]).unwrap();
+        drop(file);
+        let mut result = None;
+        while result.is_none() {
+            result = fs.scan_free_space_incremental(7).unwrap();
+            root.create_file("d.bin").unwrap().write_all(&[4; 600]).unwrap();
+            root.remove("d.bin").unwrap();
+        }
+        assert_eq!(fs.stats().unwrap().free_clusters, result.unwrap());
+        result.unwrap()
+    };
+    assert_eq!(full_scan(&mut data), result);
+}
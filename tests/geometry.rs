@@ -0,0 +1,54 @@
+// Tests of sector and cluster geometry helpers.
+
+extern crate basic_io;
+extern crate fatfs;
+
+use basic_io::{Cursor, Write};
+use fatfs::{Cluster, FatType, FileSystem, FormatOptions, FsOptions, Sector};
+
+fn read_u16(data: &[u8], offset: usize) -> u32 {
+    u16::from_le_bytes([data[offset], data[offset + 1]]) as u32
+}
+
+#[test]
+fn geometry_matches_bpb() {
+    let volumes = [(FatType::Fat12, 1024 * 1024), (FatType::Fat16, 16 * 1024 * 1024)];
+    for &(fat_type, size) in volumes.iter() {
+        let mut data = vec![0u8; size];
+        let mut cursor = Cursor::new(&mut data[..]);
+        let options = FormatOptions::new().fat_type(fat_type).bytes_per_cluster(2048);
+        fatfs::format_volume(&mut cursor, options).unwrap();
+        let (sector, first_data_sector) = {
+            let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+            let mut root = fs.root_dir();
+            root.create_file("pad.bin").unwrap().write_all(&[0xEE; 5000]).unwrap();
+            let mut file = root.create_file("a.bin").unwrap();
+            file.write_all(b"geometry").unwrap();
+            let cluster = file.clusters().next().unwrap().unwrap();
+
+            assert_eq!(fs.bytes_per_sector(), 512);
+            assert_eq!(fs.sectors_per_cluster(), 4);
+            assert_eq!(fs.cluster_size(), 2048);
+            let offset = fs.offset_from_cluster(cluster);
+            assert_eq!(fs.cluster_from_offset(offset), Some(cluster));
+            assert_eq!(fs.cluster_from_offset(offset + 2047), Some(cluster));
+            assert_eq!(fs.cluster_from_offset(offset + 2048), Some(cluster.next()));
+            let data_offset = fs.offset_from_sector(fs.first_data_sector());
+            assert_eq!(fs.offset_from_cluster(Cluster(2)), data_offset);
+            assert_eq!(fs.cluster_from_offset(data_offset - 1), None);
+            let total_clusters = fs.stats().unwrap().total_clusters;
+            let last_cluster = Cluster(total_clusters + 1);
+            let end = fs.offset_from_cluster(last_cluster) + 2048;
+            assert_eq!(fs.cluster_from_offset(end - 1), Some(last_cluster));
+            assert_eq!(fs.cluster_from_offset(end), None);
+            (fs.sector_from_cluster(cluster), fs.first_data_sector())
+        };
+        // first data sector derived from BPB
+        let root_dir_sectors = (read_u16(&data, 17) * 32).div_ceil(512);
+        let fat_sectors = data[16] as u32 * read_u16(&data, 22);
+        let expected = read_u16(&data, 14) + fat_sectors + root_dir_sectors;
+        assert_eq!(first_data_sector, Sector(expected as u64));
+        let offset = sector.0 as usize * 512;
+        assert_eq!(&data[offset..offset + 8], b"geometry");
+    }
+}
@@ -0,0 +1,160 @@
+// Tests of name, path, directory size and file size limits.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, ErrorKind, Seek, SeekFrom, Write};
+use fatfs::{FatType, FileSystem, FormatOptions, FsOptions, MAX_DIR_ENTRIES};
+use common::MB;
+
+#[test]
+fn name_length_limit() {
+    let mut data = common::format_image(MB, FormatOptions::new().fat_type(FatType::Fat12));
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new().max_name_length(8)).unwrap();
+    let free = fs.stats().unwrap().free_clusters;
+    let mut root = fs.root_dir();
+    root.create_file("12345678").unwrap();
+    // limit is in characters, not bytes
+    root.create_file("ąčęėįšųū").unwrap();
+    let err = root.create_file("123456789").err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::NameTooLong);
+    let err = root.create_dir("123456789").err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::NameTooLong);
+    let err = root.rename("12345678", &root, "123456789").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NameTooLong);
+    assert_eq!(fs.stats().unwrap().free_clusters, free);
+    fs.verify_invariants().unwrap();
+}
+
+#[test]
+fn default_name_length_limit() {
+    let mut data = common::format_image(MB, FormatOptions::new().fat_type(FatType::Fat12));
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new().max_name_length(1000)).unwrap();
+    let mut root = fs.root_dir();
+    root.create_file(&"a".repeat(255)).unwrap();
+    let err = root.create_file(&"b".repeat(256)).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::NameTooLong);
+}
+
+#[test]
+fn path_depth_limit() {
+    let mut data = common::format_image(MB, FormatOptions::new().fat_type(FatType::Fat12));
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new().max_path_depth(3)).unwrap();
+    let mut root = fs.root_dir();
+    root.create_dir("a").unwrap();
+    root.create_dir("a/b").unwrap();
+    root.create_file("/a/b/c.txt/").unwrap();
+    root.open_file("a/b/c.txt").unwrap();
+    let err = root.create_dir("a/b/c/d").err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::PathTooDeep);
+    let err = root.open_file("a/b/c/d.txt").err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::PathTooDeep);
+    let err = root.rename("a/b/c.txt", &root, "a/b/x/c.txt").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PathTooDeep);
+    // depth is counted from the directory the path is relative to
+    let mut b = root.open_dir("a/b").unwrap();
+    b.create_dir("c").unwrap();
+    let err = b.create_file("c/d/e.txt").err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    b.create_dir("c/d").unwrap();
+    b.create_file("c/d/e.txt").unwrap();
+}
+
+#[test]
+fn dir_entries_limit() {
+    let mut data = common::format_image(MB, FormatOptions::new().fat_type(FatType::Fat12));
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut cursor, FsOptions::new().max_dir_entries(6)).unwrap();
+        let mut root = fs.root_dir();
+        let mut dir = root.create_dir("dir").unwrap();
+        // "." and ".." use two slots
+        for i in 0..4 {
+            dir.create_file(&format!("{}.txt", i)).unwrap();
+        }
+        let err = dir.create_file("4.txt").err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::TooManyEntries);
+        let free = fs.stats().unwrap().free_clusters;
+        let err = dir.create_dir("sub").err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::TooManyEntries);
+        assert_eq!(fs.stats().unwrap().free_clusters, free);
+        assert_eq!(dir.iter().count(), 6);
+        // freed slot can be reused
+        dir.remove("0.txt").unwrap();
+        dir.create_file("4.txt").unwrap();
+        for i in 5..10 {
+            root.create_file(&format!("{}.txt", i)).unwrap();
+        }
+        // directory filled up to the limit can be iterated
+        assert!(root.iter().all(|e| e.is_ok()));
+    }
+    // scan stops at the first entry beyond the limit
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new().max_dir_entries(4)).unwrap();
+    let entries: Vec<_> = fs.root_dir().iter().collect();
+    assert_eq!(entries.len(), 5);
+    assert!(entries[..4].iter().all(|e| e.is_ok()));
+    assert_eq!(entries[4].as_ref().err().unwrap().kind(), ErrorKind::TooManyEntries);
+}
+
+#[test]
+fn dir_size_spec_limit() {
+    let mut data = vec![0u8; 8 * MB];
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let options = FormatOptions::new().fat_type(FatType::Fat16).bytes_per_cluster(512);
+        fatfs::format_volume(&mut cursor, options).unwrap();
+        // file filled with valid entries is turned into a directory below
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        let mut file = fs.root_dir().create_file("BIG.DAT").unwrap();
+        for i in 0..MAX_DIR_ENTRIES + 16 {
+            let mut entry = [0u8; 32];
+            entry[..11].copy_from_slice(format!("{:08}TXT", i).as_bytes());
+            entry[11] = 0x20;
+            file.write_all(&entry).unwrap();
+        }
+    }
+    let pos = data.chunks(32).position(|e| &e[..11] == b"BIG     DAT").unwrap() * 32;
+    data[pos + 11] = 0x10;
+    for b in &mut data[pos + 28..pos + 32] {
+        *b = 0;
+    }
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut dir = fs.root_dir().open_dir("BIG.DAT").unwrap();
+    // entries beyond the limit are not read
+    assert_eq!(dir.iter().count(), MAX_DIR_ENTRIES as usize);
+    let free = fs.stats().unwrap().free_clusters;
+    let err = dir.create_file("NEW.TXT").err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::DirectoryFull);
+    let err = dir.create_dir("NEW").err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::DirectoryFull);
+    assert_eq!(fs.stats().unwrap().free_clusters, free);
+}
+
+#[test]
+fn file_size_limit() {
+    let mut data = common::format_image(MB, FormatOptions::new().fat_type(FatType::Fat12));
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new().max_file_size(1000)).unwrap();
+    assert_eq!(fs.max_file_size(), 1000);
+    let mut file = fs.root_dir().create_file("a.bin").unwrap();
+    // write stops at the limit
+    assert_eq!(file.write(&[1u8; 1500]).unwrap(), 512);
+    assert_eq!(file.write(&[1u8; 1500]).unwrap(), 488);
+    let err = file.write(&[1u8; 1]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::FileTooLarge);
+    // empty writes are fine
+    assert_eq!(file.write(&[]).unwrap(), 0);
+    file.seek(SeekFrom::Start(200)).unwrap();
+    let err = file.write_all(&[2u8; 900]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::FileTooLarge);
+    assert_eq!(file.seek(SeekFrom::End(0)).unwrap(), 1000);
+    drop(file);
+    fs.verify_invariants().unwrap();
+}
@@ -0,0 +1,291 @@
+// Tests of long file names read from LFN entries.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use basic_io::{Cursor, Read, Write};
+use fatfs::{lfn_checksum, validate_lfn_sequence, Dir, FileSystem, FormatOptions, FsOptions,
+            LfnSequenceError, ReadWriteSeek};
+use common::MB;
+
+fn read_image() -> Vec<u8> {
+    fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("fat32.img")).unwrap()
+}
+
+// Offset of short name entry with given raw name
+fn entry_offset(data: &[u8], raw_name: &[u8; 11]) -> usize {
+    data.chunks(32).position(|e| &e[..11] == raw_name).unwrap() * 32
+}
+
+fn names<IO: ReadWriteSeek>(dir: &Dir<IO>) -> Vec<(String, Option<String>)> {
+    dir.iter()
+        .map(|r| {
+            let e = r.unwrap();
+            (e.short_file_name().to_string(), e.long_file_name().map(|s| s.to_string()))
+        })
+        .collect()
+}
+
+#[test]
+fn long_names_are_decoded() {
+    let mut data = read_image();
+    let mut disk = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let expected = [
+        ("LONG.TXT", "long.txt"),
+        ("SHORT.TXT", "short.txt"),
+        ("VERY", "very"),
+        ("VERY-L~1", "very-long-dir-name"),
+    ];
+    let expected: Vec<_> = expected
+        .iter()
+        .map(|&(short, long)| (short.to_string(), Some(long.to_string())))
+        .collect();
+    assert_eq!(names(&root), expected);
+    let mut dir = root.open_dir("very-long-dir-name").unwrap();
+    let entry = dir.iter().last().unwrap().unwrap();
+    assert_eq!(entry.file_name(), "very-long-file-name.txt");
+    assert!(entry.eq_name("VERY-LONG-FILE-NAME.TXT"));
+    assert!(entry.eq_name("very-l~1.txt"));
+    dir.open_file("very-long-file-name.txt").unwrap();
+}
+
+#[test]
+fn orphaned_lfn_entries_are_ignored() {
+    let mut data = read_image();
+    // LFN entry with checksum of another short name
+    let long_txt = entry_offset(&data, b"LONG    TXT");
+    data[long_txt - 32 + 13] ^= 0xFF;
+    // second part of the name is missing
+    let file = entry_offset(&data, b"VERY-L~1TXT");
+    data[file - 64] = 0xE5;
+    let mut disk = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let entry = root.iter().next().unwrap().unwrap();
+    assert_eq!(entry.long_file_name(), None);
+    assert_eq!(entry.file_name(), "LONG.TXT");
+    let dir = root.open_dir("very-long-dir-name").unwrap();
+    let entry = dir.iter().last().unwrap().unwrap();
+    assert_eq!(entry.file_name(), "VERY-L~1.TXT");
+}
+
+#[test]
+fn lfn_entries_out_of_order_are_ignored() {
+    let mut data = read_image();
+    let file = entry_offset(&data, b"VERY-L~1TXT");
+    // swap the two parts of the name
+    let (first, second) = (file - 64, file - 32);
+    for i in 0..32 {
+        data.swap(first + i, second + i);
+    }
+    let mut disk = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    let dir = fs.root_dir().open_dir("very-l~1").unwrap();
+    let entry = dir.iter().last().unwrap().unwrap();
+    assert_eq!(entry.long_file_name(), None);
+}
+
+#[test]
+fn raw_names_are_preserved() {
+    let mut data = read_image();
+    // unpaired surrogate as the first unit of the long name
+    let long_txt = entry_offset(&data, b"LONG    TXT");
+    data[long_txt - 32 + 1] = 0x00;
+    data[long_txt - 32 + 2] = 0xD8;
+    let mut disk = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    let root = fs.root_dir();
+    let mut iter = root.iter();
+    let entry = iter.next().unwrap().unwrap();
+    assert_eq!(&entry.raw_short_name(), b"LONG    TXT");
+    assert_eq!(entry.long_file_name().unwrap(), "\u{FFFD}ong.txt");
+    let mut expected: Vec<u16> = "long.txt".encode_utf16().collect();
+    expected[0] = 0xD800;
+    assert_eq!(entry.raw_lfn_units(), Some(&expected[..]));
+    let entry = iter.next().unwrap().unwrap();
+    assert_eq!(&entry.raw_short_name(), b"SHORT   TXT");
+    let expected: Vec<u16> = "short.txt".encode_utf16().collect();
+    assert_eq!(entry.raw_lfn_units(), Some(&expected[..]));
+}
+
+#[test]
+fn raw_lfn_units_are_absent_without_long_name() {
+    let mut data = read_image();
+    let long_txt = entry_offset(&data, b"LONG    TXT");
+    data[long_txt - 32] = 0xE5;
+    let mut disk = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    let entry = fs.root_dir().iter().next().unwrap().unwrap();
+    assert_eq!(entry.raw_lfn_units(), None);
+    assert_eq!(&entry.raw_short_name(), b"LONG    TXT");
+}
+
+#[test]
+fn case_is_ignored_for_non_ascii_names() {
+    let mut data = read_image();
+    // change the long name to "łong.txt"
+    let long_txt = entry_offset(&data, b"LONG    TXT");
+    data[long_txt - 32 + 1] = 0x42;
+    data[long_txt - 32 + 2] = 0x01;
+    let mut disk = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let entry = root.iter().next().unwrap().unwrap();
+    assert!(entry.eq_name("ŁONG.TXT"));
+    // existing file is opened instead of creating another one differing in case
+    assert!(!root.open_or_create_file("ŁONG.TXT").unwrap().is_created());
+    assert_eq!(root.iter().count(), 4);
+    let err = root.rename("short.txt", &root, "Łong.txt").err().unwrap();
+    assert_eq!(err.kind(), basic_io::ErrorKind::AlreadyExists);
+}
+
+#[test]
+fn paths_can_mix_long_names_and_aliases() {
+    let mut data = read_image();
+    let mut disk = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let paths = [
+        "very-long-dir-name/very-long-file-name.txt",
+        "VERY-L~1/very-long-file-name.txt",
+        "very-long-dir-name/VERY-L~1.TXT",
+        "very-l~1/very-l~1.txt",
+    ];
+    let mut contents = Vec::new();
+    for path in paths.iter() {
+        let mut file = root.open_file(path).unwrap();
+        let mut buf = [0u8; 16];
+        let n = file.read(&mut buf).unwrap();
+        contents.push(buf[..n].to_vec());
+    }
+    assert!(contents.iter().all(|c| *c == contents[0]));
+    // alias of a different entry does not match
+    assert_eq!(
+        root.open_file("very-long-dir-name/VERY-L~2.TXT").err().unwrap().kind(),
+        basic_io::ErrorKind::NotFound
+    );
+}
+
+#[test]
+fn files_can_be_renamed_and_removed_by_alias() {
+    let mut data = read_image();
+    let mut disk = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let mut dir = root.open_dir("VERY-L~1").unwrap();
+    dir.rename("VERY-L~1.TXT", &dir, "RENAMED.TXT").unwrap();
+    dir.remove("renamed.txt").unwrap();
+    assert_eq!(dir.iter().count(), 2);
+    fs.verify_invariants().unwrap();
+}
+
+#[test]
+fn long_names_are_written() {
+    // 26 units fill two LFN entries exactly so the name has no terminator
+    let name_26 = "abcdefghijklmnopqrstuvwxyz";
+    let mut data = common::format_image(4 * MB, FormatOptions::new());
+    {
+        let mut disk = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+        let mut root = fs.root_dir();
+        let mut dir = root.create_dir("Long Directory Name").unwrap();
+        dir.create_file("zażółć gęślą jaźń.txt").unwrap().write_all(b"data").unwrap();
+        root.create_file(name_26).unwrap();
+        root.create_file("short.txt").unwrap();
+    }
+    let mut disk = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    assert_eq!(
+        names(&root),
+        [
+            ("LONGDIRE".to_string(), Some("Long Directory Name".to_string())),
+            ("ABCDEFGH".to_string(), Some(name_26.to_string())),
+            ("SHORT.TXT".to_string(), None),
+        ]
+    );
+    let mut buf = [0u8; 4];
+    root.open_file("long directory name/ZAŻÓŁĆ GĘŚLĄ JAŹŃ.TXT")
+        .unwrap()
+        .read_exact(&mut buf)
+        .unwrap();
+    assert_eq!(&buf, b"data");
+    // LFN entries precede their short name entry and carry its checksum
+    let raw: Vec<_> = root.iter_raw().map(|r| r.unwrap()).take_while(|e| !e.is_end()).collect();
+    assert_eq!(raw.len(), 2 + 1 + 2 + 1 + 1);
+    assert!(raw[0].is_lfn() && raw[3].is_lfn() && !raw[6].is_lfn());
+    let lfn: Vec<_> = raw[..2].iter().map(|e| *e.bytes()).collect();
+    let mut short_name = [0u8; 11];
+    short_name.copy_from_slice(&raw[2].bytes()[..11]);
+    assert_eq!(validate_lfn_sequence(&lfn, &short_name), Ok(()));
+    fs.verify_invariants().unwrap();
+}
+
+#[test]
+fn creating_existing_long_name_opens_the_file() {
+    let mut data = common::format_image(4 * MB, FormatOptions::new());
+    let mut disk = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    root.create_file("Long File Name.txt").unwrap().write_all(b"data").unwrap();
+    root.create_file("Long File Name.txt").unwrap();
+    root.create_file("LONG FILE NAME.TXT").unwrap();
+    assert!(!root.open_or_create_file("long file name.txt").unwrap().is_created());
+    // generated short name is taken too
+    assert!(!root.open_or_create_file("longfile.txt").unwrap().is_created());
+    assert_eq!(
+        names(&root),
+        [("LONGFILE.TXT".to_string(), Some("Long File Name.txt".to_string()))]
+    );
+    let mut buf = [0u8; 4];
+    root.open_file("long file name.txt").unwrap().read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"data");
+    fs.verify_invariants().unwrap();
+}
+
+#[test]
+fn lfn_checksum_matches_specification() {
+    assert_eq!(lfn_checksum(b"README  TXT"), 0x73);
+    assert_eq!(lfn_checksum(b"LONGFI~1TXT"), 0xd4);
+}
+
+#[test]
+fn broken_lfn_sequences_are_rejected() {
+    let mut data = common::format_image(4 * MB, FormatOptions::new());
+    let mut disk = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    root.create_file("Long File Name.txt").unwrap();
+    let raw: Vec<_> = root.iter_raw().map(|r| *r.unwrap().bytes()).take(3).collect();
+    let (lfn, short_entry) = (&raw[..2], &raw[2]);
+    let mut short_name = [0u8; 11];
+    short_name.copy_from_slice(&short_entry[..11]);
+    assert_eq!(lfn_checksum(&short_name), lfn[0][13]);
+    assert_eq!(validate_lfn_sequence(lfn, &short_name), Ok(()));
+
+    let check = |entries: &[[u8; 32]]| validate_lfn_sequence(entries, &short_name);
+    assert_eq!(check(&[]), Err(LfnSequenceError::InvalidLength));
+    assert_eq!(check(&[lfn[0]; 21]), Err(LfnSequenceError::InvalidLength));
+    // entries swapped or the last one missing
+    assert_eq!(check(&[lfn[1], lfn[0]]), Err(LfnSequenceError::InvalidOrder));
+    assert_eq!(check(&lfn[1..]), Err(LfnSequenceError::InvalidOrder));
+    // short name entry in place of LFN entry
+    assert_eq!(check(&[lfn[0], *short_entry]), Err(LfnSequenceError::InvalidEntry));
+    let mut broken = [lfn[0], lfn[1]];
+    broken[1][26] = 1;
+    assert_eq!(check(&broken), Err(LfnSequenceError::InvalidEntry));
+    let mut broken = [lfn[0], lfn[1]];
+    broken[1][13] ^= 1;
+    assert_eq!(check(&broken), Err(LfnSequenceError::ChecksumMismatch));
+    // renamed short name entry
+    let mut other_name = short_name;
+    other_name[0] = b'X';
+    assert_eq!(validate_lfn_sequence(lfn, &other_name), Err(LfnSequenceError::ChecksumMismatch));
+}
@@ -0,0 +1,159 @@
+// Tests of media change detection and remounting.
+
+extern crate basic_io;
+extern crate fatfs;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use basic_io::{Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
+use fatfs::{FileSystem, FormatOptions, FsOptions, MediaIdProvider};
+
+const VOLUME_SIZE: usize = 4 * 1024 * 1024;
+
+struct CardSlot {
+    id: AtomicU64,
+}
+
+impl MediaIdProvider for CardSlot {
+    fn media_id(&self) -> Option<u64> {
+        match self.id.load(Ordering::SeqCst) {
+            0 => None,
+            id => Some(id),
+        }
+    }
+}
+
+// Device with swappable medium
+struct Reader {
+    card: Rc<RefCell<Vec<u8>>>,
+    pos: u64,
+}
+
+impl Read for Reader {
+    fn read(&mut self, buf: &mut [u8]) -> basic_io::Result<usize> {
+        let card = self.card.borrow();
+        let start = self.pos as usize;
+        let n = buf.len().min(card.len() - start);
+        buf[..n].copy_from_slice(&card[start..start + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Write for Reader {
+    fn write(&mut self, buf: &[u8]) -> basic_io::Result<usize> {
+        let mut card = self.card.borrow_mut();
+        let start = self.pos as usize;
+        let n = buf.len().min(card.len() - start);
+        card[start..start + n].copy_from_slice(&buf[..n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> basic_io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for Reader {
+    fn seek(&mut self, pos: SeekFrom) -> basic_io::Result<u64> {
+        let len = self.card.borrow().len();
+        self.pos = match pos {
+            SeekFrom::Start(n) => n,
+            SeekFrom::Current(n) => (self.pos as i64 + n) as u64,
+            SeekFrom::End(n) => (len as i64 + n) as u64,
+        };
+        Ok(self.pos)
+    }
+}
+
+fn make_card(file_name: &str) -> Vec<u8> {
+    let mut data = vec![0u8; VOLUME_SIZE];
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        fatfs::format_volume(&mut cursor, FormatOptions::new()).unwrap();
+        cursor.seek(SeekFrom::Start(0)).unwrap();
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        fs.root_dir().create_file(file_name).unwrap();
+        fs.unmount().unwrap();
+    }
+    data
+}
+
+#[test]
+fn media_change_is_detected_and_remount_mounts_new_medium() {
+    static SLOT: CardSlot = CardSlot { id: AtomicU64::new(1) };
+    let card = Rc::new(RefCell::new(make_card("FIRST.TXT")));
+    let reader = Reader { card: card.clone(), pos: 0 };
+    let mut fs = FileSystem::new(reader, FsOptions::new().media_id_provider(&SLOT)).unwrap();
+    assert!(fs.root_dir().open_file("FIRST.TXT").is_ok());
+
+    *card.borrow_mut() = make_card("SECOND.TXT");
+    SLOT.id.store(2, Ordering::SeqCst);
+    let err = fs.root_dir().open_file("FIRST.TXT").err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::MediaChanged);
+    let err = fs.root_dir().create_file("NEW.TXT").err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::MediaChanged);
+    // restoring the identifier does not make the filesystem usable again
+    SLOT.id.store(1, Ordering::SeqCst);
+    let err = fs.root_dir().open_file("FIRST.TXT").err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::MediaChanged);
+
+    SLOT.id.store(2, Ordering::SeqCst);
+    fs.remount().unwrap();
+    assert!(fs.root_dir().open_file("SECOND.TXT").is_ok());
+    assert_eq!(fs.root_dir().open_file("FIRST.TXT").err().unwrap().kind(), ErrorKind::NotFound);
+    fs.root_dir().create_file("NEW.TXT").unwrap();
+    fs.unmount().unwrap();
+}
+
+#[test]
+fn failed_remount_keeps_reporting_media_change() {
+    static SLOT: CardSlot = CardSlot { id: AtomicU64::new(1) };
+    let card = Rc::new(RefCell::new(make_card("FIRST.TXT")));
+    let reader = Reader { card: card.clone(), pos: 0 };
+    let mut fs = FileSystem::new(reader, FsOptions::new().media_id_provider(&SLOT)).unwrap();
+
+    // unformatted card
+    *card.borrow_mut() = vec![0u8; VOLUME_SIZE];
+    SLOT.id.store(2, Ordering::SeqCst);
+    assert!(fs.remount().is_err());
+    let err = fs.root_dir().open_file("FIRST.TXT").err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::MediaChanged);
+    assert_eq!(fs.unmount().err().unwrap().kind(), ErrorKind::MediaChanged);
+}
+
+#[test]
+fn unmount_after_media_change_does_not_write_new_medium() {
+    static SLOT: CardSlot = CardSlot { id: AtomicU64::new(1) };
+    let card = Rc::new(RefCell::new(make_card("FIRST.TXT")));
+    let reader = Reader { card: card.clone(), pos: 0 };
+    let fs = FileSystem::new(reader, FsOptions::new().media_id_provider(&SLOT)).unwrap();
+    fs.root_dir().create_file("NEW.TXT").unwrap();
+
+    let second = make_card("SECOND.TXT");
+    *card.borrow_mut() = second.clone();
+    SLOT.id.store(2, Ordering::SeqCst);
+    assert_eq!(fs.unmount().err().unwrap().kind(), ErrorKind::MediaChanged);
+    assert!(*card.borrow() == second);
+}
+
+#[test]
+fn dropping_dirty_file_after_media_change_does_not_panic() {
+    static SLOT: CardSlot = CardSlot { id: AtomicU64::new(1) };
+    let card = Rc::new(RefCell::new(make_card("FIRST.TXT")));
+    let reader = Reader { card: card.clone(), pos: 0 };
+    let fs = FileSystem::new(reader, FsOptions::new().media_id_provider(&SLOT)).unwrap();
+    let mut file = fs.root_dir().open_file("FIRST.TXT").unwrap();
+    file.write_all(b"unflushed").unwrap();
+
+    let second = make_card("SECOND.TXT");
+    *card.borrow_mut() = second.clone();
+    SLOT.id.store(2, Ordering::SeqCst);
+    assert_eq!(file.flush().err().unwrap().kind(), ErrorKind::MediaChanged);
+    // flush error is only logged on drop and nothing is written to the new medium
+    drop(file);
+    assert!(*card.borrow() == second);
+}
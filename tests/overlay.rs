@@ -0,0 +1,82 @@
+// Tests of mounting a read-only base image with copy-on-write overlay.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, ErrorKind, Read, Write};
+use fatfs::{FileSystem, FormatOptions, FsOptions, OverlayStore};
+use common::MB;
+
+fn read_file(data: &mut [u8], store: &mut OverlayStore, path: &str) -> basic_io::Result<Vec<u8>> {
+    let mut base = Cursor::new(data);
+    let fs = FileSystem::new_overlay(&mut base, store, FsOptions::new())?;
+    let mut file = fs.root_dir().open_file(path)?;
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1000];
+    loop {
+        match file.read(&mut chunk)? {
+            0 => return Ok(buf),
+            n => buf.extend_from_slice(&chunk[..n]),
+        }
+    }
+}
+
+#[test]
+fn base_image_is_not_modified() {
+    let original = common::format_image(4 * MB, FormatOptions::new());
+    let mut data = original.clone();
+    let mut store = OverlayStore::new();
+    {
+        let mut base = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new_overlay(&mut base, &mut store, FsOptions::new()).unwrap();
+        let mut root = fs.root_dir();
+        root.create_dir("dir").unwrap();
+        root.create_file("dir/a.txt").unwrap().write_all(&[7; 3000]).unwrap();
+        drop(root);
+        fs.unmount().unwrap();
+    }
+    assert!(data == original);
+    assert!(store.modified_size() > 0);
+    assert_eq!(store.modified_size() % 512, 0);
+}
+
+#[test]
+fn remount_from_store_shows_changes() {
+    let mut data = common::format_image(4 * MB, FormatOptions::new());
+    {
+        let mut base = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut base, FsOptions::new()).unwrap();
+        fs.root_dir().create_file("base.txt").unwrap().write_all(b"base").unwrap();
+    }
+    let mut store = OverlayStore::new();
+    {
+        let mut base = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new_overlay(&mut base, &mut store, FsOptions::new()).unwrap();
+        let mut root = fs.root_dir();
+        // write not aligned to blocks of the store
+        let mut file = root.open_file("base.txt").unwrap();
+        file.write_all(b"modified ").unwrap();
+        file.write_all(&[1; 700]).unwrap();
+        drop(file);
+        root.create_file("new.txt").unwrap().write_all(b"new").unwrap();
+        drop(root);
+        fs.unmount().unwrap();
+    }
+
+    let mut expected = b"modified ".to_vec();
+    expected.extend_from_slice(&[1; 700]);
+    assert_eq!(read_file(&mut data, &mut store, "base.txt").unwrap(), expected);
+    assert_eq!(read_file(&mut data, &mut store, "new.txt").unwrap(), b"new");
+
+    // clearing the store brings back the base image
+    let mut copy = store.clone();
+    store.clear();
+    assert_eq!(store.modified_size(), 0);
+    assert_eq!(read_file(&mut data, &mut store, "base.txt").unwrap(), b"base");
+    let err = read_file(&mut data, &mut store, "new.txt").err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    // cloned store keeps the modifications
+    assert_eq!(read_file(&mut data, &mut copy, "new.txt").unwrap(), b"new");
+}
@@ -0,0 +1,120 @@
+// Tests of filesystem objects owning their storage.
+
+extern crate basic_io;
+extern crate fatfs;
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use basic_io::{Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
+use fatfs::{FatType, File, FileSystem, FormatOptions, FsOptions, MemoryDisk};
+
+const MB: usize = 1024 * 1024;
+
+#[test]
+fn into_inner_returns_storage() {
+    let mut cursor = Cursor::new(vec![0u8; 4 * MB]);
+    let options = FormatOptions::new().fat_type(FatType::Fat16);
+    fatfs::format_volume(&mut cursor, options).unwrap();
+    cursor.seek(SeekFrom::Start(0)).unwrap();
+    let fs = FileSystem::new(cursor, FsOptions::new()).unwrap();
+    fs.root_dir().create_file("HELLO.TXT").unwrap().write_all(b"hello").unwrap();
+    // device is returned seeked to the beginning and can be mounted again right away
+    let cursor = fs.into_inner().unwrap();
+    let fs = FileSystem::new(cursor, FsOptions::new()).unwrap();
+    let mut buf = [0u8; 5];
+    fs.root_dir().open_file("HELLO.TXT").unwrap().read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"hello");
+}
+
+// Keeps a file open between calls like a VFS layer would
+struct Log {
+    file: File<'static, MemoryDisk>,
+}
+
+impl Log {
+    fn append(&mut self, line: &[u8]) {
+        self.file.write_all(line).unwrap();
+    }
+}
+
+#[test]
+fn leaked_filesystem_handles_can_be_stored() {
+    let options = FormatOptions::new().fat_type(FatType::Fat16);
+    let fs = FileSystem::new_in_memory(4 * MB as u64, options).unwrap().leak();
+    let mut log = Log {
+        file: fs.root_dir().create_file("LOG.TXT").unwrap(),
+    };
+    log.append(b"first\n");
+    log.append(b"second\n");
+    drop(log);
+    fs.flush().unwrap();
+    let mut file = fs.root_dir().open_file("LOG.TXT").unwrap();
+    let mut buf = [0u8; 13];
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"first\nsecond\n");
+}
+
+// Fails the given number of writes and then writes normally
+struct FlakyDisk {
+    inner: Cursor<Vec<u8>>,
+    failing_writes: Rc<Cell<u32>>,
+    // number of successful writes
+    writes: Rc<Cell<u32>>,
+}
+
+impl Read for FlakyDisk {
+    fn read(&mut self, buf: &mut [u8]) -> basic_io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Write for FlakyDisk {
+    fn write(&mut self, buf: &[u8]) -> basic_io::Result<usize> {
+        if self.failing_writes.get() > 0 {
+            self.failing_writes.set(self.failing_writes.get() - 1);
+            return Err(basic_io::Error::new(ErrorKind::Other, "write error"));
+        }
+        self.writes.set(self.writes.get() + 1);
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> basic_io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Seek for FlakyDisk {
+    fn seek(&mut self, pos: SeekFrom) -> basic_io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[test]
+fn failed_unmount_is_not_repeated_on_drop() {
+    for &into_inner in &[false, true] {
+        let mut cursor = Cursor::new(vec![0u8; 34 * MB]);
+        fatfs::format_volume(&mut cursor, FormatOptions::new().fat_type(FatType::Fat32)).unwrap();
+        cursor.seek(SeekFrom::Start(0)).unwrap();
+        let failing_writes = Rc::new(Cell::new(0));
+        let writes = Rc::new(Cell::new(0));
+        let disk = FlakyDisk {
+            inner: cursor,
+            failing_writes: failing_writes.clone(),
+            writes: writes.clone(),
+        };
+        let fs = FileSystem::new(disk, FsOptions::new()).unwrap();
+        // allocating a cluster makes FSInfo sector dirty
+        fs.root_dir().create_file("A.TXT").unwrap().write_all(b"a").unwrap();
+        failing_writes.set(1);
+        writes.set(0);
+        let r = match into_inner {
+            true => fs.into_inner().map(|_| ()),
+            false => fs.unmount(),
+        };
+        assert!(r.is_err());
+        // the filesystem was dropped without trying to unmount it again
+        assert_eq!(failing_writes.get(), 0);
+        assert_eq!(writes.get(), 0);
+    }
+}
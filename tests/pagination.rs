@@ -0,0 +1,76 @@
+// Tests of resuming directory iteration from a cursor.
+
+extern crate basic_io;
+extern crate fatfs;
+
+use basic_io::Cursor;
+use fatfs::{Dir, DirCursor, FatType, FileSystem, FormatOptions, FsOptions, ReadWriteSeek};
+
+const MB: usize = 1024 * 1024;
+
+fn names<IO: ReadWriteSeek>(dir: &Dir<IO>) -> Vec<String> {
+    dir.iter().map(|e| e.unwrap().file_name().to_string()).collect()
+}
+
+// Lists directory in pages of `page_size` entries resuming each page from a cursor
+fn paged_names<IO: ReadWriteSeek>(dir: &Dir<IO>, page_size: usize) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut cursor = DirCursor::from_entry_index(0);
+    loop {
+        let iter = dir.iter_from(cursor).unwrap();
+        let page: Vec<_> = iter.take(page_size).map(|e| e.unwrap()).collect();
+        result.extend(page.iter().map(|e| e.file_name().to_string()));
+        match page.last() {
+            Some(e) if page.len() == page_size => cursor = e.cursor(),
+            _ => return result,
+        }
+    }
+}
+
+#[test]
+fn paged_listing_matches_full_listing() {
+    let mut data = vec![0u8; 4 * MB];
+    let mut cursor = Cursor::new(&mut data[..]);
+    let options = FormatOptions::new().fat_type(FatType::Fat16).bytes_per_cluster(512);
+    fatfs::format_volume(&mut cursor, options).unwrap();
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let mut sub = root.create_dir("sub").unwrap();
+    for i in 0..40 {
+        root.create_file(&format!("r{}.txt", i)).unwrap();
+        // more than one cluster of entries in subdirectory
+        sub.create_file(&format!("s{}.txt", i)).unwrap();
+    }
+    root.remove("r5.txt").unwrap();
+    sub.remove("s20.txt").unwrap();
+
+    for dir in [&root, &sub].iter() {
+        let all = names(dir);
+        for &page_size in [1, 7, 16, 100].iter() {
+            assert_eq!(paged_names(dir, page_size), all);
+        }
+    }
+}
+
+#[test]
+fn iteration_starts_at_entry_index() {
+    let mut data = vec![0u8; MB];
+    let mut cursor = Cursor::new(&mut data[..]);
+    fatfs::format_volume(&mut cursor, FormatOptions::new().fat_type(FatType::Fat12)).unwrap();
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let mut sub = root.create_dir("sub").unwrap();
+    sub.create_file("a.txt").unwrap();
+    sub.create_file("b.txt").unwrap();
+
+    let skipped = sub.iter_from(DirCursor::from_entry_index(2)).unwrap();
+    let names: Vec<_> = skipped.map(|e| e.unwrap().short_file_name().to_string()).collect();
+    assert_eq!(names, ["A.TXT", "B.TXT"]);
+    let b = sub.iter().nth(3).unwrap().unwrap();
+    assert_eq!(b.cursor().entry_index(), 4);
+    for &index in [4, 1000].iter() {
+        let cursor = DirCursor::from_entry_index(index);
+        assert_eq!(sub.iter_from(cursor).unwrap().count(), 0);
+        assert_eq!(root.iter_from(cursor).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,79 @@
+// Tests of cluster chain validation when opening files in paranoid mode.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, ErrorKind, Write};
+use fatfs::{FatType, FileSystem, FsOptions};
+
+const CLUSTER_SIZE: usize = 512;
+const NAME: &[u8; 11] = b"A       BIN";
+
+fn create_volume() -> Vec<u8> {
+    let mut data = common::small_volume(FatType::Fat16);
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        let mut file = fs.root_dir().create_file("a.bin").unwrap();
+        file.write_all(&[1; CLUSTER_SIZE * 3]).unwrap();
+    }
+    data
+}
+
+fn entry_offset(data: &[u8]) -> usize {
+    data.chunks(32).position(|e| &e[..11] == NAME).unwrap() * 32
+}
+
+// Offset of FAT entry of given cluster in the first FAT
+fn fat_entry_offset(data: &[u8], cluster: u16) -> usize {
+    let reserved_sectors = u16::from_le_bytes([data[14], data[15]]) as usize;
+    reserved_sectors * 512 + cluster as usize * 2
+}
+
+fn open_error(data: &mut [u8], paranoid: bool) -> Option<ErrorKind> {
+    let mut cursor = Cursor::new(data);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new().paranoid(paranoid)).unwrap();
+    let r = fs.root_dir().open_file("a.bin");
+    r.err().map(|err| err.kind())
+}
+
+#[test]
+fn valid_file_opens() {
+    let mut data = create_volume();
+    assert_eq!(open_error(&mut data, true), None);
+}
+
+#[test]
+fn size_mismatch_is_reported() {
+    let mut data = create_volume();
+    let entry = entry_offset(&data);
+    data[entry + 28..entry + 32].copy_from_slice(&(CLUSTER_SIZE as u32 * 5).to_le_bytes());
+    assert_eq!(open_error(&mut data, false), None);
+    assert_eq!(open_error(&mut data, true), Some(ErrorKind::CorruptedFile));
+    data[entry + 28..entry + 32].copy_from_slice(&(CLUSTER_SIZE as u32 * 2).to_le_bytes());
+    assert_eq!(open_error(&mut data, true), Some(ErrorKind::CorruptedFile));
+}
+
+#[test]
+fn chain_loop_is_reported() {
+    let mut data = create_volume();
+    let entry = entry_offset(&data);
+    let first = u16::from_le_bytes([data[entry + 26], data[entry + 27]]);
+    // last cluster points back to the first one
+    let last = fat_entry_offset(&data, first + 2);
+    data[last..last + 2].copy_from_slice(&first.to_le_bytes());
+    assert_eq!(open_error(&mut data, false), None);
+    assert_eq!(open_error(&mut data, true), Some(ErrorKind::CorruptedFile));
+}
+
+#[test]
+fn free_cluster_in_chain_is_reported() {
+    let mut data = create_volume();
+    let entry = entry_offset(&data);
+    let first = u16::from_le_bytes([data[entry + 26], data[entry + 27]]);
+    let second = fat_entry_offset(&data, first + 1);
+    data[second..second + 2].copy_from_slice(&[0, 0]);
+    assert_eq!(open_error(&mut data, true), Some(ErrorKind::CorruptedFile));
+}
@@ -0,0 +1,95 @@
+// Tests of poisoning the filesystem after failed metadata writes.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{ErrorKind, Seek, SeekFrom, Write};
+use fatfs::{ClusterAlignedWriter, FatType, FileSystem, FsOptions};
+use common::FaultyDisk;
+
+fn create_disk() -> FaultyDisk {
+    FaultyDisk::new(common::small_volume(FatType::Fat16))
+}
+
+#[test]
+fn failed_metadata_write_poisons_filesystem() {
+    let mut disk = create_disk();
+    let writes_fail = disk.writes_fail.clone();
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    root.create_file("A.TXT").unwrap();
+    assert!(!fs.is_poisoned());
+    writes_fail.set(true);
+    assert!(root.create_file("B.TXT").is_err());
+    assert!(fs.is_poisoned());
+    writes_fail.set(false);
+    // device works again but filesystem refuses to modify possibly inconsistent structures
+    let err = root.create_file("C.TXT").err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::FilesystemPoisoned);
+    let err = root.create_dir("D").err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::FilesystemPoisoned);
+    // reading still works
+    assert!(root.open_file("A.TXT").is_ok());
+}
+
+#[test]
+fn unmount_of_poisoned_filesystem_fails_and_sets_io_error_flag() {
+    let mut disk = create_disk();
+    let writes_fail = disk.writes_fail.clone();
+    {
+        let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+        writes_fail.set(true);
+        assert!(fs.root_dir().create_file("A.TXT").is_err());
+        writes_fail.set(false);
+        assert_eq!(fs.unmount().err().unwrap().kind(), ErrorKind::FilesystemPoisoned);
+    }
+    disk.seek(SeekFrom::Start(0)).unwrap();
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    assert!(!fs.is_poisoned());
+    assert!(fs.read_status_flags().unwrap().io_error);
+}
+
+#[test]
+fn poison_can_be_cleared_after_repair() {
+    let mut disk = create_disk();
+    let writes_fail = disk.writes_fail.clone();
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    writes_fail.set(true);
+    assert!(root.create_file("A.TXT").is_err());
+    writes_fail.set(false);
+    assert!(fs.is_poisoned());
+    // device can still be accessed for repair
+    fs.with_disk(|_| Ok(())).unwrap();
+    fs.clear_poison();
+    assert!(!fs.is_poisoned());
+    let mut file = root.create_file("B.TXT").unwrap();
+    file.write_all(b"hello").unwrap();
+    file.flush().unwrap();
+    drop(file);
+    drop(root);
+    fs.unmount().unwrap();
+}
+
+#[test]
+fn dropping_dirty_file_on_poisoned_filesystem_does_not_panic() {
+    let mut disk = create_disk();
+    let writes_fail = disk.writes_fail.clone();
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let mut file = root.create_file("A.TXT").unwrap();
+    file.write_all(&[1; 512]).unwrap();
+    let mut writer = ClusterAlignedWriter::new(root.create_file("B.TXT").unwrap()).unwrap();
+    writer.write_all(b"buffered").unwrap();
+    writes_fail.set(true);
+    // allocation of the next cluster fails
+    assert!(file.write_all(&[2; 512]).is_err());
+    writes_fail.set(false);
+    assert!(fs.is_poisoned());
+    assert_eq!(file.flush().err().unwrap().kind(), ErrorKind::FilesystemPoisoned);
+    // flush errors are only logged on drop
+    drop(file);
+    drop(writer);
+}
@@ -0,0 +1,126 @@
+// Tests of strict and relaxed mount profiles.
+
+extern crate basic_io;
+extern crate fatfs;
+
+use std::fs;
+use std::path::Path;
+
+use basic_io::{Cursor, ErrorKind, Read, Seek, SeekFrom};
+use fatfs::{Cluster, FileSystem, FsOptions, MountProfile};
+
+fn read_image() -> Vec<u8> {
+    fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("fat32.img")).unwrap()
+}
+
+fn options(profile: MountProfile) -> FsOptions {
+    FsOptions::new().profile(profile)
+}
+
+fn mount_error(data: &mut [u8], profile: MountProfile) -> Option<ErrorKind> {
+    let mut disk = Cursor::new(data);
+    FileSystem::new(&mut disk, options(profile)).err().map(|err| err.kind())
+}
+
+// Offset of short name entry with given raw name
+fn entry_offset(data: &[u8], raw_name: &[u8; 11]) -> usize {
+    data.chunks(32).position(|e| &e[..11] == raw_name).unwrap() * 32
+}
+
+// Offset of FAT entry of given cluster in the first FAT
+fn fat_entry_offset(data: &[u8], cluster: u32) -> usize {
+    let reserved_sectors = u16::from_le_bytes([data[14], data[15]]) as usize;
+    reserved_sectors * 512 + cluster as usize * 4
+}
+
+#[test]
+fn valid_image_mounts_in_both_profiles() {
+    let mut data = read_image();
+    for &profile in &[MountProfile::Strict, MountProfile::Relaxed] {
+        let mut disk = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut disk, options(profile)).unwrap();
+        assert_eq!(fs.root_dir().iter().count(), 4);
+        let mut buf = Vec::new();
+        let mut file = fs.root_dir().open_file("very-long-dir-name/very-long-file-name.txt");
+        let file = file.as_mut().ok().unwrap();
+        let mut chunk = [0; 100];
+        loop {
+            let n = file.read(&mut chunk).unwrap();
+            if n == 0 {
+                break;
+            }
+            buf.extend_from_slice(&chunk[..n]);
+        }
+        assert_eq!(buf.len(), 14);
+    }
+}
+
+#[test]
+fn boot_sector_deviations_are_rejected_only_in_strict_profile() {
+    let corruptions: &[fn(&mut Vec<u8>)] = &[
+        // invalid jump instruction
+        |data| data[0] = 0,
+        // reserved field in FAT32 extended BPB
+        |data| data[60] = 1,
+        // unknown filesystem version
+        |data| data[42] = 1,
+        // invalid media descriptor
+        |data| data[21] = 0x12,
+        // FSInfo signature
+        |data| data[512] = 0,
+    ];
+    for (i, corrupt) in corruptions.iter().enumerate() {
+        let mut data = read_image();
+        corrupt(&mut data);
+        assert_eq!(mount_error(&mut data, MountProfile::Relaxed), None, "corruption {}", i);
+        assert_eq!(
+            mount_error(&mut data, MountProfile::Strict),
+            Some(ErrorKind::Other),
+            "corruption {}",
+            i
+        );
+    }
+}
+
+#[test]
+fn reserved_fat_entries_mismatch_is_rejected_only_in_strict_profile() {
+    let mut data = read_image();
+    // FAT[0] does not contain media descriptor from BPB
+    let fat_0 = fat_entry_offset(&data, 0);
+    data[fat_0] = 0xF0;
+    assert_eq!(mount_error(&mut data, MountProfile::Relaxed), None);
+    assert_eq!(mount_error(&mut data, MountProfile::Strict), Some(ErrorKind::Other));
+}
+
+#[test]
+fn free_cluster_in_chain_is_an_error_in_strict_profile() {
+    let mut data = read_image();
+    let entry = entry_offset(&data, b"LONG    TXT");
+    let first = u32::from(u16::from_le_bytes([data[entry + 26], data[entry + 27]]));
+    let fat_entry = fat_entry_offset(&data, first);
+    data[fat_entry..fat_entry + 4].copy_from_slice(&[0; 4]);
+    let chain = |data: &mut [u8], profile| {
+        let mut disk = Cursor::new(data);
+        let fs = FileSystem::new(&mut disk, options(profile)).unwrap();
+        let r: Result<Vec<Cluster>, _> = fs.cluster_iter(Cluster(first)).collect();
+        r.map_err(|err| err.kind())
+    };
+    // relaxed profile treats free cluster as end of chain
+    assert_eq!(chain(&mut data, MountProfile::Relaxed), Ok(vec![]));
+    assert_eq!(chain(&mut data, MountProfile::Strict), Err(ErrorKind::CorruptedFile));
+}
+
+#[test]
+fn orphaned_lfn_entries_are_an_error_in_strict_profile() {
+    let mut data = read_image();
+    let entry = entry_offset(&data, b"LONG    TXT");
+    data[entry - 32 + 13] ^= 0xFF;
+    let mut disk = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut disk, options(MountProfile::Relaxed)).unwrap();
+    assert!(fs.root_dir().iter().all(|r| r.is_ok()));
+    drop(fs);
+    disk.seek(SeekFrom::Start(0)).unwrap();
+    let fs = FileSystem::new(&mut disk, options(MountProfile::Strict)).unwrap();
+    let err = fs.root_dir().iter().next().unwrap().err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::CorruptedFile);
+}
@@ -0,0 +1,79 @@
+// Tests of the entry modification API for recovery tools (enabled by `recovery` feature).
+#![cfg(feature = "recovery")]
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, ErrorKind, Read, Write};
+use fatfs::{CheckIssueKind, CheckOptions, Cluster, Dir, DirEntry, FatType, FileSystem, FsOptions,
+            ReadWriteSeek};
+
+fn entry<'a, IO: ReadWriteSeek>(dir: &Dir<'a, IO>, name: &str) -> DirEntry<'a, IO> {
+    dir.iter().map(|e| e.unwrap()).find(|e| e.file_name() == name).unwrap()
+}
+
+fn read_all<IO: ReadWriteSeek>(entry: &DirEntry<IO>) -> Vec<u8> {
+    let mut buf = vec![0; entry.len() as usize];
+    let mut file = entry.to_file();
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(file.read(&mut [0]).unwrap(), 0);
+    buf
+}
+
+#[test]
+fn relink_points_entry_to_validated_chain() {
+    let mut data = common::small_volume(FatType::Fat16);
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let mut file = root.create_file("DATA.BIN").unwrap();
+    file.write_all(&[7; 1000]).unwrap();
+    let first_cluster = Some(file.clusters().next().unwrap().unwrap());
+    drop(file);
+    root.create_file("LOST.BIN").unwrap().write_all(b"lost").unwrap();
+    root.create_dir("DIR").unwrap();
+
+    let mut lost = entry(&root, "LOST.BIN");
+    let invalid = [
+        // chain has 2 clusters
+        (first_cluster, 300),
+        (first_cluster, 1100),
+        // free cluster
+        (Some(Cluster(5000)), 512),
+        // reserved cluster number
+        (Some(Cluster(1)), 512),
+    ];
+    for &(cluster, size) in invalid.iter() {
+        let err = lost.relink(cluster, size).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput, "{:?} {}", cluster, size);
+    }
+    // entry is not modified by rejected calls
+    assert_eq!(read_all(&entry(&root, "LOST.BIN")), b"lost");
+
+    lost.relink(first_cluster, 900).unwrap();
+    assert_eq!(lost.len(), 900);
+    let relinked = entry(&root, "LOST.BIN");
+    assert_eq!(relinked.len(), 900);
+    assert_eq!(relinked.to_file().clusters().next().unwrap().ok(), first_cluster);
+    assert_eq!(read_all(&relinked), vec![7; 900]);
+
+    // empty file has no chain
+    lost.relink(None, 0).unwrap();
+    assert!(entry(&root, "LOST.BIN").to_file().clusters().next().is_none());
+    assert_eq!(lost.relink(None, 1).err().unwrap().kind(), ErrorKind::InvalidInput);
+
+    // directories must have size 0 and a chain
+    let mut dir = entry(&root, "DIR");
+    assert_eq!(dir.relink(first_cluster, 512).err().unwrap().kind(), ErrorKind::InvalidInput);
+    assert_eq!(dir.relink(None, 0).err().unwrap().kind(), ErrorKind::InvalidInput);
+    // the old chain of LOST.BIN is leaked but entries are consistent
+    let summary = fs.check(CheckOptions::new(), &mut |issue| panic!("{:?}", issue)).unwrap();
+    assert!(summary.is_clean());
+    dir.relink(first_cluster, 0).unwrap();
+    // directory points to file data now
+    let mut issues = Vec::new();
+    fs.check(CheckOptions::new(), &mut |issue| issues.push(issue.kind)).unwrap();
+    assert!(issues.contains(&CheckIssueKind::MissingDotEntries));
+}
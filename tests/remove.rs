@@ -0,0 +1,166 @@
+// Tests of typed file and directory removal.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, ErrorKind, Read, Write};
+use fatfs::{CheckOptions, FatType, FileSystem, FormatOptions, FsOptions, ReadWriteSeek};
+use common::MB;
+
+fn create_volume() -> Vec<u8> {
+    let mut data = common::format_image(MB, FormatOptions::new().fat_type(FatType::Fat12));
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        let mut root = fs.root_dir();
+        root.create_file("file.txt").unwrap().write_all(b"data").unwrap();
+        root.create_dir("empty").unwrap();
+        root.create_dir("full").unwrap().create_file("inner.txt").unwrap();
+    }
+    data
+}
+
+fn exists<IO: ReadWriteSeek>(fs: &FileSystem<IO>, name: &str) -> bool {
+    fs.root_dir().iter().any(|e| e.unwrap().eq_name(name))
+}
+
+#[test]
+fn remove_file_checks_type() {
+    let mut data = create_volume();
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let free = fs.stats().unwrap().free_clusters;
+    let mut root = fs.root_dir();
+    assert_eq!(root.remove_file("empty").unwrap_err().kind(), ErrorKind::IsADirectory);
+    assert_eq!(root.remove_file("missing").unwrap_err().kind(), ErrorKind::NotFound);
+    let err = root.remove_file("file.txt/inner.txt").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotADirectory);
+    assert!(exists(&fs, "empty"));
+    root.remove_file("file.txt").unwrap();
+    assert!(!exists(&fs, "file.txt"));
+    root.remove_file("full/inner.txt").unwrap();
+    assert_eq!(fs.stats().unwrap().free_clusters, free + 1);
+    fs.verify_invariants().unwrap();
+}
+
+#[test]
+fn remove_dir_checks_type() {
+    let mut data = create_volume();
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    assert_eq!(root.remove_dir("file.txt").unwrap_err().kind(), ErrorKind::NotADirectory);
+    assert_eq!(root.remove_dir("full").unwrap_err().kind(), ErrorKind::DirectoryNotEmpty);
+    assert_eq!(root.remove_dir("full/inner.txt").unwrap_err().kind(), ErrorKind::NotADirectory);
+    assert!(exists(&fs, "file.txt"));
+    root.remove_dir("empty").unwrap();
+    assert!(!exists(&fs, "empty"));
+    fs.verify_invariants().unwrap();
+}
+
+#[test]
+fn remove_accepts_both_types() {
+    let mut data = create_volume();
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    assert_eq!(root.remove("full").unwrap_err().kind(), ErrorKind::DirectoryNotEmpty);
+    root.remove("file.txt").unwrap();
+    root.remove("full/inner.txt").unwrap();
+    root.remove("full").unwrap();
+    root.remove("empty").unwrap();
+    assert_eq!(fs.root_dir().iter().count(), 0);
+    fs.verify_invariants().unwrap();
+}
+
+#[test]
+fn handle_of_removed_file_does_not_overwrite_new_file() {
+    let mut data = create_volume();
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let mut old = root.open_file("file.txt").unwrap();
+    old.write_all(b" not flushed").unwrap();
+    root.remove_file("file.txt").unwrap();
+    // new file reuses the directory entry slot and the freed cluster
+    root.create_file("file.txt").unwrap().write_all(b"new").unwrap();
+    assert_eq!(old.flush().unwrap_err().kind(), ErrorKind::NotFound);
+    assert_eq!(old.write(b"more").unwrap_err().kind(), ErrorKind::NotFound);
+    assert_eq!(old.read(&mut [0u8; 4]).unwrap_err().kind(), ErrorKind::NotFound);
+    drop(old);
+    let mut file = root.open_file("file.txt").unwrap();
+    let mut buf = [0u8; 3];
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"new");
+    assert_eq!(file.read(&mut buf).unwrap(), 0);
+    fs.verify_invariants().unwrap();
+}
+
+#[test]
+fn retain_removes_rejected_files() {
+    let mut data = create_volume();
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let free_before = fs.stats().unwrap().free_clusters;
+    for i in 0..30 {
+        root.create_file(&format!("log{}.old", i)).unwrap().write_all(&vec![1; i * 300]).unwrap();
+    }
+    root.create_dir("logs.old").unwrap();
+    // freed clusters are accounted for in a scan in progress
+    assert_eq!(fs.scan_free_space_incremental(40).unwrap(), None);
+
+    let mut seen = Vec::new();
+    let removed = root
+        .retain(&mut |e| {
+            seen.push(e.file_name().to_string());
+            !e.file_name().to_string().ends_with(".OLD")
+        })
+        .unwrap();
+    assert_eq!(removed, 30);
+    // directories are never passed to the predicate
+    assert_eq!(seen.len(), 31);
+    assert!(!seen.iter().any(|name| name == "EMPTY" || name == "LOGS.OLD"));
+    assert_eq!(fs.stats().unwrap().free_clusters, free_before - 1);
+    // finished scan replaces the free cluster count
+    while fs.scan_free_space_incremental(1000).unwrap().is_none() {}
+    assert_eq!(fs.stats().unwrap().free_clusters, free_before - 1);
+    let names: Vec<_> = root.iter().map(|e| e.unwrap().file_name().to_string()).collect();
+    assert_eq!(names, ["FILE.TXT", "EMPTY", "FULL", "LOGS.OLD"]);
+    assert_eq!(root.retain(&mut |_| true).unwrap(), 0);
+    drop(root);
+    let summary = fs.check(CheckOptions::new(), &mut |issue| panic!("{:?}", issue)).unwrap();
+    assert!(summary.is_clean());
+}
+
+#[test]
+fn directories_in_use_cannot_be_removed_or_renamed() {
+    let mut data = create_volume();
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    // directory opened by a handle
+    let empty = root.open_dir("empty").unwrap();
+    assert_eq!(root.remove_dir("empty").unwrap_err().kind(), ErrorKind::Busy);
+    assert_eq!(root.remove("empty").unwrap_err().kind(), ErrorKind::Busy);
+    assert_eq!(root.rename("empty", &root, "other").unwrap_err().kind(), ErrorKind::Busy);
+    // cloned handle keeps the directory open
+    let clone = empty.clone();
+    drop(empty);
+    assert_eq!(root.remove_dir("empty").unwrap_err().kind(), ErrorKind::Busy);
+    drop(clone);
+    root.rename("empty", &root, "other").unwrap();
+    root.remove_dir("other").unwrap();
+
+    // open file deep inside the directory
+    root.create_dir("full/sub").unwrap();
+    let file = root.create_file("full/sub/deep.txt").unwrap();
+    assert_eq!(root.rename("full", &root, "moved").unwrap_err().kind(), ErrorKind::Busy);
+    // open files themselves can be renamed and removed
+    root.rename("full/sub/deep.txt", &root, "full/sub/x.txt").unwrap();
+    drop(file);
+    root.rename("full", &root, "moved").unwrap();
+    assert!(exists(&fs, "moved"));
+}
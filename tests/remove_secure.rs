@@ -0,0 +1,49 @@
+// Tests of removing files with their data overwritten.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, ErrorKind, Write};
+use fatfs::{FatType, FileSystem, FsOptions};
+
+const SECRET: &[u8] = b"top secret payload";
+
+fn contains(data: &[u8], needle: &[u8]) -> bool {
+    data.windows(needle.len()).any(|w| w == needle)
+}
+
+#[test]
+fn data_and_name_are_overwritten() {
+    let mut data = common::small_volume(FatType::Fat16);
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        let mut root = fs.root_dir();
+        let mut sub = root.create_dir("SUB").unwrap();
+        let mut file = sub.create_file("SECRET.TXT").unwrap();
+        for _ in 0..100 {
+            file.write_all(SECRET).unwrap();
+        }
+        drop(file);
+        let free_clusters = fs.stats().unwrap().free_clusters;
+        root.remove_secure("SUB/SECRET.TXT", 3).unwrap();
+        assert_eq!(fs.stats().unwrap().free_clusters, free_clusters + 4);
+        assert_eq!(root.open_file("SUB/SECRET.TXT").err().unwrap().kind(), ErrorKind::NotFound);
+        fs.verify_invariants().unwrap();
+    }
+    assert!(!contains(&data, SECRET));
+    assert!(!contains(&data, b"ECRET  TXT"));
+}
+
+#[test]
+fn directories_are_rejected() {
+    let mut data = common::small_volume(FatType::Fat16);
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    root.create_dir("SUB").unwrap();
+    assert_eq!(root.remove_secure("SUB", 1).err().unwrap().kind(), ErrorKind::IsADirectory);
+    root.open_dir("SUB").unwrap();
+}
@@ -0,0 +1,196 @@
+// Tests of renaming and moving files and directories.
+
+extern crate basic_io;
+extern crate fatfs;
+
+use basic_io::{Cursor, Read, Write};
+use fatfs::{CheckOptions, Dir, FatType, FileSystem, FormatOptions, FsOptions, ReadWriteSeek};
+
+const VOLUME_SIZE: usize = 4 * 1024 * 1024;
+
+fn with_fs<F: FnOnce(&FileSystem<&mut Cursor<&mut [u8]>>)>(f: F) {
+    let mut data = vec![0u8; VOLUME_SIZE];
+    let mut disk = Cursor::new(&mut data[..]);
+    fatfs::format_volume(&mut disk, FormatOptions::new()).unwrap();
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    f(&fs);
+    fs.verify_invariants().unwrap();
+}
+
+fn read_to_vec<IO: ReadWriteSeek>(dir: &mut Dir<IO>, path: &str) -> Vec<u8> {
+    let mut file = dir.open_file(path).unwrap();
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 512];
+    loop {
+        let n = file.read(&mut chunk).unwrap();
+        if n == 0 {
+            return buf;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+}
+
+fn names<IO: ReadWriteSeek>(dir: &Dir<IO>) -> Vec<String> {
+    dir.iter().map(|r| r.unwrap().file_name().to_string()).collect()
+}
+
+#[test]
+fn rename_file_in_same_directory() {
+    with_fs(|fs| {
+        let mut root = fs.root_dir();
+        root.create_file("a.txt").unwrap().write_all(b"hello").unwrap();
+        root.rename("a.txt", &root, "b.txt").unwrap();
+        assert_eq!(names(&root), ["B.TXT"]);
+        assert_eq!(read_to_vec(&mut root, "b.txt"), b"hello");
+    });
+}
+
+#[test]
+fn move_file_between_directories() {
+    with_fs(|fs| {
+        let mut root = fs.root_dir();
+        let src = root.create_dir("src").unwrap();
+        let dst = root.create_dir("dst").unwrap();
+        root.create_file("src/a.txt").unwrap().write_all(&[7u8; 3000]).unwrap();
+        src.rename("a.txt", &dst, "c.txt").unwrap();
+        assert_eq!(names(&src), [".", ".."]);
+        assert_eq!(names(&dst), [".", "..", "C.TXT"]);
+        assert_eq!(read_to_vec(&mut root, "dst/c.txt"), vec![7u8; 3000]);
+    });
+}
+
+#[test]
+fn move_directory_updates_dotdot_entry() {
+    with_fs(|fs| {
+        let mut root = fs.root_dir();
+        root.create_dir("a").unwrap();
+        root.create_dir("a/b").unwrap();
+        root.create_file("a/b/f.txt").unwrap().write_all(b"data").unwrap();
+        root.create_dir("x").unwrap();
+        root.rename("a/b", &root, "x/b").unwrap();
+        fs.verify_invariants().unwrap();
+        let moved = root.open_dir("x/b").unwrap();
+        assert_eq!(names(&moved), [".", "..", "F.TXT"]);
+        drop(moved);
+        assert_eq!(read_to_vec(&mut root, "x/b/../b/f.txt"), b"data");
+        // move back to root
+        root.rename("x/b", &root, "b").unwrap();
+        fs.verify_invariants().unwrap();
+        assert_eq!(read_to_vec(&mut root, "b/f.txt"), b"data");
+    });
+}
+
+#[test]
+fn move_directory_to_fat32_root_dir() {
+    let mut data = vec![0u8; 40 * 1024 * 1024];
+    let mut disk = Cursor::new(&mut data[..]);
+    fatfs::format_volume(&mut disk, FormatOptions::new().fat_type(FatType::Fat32)).unwrap();
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    {
+        let mut root = fs.root_dir();
+        root.create_dir("a").unwrap();
+        root.create_dir("a/b").unwrap();
+        root.create_file("a/b/f.txt").unwrap().write_all(b"data").unwrap();
+        root.rename("a/b", &root, "b").unwrap();
+        assert_eq!(read_to_vec(&mut root, "b/../b/f.txt"), b"data");
+    }
+    fs.verify_invariants().unwrap();
+    // ".." entry of a directory placed in root dir must contain zero even if root dir has a cluster
+    let mut issues = 0;
+    fs.check(CheckOptions::new(), &mut |_| issues += 1).unwrap();
+    assert_eq!(issues, 0);
+}
+
+#[test]
+fn rename_errors() {
+    with_fs(|fs| {
+        let mut root = fs.root_dir();
+        root.create_file("a.txt").unwrap();
+        root.create_file("b.txt").unwrap();
+        root.create_dir("d").unwrap();
+        root.create_dir("d/e").unwrap();
+        let err = root.rename("a.txt", &root, "b.txt").unwrap_err();
+        assert_eq!(err.kind(), basic_io::ErrorKind::AlreadyExists);
+        let err = root.rename("missing.txt", &root, "c.txt").unwrap_err();
+        assert_eq!(err.kind(), basic_io::ErrorKind::NotFound);
+        let err = root.rename("d", &root, "d/e/d").unwrap_err();
+        assert_eq!(err.kind(), basic_io::ErrorKind::InvalidInput);
+        let err = root.rename("d", &root, "d/d").unwrap_err();
+        assert_eq!(err.kind(), basic_io::ErrorKind::InvalidInput);
+        // renaming to the same name does nothing
+        root.rename("a.txt", &root, "A.TXT").unwrap();
+        assert_eq!(names(&root), ["A.TXT", "B.TXT", "D"]);
+    });
+}
+
+#[test]
+fn rename_to_name_matching_ignoring_case() {
+    with_fs(|fs| {
+        let mut root = fs.root_dir();
+        let mut file = root.create_file("Meeting notes.txt").unwrap();
+        file.write_all(b"hello").unwrap();
+        let offset_range = root.iter().next().unwrap().unwrap().offset_range();
+        // the same number of LFN entries - name is rewritten in place
+        root.rename("meeting notes.txt", &root, "Meeting Notes.txt").unwrap();
+        assert_eq!(names(&root), ["Meeting Notes.txt"]);
+        assert_eq!(root.iter().next().unwrap().unwrap().offset_range(), offset_range);
+        // name stored in the short name entry needs no LFN entries - entry is written again
+        root.rename("meeting notes.txt", &root, "meetin~1.txt").unwrap();
+        assert_eq!(names(&root), ["MEETIN~1.TXT"]);
+        assert_eq!(root.iter().next().unwrap().unwrap().slot_count(), 1);
+        // open file follows the entry
+        file.write_all(b" world").unwrap();
+        file.flush().unwrap();
+        drop(file);
+        assert_eq!(read_to_vec(&mut root, "meetin~1.txt"), b"hello world");
+        // short names are upper case - renaming to a lower case variant does nothing
+        root.rename("meetin~1.txt", &root, "meetin~1.txt").unwrap();
+        assert_eq!(names(&root), ["MEETIN~1.TXT"]);
+    });
+}
+
+#[test]
+fn open_file_follows_its_entry_when_moved() {
+    with_fs(|fs| {
+        let mut root = fs.root_dir();
+        let dst = root.create_dir("dst").unwrap();
+        let mut file = root.create_file("a.txt").unwrap();
+        file.write_all(b"hello").unwrap();
+        root.rename("a.txt", &dst, "b.txt").unwrap();
+        file.write_all(b" world").unwrap();
+        file.flush().unwrap();
+        drop(file);
+        assert_eq!(names(&root), ["DST"]);
+        assert_eq!(read_to_vec(&mut root, "dst/b.txt"), b"hello world");
+    });
+}
+
+#[test]
+fn move_resolves_short_name_collision_in_destination() {
+    with_fs(|fs| {
+        let mut root = fs.root_dir();
+        let dst = root.create_dir("dst").unwrap();
+        root.create_file("dst/longfile2.txt").unwrap();
+        root.create_file("a.txt").unwrap().write_all(b"moved").unwrap();
+        root.rename("a.txt", &dst, "longfile1.txt").unwrap();
+        assert_eq!(names(&dst), [".", "..", "longfile2.txt", "longfile1.txt"]);
+        let short_names: Vec<String> =
+            dst.iter().map(|r| r.unwrap().short_file_name().to_string()).collect();
+        assert_eq!(short_names, [".", "..", "LONGFILE.TXT", "LONGFI~1.TXT"]);
+        assert_eq!(read_to_vec(&mut root, "dst/longfile1.txt"), b"moved");
+        assert_eq!(read_to_vec(&mut root, "dst/longfi~1.txt"), b"moved");
+    });
+}
+
+#[test]
+fn file_in_path_is_not_a_directory() {
+    with_fs(|fs| {
+        let mut root = fs.root_dir();
+        root.create_file("a.txt").unwrap();
+        root.create_file("b.txt").unwrap();
+        let err = root.rename("a.txt/x", &root, "c.txt").unwrap_err();
+        assert_eq!(err.kind(), basic_io::ErrorKind::NotADirectory);
+        let err = root.rename("a.txt", &root, "b.txt/a.txt").unwrap_err();
+        assert_eq!(err.kind(), basic_io::ErrorKind::NotADirectory);
+    });
+}
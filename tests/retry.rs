@@ -0,0 +1,163 @@
+// Tests of retrying failed device accesses.
+
+extern crate basic_io;
+extern crate fatfs;
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use basic_io::{Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
+use fatfs::{FatType, FileSystem, FormatOptions, FsOptions, RetryBackoff, RetryPolicy};
+
+const VOLUME_SIZE: usize = 1024 * 1024;
+
+// Device failing every `fail_every`-th read and write. A failed access moves the position to
+// simulate a device left in unknown state. All writes fail while `writes_fail` is set.
+struct FlakyDisk {
+    data: Vec<u8>,
+    pos: u64,
+    accesses: u32,
+    fail_every: u32,
+    writes_fail: Rc<Cell<bool>>,
+}
+
+impl FlakyDisk {
+    fn new(fail_every: u32) -> Self {
+        Self::with_format(fail_every, VOLUME_SIZE, FormatOptions::new())
+    }
+
+    fn with_format(fail_every: u32, size: usize, format_options: FormatOptions) -> Self {
+        let mut data = vec![0u8; size];
+        {
+            let mut cursor = Cursor::new(&mut data[..]);
+            fatfs::format_volume(&mut cursor, format_options).unwrap();
+        }
+        FlakyDisk { data, pos: 0, accesses: 0, fail_every, writes_fail: Rc::new(Cell::new(false)) }
+    }
+
+    fn fail(&mut self) -> basic_io::Result<()> {
+        self.accesses += 1;
+        if self.fail_every != 0 && self.accesses.is_multiple_of(self.fail_every) {
+            self.pos = 0;
+            return Err(basic_io::Error::new(ErrorKind::Other, "transient error"));
+        }
+        Ok(())
+    }
+}
+
+impl Read for FlakyDisk {
+    fn read(&mut self, buf: &mut [u8]) -> basic_io::Result<usize> {
+        self.fail()?;
+        let start = self.pos as usize;
+        let n = buf.len().min(self.data.len() - start);
+        buf[..n].copy_from_slice(&self.data[start..start + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Write for FlakyDisk {
+    fn write(&mut self, buf: &[u8]) -> basic_io::Result<usize> {
+        if self.writes_fail.get() {
+            return Err(basic_io::Error::new(ErrorKind::Other, "write error"));
+        }
+        self.fail()?;
+        let start = self.pos as usize;
+        let n = buf.len().min(self.data.len() - start);
+        self.data[start..start + n].copy_from_slice(&buf[..n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> basic_io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for FlakyDisk {
+    fn seek(&mut self, pos: SeekFrom) -> basic_io::Result<u64> {
+        self.pos = match pos {
+            SeekFrom::Start(n) => n,
+            SeekFrom::Current(n) => (self.pos as i64 + n) as u64,
+            SeekFrom::End(n) => (self.data.len() as i64 + n) as u64,
+        };
+        Ok(self.pos)
+    }
+}
+
+static BACKOFF_CALLS: AtomicU32 = AtomicU32::new(0);
+
+struct CountingBackoff;
+
+impl RetryBackoff for CountingBackoff {
+    fn backoff(&self, attempt: u32) {
+        assert_eq!(attempt, 1);
+        BACKOFF_CALLS.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn write_and_read_back(disk: &mut FlakyDisk, options: FsOptions) -> basic_io::Result<()> {
+    let fs = FileSystem::new(disk, options)?;
+    let content: Vec<u8> = (0..20000).map(|i| (i % 251) as u8).collect();
+    let mut dir = fs.root_dir().create_dir("dir")?;
+    dir.create_file("file.bin")?.write_all(&content)?;
+    let mut buf = Vec::new();
+    let mut file = fs.root_dir().open_file("dir/file.bin")?;
+    let mut chunk = [0u8; 1000];
+    loop {
+        let n = file.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    assert_eq!(buf, content);
+    fs.verify_invariants()
+}
+
+#[test]
+fn transient_errors_are_retried() {
+    let mut disk = FlakyDisk::new(5);
+    let policy = RetryPolicy::new(1).backoff(&CountingBackoff);
+    write_and_read_back(&mut disk, FsOptions::new().retry_policy(policy)).unwrap();
+    assert!(BACKOFF_CALLS.load(Ordering::SeqCst) > 0);
+}
+
+#[test]
+fn errors_are_returned_without_retry_policy() {
+    let mut disk = FlakyDisk::new(5);
+    let err = write_and_read_back(&mut disk, FsOptions::new()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Other);
+}
+
+fn io_error_flag(disk: &mut FlakyDisk) -> bool {
+    disk.seek(SeekFrom::Start(0)).unwrap();
+    let fs = FileSystem::new(disk, FsOptions::new()).unwrap();
+    fs.read_status_flags().unwrap().io_error
+}
+
+#[test]
+fn failed_write_sets_io_error_flag() {
+    let format_options = FormatOptions::new().fat_type(FatType::Fat16).bytes_per_cluster(512);
+    let mut disk = FlakyDisk::with_format(0, 4 * VOLUME_SIZE, format_options);
+    let writes_fail = disk.writes_fail.clone();
+    {
+        let fs = FileSystem::new(&mut disk, FsOptions::new().retry_policy(RetryPolicy::new(2)))
+            .unwrap();
+        fs.root_dir().create_file("ok.txt").unwrap();
+        assert!(!fs.read_status_flags().unwrap().io_error);
+        writes_fail.set(true);
+        assert!(fs.root_dir().create_file("fail.txt").is_err());
+        assert!(fs.read_status_flags().unwrap().io_error);
+        writes_fail.set(false);
+    }
+    assert!(io_error_flag(&mut disk));
+    {
+        disk.seek(SeekFrom::Start(0)).unwrap();
+        let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+        fs.clear_io_error().unwrap();
+        assert!(!fs.read_status_flags().unwrap().io_error);
+    }
+    assert!(!io_error_flag(&mut disk));
+}
@@ -0,0 +1,51 @@
+// Tests of root directory handles returned by `FileSystem::root_dir`.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::Cursor;
+use fatfs::{FatType, FileSystem, FormatOptions, FsOptions};
+use common::MB;
+
+fn names(fs: &FileSystem<&mut Cursor<&mut [u8]>>) -> Vec<String> {
+    fs.root_dir().iter().map(|e| e.unwrap().file_name().to_string()).collect()
+}
+
+#[test]
+fn fixed_root_region_holds_declared_entries() {
+    let volumes = [
+        common::format_image(MB, FormatOptions::new().fat_type(FatType::Fat12)),
+        common::small_volume(FatType::Fat16),
+    ];
+    for mut data in volumes.iter().cloned() {
+        let root_entries = u16::from_le_bytes([data[17], data[18]]) as usize;
+        let mut cursor = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        let mut root = fs.root_dir();
+        for i in 0..root_entries {
+            root.create_file(&format!("F{}", i)).unwrap();
+        }
+        assert!(fs.root_dir().create_file("LAST").is_err());
+        drop(root);
+        assert_eq!(names(&fs).len(), root_entries);
+    }
+}
+
+#[test]
+fn fat32_root_handles_see_grown_directory() {
+    let mut data = common::small_volume(FatType::Fat32);
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    // handle created before the root directory chain grows
+    let mut old_root = fs.root_dir();
+    let mut root = fs.root_dir();
+    // 512 byte clusters hold 16 entries
+    for i in 0..40 {
+        root.create_file(&format!("F{}", i)).unwrap();
+    }
+    assert_eq!(old_root.iter().count(), 40);
+    assert_eq!(names(&fs).len(), 40);
+    assert!(old_root.open_file("F39").is_ok());
+}
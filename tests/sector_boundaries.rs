@@ -0,0 +1,125 @@
+// Tests of accesses crossing sector boundaries.
+//
+// Block devices can only transfer whole sectors so the library must never issue an access that
+// starts inside a sector and ends in another one. FAT12 is the interesting case because its
+// 12-bit entries straddle sector boundaries (e.g. entry 341 occupies bytes 511 and 512 of FAT).
+
+extern crate basic_io;
+extern crate fatfs;
+
+use basic_io::{Cursor, Read, Seek, SeekFrom, Write};
+use fatfs::{FatType, FileSystem, FormatOptions, FsOptions};
+
+const SECTOR_SIZE: u64 = 512;
+const VOLUME_SIZE: usize = 1024 * 1024;
+
+// Device recording accesses which are not confined to a single sector. Accesses covering whole
+// sectors (e.g. file data) are allowed.
+struct SectorCheckingDisk {
+    data: Vec<u8>,
+    pos: u64,
+    violations: Vec<(u64, usize)>,
+}
+
+impl SectorCheckingDisk {
+    fn check_access(&mut self, len: usize) {
+        let first_sector = self.pos / SECTOR_SIZE;
+        let last_sector = (self.pos + len as u64).saturating_sub(1) / SECTOR_SIZE;
+        let aligned = self.pos.is_multiple_of(SECTOR_SIZE) && (len as u64).is_multiple_of(SECTOR_SIZE);
+        if len > 0 && first_sector != last_sector && !aligned {
+            self.violations.push((self.pos, len));
+        }
+    }
+}
+
+impl Read for SectorCheckingDisk {
+    fn read(&mut self, buf: &mut [u8]) -> basic_io::Result<usize> {
+        self.check_access(buf.len());
+        let start = self.pos as usize;
+        let n = buf.len().min(self.data.len() - start);
+        buf[..n].copy_from_slice(&self.data[start..start + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Write for SectorCheckingDisk {
+    fn write(&mut self, buf: &[u8]) -> basic_io::Result<usize> {
+        self.check_access(buf.len());
+        let start = self.pos as usize;
+        let n = buf.len().min(self.data.len() - start);
+        self.data[start..start + n].copy_from_slice(&buf[..n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> basic_io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for SectorCheckingDisk {
+    fn seek(&mut self, pos: SeekFrom) -> basic_io::Result<u64> {
+        self.pos = match pos {
+            SeekFrom::Start(n) => n,
+            SeekFrom::Current(n) => (self.pos as i64 + n) as u64,
+            SeekFrom::End(n) => (self.data.len() as i64 + n) as u64,
+        };
+        Ok(self.pos)
+    }
+}
+
+fn fat12_disk() -> SectorCheckingDisk {
+    let mut data = vec![0u8; VOLUME_SIZE];
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let options = FormatOptions::new().fat_type(FatType::Fat12).bytes_per_cluster(512);
+        fatfs::format_volume(&mut cursor, options).unwrap();
+    }
+    SectorCheckingDisk { data, pos: 0, violations: Vec::new() }
+}
+
+#[test]
+fn fat12_entries_crossing_sector_boundary() {
+    let mut disk = fat12_disk();
+    // enough clusters for chain to use FAT entries 341 and 682 which cross sector boundaries
+    let num_clusters = 1000;
+    {
+        let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+        assert_eq!(fs.fat_type(), FatType::Fat12);
+        let mut file = fs.root_dir().create_file("big.bin").unwrap();
+        for i in 0..num_clusters {
+            file.write_all(&[(i % 256) as u8; SECTOR_SIZE as usize]).unwrap();
+        }
+    }
+    disk.seek(SeekFrom::Start(0)).unwrap();
+    {
+        let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+        let mut file = fs.root_dir().open_file("big.bin").unwrap();
+        let mut buf = [0u8; SECTOR_SIZE as usize];
+        for i in 0..num_clusters {
+            file.read_exact(&mut buf).unwrap();
+            assert!(buf.iter().all(|&b| b == (i % 256) as u8), "cluster {} is corrupted", i);
+        }
+        assert_eq!(file.read(&mut buf).unwrap(), 0);
+        fs.verify_invariants().unwrap();
+    }
+    assert_eq!(disk.violations, Vec::new());
+}
+
+#[test]
+fn fat12_free_cluster_count_across_sectors() {
+    let mut disk = fat12_disk();
+    {
+        let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+        let stats = fs.stats().unwrap();
+        let mut file = fs.root_dir().create_file("half.bin").unwrap();
+        let num_clusters = stats.total_clusters / 2;
+        for _ in 0..num_clusters {
+            file.write_all(&[0xAA; SECTOR_SIZE as usize]).unwrap();
+        }
+        drop(file);
+        assert_eq!(fs.stats().unwrap().free_clusters, stats.free_clusters - num_clusters);
+    }
+    assert_eq!(disk.violations, Vec::new());
+}
@@ -0,0 +1,76 @@
+// Tests of short name generation and collision policies.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, ErrorKind, Read, Write};
+use fatfs::{FileSystem, FormatOptions, FsOptions, ShortNameCollision};
+use common::MB;
+
+fn with_fs<F: FnOnce(&FileSystem<&mut Cursor<&mut [u8]>>)>(collision: ShortNameCollision, f: F) {
+    let mut data = common::format_image(4 * MB, FormatOptions::new());
+    let mut disk = Cursor::new(&mut data[..]);
+    let options = FsOptions::new().short_name_collision(collision);
+    let fs = FileSystem::new(&mut disk, options).unwrap();
+    f(&fs);
+    fs.verify_invariants().unwrap();
+}
+
+fn short_name_of(fs: &FileSystem<&mut Cursor<&mut [u8]>>, name: &str) -> String {
+    let e = fs.root_dir().iter().map(|r| r.unwrap()).find(|e| e.eq_name(name)).unwrap();
+    e.short_file_name().to_string()
+}
+
+#[test]
+fn files_with_numeric_tails_are_reachable_by_long_name() {
+    with_fs(ShortNameCollision::NumericTail, |fs| {
+        let mut root = fs.root_dir();
+        for i in 1..4 {
+            let name = format!("longfilename{}.txt", i);
+            root.create_file(&name).unwrap().write_all(name.as_bytes()).unwrap();
+        }
+        assert_eq!(short_name_of(fs, "longfilename1.txt"), "LONGFILE.TXT");
+        assert_eq!(short_name_of(fs, "longfilename2.txt"), "LONGFI~1.TXT");
+        assert_eq!(short_name_of(fs, "longfilename3.txt"), "LONGFI~2.TXT");
+        for i in 1..4 {
+            let name = format!("longfilename{}.txt", i);
+            let mut buf = vec![0u8; name.len()];
+            root.open_file(&name).unwrap().read_exact(&mut buf).unwrap();
+            assert_eq!(buf, name.as_bytes());
+        }
+    });
+}
+
+#[test]
+fn hash_tails_do_not_depend_on_creation_order() {
+    let mut names = Vec::new();
+    for order in [["longfile1.txt", "longfile2.txt"], ["longfile2.txt", "longfile1.txt"]].iter() {
+        with_fs(ShortNameCollision::HashTail, |fs| {
+            let mut root = fs.root_dir();
+            root.create_file("longfile0.txt").unwrap();
+            for name in order.iter() {
+                root.create_file(name).unwrap();
+            }
+            assert!(root.open_file("longfile1.txt").is_ok());
+            assert!(root.open_file("longfile2.txt").is_ok());
+            names.push((short_name_of(fs, "longfile1.txt"), short_name_of(fs, "longfile2.txt")));
+        });
+    }
+    assert_eq!(names[0], names[1]);
+    assert!(names[0].0.ends_with("~1.TXT"));
+    assert_ne!(names[0].0, names[0].1);
+}
+
+#[test]
+fn collision_can_be_rejected() {
+    with_fs(ShortNameCollision::Error, |fs| {
+        let mut root = fs.root_dir();
+        root.create_file("longfile1.txt").unwrap();
+        let err = root.create_file("longfile2.txt").err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+        assert_eq!(root.iter().count(), 1);
+        assert!(root.open_file("longfile1.txt").is_ok());
+    });
+}
@@ -0,0 +1,59 @@
+// Tests of slack space and directory slot usage statistics.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, Write};
+use fatfs::{DirSlotUsage, FatType, FileSystem, FormatOptions, FsOptions};
+use common::MB;
+
+fn create_volume() -> Vec<u8> {
+    let options = FormatOptions::new().fat_type(FatType::Fat16).bytes_per_cluster(2048);
+    common::format_image(16 * MB, options)
+}
+
+#[test]
+fn file_slack() {
+    let mut data = create_volume();
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    root.create_file("EMPTY.BIN").unwrap();
+    root.create_file("SMALL.BIN").unwrap().write_all(&[1; 100]).unwrap();
+    root.create_file("EXACT.BIN").unwrap().write_all(&[1; 4096]).unwrap();
+    root.create_dir("SUB").unwrap();
+    let sizes: Vec<_> = root
+        .iter()
+        .map(|r| r.unwrap())
+        .map(|e| (e.file_name().to_string(), e.allocated_len(), e.slack()))
+        .collect();
+    assert_eq!(
+        sizes,
+        vec![
+            ("EMPTY.BIN".to_string(), 0, 0),
+            ("SMALL.BIN".to_string(), 2048, 1948),
+            ("EXACT.BIN".to_string(), 4096, 0),
+            ("SUB".to_string(), 0, 0),
+        ]
+    );
+}
+
+#[test]
+fn directory_slots() {
+    let mut data = create_volume();
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let mut sub = root.create_dir("SUB").unwrap();
+    // one cluster holds 64 entries
+    assert_eq!(sub.slot_usage().unwrap(), DirSlotUsage { used: 2, free: 62 });
+    sub.create_file("A.TXT").unwrap();
+    sub.create_file("B.TXT").unwrap();
+    sub.remove("A.TXT").unwrap();
+    assert_eq!(sub.slot_usage().unwrap(), DirSlotUsage { used: 3, free: 61 });
+    let root_usage = root.slot_usage().unwrap();
+    assert_eq!(root_usage.used, 1);
+    assert_eq!(root_usage.used + root_usage.free, 512);
+}
@@ -0,0 +1,38 @@
+// Tests of reading directory listing unaffected by later modifications.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::Cursor;
+use fatfs::{FatType, FileSystem, FsOptions};
+
+#[test]
+fn snapshot_is_not_affected_by_modifications() {
+    let mut data = common::small_volume(FatType::Fat32);
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut dir = fs.root_dir().create_dir("dir").unwrap();
+    // 512 byte clusters hold 16 entries
+    for i in 0..40 {
+        dir.create_file(&format!("OLD{}", i)).unwrap();
+    }
+    let snapshot = dir.snapshot().unwrap();
+    let names: Vec<_> = snapshot.iter().map(|e| e.file_name().to_string()).collect();
+    assert_eq!(names.len(), 42);
+    assert_eq!(names[..3], [".", "..", "OLD0"][..]);
+
+    // new files reuse slots freed by removed files
+    for e in snapshot.iter().filter(|e| e.is_file()) {
+        let name = e.file_name().to_string();
+        dir.remove_file(&name).unwrap();
+        dir.create_file(&format!("NEW{}", &name[3..])).unwrap();
+    }
+    let after: Vec<_> = snapshot.iter().map(|e| e.file_name().to_string()).collect();
+    assert_eq!(after, names);
+    let current = dir.snapshot().unwrap();
+    assert_eq!(current.len(), 42);
+    let mut files = current.iter().filter(|e| e.is_file());
+    assert!(files.all(|e| e.file_name().to_string().starts_with("NEW")));
+}
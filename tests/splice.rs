@@ -0,0 +1,169 @@
+// Tests of splitting and joining cluster chains of files.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
+use fatfs::{Dir, FatType, FileSystem, FsOptions, ReadWriteSeek};
+
+const CLUSTER_SIZE: usize = 512;
+
+// Returns data in which every cluster is filled with its index
+fn cluster_pattern(num_clusters: usize) -> Vec<u8> {
+    (0..num_clusters * CLUSTER_SIZE).map(|i| (i / CLUSTER_SIZE) as u8).collect()
+}
+
+#[test]
+fn concatenate_files() {
+    let mut data = common::small_volume(FatType::Fat16);
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let first = cluster_pattern(3);
+    let second = b"appended without copying".to_vec();
+    root.create_file("FIRST.BIN").unwrap().write_all(&first).unwrap();
+    root.create_file("SECOND.BIN").unwrap().write_all(&second).unwrap();
+    let free_clusters = fs.stats().unwrap().free_clusters;
+    {
+        let mut src = root.open_file("SECOND.BIN").unwrap();
+        let chain = src.split_chain(0).unwrap().unwrap();
+        assert_eq!(chain.len(), second.len() as u64);
+        let mut dst = root.open_file("FIRST.BIN").unwrap();
+        dst.append_chain(chain).unwrap();
+        dst.seek(SeekFrom::Start(0)).unwrap();
+        let mut buf = vec![0u8; first.len() + second.len()];
+        dst.read_exact(&mut buf).unwrap();
+        assert_eq!(dst.read(&mut [0u8; 1]).unwrap(), 0);
+        assert_eq!(&buf[..first.len()], &first[..]);
+        assert_eq!(&buf[first.len()..], &second[..]);
+    }
+    assert_eq!(fs.stats().unwrap().free_clusters, free_clusters);
+    assert_eq!(root.open_file("SECOND.BIN").unwrap().seek(SeekFrom::End(0)).unwrap(), 0);
+    fs.verify_invariants().unwrap();
+}
+
+#[test]
+fn remove_middle_of_file() {
+    let mut data = common::small_volume(FatType::Fat16);
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let content = cluster_pattern(5);
+    root.create_file("FILE.BIN").unwrap().write_all(&content).unwrap();
+    let free_clusters = fs.stats().unwrap().free_clusters;
+    {
+        let mut file = root.open_file("FILE.BIN").unwrap();
+        file.seek(SeekFrom::End(0)).unwrap();
+        let tail = file.split_chain(3).unwrap().unwrap();
+        let middle = file.split_chain(1).unwrap().unwrap();
+        assert_eq!(file.seek(SeekFrom::Current(0)).unwrap(), CLUSTER_SIZE as u64);
+        fs.free_chain(middle).unwrap();
+        file.append_chain(tail).unwrap();
+        // writing at the old end of file goes to the attached chain
+        file.write_all(&[0xAA]).unwrap();
+    }
+    assert_eq!(fs.stats().unwrap().free_clusters, free_clusters + 2);
+    let mut file = root.open_file("FILE.BIN").unwrap();
+    let mut buf = vec![0u8; 3 * CLUSTER_SIZE];
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(file.read(&mut [0u8; 1]).unwrap(), 0);
+    let mut expected = content[..CLUSTER_SIZE].to_vec();
+    expected.push(0xAA);
+    expected.extend_from_slice(&content[3 * CLUSTER_SIZE + 1..]);
+    assert_eq!(buf, expected);
+    fs.verify_invariants().unwrap();
+}
+
+#[test]
+fn split_past_end_of_file() {
+    let mut data = common::small_volume(FatType::Fat16);
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut file = fs.root_dir().create_file("FILE.BIN").unwrap();
+    assert!(file.split_chain(0).unwrap().is_none());
+    file.write_all(&cluster_pattern(2)).unwrap();
+    assert!(file.split_chain(2).unwrap().is_none());
+}
+
+#[test]
+fn append_to_unaligned_file() {
+    let mut data = common::small_volume(FatType::Fat16);
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    root.create_file("SRC.BIN").unwrap().write_all(&cluster_pattern(2)).unwrap();
+    let mut dst = root.create_file("DST.BIN").unwrap();
+    dst.write_all(b"short").unwrap();
+    let chain = root.open_file("SRC.BIN").unwrap().split_chain(1).unwrap().unwrap();
+    assert_eq!(dst.append_chain(chain).err().unwrap().kind(), ErrorKind::InvalidInput);
+}
+
+fn read_all<IO: ReadWriteSeek>(dir: &mut Dir<IO>, path: &str, len: usize) -> Vec<u8> {
+    let mut file = dir.open_file(path).unwrap();
+    let mut buf = vec![0u8; len];
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(file.read(&mut [0u8; 1]).unwrap(), 0);
+    buf
+}
+
+#[test]
+fn append_file_links_chain() {
+    let mut data = common::small_volume(FatType::Fat16);
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let first = cluster_pattern(2);
+    let second = cluster_pattern(3)[100..].to_vec();
+    root.create_file("LOG.BIN").unwrap().write_all(&first).unwrap();
+    root.create_dir("OLD").unwrap();
+    root.create_file("OLD/LOG.BIN").unwrap().write_all(&second).unwrap();
+    let free_clusters = fs.stats().unwrap().free_clusters;
+    {
+        let mut file = root.open_file("LOG.BIN").unwrap();
+        file.append_file(&mut root, "OLD/LOG.BIN").unwrap();
+        assert_eq!(file.seek(SeekFrom::Current(0)).unwrap(), 0);
+    }
+    assert_eq!(fs.stats().unwrap().free_clusters, free_clusters);
+    assert_eq!(root.open_file("OLD/LOG.BIN").err().unwrap().kind(), ErrorKind::NotFound);
+    let mut expected = first.clone();
+    expected.extend_from_slice(&second);
+    assert_eq!(read_all(&mut root, "LOG.BIN", expected.len()), expected);
+    fs.verify_invariants().unwrap();
+}
+
+#[test]
+fn append_file_copies_to_unaligned_file() {
+    let mut data = common::small_volume(FatType::Fat16);
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let first = b"header".to_vec();
+    let second = cluster_pattern(3);
+    root.create_file("LOG.BIN").unwrap().write_all(&first).unwrap();
+    root.create_file("OLD.BIN").unwrap().write_all(&second).unwrap();
+    {
+        let mut file = root.open_file("LOG.BIN").unwrap();
+        file.seek(SeekFrom::Start(2)).unwrap();
+        file.append_file(&mut root, "OLD.BIN").unwrap();
+        assert_eq!(file.seek(SeekFrom::Current(0)).unwrap(), 2);
+    }
+    assert_eq!(root.open_file("OLD.BIN").err().unwrap().kind(), ErrorKind::NotFound);
+    let mut expected = first.clone();
+    expected.extend_from_slice(&second);
+    assert_eq!(read_all(&mut root, "LOG.BIN", expected.len()), expected);
+    fs.verify_invariants().unwrap();
+}
+
+#[test]
+fn append_file_to_itself() {
+    let mut data = common::small_volume(FatType::Fat16);
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    let mut file = root.create_file("LOG.BIN").unwrap();
+    file.write_all(&cluster_pattern(1)).unwrap();
+    let err = file.append_file(&mut root, "LOG.BIN").err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
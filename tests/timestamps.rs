@@ -0,0 +1,211 @@
+// Tests of file timestamps.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, ErrorKind, Read, Write};
+use fatfs::{CheckOptions, Date, DateTime, FileSystem, FormatOptions, FsOptions, Time, TimeProvider};
+use common::MB;
+
+fn date_time(year: u16, month: u16, day: u16, hour: u16, min: u16, sec: u16) -> DateTime {
+    DateTime {
+        date: Date { year, month, day },
+        time: Time { hour, min, sec },
+    }
+}
+
+#[test]
+fn date_validation() {
+    assert!(Date::new(2000, 2, 29).is_ok());
+    assert!(Date::new(2107, 12, 31).is_ok());
+    assert_eq!(Date::new(1900, 2, 29).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(Date::new(1979, 12, 31).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(Date::new(2108, 1, 1).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(Date::new(2001, 2, 29).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(Date::new(2001, 13, 1).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(Date::new(2001, 4, 31).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(Date::new(2001, 1, 0).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert!(Time::new(23, 59, 59).is_ok());
+    assert_eq!(Time::new(24, 0, 0).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(Time::new(12, 60, 0).unwrap_err().kind(), ErrorKind::InvalidInput);
+}
+
+#[test]
+fn date_time_clamping() {
+    assert_eq!(date_time(1970, 6, 15, 12, 0, 0).clamp(), date_time(1980, 1, 1, 0, 0, 0));
+    assert_eq!(date_time(2200, 6, 15, 12, 0, 0).clamp(), date_time(2107, 12, 31, 23, 59, 59));
+    assert_eq!(date_time(2001, 2, 30, 25, 61, 99).clamp(), date_time(2001, 2, 28, 23, 59, 59));
+    assert_eq!(date_time(2004, 0, 0, 1, 2, 3).clamp(), date_time(2004, 1, 1, 1, 2, 3));
+}
+
+#[test]
+fn setters_reject_invalid_timestamps() {
+    let mut data = common::format_image(MB, FormatOptions::new());
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut file = fs.root_dir().create_file("a.txt").unwrap();
+    let err = file.set_modified(date_time(1979, 1, 1, 0, 0, 0)).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    assert!(file.set_created(date_time(2000, 1, 1, 24, 0, 0)).is_err());
+    assert!(file.set_accessed(Date { year: 2000, month: 2, day: 30 }).is_err());
+    file.set_modified(date_time(2018, 5, 20, 13, 45, 30)).unwrap();
+    file.set_accessed(Date { year: 2018, month: 5, day: 21 }).unwrap();
+    drop(file);
+    let entry = fs.root_dir().iter().next().unwrap().unwrap();
+    assert_eq!(entry.modified(), date_time(2018, 5, 20, 13, 45, 30));
+    assert_eq!(entry.accessed(), Date { year: 2018, month: 5, day: 21 });
+}
+
+struct EpochTimeProvider;
+
+impl TimeProvider for EpochTimeProvider {
+    fn get_current_date(&self) -> Date {
+        self.get_current_date_time().date
+    }
+
+    fn get_current_date_time(&self) -> DateTime {
+        date_time(1970, 1, 1, 0, 0, 0)
+    }
+}
+
+#[test]
+fn time_provider_values_are_clamped() {
+    let mut data = common::format_image(MB, FormatOptions::new());
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new().time_provider(&EpochTimeProvider))
+        .unwrap();
+    fs.root_dir().create_dir("dir").unwrap().create_file("a.txt").unwrap();
+    let entry = fs.root_dir().iter().next().unwrap().unwrap();
+    assert_eq!(entry.created(), date_time(1980, 1, 1, 0, 0, 0));
+    assert_eq!(entry.modified(), date_time(1980, 1, 1, 0, 0, 0));
+}
+
+#[test]
+fn unix_epoch_conversion() {
+    let cases = [
+        (315532800, date_time(1980, 1, 1, 0, 0, 0)),
+        (951827445, date_time(2000, 2, 29, 12, 30, 45)),
+        (1700000000, date_time(2023, 11, 14, 22, 13, 20)),
+        (4107542400, date_time(2100, 3, 1, 0, 0, 0)),
+        (4354819199, date_time(2107, 12, 31, 23, 59, 59)),
+    ];
+    for &(secs, expected) in cases.iter() {
+        assert_eq!(DateTime::from_unix_epoch(secs), expected);
+        assert_eq!(expected.to_unix_epoch(), secs);
+    }
+    // out of range timestamps are clamped
+    assert_eq!(DateTime::from_unix_epoch(-1), date_time(1980, 1, 1, 0, 0, 0));
+    assert_eq!(DateTime::from_unix_epoch(0), date_time(1980, 1, 1, 0, 0, 0));
+    assert_eq!(DateTime::from_unix_epoch(i64::MAX), date_time(2107, 12, 31, 23, 59, 59));
+    // every day in range converts back and forth
+    let mut secs = 315532800 + 3661;
+    while secs < 4354819199 {
+        assert_eq!(DateTime::from_unix_epoch(secs).to_unix_epoch(), secs);
+        secs += 86400;
+    }
+}
+
+struct FixedTimeProvider;
+
+impl TimeProvider for FixedTimeProvider {
+    fn get_current_date(&self) -> Date {
+        self.get_current_date_time().date
+    }
+
+    fn get_current_date_time(&self) -> DateTime {
+        date_time(2020, 5, 5, 10, 0, 0)
+    }
+}
+
+fn read_file_and_get_accessed(data: &mut [u8], options: FsOptions) -> Date {
+    let mut cursor = Cursor::new(data);
+    let fs = FileSystem::new(&mut cursor, options.time_provider(&FixedTimeProvider)).unwrap();
+    let mut buf = [0u8; 5];
+    fs.root_dir().open_file("a.txt").unwrap().read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"hello");
+    let entry = fs.root_dir().iter().next().unwrap().unwrap();
+    entry.accessed()
+}
+
+#[test]
+fn accessed_date_update() {
+    let mut data = common::format_image(MB, FormatOptions::new());
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        fs.root_dir().create_file("a.txt").unwrap().write_all(b"hello").unwrap();
+    }
+    let initial = Date { year: 1980, month: 1, day: 1 };
+    assert_eq!(read_file_and_get_accessed(&mut data, FsOptions::new()), initial);
+    let options = FsOptions::new().update_accessed_date(true);
+    let updated = Date { year: 2020, month: 5, day: 5 };
+    assert_eq!(read_file_and_get_accessed(&mut data, options), updated);
+}
+
+struct OddSecondTimeProvider;
+
+impl TimeProvider for OddSecondTimeProvider {
+    fn get_current_date(&self) -> Date {
+        self.get_current_date_time().date
+    }
+
+    fn get_current_date_time(&self) -> DateTime {
+        date_time(2021, 7, 14, 9, 30, 17)
+    }
+}
+
+#[test]
+fn created_file_has_timestamps_without_lookup() {
+    let mut data = common::format_image(MB, FormatOptions::new());
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let options = FsOptions::new().time_provider(&OddSecondTimeProvider);
+        let fs = FileSystem::new(&mut cursor, options).unwrap();
+        let file = fs.root_dir().create_file("a.txt").unwrap();
+        // creation time keeps odd seconds in the fine resolution field
+        assert_eq!(file.created(), date_time(2021, 7, 14, 9, 30, 17));
+        assert_eq!(file.modified(), date_time(2021, 7, 14, 9, 30, 16));
+        assert_eq!(file.accessed(), Date { year: 2021, month: 7, day: 14 });
+    }
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let entry = fs.root_dir().iter().next().unwrap().unwrap();
+    assert_eq!(entry.created(), date_time(2021, 7, 14, 9, 30, 17));
+    assert_eq!(entry.modified(), date_time(2021, 7, 14, 9, 30, 16));
+    assert_eq!(entry.accessed(), Date { year: 2021, month: 7, day: 14 });
+}
+
+#[test]
+fn created_directory_dot_entries_share_timestamps_and_pass_check() {
+    let mut data = common::format_image(MB, FormatOptions::new());
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let options = FsOptions::new().time_provider(&FixedTimeProvider);
+        let fs = FileSystem::new(&mut cursor, options).unwrap();
+        fs.root_dir().create_dir("parent").unwrap();
+    }
+    let mut cursor = Cursor::new(&mut data[..]);
+    let options = FsOptions::new().time_provider(&OddSecondTimeProvider);
+    let fs = FileSystem::new(&mut cursor, options).unwrap();
+    let mut parent = fs.root_dir().open_dir("parent").unwrap();
+    parent.create_dir("child").unwrap();
+
+    // adding an entry updates modification time of the parent only
+    let parent_entry = fs.root_dir().iter().next().unwrap().unwrap();
+    assert_eq!(parent_entry.created(), date_time(2020, 5, 5, 10, 0, 0));
+    assert_eq!(parent_entry.modified(), date_time(2021, 7, 14, 9, 30, 16));
+    let child_entry = parent.iter().map(|e| e.unwrap()).find(|e| e.eq_name("child")).unwrap();
+    let dots: Vec<_> = child_entry.to_dir().iter().map(|e| e.unwrap()).collect();
+    assert_eq!(dots.len(), 2);
+    for dot in dots {
+        assert_eq!(dot.created(), child_entry.created(), "{}", dot.short_file_name());
+        assert_eq!(dot.modified(), child_entry.modified(), "{}", dot.short_file_name());
+        assert_eq!(dot.accessed(), child_entry.accessed(), "{}", dot.short_file_name());
+    }
+
+    let mut issues = Vec::new();
+    fs.check(CheckOptions::new(), &mut |issue| issues.push(issue.name.to_string())).unwrap();
+    assert!(issues.is_empty(), "{:?}", issues);
+}
@@ -0,0 +1,216 @@
+// Tests of fatfs-tool commands run as a separate process.
+
+extern crate basic_io;
+extern crate fatfs;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use basic_io::{Cursor, Read, Seek, SeekFrom, Write};
+use fatfs::{DateTime, FileSystem, FsOptions};
+
+// Directory removed when the test ends
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("fatfs-tool-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// Runs the tool and returns its exit code and standard output
+fn run_status(args: &[&str]) -> (i32, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_fatfs-tool")).args(args).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    (output.status.code().unwrap(), stdout)
+}
+
+// Runs the tool and returns its standard output
+fn run_raw(args: &[&str]) -> Vec<u8> {
+    let output = Command::new(env!("CARGO_BIN_EXE_fatfs-tool")).args(args).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    output.stdout
+}
+
+fn run(args: &[&str]) -> String {
+    String::from_utf8(run_raw(args)).unwrap()
+}
+
+// Creates an image and runs `f` on its filesystem
+fn create_image<F: FnOnce(&FileSystem<&mut Cursor<&mut [u8]>>)>(image: &Path, f: F) {
+    run(&["create", image.to_str().unwrap(), "4M"]);
+    let mut data = fs::read(image).unwrap();
+    {
+        let mut disk = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+        f(&fs);
+        fs.unmount().unwrap();
+    }
+    fs::write(image, &data).unwrap();
+}
+
+fn read_image_file(image: &Path, path: &str) -> (Vec<u8>, DateTime) {
+    let mut data = fs::read(image).unwrap();
+    let mut disk = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    let mut file = fs.root_dir().open_file(path).unwrap();
+    let len = file.seek(SeekFrom::End(0)).unwrap();
+    file.seek(SeekFrom::Start(0)).unwrap();
+    let mut buf = vec![0u8; len as usize];
+    file.read_exact(&mut buf).unwrap();
+    (buf, file.modified())
+}
+
+#[test]
+fn sync_copies_changed_files_only() {
+    let tmp = TempDir::new("sync");
+    let host = tmp.0.join("host");
+    let image = tmp.0.join("image.img");
+    let image_arg = image.to_str().unwrap();
+    fs::create_dir_all(host.join("sub")).unwrap();
+    fs::write(host.join("a.txt"), b"first").unwrap();
+    fs::write(host.join("sub/b.txt"), b"second").unwrap();
+    run(&["create", image_arg, "4M"]);
+
+    let out = run(&["sync", host.to_str().unwrap(), image_arg]);
+    assert!(out.ends_with("3 added, 0 updated, 0 deleted, 0 unchanged, 0 skipped\n"), "{}", out);
+    let (contents, modified) = read_image_file(&image, "sub/b.txt");
+    assert_eq!(contents, b"second");
+    let host_modified = fs::metadata(host.join("sub/b.txt")).unwrap().modified().unwrap();
+    let secs = host_modified.duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() as i64;
+    assert_eq!(modified.to_unix_epoch(), secs / 2 * 2);
+
+    let out = run(&["sync", host.to_str().unwrap(), image_arg]);
+    assert!(out.ends_with("0 added, 0 updated, 0 deleted, 2 unchanged, 0 skipped\n"), "{}", out);
+
+    fs::write(host.join("a.txt"), b"changed").unwrap();
+    fs::remove_dir_all(host.join("sub")).unwrap();
+    let out = run(&["sync", host.to_str().unwrap(), image_arg, "--delete"]);
+    assert!(out.ends_with("0 added, 1 updated, 1 deleted, 0 unchanged, 0 skipped\n"), "{}", out);
+    assert_eq!(read_image_file(&image, "a.txt").0, b"changed");
+}
+
+#[test]
+fn fsck_reports_and_repairs_size_mismatch() {
+    let tmp = TempDir::new("fsck");
+    let image = tmp.0.join("image.img");
+    let image_arg = image.to_str().unwrap();
+    let mut cluster_size = 0;
+    create_image(&image, |fs| {
+        cluster_size = fs.stats().unwrap().cluster_size;
+        fs.root_dir().create_file("big.bin").unwrap().write_all(&[1; 100]).unwrap();
+    });
+    // make size in the entry larger than the cluster chain
+    let mut data = fs::read(&image).unwrap();
+    let entry = data.chunks(32).position(|e| &e[..11] == b"BIG     BIN").unwrap();
+    data[entry * 32 + 28..entry * 32 + 32].copy_from_slice(&(cluster_size * 2).to_le_bytes());
+    fs::write(&image, &data).unwrap();
+
+    let (code, out) = run_status(&["fsck", image_arg]);
+    assert_eq!(code, 4, "{}", out);
+    assert!(out.contains("BIG.BIN: "), "{}", out);
+    assert!(out.ends_with("1 problems found, 0 repaired\n"), "{}", out);
+    // image is not modified without --repair
+    assert!(fs::read(&image).unwrap() == data);
+
+    let (code, out) = run_status(&["fsck", image_arg, "--repair"]);
+    assert_eq!(code, 1, "{}", out);
+    assert!(out.ends_with("1 problems found, 1 repaired\n"), "{}", out);
+    let (code, out) = run_status(&["fsck", image_arg]);
+    assert_eq!(code, 0, "{}", out);
+    assert!(out.ends_with("no problems found\n"), "{}", out);
+    assert_eq!(run_status(&["fsck", image_arg, "--unknown"]).0, 16);
+}
+
+#[test]
+fn debug_prints_raw_entries_and_fat_chains() {
+    let tmp = TempDir::new("debug");
+    let image = tmp.0.join("image.img");
+    let image_arg = image.to_str().unwrap();
+    let mut clusters = Vec::new();
+    let mut cluster_size = 0;
+    create_image(&image, |fs| {
+        cluster_size = fs.stats().unwrap().cluster_size as usize;
+        let mut dir = fs.root_dir().create_dir("sub").unwrap();
+        let mut file = dir.create_file("long file name.txt").unwrap();
+        file.write_all(&vec![1; cluster_size * 2 + 1]).unwrap();
+        clusters = file.clusters().map(|c| c.unwrap().0.to_string()).collect();
+    });
+
+    let out = run(&["debug", image_arg, "--dir", "/sub"]);
+    assert!(out.contains("short name: \".          \" attrs=0x10"), "{}", out);
+    assert!(out.contains("long name part: order=0x42"), "{}", out);
+    assert!(out.contains("long name part: order=0x01"), "{}", out);
+    assert!(out.contains("short name: \"LONGFILETXT\""), "{}", out);
+    let size = format!("cluster={} size={}\n", clusters[0], cluster_size * 2 + 1);
+    assert!(out.contains(&size), "{}", out);
+
+    let out = run(&["debug", image_arg, "--fat-chain", &clusters[0]]);
+    assert_eq!(out, format!("{}\n", clusters.join(" -> ")));
+    let (code, _) = run_status(&["debug", image_arg, "--fat-chain", "1"]);
+    assert_eq!(code, 16);
+}
+
+#[test]
+fn tree_prints_sizes_and_usage_summary() {
+    let tmp = TempDir::new("tree");
+    let image = tmp.0.join("image.img");
+    let mut cluster_size = 0;
+    create_image(&image, |fs| {
+        cluster_size = fs.stats().unwrap().cluster_size as u64;
+        let mut dir = fs.root_dir().create_dir("docs").unwrap();
+        dir.create_file("a.txt").unwrap().write_all(&[1; 100]).unwrap();
+        let b = vec![2; cluster_size as usize + 1];
+        dir.create_file("b.txt").unwrap().write_all(&b).unwrap();
+        fs.root_dir().create_file("top.bin").unwrap();
+    });
+
+    let out = run(&["tree", image.to_str().unwrap()]);
+    let lines: Vec<&str> = out.lines().collect();
+    let bytes = 100 + cluster_size + 1;
+    let allocated = 3 * cluster_size;
+    assert_eq!(lines.len(), 6, "{}", out);
+    let summary = format!("(3 files, {} bytes, {} bytes allocated)", bytes, allocated);
+    assert_eq!(lines[0], format!("/  {}", summary));
+    let summary = format!("(2 files, {} bytes, {} bytes allocated)", bytes, allocated);
+    assert!(lines[1].starts_with("  DOCS/  ") && lines[1].ends_with(&summary), "{}", out);
+    assert!(lines[2].starts_with("    A.TXT  100  "), "{}", out);
+    assert!(lines[3].starts_with(&format!("    B.TXT  {}  ", cluster_size + 1)), "{}", out);
+    assert!(lines[4].starts_with("  TOP.BIN  0  "), "{}", out);
+    assert_eq!(lines[5], "1 directories, 3 files");
+}
+
+#[test]
+fn cat_streams_requested_range() {
+    let tmp = TempDir::new("cat");
+    let image = tmp.0.join("image.img");
+    let image_arg = image.to_str().unwrap();
+    let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+    create_image(&image, |fs| {
+        fs.root_dir().create_dir("dir").unwrap();
+        fs.root_dir().create_file("dir/data.bin").unwrap().write_all(&data).unwrap();
+    });
+
+    assert!(run_raw(&["cat", image_arg, "/dir/data.bin"]) == data);
+    let out = run_raw(&["cat", image_arg, "dir/data.bin", "--offset", "70000", "--len", "1000"]);
+    assert!(out[..] == data[70000..71000]);
+    // range past the end of file is cut
+    let out = run_raw(&["cat", image_arg, "dir/data.bin", "--offset", "199990", "--len", "100"]);
+    assert!(out[..] == data[199990..]);
+    assert!(run_raw(&["cat", image_arg, "dir/data.bin", "--offset", "300000"]).is_empty());
+    // image is only read
+    let before = fs::read(&image).unwrap();
+    run_raw(&["cat", image_arg, "dir/data.bin", "--len", "10"]);
+    assert!(fs::read(&image).unwrap() == before);
+    assert_eq!(run_status(&["cat", image_arg, "dir/missing.bin"]).0, 16);
+}
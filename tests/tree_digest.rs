@@ -0,0 +1,84 @@
+// Tests of directory tree digests.
+
+extern crate basic_io;
+extern crate fatfs;
+
+use std::hash::Hasher;
+
+use basic_io::{Cursor, Write};
+use fatfs::{FileSystem, FormatOptions, FsOptions};
+
+// FNV-1a 64-bit
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 = (self.0 ^ u64::from(*b)).wrapping_mul(0x100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+// Creates tree from (path, contents) pairs, directories have no contents
+fn digest(entries: &[(&str, Option<&[u8]>)]) -> u64 {
+    let mut data = vec![0u8; 4 * 1024 * 1024];
+    let mut disk = Cursor::new(&mut data[..]);
+    fatfs::format_volume(&mut disk, FormatOptions::new()).unwrap();
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    for &(path, contents) in entries {
+        match contents {
+            Some(contents) => root.create_file(path).unwrap().write_all(contents).unwrap(),
+            None => {
+                root.create_dir(path).unwrap();
+            }
+        }
+    }
+    root.tree_digest::<Fnv>().unwrap()
+}
+
+#[test]
+fn digest_does_not_depend_on_entry_order() {
+    let big = vec![0x33u8; 5000];
+    let a = digest(&[
+        ("a.txt", Some(b"hello")),
+        ("d", None),
+        ("d/big.bin", Some(&big)),
+        ("d/e", None),
+    ]);
+    let b = digest(&[
+        ("d", None),
+        ("d/e", None),
+        ("d/big.bin", Some(&big)),
+        ("a.txt", Some(b"hello")),
+    ]);
+    assert_eq!(a, b);
+}
+
+#[test]
+fn digest_covers_names_sizes_and_contents() {
+    let base = digest(&[("a.txt", Some(b"hello")), ("d", None)]);
+    let changes = [
+        digest(&[("b.txt", Some(b"hello")), ("d", None)]),
+        digest(&[("a.txt", Some(b"hellO")), ("d", None)]),
+        digest(&[("a.txt", Some(b"hello!")), ("d", None)]),
+        digest(&[("a.txt", Some(b"hello")), ("e", None)]),
+        digest(&[("a.txt", Some(b"hello")), ("d", None), ("d/x", Some(b""))]),
+        digest(&[("a.txt", Some(b"hello"))]),
+    ];
+    for (i, d) in changes.iter().enumerate() {
+        assert_ne!(*d, base, "change {}", i);
+    }
+    // file and empty directory with the same name differ
+    assert_ne!(digest(&[("d", Some(b""))]), digest(&[("d", None)]));
+}
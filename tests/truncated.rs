@@ -0,0 +1,87 @@
+// Tests mounting volumes stored on devices smaller than the BPB claims.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, ErrorKind, Read, Write};
+use fatfs::{FatType, FileSystem, FormatOptions, FsOptions};
+use common::MB;
+
+// Formats a volume and creates a small file at the start and a big file filling most of it
+fn create_volume(fat_type: FatType, size: usize) -> Vec<u8> {
+    let options = FormatOptions::new().fat_type(fat_type).bytes_per_cluster(512);
+    let mut data = common::format_image(size, options);
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        let mut root = fs.root_dir();
+        root.create_file("small.txt").unwrap().write_all(b"hello").unwrap();
+        let mut big = root.create_file("big.bin").unwrap();
+        for _ in 0..(size / 2 / 1024) {
+            big.write_all(&[0xAB; 1024]).unwrap();
+        }
+    }
+    data
+}
+
+#[test]
+fn mount_fails_on_truncated_device() {
+    let volumes = [(FatType::Fat12, MB), (FatType::Fat16, 4 * MB), (FatType::Fat32, 40 * MB)];
+    for &(fat_type, size) in volumes.iter() {
+        let data = create_volume(fat_type, size);
+        let mut truncated = data[..size * 3 / 4].to_vec();
+        let mut cursor = Cursor::new(&mut truncated[..]);
+        let err = FileSystem::new(&mut cursor, FsOptions::new()).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::TruncatedVolume);
+    }
+}
+
+#[test]
+fn truncated_device_is_mounted_read_only() {
+    let size = 4 * MB;
+    let data = create_volume(FatType::Fat16, size);
+    let mut truncated = data[..size / 2].to_vec();
+    {
+        let mut cursor = Cursor::new(&mut truncated[..]);
+        let options = FsOptions::new().allow_truncated(true);
+        let fs = FileSystem::new(&mut cursor, options).unwrap();
+        assert!(fs.is_read_only());
+        let mut root = fs.root_dir();
+
+        let mut buf = Vec::new();
+        let mut small = root.open_file("small.txt").unwrap();
+        let mut chunk = [0u8; 16];
+        let n = small.read(&mut chunk).unwrap();
+        buf.extend_from_slice(&chunk[..n]);
+        assert_eq!(buf, b"hello");
+
+        let mut big = root.open_file("big.bin").unwrap();
+        let mut chunk = [0u8; 1024];
+        let err = loop {
+            match big.read(&mut chunk) {
+                Ok(0) => panic!("file read past the end of device"),
+                Ok(_) => assert!(chunk.iter().all(|&b| b == 0xAB)),
+                Err(err) => break err,
+            }
+        };
+        assert_eq!(err.kind(), ErrorKind::TruncatedVolume);
+
+        let err = root.create_file("new.txt").err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::TruncatedVolume);
+        let err = small.write(b"x").err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::TruncatedVolume);
+    }
+    // nothing was written to the device, including on unmount
+    assert_eq!(truncated[..], data[..truncated.len()]);
+}
+
+#[test]
+fn complete_device_is_mounted_read_write() {
+    let mut data = create_volume(FatType::Fat12, MB);
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new().allow_truncated(true)).unwrap();
+    assert!(!fs.is_read_only());
+    fs.root_dir().create_file("new.txt").unwrap().write_all(b"data").unwrap();
+}
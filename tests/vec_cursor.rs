@@ -0,0 +1,48 @@
+// Tests of growable in-memory devices backed by Vec.
+
+extern crate basic_io;
+extern crate fatfs;
+
+use basic_io::{Cursor, Read, Seek, SeekFrom, Write};
+use fatfs::{FileSystem, FormatOptions, FsOptions};
+
+const VOLUME_SECTORS: u32 = 8192;
+
+#[test]
+fn writes_past_end_grow_vector() {
+    let mut cursor = Cursor::new(vec![1u8; 4]);
+    cursor.seek(SeekFrom::Start(2)).unwrap();
+    cursor.write_all(&[2; 4]).unwrap();
+    cursor.seek(SeekFrom::Start(10)).unwrap();
+    cursor.write_all(&[3; 2]).unwrap();
+    assert_eq!(cursor.get_ref(), &[1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 3, 3]);
+    assert_eq!(cursor.seek(SeekFrom::End(0)).unwrap(), 12);
+    let mut buf = [0; 4];
+    assert_eq!(cursor.read(&mut buf).unwrap(), 0);
+}
+
+#[test]
+fn volume_is_generated_in_empty_vector() {
+    let mut data = Vec::new();
+    {
+        let mut cursor = Cursor::new(&mut data);
+        let options = FormatOptions::new().total_sectors(VOLUME_SECTORS);
+        fatfs::format_volume(&mut cursor, options).unwrap();
+    }
+    // only metadata was written
+    let volume_size = VOLUME_SECTORS as usize * 512;
+    assert!(data.len() < volume_size);
+    data.resize(volume_size, 0);
+    let mut cursor = Cursor::new(data);
+    {
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        fs.root_dir().create_file("a.txt").unwrap().write_all(b"generated").unwrap();
+        fs.unmount().unwrap();
+    }
+    cursor.seek(SeekFrom::Start(0)).unwrap();
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut buf = [0; 9];
+    fs.root_dir().open_file("a.txt").unwrap().read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"generated");
+    fs.verify_invariants().unwrap();
+}
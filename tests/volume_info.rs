@@ -0,0 +1,232 @@
+// Tests of identifying a volume before mounting it.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, Seek, SeekFrom};
+use fatfs::{
+    CheckIssueKind, CheckOptions, FatType, FileSystem, FormatOptions, FsOptions, ReadWriteSeek,
+};
+use common::MB;
+
+#[test]
+fn peek_volume_info() {
+    let mut cards = [
+        common::format_image(MB, FormatOptions::new().volume_id(0x1234).volume_label("CARD A")),
+        common::format_image(
+            40 * MB,
+            FormatOptions::new().fat_type(FatType::Fat32).volume_id(0xCAFE).volume_label("B"),
+        ),
+    ];
+    let expected = [(0x1234, "CARD A", FatType::Fat12), (0xCAFE, "B", FatType::Fat32)];
+    for (data, &(volume_id, label, fat_type)) in cards.iter_mut().zip(expected.iter()) {
+        let mut cursor = Cursor::new(&mut data[..]);
+        cursor.seek(SeekFrom::Start(1000)).unwrap();
+        let info = FileSystem::peek_volume_info(&mut cursor).unwrap();
+        assert_eq!(info.volume_id, volume_id);
+        assert_eq!(info.volume_label(), label);
+        assert_eq!(info.fat_type, fat_type);
+        // device is left at the beginning so it can be mounted right away
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        assert!(fs.matches_serial(volume_id));
+        assert!(!fs.matches_serial(volume_id + 1));
+    }
+}
+
+#[test]
+fn peek_volume_info_rejects_invalid_boot_sector() {
+    let mut data = common::format_image(MB, FormatOptions::new());
+    data[510] = 0;
+    let mut cursor = Cursor::new(&mut data[..]);
+    assert!(FileSystem::peek_volume_info(&mut cursor).is_err());
+}
+
+#[test]
+fn volume_info_from_boot_sector() {
+    let options = FormatOptions::new().fat_type(FatType::Fat16).volume_id(0xBEEF).volume_label("S");
+    let mut data = common::format_image(16 * MB, options);
+    let mut sector = [0u8; 512];
+    sector.copy_from_slice(&data[..512]);
+    let info = fatfs::VolumeInfo::from_boot_sector(&sector).unwrap();
+    let mut cursor = Cursor::new(&mut data[..]);
+    assert_eq!(info, FileSystem::peek_volume_info(&mut cursor).unwrap());
+    assert_eq!(info.volume_id, 0xBEEF);
+    assert_eq!(info.fat_type, FatType::Fat16);
+    {
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        let stats = fs.stats().unwrap();
+        assert_eq!(info.max_file_size, stats.total_clusters * stats.cluster_size);
+    }
+    sector[511] = 0;
+    assert!(fatfs::VolumeInfo::from_boot_sector(&sector).is_err());
+}
+
+// Writes entry with VOLUME_ID attribute in the given slot of the root directory
+fn add_label_entry(data: &mut [u8], slot: usize, label: &[u8; 11], deleted: bool) {
+    let pos = {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        let first = fs.root_dir().iter_raw().next().unwrap().unwrap();
+        first.abs_pos().unwrap() as usize + slot * 32
+    };
+    data[pos..pos + 11].copy_from_slice(label);
+    data[pos + 11] = 0x08;
+    if deleted {
+        data[pos] = 0xE5;
+    }
+}
+
+fn mounted_label(data: &mut [u8]) -> String {
+    let mut cursor = Cursor::new(data);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    // label entry is not listed as a file
+    assert!(fs.root_dir().iter().next().is_none());
+    fs.volume_label().to_string()
+}
+
+#[test]
+fn volume_label_falls_back_to_root_directory_entry() {
+    let mut data = common::format_image(MB, FormatOptions::new());
+    assert_eq!(mounted_label(&mut data), "NO NAME");
+    add_label_entry(&mut data, 0, b"ROOT LABEL ", false);
+    assert_eq!(mounted_label(&mut data), "ROOT LABEL");
+
+    // label from BPB takes precedence
+    let mut data = common::format_image(MB, FormatOptions::new().volume_label("BPB"));
+    add_label_entry(&mut data, 0, b"ROOT LABEL ", false);
+    assert_eq!(mounted_label(&mut data), "BPB");
+
+    // deleted label entry is ignored
+    let mut data = common::format_image(MB, FormatOptions::new());
+    add_label_entry(&mut data, 0, b"ROOT LABEL ", true);
+    assert_eq!(mounted_label(&mut data), "NO NAME");
+}
+
+fn volume_entries<IO: ReadWriteSeek>(fs: &FileSystem<IO>) -> Vec<[u8; 11]> {
+    let mut labels = Vec::new();
+    for r in fs.root_dir().volume_entries() {
+        let mut label = [0u8; 11];
+        label.copy_from_slice(&r.unwrap().bytes()[..11]);
+        labels.push(label);
+    }
+    labels
+}
+
+#[test]
+fn set_volume_label() {
+    let mut data = common::format_image(MB, FormatOptions::new());
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let mut fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        fs.root_dir().create_file("a.txt").unwrap();
+        fs.set_volume_label("Device 1").unwrap();
+        assert_eq!(fs.volume_label(), "DEVICE 1");
+        fs.set_volume_label("Sensor").unwrap();
+        assert_eq!(volume_entries(&fs), vec![*b"SENSOR     "]);
+        assert_eq!(fs.root_dir().iter().count(), 1);
+    }
+    let mut cursor = Cursor::new(&mut data[..]);
+    assert_eq!(FileSystem::peek_volume_info(&mut cursor).unwrap().volume_label(), "SENSOR");
+    let mut fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    assert_eq!(fs.volume_label(), "SENSOR");
+    // empty label removes the entry
+    fs.set_volume_label("").unwrap();
+    assert_eq!(fs.volume_label(), "NO NAME");
+    assert!(volume_entries(&fs).is_empty());
+    fs.verify_invariants().unwrap();
+}
+
+#[test]
+fn set_volume_label_updates_backup_boot_sector() {
+    let options = FormatOptions::new().fat_type(FatType::Fat32);
+    let mut data = common::format_image(40 * MB, options);
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let mut fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        fs.set_volume_label("backup").unwrap();
+    }
+    let backup_sector = (data[50] as usize | (data[51] as usize) << 8) * 512;
+    assert_eq!(&data[71..82], b"BACKUP     ");
+    assert_eq!(&data[backup_sector + 71..backup_sector + 82], b"BACKUP     ");
+}
+
+#[test]
+fn set_volume_label_rejects_invalid_labels() {
+    let mut data = common::format_image(MB, FormatOptions::new().volume_label("OLD"));
+    let mut cursor = Cursor::new(&mut data[..]);
+    let mut fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    for &label in ["TWELVE CHARS", "A*B", "X.Y", "ĄČ"].iter() {
+        let err = fs.set_volume_label(label).err().unwrap();
+        assert_eq!(err.kind(), basic_io::ErrorKind::InvalidInput);
+    }
+    assert_eq!(fs.volume_label(), "OLD");
+}
+
+#[test]
+fn set_volume_label_reuses_deleted_slot() {
+    let mut data = common::format_image(MB, FormatOptions::new());
+    let mut cursor = Cursor::new(&mut data[..]);
+    let mut fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    for name in ["A.TXT", "B.TXT", "C.TXT"].iter() {
+        root.create_file(name).unwrap();
+    }
+    root.remove("B.TXT").unwrap();
+    drop(root);
+    fs.set_volume_label("LABEL").unwrap();
+    let entry = fs.root_dir().volume_entries().next().unwrap().unwrap();
+    assert_eq!(entry.offset(), 32);
+    assert_eq!(entry.volume_label(), "LABEL");
+}
+
+#[test]
+fn duplicate_volume_labels() {
+    let mut data = common::format_image(MB, FormatOptions::new());
+    add_label_entry(&mut data, 0, b"FIRST      ", false);
+    add_label_entry(&mut data, 1, b"SECOND     ", false);
+    add_label_entry(&mut data, 2, b"DELETED    ", true);
+    add_label_entry(&mut data, 3, b"THIRD      ", false);
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        // the first entry is used as the label
+        assert_eq!(fs.volume_label(), "FIRST");
+        let labels: Vec<_> = fs
+            .root_dir()
+            .volume_entries()
+            .map(|r| r.unwrap().volume_label().to_string())
+            .collect();
+        assert_eq!(labels, vec!["FIRST", "SECOND", "THIRD"]);
+
+        let mut found = Vec::new();
+        let summary = fs
+            .check(CheckOptions::new().repair(true), &mut |issue| {
+                assert_eq!(issue.kind, CheckIssueKind::DuplicateVolumeLabel);
+                assert!(issue.repaired);
+                found.push(issue.name.to_string());
+            })
+            .unwrap();
+        assert_eq!(summary.issues, 2);
+        assert_eq!(found, vec!["SECOND", "THIRD"]);
+        assert_eq!(volume_entries(&fs), vec![*b"FIRST      "]);
+    }
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    assert!(fs.check(CheckOptions::new(), &mut |_| {}).unwrap().is_clean());
+}
+
+#[test]
+fn removing_volume_label_removes_duplicates() {
+    let mut data = common::format_image(MB, FormatOptions::new());
+    add_label_entry(&mut data, 0, b"FIRST      ", false);
+    add_label_entry(&mut data, 1, b"SECOND     ", false);
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let mut fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        fs.set_volume_label("").unwrap();
+        assert!(volume_entries(&fs).is_empty());
+    }
+    assert_eq!(mounted_label(&mut data), "NO NAME");
+}
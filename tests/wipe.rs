@@ -0,0 +1,51 @@
+// Tests of overwriting free space and deleted directory entries.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, Read, Write};
+use fatfs::{FatType, FileSystem, FsOptions};
+
+const SECRET: &[u8] = b"top secret payload";
+
+fn contains(data: &[u8], needle: &[u8]) -> bool {
+    data.windows(needle.len()).any(|w| w == needle)
+}
+
+fn wipe_removed_file(fat_type: FatType) {
+    let mut data = common::small_volume(fat_type);
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+        let mut root = fs.root_dir();
+        let mut sub = root.create_dir("sub").unwrap();
+        sub.create_file("SECRET.TXT").unwrap().write_all(SECRET).unwrap();
+        root.create_file("kept.txt").unwrap().write_all(SECRET).unwrap();
+        sub.remove("SECRET.TXT").unwrap();
+        let free_clusters = fs.stats().unwrap().free_clusters;
+        assert_eq!(fs.wipe_free_space(0xA5).unwrap(), free_clusters);
+        assert_eq!(fs.wipe_deleted_entries(0).unwrap(), 1);
+        let mut buf = [0u8; 18];
+        root.open_file("kept.txt").unwrap().read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], SECRET);
+        fs.verify_invariants().unwrap();
+        assert_eq!(sub.iter().count(), 2);
+    }
+    // only the kept file contains the payload
+    let first = data.windows(SECRET.len()).position(|w| w == SECRET).unwrap();
+    assert!(!contains(&data[first + 1..], SECRET));
+    assert!(!contains(&data, b"ECRET  TXT"));
+    assert!(contains(&data, &[0xA5; 512]));
+}
+
+#[test]
+fn wipe_fat16() {
+    wipe_removed_file(FatType::Fat16);
+}
+
+#[test]
+fn wipe_fat32() {
+    wipe_removed_file(FatType::Fat32);
+}
@@ -0,0 +1,69 @@
+// Tests of temporary raw access to the device of a mounted filesystem.
+
+extern crate basic_io;
+extern crate fatfs;
+
+mod common;
+
+use basic_io::{Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
+use fatfs::{FatType, FileSystem, FsOptions, ReadWriteSeek};
+
+// Counts used data cluster entries in the first FAT of a FAT16 volume
+fn used_clusters(disk: &mut dyn ReadWriteSeek) -> basic_io::Result<usize> {
+    let mut boot = [0u8; 512];
+    disk.seek(SeekFrom::Start(0))?;
+    disk.read_exact(&mut boot)?;
+    let reserved_sectors = u16::from(boot[14]) | u16::from(boot[15]) << 8;
+    let mut fat = [0u8; 512];
+    disk.seek(SeekFrom::Start(reserved_sectors as u64 * 512))?;
+    disk.read_exact(&mut fat)?;
+    Ok(fat[4..].chunks(2).filter(|e| e[0] != 0 || e[1] != 0).count())
+}
+
+#[test]
+fn pending_fat_writes_are_flushed_before_lending() {
+    let mut data = common::small_volume(FatType::Fat16);
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new().lazy_fat_writes(true)).unwrap();
+    let mut file = fs.root_dir().create_file("a.bin").unwrap();
+    file.write_all(&[1u8; 1024]).unwrap();
+    drop(file);
+    assert_eq!(fs.with_disk(|disk| used_clusters(disk)).unwrap(), 2);
+}
+
+#[test]
+fn filesystem_keeps_working_after_raw_access() {
+    let mut data = common::small_volume(FatType::Fat16);
+    let last_sector = data.len() as u64 - 512;
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let mut root = fs.root_dir();
+    root.create_file("a.txt").unwrap().write_all(b"hello").unwrap();
+    // vendor command leaving the device at a random position
+    fs.with_disk(|disk| {
+        disk.seek(SeekFrom::Start(last_sector))?;
+        disk.write_all(&[0xAA; 512])
+    })
+    .unwrap();
+    let mut buf = [0u8; 5];
+    root.open_file("a.txt").unwrap().read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"hello");
+    root.create_file("b.txt").unwrap();
+    fs.verify_invariants().unwrap();
+}
+
+#[test]
+fn changed_volume_is_reported() {
+    let mut data = common::small_volume(FatType::Fat16);
+    let mut cursor = Cursor::new(&mut data[..]);
+    let fs = FileSystem::new(&mut cursor, FsOptions::new()).unwrap();
+    let err = fs
+        .with_disk(|disk| {
+            // overwrite volume serial number in extended BPB
+            disk.seek(SeekFrom::Start(39))?;
+            disk.write_all(&[1, 2, 3, 4])
+        })
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), ErrorKind::Other);
+}